@@ -3,12 +3,17 @@
 use git_version::git_version;
 
 use crate::{
-    completions::GenCompletionsCommand, convert::ConvertCommand, eval::EvalCommand,
-    export::ExportCommand, pprint_ast::PprintAstCommand, query::QueryCommand,
-    typecheck::TypecheckCommand,
+    completions::GenCompletionsCommand, convert::ConvertCommand, diff::DiffCommand,
+    eval::EvalCommand, export::ExportCommand, pprint_ast::PprintAstCommand, query::QueryCommand,
+    schema::SchemaCommand, typecheck::TypecheckCommand, watch::WatchCommand,
 };
 
-use nickel_lang_core::error::report::ErrorFormat;
+use std::str::FromStr;
+
+use nickel_lang_core::error::{
+    report::ErrorFormat,
+    warning::{UnknownWarningKind, WarningKind, WarningLevel, WarningLevels},
+};
 
 #[cfg(feature = "repl")]
 use crate::repl::ReplCommand;
@@ -62,6 +67,69 @@ pub struct GlobalOptions {
     /// Print all recorded metrics at the very end of the program
     #[arg(long, global = true, default_value_t = false)]
     pub metrics: bool,
+
+    /// Reports the given warnings, which might be silenced by default. Accepts the code of a
+    /// warning, such as `unused-binding`, or `all`
+    #[arg(long, global = true, value_name = "WARNING", value_delimiter = ',')]
+    pub warn: Vec<WarningSelector>,
+
+    /// Reports the given warnings as errors, which makes the command fail. Accepts the code of a
+    /// warning, such as `unused-binding`, or `all`
+    #[arg(long, global = true, value_name = "WARNING", value_delimiter = ',')]
+    pub deny: Vec<WarningSelector>,
+
+    /// Silences the given warnings. Accepts the code of a warning, such as `unused-binding`, or
+    /// `all`
+    #[arg(long, global = true, value_name = "WARNING", value_delimiter = ',')]
+    pub allow: Vec<WarningSelector>,
+}
+
+/// The warnings targeted by `--warn`, `--deny` or `--allow`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningSelector {
+    All,
+    Kind(WarningKind),
+}
+
+impl FromStr for WarningSelector {
+    type Err = UnknownWarningKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(WarningSelector::All),
+            _ => s.parse().map(WarningSelector::Kind),
+        }
+    }
+}
+
+impl GlobalOptions {
+    /// Computes the level of each kind of warning from `--warn`, `--deny` and `--allow`. A
+    /// specific warning takes precedence over `all`, whatever the order of the options. Otherwise,
+    /// `--deny` takes precedence over `--warn`, which takes precedence over `--allow`.
+    pub fn warning_levels(&self) -> WarningLevels {
+        let mut levels = WarningLevels::default();
+        let by_level = [
+            (WarningLevel::Allow, &self.allow),
+            (WarningLevel::Warn, &self.warn),
+            (WarningLevel::Deny, &self.deny),
+        ];
+
+        for (level, selectors) in by_level {
+            if selectors.contains(&WarningSelector::All) {
+                levels.set_all(level);
+            }
+        }
+
+        for (level, selectors) in by_level {
+            for selector in selectors {
+                if let WarningSelector::Kind(kind) = selector {
+                    levels.set(*kind, level);
+                }
+            }
+        }
+
+        levels
+    }
 }
 
 /// Available subcommands.
@@ -80,6 +148,13 @@ pub enum Command {
     Typecheck(TypecheckCommand),
     /// Converts from a plain-data format to Nickel.
     Convert(ConvertCommand),
+    /// Exports a Nickel program, then exports it again each time one of the files it depends on
+    /// changes
+    Watch(WatchCommand),
+    /// Evaluates two Nickel programs and prints the differences between the results
+    Diff(DiffCommand),
+    /// Translates the interface of a configuration to other schema languages
+    Schema(SchemaCommand),
     /// Performs packaging and dependency-resolution operations
     #[cfg(feature = "package-experimental")]
     Package(PackageCommand),
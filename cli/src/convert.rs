@@ -17,6 +17,16 @@ use crate::{
     global::GlobalContext,
 };
 
+/// What the input of the `convert` subcommand describes.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConvertFrom {
+    /// Data, which is converted to the equivalent Nickel value.
+    #[default]
+    Data,
+    /// A JSON schema, which is converted to a Nickel contract. The input is always read as JSON.
+    JsonSchema,
+}
+
 #[derive(clap::Parser, Debug)]
 #[clap(group(ArgGroup::new("fmt_or_file").args(&["file", "stdin_format"]).required(true)))]
 pub struct ConvertCommand {
@@ -27,6 +37,12 @@ pub struct ConvertCommand {
     #[arg(long, value_enum, conflicts_with = "file")]
     pub stdin_format: Option<InputFormat>,
 
+    /// What the input describes. By default, the input is converted to the equivalent Nickel
+    /// value. With `json-schema`, the input is a JSON schema which is converted to a Nickel
+    /// contract validating the same values.
+    #[arg(long, value_enum, default_value_t)]
+    pub from: ConvertFrom,
+
     /// Output file. Standard output by default
     #[arg(short, long)]
     pub output: Option<PathBuf>,
@@ -53,6 +69,9 @@ impl ConvertCommand {
                 unreachable!("clap shouldn't allow this")
             }
             (None, Some(stdin_format)) => (stdin_format, Path::new("<stdin>")),
+            (Some(path), None) if self.from == ConvertFrom::JsonSchema => {
+                (InputFormat::Json, path.as_path())
+            }
             (Some(path), None) => {
                 let format = InputFormat::from_path(path).ok_or_else(|| Error::CliUsage {
                     files: Files::empty(),
@@ -85,6 +104,10 @@ impl ConvertCommand {
         let file_id = files.add(name, data.as_str());
         let alloc = AstAlloc::new();
         let ast = match format {
+            _ if self.from == ConvertFrom::JsonSchema => {
+                nickel_lang_core::serialize::json_schema::ast_from_str(&alloc, &data, file_id)
+                    .map_err(|e| e.into())
+            }
             // In principle, we could just pass Nickel input straight through without parsing it,
             // but then we wouldn't catch parse errors.
             InputFormat::Nickel => parser::grammar::TermParser::new()
@@ -109,6 +132,18 @@ impl ConvertCommand {
                 // We convert text to Nickel by wrapping it in a string.
                 Ok(Node::String(alloc.alloc_str(&data)).into())
             }
+            InputFormat::Csv => {
+                nickel_lang_core::serialize::csv_deser::ast_from_str(&alloc, &data, file_id, b',')
+                    .map_err(|e| e.into())
+            }
+            InputFormat::Tsv => {
+                nickel_lang_core::serialize::csv_deser::ast_from_str(&alloc, &data, file_id, b'\t')
+                    .map_err(|e| e.into())
+            }
+            InputFormat::Ini => {
+                nickel_lang_core::serialize::ini_deser::ast_from_str(&alloc, &data, file_id)
+                    .map_err(|e| e.into())
+            }
             #[cfg(feature = "nix-experimental")]
             InputFormat::Nix => {
                 // We already errored for InputFormat::Nix
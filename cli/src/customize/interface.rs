@@ -10,16 +10,16 @@ use nickel_lang_core::eval::value::{Container, NickelValue};
 /// Interface is used to derive a command-line interface from a configuration when using the
 /// `customize_mode` option.
 #[derive(Debug, Clone, Default)]
-pub(super) struct ValueInterface {
-    pub(super) fields: HashMap<LocIdent, FieldInterface>,
+pub(crate) struct ValueInterface {
+    pub(crate) fields: HashMap<LocIdent, FieldInterface>,
 }
 
 /// The interface of a specific field. This field can be itself a record and contain subfields.
 #[derive(Debug, Clone, Default)]
-pub(super) struct FieldInterface {
+pub(crate) struct FieldInterface {
     /// The interface of the subfields of this field, if it's a record itself.
-    pub(super) subfields: Option<ValueInterface>,
-    pub(super) field: Field,
+    pub(crate) subfields: Option<ValueInterface>,
+    pub(crate) field: Field,
 }
 
 impl Combine for ValueInterface {
@@ -185,27 +185,27 @@ impl FieldInterface {
     /// on what is the meaning of "input", "output", and if those concept should be made
     /// first-class ([related issue](https://github.com/tweag/nickel/issues/1505)). For now, this
     /// logic seems to be a reasonable first approximation.
-    pub(super) fn is_input(&self) -> bool {
+    pub(crate) fn is_input(&self) -> bool {
         !self.is_defined() || self.is_default()
     }
 
     /// Return `true` is the field has a value.
-    pub(super) fn is_defined(&self) -> bool {
+    pub(crate) fn is_defined(&self) -> bool {
         self.field.value.is_some()
     }
 
     /// Return true is the field's merge priority is `default`.
-    pub(super) fn is_default(&self) -> bool {
+    pub(crate) fn is_default(&self) -> bool {
         matches!(self.field.metadata.priority(), MergePriority::Bottom)
     }
 
-    pub(super) fn has_subfields(&self) -> bool {
+    pub(crate) fn has_subfields(&self) -> bool {
         matches!(&self.subfields, Some(intf) if !intf.fields.is_empty())
     }
 
     /// Return the list of the type and contract annotations joined as a comma-separated string, if
     /// any.
-    pub(super) fn type_and_contracts(&self) -> Option<String> {
+    pub(crate) fn type_and_contracts(&self) -> Option<String> {
         let annotation = &self.field.metadata.as_ref()?.annotation;
 
         (!annotation.is_empty()).then(|| {
@@ -0,0 +1,403 @@
+//! The `diff` subcommand, which evaluates two configurations and prints the differences between
+//! the results, field by field.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use nickel_lang_core::{
+    error::Reporter as _,
+    eval::{
+        cache::CacheImpl,
+        value::{Container, NickelValue},
+    },
+    files::Files,
+    identifier::{Ident, LocIdent},
+    position::{PosTable, TermPos},
+    pretty::{PrettyPrintCap, ident_quoted},
+    program::Program,
+};
+
+use crate::{
+    customize::ExtractFieldOnly,
+    error::{CliUsageError, Error},
+    global::GlobalContext,
+    input::{InputOptions, NickelOnly, Prepare, PrepareResult},
+};
+
+/// The maximum width of the values printed in a diff. Longer values are truncated.
+const VALUE_MAX_WIDTH: usize = 80;
+
+#[derive(clap::Parser, Debug)]
+pub struct DiffCommand {
+    /// Compares the input file with its version at the given git revision, instead of comparing
+    /// two files. The revision can be anything understood by `git rev-parse`, such as `HEAD~1` or
+    /// a branch name. The files imported by the input are taken from the same revision.
+    #[cfg(feature = "git")]
+    #[arg(long)]
+    pub rev: Option<String>,
+
+    #[command(flatten)]
+    pub input: InputOptions<ExtractFieldOnly, NickelOnly>,
+}
+
+/// A step in the path from the root of a configuration to a value.
+#[derive(Clone, Copy, Debug)]
+enum PathElem {
+    Field(Ident),
+    Index(usize),
+}
+
+struct DisplayPath<'a>(&'a [PathElem]);
+
+impl fmt::Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "(root)");
+        }
+
+        for (i, elem) in self.0.iter().enumerate() {
+            match elem {
+                PathElem::Field(id) if i == 0 => write!(f, "{}", ident_quoted(*id))?,
+                PathElem::Field(id) => write!(f, ".{}", ident_quoted(*id))?,
+                PathElem::Index(idx) => write!(f, "[{idx}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A value of one of the configurations, together with the position of the field it's the value
+/// of, if any.
+#[derive(Clone, Copy)]
+struct Definition<'a> {
+    value: &'a NickelValue,
+    field_pos: TermPos,
+}
+
+impl<'a> Definition<'a> {
+    fn field(id: LocIdent, value: &'a NickelValue) -> Self {
+        Definition {
+            value,
+            field_pos: id.pos,
+        }
+    }
+
+    fn bare(value: &'a NickelValue) -> Self {
+        Definition {
+            value,
+            field_pos: TermPos::None,
+        }
+    }
+}
+
+/// A difference between the old and the new configuration, at a given path.
+enum Change<'a> {
+    Added(Definition<'a>),
+    Removed(Definition<'a>),
+    Changed(Definition<'a>, Definition<'a>),
+}
+
+struct Difference<'a> {
+    path: Vec<PathElem>,
+    change: Change<'a>,
+}
+
+/// Returns the exported fields of a record, sorted by name, or `None` if `value` isn't a record.
+fn fields(value: &NickelValue) -> Option<BTreeMap<&'static str, (LocIdent, &NickelValue)>> {
+    let fields = match value.as_record()? {
+        Container::Empty => BTreeMap::new(),
+        Container::Alloc(record) => record
+            .iter_serializable()
+            // Missing definitions are reported as errors by the evaluation for export.
+            .filter_map(Result::ok)
+            .map(|(id, value)| {
+                // We retrieve the original key, which holds the position of the field.
+                // unwrap(): `id` comes from the fields of the record.
+                let (key, _) = record.fields.get_key_value(&LocIdent::from(id)).unwrap();
+                (id.label(), (*key, value))
+            })
+            .collect(),
+    };
+
+    Some(fields)
+}
+
+/// Returns the elements of an array, or `None` if `value` isn't an array.
+fn elements(value: &NickelValue) -> Option<Vec<&NickelValue>> {
+    let elements = match value.as_array()? {
+        Container::Empty => Vec::new(),
+        Container::Alloc(array_data) => array_data.array.iter().collect(),
+    };
+
+    Some(elements)
+}
+
+/// Compares two fully evaluated values recursively, pushing the differences found to `diffs`.
+/// Records are compared field by field and arrays element by element. Any other pair of values is
+/// compared as a whole.
+fn diff_values<'a>(
+    path: &mut Vec<PathElem>,
+    old: Definition<'a>,
+    new: Definition<'a>,
+    diffs: &mut Vec<Difference<'a>>,
+) {
+    if let (Some(old_fields), Some(new_fields)) = (fields(old.value), fields(new.value)) {
+        let labels: BTreeSet<_> = old_fields.keys().chain(new_fields.keys()).collect();
+
+        for label in labels {
+            let change = match (old_fields.get(label), new_fields.get(label)) {
+                (Some((old_id, old_value)), Some((new_id, new_value))) => {
+                    path.push(PathElem::Field(old_id.ident()));
+                    diff_values(
+                        path,
+                        Definition::field(*old_id, old_value),
+                        Definition::field(*new_id, new_value),
+                        diffs,
+                    );
+                    None
+                }
+                (Some((id, value)), None) => {
+                    Some((*id, Change::Removed(Definition::field(*id, value))))
+                }
+                (None, Some((id, value))) => {
+                    Some((*id, Change::Added(Definition::field(*id, value))))
+                }
+                (None, None) => unreachable!("the label comes from one of the records"),
+            };
+
+            if let Some((id, change)) = change {
+                path.push(PathElem::Field(id.ident()));
+                diffs.push(Difference {
+                    path: path.clone(),
+                    change,
+                });
+            }
+
+            path.pop();
+        }
+    } else if let (Some(old_elts), Some(new_elts)) = (elements(old.value), elements(new.value)) {
+        for idx in 0..old_elts.len().max(new_elts.len()) {
+            path.push(PathElem::Index(idx));
+
+            let change = match (old_elts.get(idx), new_elts.get(idx)) {
+                (Some(old_elt), Some(new_elt)) => {
+                    diff_values(
+                        path,
+                        Definition::bare(old_elt),
+                        Definition::bare(new_elt),
+                        diffs,
+                    );
+                    None
+                }
+                (Some(old_elt), None) => Some(Change::Removed(Definition::bare(old_elt))),
+                (None, Some(new_elt)) => Some(Change::Added(Definition::bare(new_elt))),
+                (None, None) => unreachable!("the index is smaller than one of the lengths"),
+            };
+
+            if let Some(change) = change {
+                diffs.push(Difference {
+                    path: path.clone(),
+                    change,
+                });
+            }
+
+            path.pop();
+        }
+    } else if old.value != new.value {
+        diffs.push(Difference {
+            path: path.clone(),
+            change: Change::Changed(old, new),
+        });
+    }
+}
+
+/// The evaluated configuration on one side of the diff, together with what's needed to locate
+/// its values in the source.
+struct Evaluated {
+    value: NickelValue,
+    pos_table: PosTable,
+    files: Files,
+    /// When the configuration has been checked out from a git revision, the checkout directory and
+    /// the revision, such that locations are displayed relative to the repository.
+    checkout: Option<(PathBuf, String)>,
+}
+
+impl Evaluated {
+    /// Renders the location of a definition as `file:line:column`, if it's known. This is the
+    /// position of the field for record fields, which is more telling than the position of a
+    /// value which might have been computed elsewhere, and the position of the value otherwise.
+    fn location(&self, def: Definition<'_>) -> Option<String> {
+        let span = def
+            .field_pos
+            .into_opt()
+            .or_else(|| def.value.pos(&self.pos_table).into_opt())?;
+        let location = self.files.location(span.src_id, span.start).ok()?;
+        let name = Path::new(self.files.name(span.src_id));
+
+        let name = match &self.checkout {
+            Some((dir, rev)) => match name.strip_prefix(dir) {
+                Ok(relative) => format!("{rev}:{}", relative.display()),
+                Err(_) => name.display().to_string(),
+            },
+            None => name.display().to_string(),
+        };
+
+        Some(format!(
+            "{name}:{}:{}",
+            location.line.number(),
+            location.column.number()
+        ))
+    }
+
+    fn write_value(
+        &self,
+        out: &mut impl std::io::Write,
+        sign: char,
+        def: Definition<'_>,
+    ) -> std::io::Result<()> {
+        write!(
+            out,
+            "  {sign} {}",
+            def.value.pretty_print_cap(VALUE_MAX_WIDTH)
+        )?;
+
+        match self.location(def) {
+            Some(location) => writeln!(out, " (at {location})"),
+            None => writeln!(out),
+        }
+    }
+}
+
+/// One side of the diff, which is evaluated with the options of the command but a single input
+/// file.
+struct Side<'a> {
+    input: &'a InputOptions<ExtractFieldOnly, NickelOnly>,
+    file: PathBuf,
+}
+
+impl Prepare for Side<'_> {
+    fn prepare(&self, ctx: &mut GlobalContext) -> PrepareResult<Program<CacheImpl>> {
+        self.input
+            .prepare_files(ctx, std::slice::from_ref(&self.file))
+    }
+}
+
+impl DiffCommand {
+    pub fn run(self, ctxt: &mut GlobalContext) {
+        if let Err(error) = self.diff(ctxt) {
+            ctxt.reporter.report(error);
+        }
+    }
+
+    /// Returns the revision to compare the input with, if any. Always `None` when git support is
+    /// disabled.
+    fn rev(&self) -> Option<&String> {
+        #[cfg(feature = "git")]
+        return self.rev.as_ref();
+        #[cfg(not(feature = "git"))]
+        None
+    }
+
+    fn diff(&self, ctxt: &mut GlobalContext) -> Result<(), Error> {
+        // Keeps the checkout of the old revision alive until we're done with it.
+        #[cfg(feature = "git")]
+        let mut _checkout_dir = None;
+
+        let (old_file, new_file, checkout) = match (self.rev(), self.input.files.as_slice()) {
+            (None, [old, new]) => (old.clone(), new.clone(), None),
+            #[cfg(feature = "git")]
+            (Some(rev), [file]) => {
+                let dir = tempfile::tempdir()?;
+                let checkout = nickel_lang_git::checkout_local(file, rev, dir.path())?;
+                let relative = file
+                    .canonicalize()?
+                    .strip_prefix(checkout.workdir.canonicalize()?)
+                    // unwrap(): the repository has been discovered from the file's location, so
+                    // its working tree contains the file.
+                    .unwrap()
+                    .to_owned();
+                let old_file = dir.path().join(relative);
+
+                let checkout = (dir.path().to_owned(), rev.clone());
+                _checkout_dir = Some(dir);
+                (old_file, file.clone(), Some(checkout))
+            }
+            _ => {
+                return Err(Error::CliUsage {
+                    files: Files::empty(),
+                    error: CliUsageError::InvalidDiffInputs {
+                        with_rev: self.rev().is_some(),
+                    },
+                });
+            }
+        };
+
+        let Some(old) = self.evaluate(ctxt, old_file, checkout) else {
+            return Ok(());
+        };
+        let Some(new) = self.evaluate(ctxt, new_file, None) else {
+            return Ok(());
+        };
+
+        let mut diffs = Vec::new();
+        diff_values(
+            &mut Vec::new(),
+            Definition::bare(&old.value),
+            Definition::bare(&new.value),
+            &mut diffs,
+        );
+
+        let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+
+        for Difference { path, change } in diffs {
+            let path = DisplayPath(&path);
+
+            match change {
+                Change::Added(value) => {
+                    writeln!(out, "+ {path}")?;
+                    new.write_value(&mut out, '+', value)?;
+                }
+                Change::Removed(value) => {
+                    writeln!(out, "- {path}")?;
+                    old.write_value(&mut out, '-', value)?;
+                }
+                Change::Changed(old_value, new_value) => {
+                    writeln!(out, "~ {path}")?;
+                    old.write_value(&mut out, '-', old_value)?;
+                    new.write_value(&mut out, '+', new_value)?;
+                }
+            }
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Fully evaluates one side of the diff. Errors are reported to the global context, in which
+    /// case `None` is returned.
+    fn evaluate(
+        &self,
+        ctxt: &mut GlobalContext,
+        file: PathBuf,
+        checkout: Option<(PathBuf, String)>,
+    ) -> Option<Evaluated> {
+        let side = Side {
+            input: &self.input,
+            file,
+        };
+
+        ctxt.with_program(&side, |program| {
+            Ok(Evaluated {
+                value: program.eval_full_for_export()?,
+                pos_table: program.pos_table().clone(),
+                files: program.files(),
+                checkout,
+            })
+        })
+    }
+}
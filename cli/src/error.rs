@@ -4,10 +4,12 @@ use std::path::PathBuf;
 
 use nickel_lang_core::{
     error::{
-        Diagnostic, IntoDiagnostics, ParseError,
+        Diagnostic, IntoDiagnostics, Label, ParseError, Severity,
         report::{ColorOpt, ErrorFormat, report},
+        warning::{WarningLevel, WarningLevels},
     },
     files::{FileId, Files},
+    position::RawSpan,
     program::{FieldOverride, FieldPath},
 };
 
@@ -33,6 +35,11 @@ pub enum CliUsageError {
     FieldPathParseError { error: ParseError },
     /// Couldn't determine the format of an input.
     CantDetectFormat { path: PathBuf },
+    /// Tried to watch a program read from the standard input.
+    NoFilesToWatch,
+    /// Called `nickel diff` with a wrong number of input files: two files are expected, or only
+    /// one together with `--rev`.
+    InvalidDiffInputs { with_rev: bool },
     #[cfg(feature = "nix-experimental")]
     NoNixConversion { path: PathBuf },
 }
@@ -45,6 +52,13 @@ pub enum Error {
     Io {
         error: std::io::Error,
     },
+    Watch {
+        error: notify_debouncer_full::notify::Error,
+    },
+    #[cfg(feature = "git")]
+    Git {
+        error: nickel_lang_git::Error,
+    },
     #[cfg(feature = "repl")]
     Repl {
         error: nickel_lang_core::repl::InitError,
@@ -164,6 +178,32 @@ impl IntoDiagnostics for CliUsageError {
                     path.display()
                 ))]
             }
+            CliUsageError::NoFilesToWatch => {
+                vec![
+                    Diagnostic::error()
+                        .with_message("no input file to watch")
+                        .with_notes(vec![
+                            "`nickel watch` can't read its input from stdin. Please provide \
+                            the input files on the command line."
+                                .to_owned(),
+                        ]),
+                ]
+            }
+            CliUsageError::InvalidDiffInputs { with_rev } => {
+                let note = if with_rev {
+                    "With `--rev`, `nickel diff` compares a single file with its version at the \
+                    given revision. Please provide exactly one input file."
+                } else {
+                    "`nickel diff` compares an old configuration with a new one. Please provide \
+                    exactly two input files, or a single one together with `--rev`."
+                };
+
+                vec![
+                    Diagnostic::error()
+                        .with_message("wrong number of input files to compare")
+                        .with_notes(vec![note.to_owned()]),
+                ]
+            }
             #[cfg(feature = "nix-experimental")]
             CliUsageError::NoNixConversion { path } => {
                 vec![Diagnostic::error().with_message(format!(
@@ -190,6 +230,15 @@ pub enum Warning {
         files: Files,
         warning: nickel_lang_core::error::Warning,
     },
+
+    /// A type or a contract couldn't be translated when exporting the schema of a configuration,
+    /// and has been replaced with a schema accepting any value.
+    UntranslatableContract {
+        files: Files,
+        path: FieldPath,
+        contract: String,
+        span: Option<RawSpan>,
+    },
 }
 
 impl Eq for Warning {}
@@ -202,6 +251,20 @@ impl PartialEq for Warning {
         match (self, other) {
             (Warning::EmptyQueryPath, Warning::EmptyQueryPath) => true,
             (Warning::Program { warning: a, .. }, Warning::Program { warning: b, .. }) => a == b,
+            (
+                Warning::UntranslatableContract {
+                    path: path_a,
+                    contract: contract_a,
+                    span: span_a,
+                    ..
+                },
+                Warning::UntranslatableContract {
+                    path: path_b,
+                    contract: contract_b,
+                    span: span_b,
+                    ..
+                },
+            ) => path_a == path_b && contract_a == contract_b && span_a == span_b,
             _ => false,
         }
     }
@@ -212,6 +275,16 @@ impl std::hash::Hash for Warning {
         std::mem::discriminant(self).hash(state);
         match self {
             Warning::Program { warning, .. } => warning.hash(state),
+            Warning::UntranslatableContract {
+                path,
+                contract,
+                span,
+                ..
+            } => {
+                path.hash(state);
+                contract.hash(state);
+                span.hash(state);
+            }
             Warning::EmptyQueryPath => {}
         }
     }
@@ -243,6 +316,57 @@ impl Warning {
             Warning::Program { mut files, warning } => {
                 core_report(&mut files, warning, format, color)
             }
+            Warning::UntranslatableContract {
+                mut files,
+                path,
+                contract,
+                span,
+            } => {
+                let diag = Diagnostic::warning()
+                    .with_message(format!(
+                        "the contract `{contract}` of field `{path}` can't be translated to JSON Schema"
+                    ))
+                    .with_labels(
+                        span.into_iter()
+                            .map(|span| {
+                                Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
+                            })
+                            .collect(),
+                    )
+                    .with_notes(vec![
+                        "It has been exported as the schema `{}`, which accepts any value.".into(),
+                    ]);
+                core_report(&mut files, diag, format, color);
+            }
+        }
+    }
+
+    /// The level of this warning according to the given configuration. Warnings which don't
+    /// originate from a program can't be configured and are always reported.
+    pub fn level(&self, levels: &WarningLevels) -> WarningLevel {
+        match self {
+            Warning::Program { files, warning } => levels.level_of(warning, files),
+            Warning::EmptyQueryPath | Warning::UntranslatableContract { .. } => WarningLevel::Warn,
+        }
+    }
+
+    /// Report this warning as an error on the standard error stream, because it has been denied
+    /// on the command line.
+    pub fn report_denied(self, format: ErrorFormat, color: ColorOpt) {
+        use nickel_lang_core::error::report::report as core_report;
+        match self {
+            Warning::Program { mut files, warning } => {
+                let code = warning.kind().code();
+
+                for mut diag in warning.into_diagnostics(&mut files) {
+                    diag.severity = Severity::Error;
+                    diag.notes.push(format!(
+                        "`{code}` warnings are denied by the command-line options."
+                    ));
+                    core_report(&mut files, diag, format, color);
+                }
+            }
+            _ => self.report(format, color),
         }
     }
 }
@@ -255,6 +379,19 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<notify_debouncer_full::notify::Error> for Error {
+    fn from(error: notify_debouncer_full::notify::Error) -> Self {
+        Error::Watch { error }
+    }
+}
+
+#[cfg(feature = "git")]
+impl From<nickel_lang_git::Error> for Error {
+    fn from(error: nickel_lang_git::Error) -> Self {
+        Error::Git { error }
+    }
+}
+
 #[cfg(feature = "format")]
 impl From<crate::format::FormatError> for Error {
     fn from(error: crate::format::FormatError) -> Self {
@@ -302,6 +439,9 @@ impl Error {
         match self {
             Error::Program { mut files, error } => core_report(&mut files, error, format, color),
             Error::Io { error } => report_with_msg("IO error", error.to_string()),
+            Error::Watch { error } => report_with_msg("file watcher error", error.to_string()),
+            #[cfg(feature = "git")]
+            Error::Git { error } => report_with_msg("git error", error.to_string()),
             #[cfg(feature = "repl")]
             Error::Repl { error } => {
                 use nickel_lang_core::repl::InitError;
@@ -31,7 +31,7 @@ impl ExportCommand {
         ctxt.with_program(&self.input, |program| self.export(program));
     }
 
-    fn export(&self, program: &mut Program<CacheImpl>) -> Result<(), Error> {
+    pub fn export(&self, program: &mut Program<CacheImpl>) -> Result<(), Error> {
         let rt = program.eval_full_for_export()?;
 
         serialize::validate(self.format, &rt)
@@ -6,16 +6,20 @@ use std::{
 };
 
 use nickel_lang_core::{
-    error::Error as CoreError, error::Reporter, eval::cache::CacheImpl, files::Files,
-    program::Program,
+    error::Error as CoreError, error::Reporter, error::warning::WarningLevel,
+    eval::cache::CacheImpl, files::Files, program::Program,
 };
 
 use crate::{
     cli::GlobalOptions,
+    color_opt_from_clap,
     error::{Error, Warning},
     input::{Prepare, PrepareError},
 };
 
+/// How many warnings do we emit?
+const WARNING_LIMIT: usize = 10;
+
 /// Contains global state for the nickel CLI.
 pub struct GlobalContext {
     /// The CLI arguments that are applicable to all commands.
@@ -74,13 +78,24 @@ impl GlobalContext {
         f: F,
     ) -> Option<T> {
         let result = match preparer.prepare(self) {
-            Ok(mut prog) => f(&mut prog).map_err(|error| {
-                Error::Program {
-                    error,
-                    files: prog.files(),
+            Ok(mut prog) => {
+                let result = f(&mut prog);
+
+                #[cfg(feature = "incremental-experimental")]
+                if result.is_ok()
+                    && let Err(error) = prog.persist_incremental_cache()
+                {
+                    eprintln!("warning: couldn't save the incremental evaluation cache: {error}");
                 }
-                .into()
-            }),
+
+                result.map_err(|error| {
+                    Error::Program {
+                        error,
+                        files: prog.files(),
+                    }
+                    .into()
+                })
+            }
             Err(e) => Err(e),
         };
 
@@ -94,6 +109,46 @@ impl GlobalContext {
         }
     }
 
+    /// Reports all the warnings and errors that have been received so far on the standard error
+    /// stream, using the output options of the global CLI arguments. Warnings are filtered and
+    /// possibly turned into errors according to their configured level. Returns `true` if at least
+    /// one error was reported.
+    pub fn report_diagnostics(&mut self) -> bool {
+        let error_format = self.opts.error_format;
+        let color = color_opt_from_clap(self.opts.color);
+
+        let levels = self.opts.warning_levels();
+        let mut warnings = Vec::new();
+        let mut denied = Vec::new();
+
+        for w in self.deduplicated_warnings() {
+            match w.level(&levels) {
+                WarningLevel::Allow => (),
+                WarningLevel::Warn => warnings.push(w),
+                WarningLevel::Deny => denied.push(w),
+            }
+        }
+
+        for w in warnings.drain(..WARNING_LIMIT.min(warnings.len())) {
+            w.report(error_format, color);
+        }
+        if !warnings.is_empty() {
+            eprintln!("(suppressed {} additional warnings)", warnings.len());
+        }
+
+        let mut has_errors = !denied.is_empty();
+        for w in denied {
+            w.report_denied(error_format, color);
+        }
+
+        for e in self.errors.try_iter() {
+            e.report(error_format, color);
+            has_errors = true;
+        }
+
+        has_errors
+    }
+
     /// Drains all the warnings that have been received so far and deduplicates them.
     pub fn deduplicated_warnings(&mut self) -> Vec<Warning> {
         let mut seen: HashSet<Warning> = HashSet::new();
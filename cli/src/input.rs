@@ -59,7 +59,23 @@ pub struct InputOptions<Customize: clap::Args, InputFormatOptions: clap::Args> {
     #[arg(long, global = true)]
     pub package_cache_dir: Option<PathBuf>,
 
+    /// Directory where typechecking results are cached between runs.
+    ///
+    /// Files that have already been typechecked by a previous run using the same cache
+    /// directory, and which haven't changed since (nor have their imports), aren't typechecked
+    /// again. Only typechecking is cached: files are still parsed and transformed on each run.
+    /// If not specified, the directory given by the environment variable
+    /// `NICKEL_CACHE_DIR` is used, if any. Otherwise, no cache is used.
+    #[arg(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+
     /// Enable incremental evaluation (experimental)
+    ///
+    /// Values computed during evaluation are saved in the cache directory (see `--cache-dir`),
+    /// and re-used by subsequent runs when the expressions they come from haven't changed. If no
+    /// cache directory is given, they are saved in a platform-dependent location, like
+    /// `$XDG_CACHE_HOME/nickel` on linux. This flag alone doesn't enable the cache of
+    /// typechecking results.
     #[cfg(feature = "incremental-experimental")]
     #[arg(long, global = true)]
     pub incremental: bool,
@@ -91,6 +107,19 @@ pub trait Prepare {
 
 impl<C: clap::Args + Customize, F: clap::Args + InputFormatOptions> Prepare for InputOptions<C, F> {
     fn prepare(&self, ctx: &mut GlobalContext) -> PrepareResult<Program<CacheImpl>> {
+        self.prepare_files(ctx, &self.files)
+    }
+}
+
+impl<C: clap::Args + Customize, F: clap::Args + InputFormatOptions> InputOptions<C, F> {
+    /// Prepares a program as [Prepare::prepare] does, but with `files` as the input files in place
+    /// of the ones given on the command line. This is used by commands which evaluate several
+    /// programs sharing the same options.
+    pub fn prepare_files(
+        &self,
+        ctx: &mut GlobalContext,
+        files: &[PathBuf],
+    ) -> PrepareResult<Program<CacheImpl>> {
         // Resolve the package map first — it's independent of program construction and may itself
         // fail (manifest open / lock / package fetch).
         #[cfg(feature = "package-experimental")]
@@ -105,7 +134,7 @@ impl<C: clap::Args + Customize, F: clap::Args + InputFormatOptions> Prepare for
                 // is harder. For now, we take the parent directory if it's
                 // unique and otherwise we require an explicit manifest
                 // path.
-                let mut parents = self.files.iter().map(|p| p.parent()).collect::<Vec<_>>();
+                let mut parents = files.iter().map(|p| p.parent()).collect::<Vec<_>>();
                 parents.sort();
                 parents.dedup();
                 let dir = match parents.as_slice() {
@@ -135,10 +164,10 @@ impl<C: clap::Args + Customize, F: clap::Args + InputFormatOptions> Prepare for
             .with_trace(std::io::stderr())
             .with_reporter(ctx.reporter.clone());
 
-        builder = if self.files.is_empty() {
+        builder = if files.is_empty() {
             builder.add_stdin(self.format_options.stdin_format())
         } else {
-            builder.add_paths(self.files.iter().cloned())
+            builder.add_paths(files.iter().cloned())
         };
 
         builder = builder
@@ -149,14 +178,35 @@ impl<C: clap::Args + Customize, F: clap::Args + InputFormatOptions> Prepare for
             builder = builder.add_import_paths(nickel_path.split(':').map(PathBuf::from));
         }
 
-        #[cfg(feature = "package-experimental")]
-        if let Some(map) = package_map {
-            builder = builder.with_package_map(map);
-        }
+        let cache_dir = self
+            .cache_dir
+            .clone()
+            .or_else(|| std::env::var_os("NICKEL_CACHE_DIR").map(PathBuf::from));
 
         #[cfg(feature = "incremental-experimental")]
         if self.incremental {
+            // The values computed by incremental evaluation are saved in the cache directory, or in
+            // the default location if there's none. This doesn't enable the cache of typechecking
+            // results, which must be requested explicitly.
+            let incremental_cache_dir = cache_dir.clone().or_else(|| {
+                directories::ProjectDirs::from("org", "nickel-lang", "nickel")
+                    .map(|dirs| dirs.cache_dir().to_owned())
+            });
+
             builder = builder.with_incremental_evaluation();
+
+            if let Some(dir) = incremental_cache_dir {
+                builder = builder.with_incremental_cache(dir);
+            }
+        }
+
+        if let Some(cache_dir) = cache_dir {
+            builder = builder.with_persistent_cache(cache_dir);
+        }
+
+        #[cfg(feature = "package-experimental")]
+        if let Some(map) = package_map {
+            builder = builder.with_package_map(map);
         }
 
         // The builder defers all I/O to `build()`, so any failure here is from opening an input
@@ -185,7 +235,7 @@ impl<C: clap::Args + Customize, F: clap::Args + InputFormatOptions> Prepare for
 /// in from stdin. This is to allow the --stdin-format flag to
 /// override the default Nickel format for certain subcommands while
 /// other commands can exclude it.
-trait InputFormatOptions {
+pub trait InputFormatOptions {
     fn stdin_format(&self) -> InputFormat;
 }
 
@@ -23,6 +23,7 @@ mod cli;
 mod completions;
 mod convert;
 mod customize;
+mod diff;
 mod error;
 mod eval;
 mod export;
@@ -30,7 +31,9 @@ mod global;
 mod input;
 mod pprint_ast;
 mod query;
+mod schema;
 mod typecheck;
+mod watch;
 
 use std::process::ExitCode;
 
@@ -38,17 +41,12 @@ use global::GlobalContext;
 
 use crate::cli::{Command, Options};
 
-/// How many warnings do we emit?
-const WARNING_LIMIT: usize = 10;
-
 fn main() -> ExitCode {
     #[cfg(feature = "metrics")]
     let metrics = metrics::Recorder::install();
 
     let opts = <Options as clap::Parser>::parse();
 
-    let error_format = opts.global.error_format;
-    let color = opts.global.color;
     #[cfg(feature = "metrics")]
     let report_metrics = opts.global.metrics;
 
@@ -62,6 +60,9 @@ fn main() -> ExitCode {
         Command::Typecheck(typecheck) => typecheck.run(&mut ctxt),
         Command::GenCompletions(completions) => completions.run(&mut ctxt),
         Command::Convert(convert) => convert.run(&mut ctxt),
+        Command::Watch(watch) => watch.run(&mut ctxt),
+        Command::Diff(diff) => diff.run(&mut ctxt),
+        Command::Schema(schema) => schema.run(&mut ctxt),
 
         #[cfg(feature = "package-experimental")]
         Command::Package(package) => package.run(&mut ctxt),
@@ -83,21 +84,11 @@ fn main() -> ExitCode {
         metrics.report();
     }
 
-    let mut warnings = ctxt.deduplicated_warnings();
-    for w in warnings.drain(..WARNING_LIMIT.min(warnings.len())) {
-        w.report(error_format, color_opt_from_clap(color));
-    }
-    if !warnings.is_empty() {
-        eprintln!("(suppressed {} additional warnings)", warnings.len());
+    if ctxt.report_diagnostics() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
     }
-
-    let mut code = ExitCode::SUCCESS;
-    for e in ctxt.errors.try_iter() {
-        e.report(error_format, color_opt_from_clap(color));
-        code = ExitCode::FAILURE;
-    }
-
-    code
 }
 
 fn color_opt_from_clap(c: clap::ColorChoice) -> nickel_lang_core::error::report::ColorOpt {
@@ -0,0 +1,340 @@
+//! The `schema` subcommand, which translates the interface of a configuration to other schema
+//! languages.
+
+use std::{fs, io::Write as _, path::PathBuf};
+
+use nickel_lang_core::{
+    error::Reporter as _,
+    eval::cache::CacheImpl,
+    position::TermPos,
+    program::{FieldPath, Program},
+    term::MergePriority,
+    typ::{EnumRowsIteratorItem, RecordRows, RecordRowsIteratorItem, Type, TypeF},
+};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    customize::{
+        ExtractFieldOnly,
+        interface::{FieldInterface, ValueInterface},
+    },
+    error::{CliResult, Warning},
+    global::GlobalContext,
+    input::{InputOptions, StdinFormat},
+};
+
+/// The JSON Schema dialect of the exported schemas.
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Exports the interface of a configuration as a JSON schema.
+    ///
+    /// The schema describes the fields of the configuration, as they would be listed by the
+    /// customize mode: their types and contracts, whether they're optional, their documentation
+    /// and their default values. Custom contracts which have no JSON Schema counterpart are
+    /// exported as the empty schema `{}`, which accepts any value, and a warning is emitted.
+    Export(SchemaExportCommand),
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct SchemaCommand {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct SchemaExportCommand {
+    /// Output file. Standard output by default
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub inputs: InputOptions<ExtractFieldOnly, StdinFormat>,
+}
+
+impl SchemaCommand {
+    pub fn run(self, ctxt: &mut GlobalContext) {
+        match self.command {
+            Command::Export(export) => export.run(ctxt),
+        }
+    }
+}
+
+/// Standard library contracts which have an exact JSON Schema counterpart, by name.
+fn std_contract_schema(name: &str) -> Option<Value> {
+    let schema = match name {
+        "std.number.Integer" => json!({ "type": "integer" }),
+        "std.number.Nat" => json!({ "type": "integer", "minimum": 0 }),
+        "std.number.PosNat" => json!({ "type": "integer", "minimum": 1 }),
+        "std.string.NonEmpty" => json!({ "type": "string", "minLength": 1 }),
+        "std.array.NonEmpty" => json!({ "type": "array", "minItems": 1 }),
+        // This contract converts strings to enum tags, which are serialized as strings anyway.
+        // The values are constrained by the enum type that usually follows it.
+        "std.enum.TagOrString" => json!({}),
+        _ => return None,
+    };
+
+    Some(schema)
+}
+
+fn is_trivial(schema: &Value) -> bool {
+    schema.as_object().is_some_and(Map::is_empty)
+}
+
+/// A type or a contract that couldn't be translated to JSON Schema.
+struct Fallback {
+    path: FieldPath,
+    contract: String,
+    pos: TermPos,
+}
+
+/// Translates field interfaces to JSON schemas, recording the contracts that couldn't be
+/// translated along the way.
+struct Translator<'a> {
+    /// The program being translated, used to evaluate default values.
+    program: &'a mut Program<CacheImpl>,
+    fallbacks: Vec<Fallback>,
+}
+
+impl Translator<'_> {
+    fn fallback(&mut self, path: &FieldPath, typ: &Type) -> Value {
+        self.fallbacks.push(Fallback {
+            path: path.clone(),
+            contract: typ.to_string(),
+            pos: typ.pos,
+        });
+        json!({})
+    }
+
+    fn typ(&mut self, path: &FieldPath, typ: &Type) -> Value {
+        match &typ.typ {
+            TypeF::Dyn | TypeF::Wildcard(_) => json!({}),
+            TypeF::Number => json!({ "type": "number" }),
+            TypeF::Bool => json!({ "type": "boolean" }),
+            TypeF::String => json!({ "type": "string" }),
+            TypeF::Array(elts) => json!({ "type": "array", "items": self.typ(path, elts) }),
+            TypeF::Dict { type_fields, .. } => json!({
+                "type": "object",
+                "additionalProperties": self.typ(path, type_fields),
+            }),
+            TypeF::Record(rrows) => {
+                let mut properties = Map::new();
+                let mut required = Vec::new();
+                let mut open = false;
+
+                for item in rrows.iter() {
+                    match item {
+                        RecordRowsIteratorItem::Row(row) => {
+                            required.push(Value::from(row.id.label()));
+                            properties.insert(row.id.label().to_owned(), self.typ(path, row.typ));
+                        }
+                        RecordRowsIteratorItem::TailDyn | RecordRowsIteratorItem::TailVar(_) => {
+                            open = true
+                        }
+                    }
+                }
+
+                let mut schema = json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                });
+
+                if !open {
+                    schema["additionalProperties"] = Value::Bool(false);
+                }
+
+                schema
+            }
+            TypeF::Enum(erows) => {
+                // Enum tags are serialized as strings, but variants with an argument can't be
+                // serialized at all.
+                let tags = erows
+                    .iter()
+                    .map(|item| match item {
+                        EnumRowsIteratorItem::Row(row) if row.typ.is_none() => {
+                            Some(Value::from(row.id.label()))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>();
+
+                match tags {
+                    Some(tags) => json!({ "enum": tags }),
+                    None => self.fallback(path, typ),
+                }
+            }
+            TypeF::Contract(_) => match std_contract_schema(&typ.to_string()) {
+                Some(schema) => schema,
+                None => self.fallback(path, typ),
+            },
+            _ => self.fallback(path, typ),
+        }
+    }
+
+    /// Translates the interface of a field. `row_types` are the types given to this field by the
+    /// record types annotating the parent record.
+    fn field(&mut self, path: &FieldPath, field: &FieldInterface, row_types: &[Type]) -> Value {
+        let has_subfields = field.has_subfields();
+        let mut record_types = Vec::new();
+        let mut schemas = Vec::new();
+
+        let annots = field.field.metadata.iter_annots().map(|annot| &annot.typ);
+
+        for typ in annots.chain(row_types) {
+            match &typ.typ {
+                // The fields of record types are already part of the subfields of the interface,
+                // but without their type, which is added back when translating the subfields.
+                TypeF::Record(rrows) if has_subfields => record_types.push(rrows),
+                // Similarly, we assume that a custom contract annotating a field with subfields
+                // is a record contract, whose fields are already part of the subfields.
+                TypeF::Contract(_)
+                    if has_subfields && std_contract_schema(&typ.to_string()).is_none() => {}
+                _ => schemas.push(self.typ(path, typ)),
+            }
+        }
+
+        schemas.retain(|schema| !is_trivial(schema));
+
+        if let Some(subfields) = field.subfields.as_ref().filter(|_| has_subfields) {
+            schemas.push(self.object(path, subfields, &record_types));
+        }
+
+        let mut schema = match schemas.len() {
+            0 => json!({}),
+            // unwrap(): we just checked that there is exactly one element
+            1 => schemas.pop().unwrap(),
+            _ => json!({ "allOf": schemas }),
+        };
+
+        if let Some(doc) = field.field.metadata.doc() {
+            schema["description"] = Value::from(doc);
+        }
+
+        // Default values are only evaluated to a weak head normal form by the record spine
+        // evaluation. If the full evaluation fails, typically because the default value depends on
+        // fields that haven't been defined, it's just left out.
+        if let (MergePriority::Bottom, Some(value)) =
+            (field.field.metadata.priority(), field.field.value.as_ref())
+            && let Ok(default) = self
+                .program
+                .eval_full_for_export_closure(value.clone().into())
+            && let Ok(default) = serde_json::to_value(&default)
+        {
+            schema["default"] = default;
+        }
+
+        schema
+    }
+
+    /// Translates the interface of a record. `record_types` are the record types annotating this
+    /// record, which give the types of the fields coming from these annotations.
+    fn object(
+        &mut self,
+        path: &FieldPath,
+        interface: &ValueInterface,
+        record_types: &[&RecordRows],
+    ) -> Value {
+        let mut fields: Vec<_> = interface
+            .fields
+            .iter()
+            .filter(|(_, field)| !field.field.metadata.not_exported())
+            .collect();
+        fields.sort_by_key(|(id, _)| id.label());
+
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+
+        for (id, field) in fields {
+            let mut path = path.clone();
+            path.0.push(*id);
+
+            let row_types: Vec<_> = record_types
+                .iter()
+                .filter_map(|rrows| rrows.find_row(id.ident()))
+                .map(|row| *row.0.typ)
+                .collect();
+
+            let schema = self.field(&path, field, &row_types);
+            properties.insert(id.label().to_owned(), schema);
+
+            // Fields with a definition don't need to be provided.
+            if !field.field.metadata.opt() && !field.is_defined() {
+                required.push(Value::from(id.label()));
+            }
+        }
+
+        let mut schema = json!({ "type": "object", "properties": properties });
+
+        if !required.is_empty() {
+            schema["required"] = Value::from(required);
+        }
+
+        let closed = record_types.iter().any(|rrows| {
+            rrows
+                .iter()
+                .all(|item| matches!(item, RecordRowsIteratorItem::Row(_)))
+        });
+
+        if closed {
+            schema["additionalProperties"] = Value::Bool(false);
+        }
+
+        schema
+    }
+}
+
+impl SchemaExportCommand {
+    pub fn run(self, ctxt: &mut GlobalContext) {
+        let result = ctxt.with_program(&self.inputs, |program| {
+            // We closurize the leaves of the record spine, so that default values can be evaluated
+            // further.
+            let value = program.eval_closurized_record_spine()?;
+            let files = program.files();
+
+            let mut translator = Translator {
+                program,
+                fallbacks: Vec::new(),
+            };
+            let mut schema =
+                translator.object(&FieldPath::new(), &ValueInterface::from(&value), &[]);
+            schema["$schema"] = Value::from(JSON_SCHEMA_DIALECT);
+
+            Ok((schema, translator.fallbacks, files))
+        });
+
+        let Some((schema, fallbacks, files)) = result else {
+            return;
+        };
+
+        for Fallback {
+            path,
+            contract,
+            pos,
+        } in fallbacks
+        {
+            ctxt.reporter.report(Warning::UntranslatableContract {
+                files: files.clone(),
+                path,
+                contract,
+                span: pos.into_opt(),
+            });
+        }
+
+        ctxt.reporter.report_result(self.write(&schema));
+    }
+
+    fn write(&self, schema: &Value) -> CliResult<()> {
+        let mut output =
+            serde_json::to_string_pretty(schema).expect("serializing a JSON value can't fail");
+        output.push('\n');
+
+        match &self.output {
+            Some(path) => fs::write(path, output)?,
+            None => std::io::stdout().write_all(output.as_bytes())?,
+        }
+
+        Ok(())
+    }
+}
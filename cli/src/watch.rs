@@ -0,0 +1,141 @@
+//! The `watch` subcommand, which exports a configuration and exports it again each time one of its
+//! sources changes.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, channel},
+    time::Duration,
+};
+
+use nickel_lang_core::{cache::normalize_path, error::Reporter, files::Files};
+use notify_debouncer_full::{
+    DebounceEventResult, new_debouncer,
+    notify::{EventKind, RecursiveMode},
+};
+
+use crate::{
+    error::{CliUsageError, Error},
+    export::ExportCommand,
+    global::GlobalContext,
+};
+
+/// How long to wait for the filesystem to settle before exporting again. Saving a file often
+/// generates a burst of events, which shouldn't trigger as many exports.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(clap::Parser, Debug)]
+pub struct WatchCommand {
+    #[command(flatten)]
+    pub export: ExportCommand,
+}
+
+impl WatchCommand {
+    pub fn run(self, ctxt: &mut GlobalContext) {
+        if self.export.input.files.is_empty() {
+            ctxt.reporter.report(Error::CliUsage {
+                files: Files::empty(),
+                error: CliUsageError::NoFilesToWatch,
+            });
+            return;
+        }
+
+        let (tx, rx) = channel();
+        let mut debouncer = match new_debouncer(DEBOUNCE_DELAY, None, tx) {
+            Ok(debouncer) => debouncer,
+            Err(error) => {
+                ctxt.reporter.report(Error::from(error));
+                return;
+            }
+        };
+
+        let mut watched_dirs: HashSet<PathBuf> = HashSet::new();
+
+        loop {
+            let sources = self.export_once(ctxt);
+            ctxt.report_diagnostics();
+
+            // We watch the parent directories rather than the files themselves: many editors
+            // save a file by replacing it, which would silently end a watch on the file.
+            let dirs: HashSet<PathBuf> = sources
+                .iter()
+                .filter_map(|path| path.parent())
+                .map(Path::to_owned)
+                .collect();
+
+            for dir in watched_dirs.difference(&dirs) {
+                // The directory might have been removed in the meantime, in which case the watch
+                // is already gone.
+                let _ = debouncer.unwatch(dir);
+            }
+
+            for dir in dirs.difference(&watched_dirs) {
+                if let Err(error) = debouncer.watch(dir, RecursiveMode::NonRecursive) {
+                    ctxt.reporter.report(Error::from(error));
+                }
+            }
+
+            watched_dirs = dirs;
+            ctxt.report_diagnostics();
+
+            eprintln!(
+                "Watching {} file(s) for changes. Press Ctrl-C to stop.",
+                sources.len()
+            );
+
+            if !wait_for_change(&rx, &sources) {
+                return;
+            }
+        }
+    }
+
+    /// Runs the export once, and returns the normalized paths of all the files it depends on.
+    fn export_once(&self, ctxt: &mut GlobalContext) -> HashSet<PathBuf> {
+        let mut sources = Vec::new();
+
+        ctxt.with_program(&self.export.input, |program| {
+            let result = self.export.export(program);
+            sources = program.source_paths();
+            result
+        });
+
+        // If the program couldn't even be built, we still watch the input files, so that fixing
+        // them triggers a new export.
+        sources
+            .into_iter()
+            .chain(
+                self.export
+                    .input
+                    .files
+                    .iter()
+                    .filter_map(|path| normalize_path(path).ok()),
+            )
+            .collect()
+    }
+}
+
+/// Blocks until one of the `sources` is created, modified or removed. Returns `false` if the
+/// watcher was shut down in the meantime.
+fn wait_for_change(rx: &Receiver<DebounceEventResult>, sources: &HashSet<PathBuf>) -> bool {
+    for result in rx {
+        match result {
+            Ok(events) => {
+                let changed = events.iter().any(|ev| {
+                    !matches!(ev.kind, EventKind::Access(_))
+                        && ev.paths.iter().any(|path| sources.contains(path))
+                });
+
+                if changed {
+                    return true;
+                }
+            }
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("warning: while watching for changes: {error}");
+                }
+            }
+        }
+    }
+
+    false
+}
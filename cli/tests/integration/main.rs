@@ -1,8 +1,9 @@
 mod stdin_format;
 use std::{
-    io::Write,
+    io::{BufRead, BufReader, Lines, Write},
     path::PathBuf,
-    process::{Command, Stdio},
+    process::{ChildStderr, Command, Stdio},
+    time::{Duration, Instant},
 };
 
 use tempfile::tempdir;
@@ -130,3 +131,249 @@ fn merge_mixed_formats() {
 "
     );
 }
+
+/// Polls `path` until its content satisfies `pred`, failing the test after a generous timeout.
+fn wait_for_file(path: &std::path::Path, pred: impl Fn(&str) -> bool) -> String {
+    let deadline = Instant::now() + Duration::from_secs(60);
+
+    loop {
+        if let Ok(content) = std::fs::read_to_string(path)
+            && pred(&content)
+        {
+            return content;
+        }
+
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for {}",
+            path.display()
+        );
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn watch_reexports_on_import_change() {
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let main = dir.path().join("main.ncl");
+    let lib = dir.path().join("lib.ncl");
+    let output = dir.path().join("output.json");
+
+    std::fs::write(&main, "{ value = import \"lib.ncl\" }").unwrap();
+    std::fs::write(&lib, "1").unwrap();
+
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let mut nickel = Command::new(nickel_bin)
+        .arg("watch")
+        .arg(&main)
+        .arg("-o")
+        .arg(&output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Nickel should be runnable");
+
+    let mut stderr = BufReader::new(nickel.stderr.take().expect("stderr should be piped")).lines();
+
+    let result = std::panic::catch_unwind(move || {
+        // The watcher is set up once the status line has been printed.
+        let wait_for_watching = |stderr: &mut Lines<BufReader<ChildStderr>>| {
+            stderr
+                .map_while(Result::ok)
+                .find(|line| line.starts_with("Watching"))
+                .expect("nickel watch should report that it's watching files");
+        };
+
+        wait_for_watching(&mut stderr);
+        wait_for_file(&output, |content| content.contains("\"value\": 1"));
+
+        std::fs::write(&lib, "2").unwrap();
+        wait_for_watching(&mut stderr);
+        wait_for_file(&output, |content| content.contains("\"value\": 2"));
+    });
+
+    nickel.kill().expect("Nickel should be killable");
+    let _ = nickel.wait();
+    result.expect("the output should have been updated after the change");
+}
+
+#[test]
+fn cache_dir_invalidates_changed_imports() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let main = dir.path().join("main.ncl");
+    let lib = dir.path().join("lib.ncl");
+    let cache_dir = dir.path().join("cache");
+
+    std::fs::write(&main, "(import \"lib.ncl\").value").unwrap();
+    std::fs::write(&lib, "{ value : Number = 1 + 1 }").unwrap();
+
+    let export = || {
+        Command::new(nickel_bin)
+            .arg("export")
+            .arg(&main)
+            .arg("--cache-dir")
+            .arg(&cache_dir)
+            .output()
+            .expect("Nickel should be runnable")
+    };
+
+    for _ in 0..2 {
+        let output = export();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    }
+
+    // One entry for the main file and one for the library.
+    let entries = std::fs::read_dir(cache_dir.join("typecheck"))
+        .unwrap()
+        .count();
+    assert_eq!(entries, 2);
+
+    // The main file is unchanged, but its cached result must be invalidated by the change of the
+    // library, which doesn't typecheck anymore.
+    std::fs::write(&lib, "{ value : Number = \"2\" }").unwrap();
+
+    let output = export();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("incompatible types"));
+}
+
+#[cfg(feature = "incremental-experimental")]
+#[test]
+fn incremental_reuses_values_between_runs() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let main = dir.path().join("main.ncl");
+    let cache_home = dir.path().join("cache");
+
+    let export = || {
+        Command::new(nickel_bin)
+            .arg("export")
+            .arg("--incremental")
+            .arg(&main)
+            .env("XDG_CACHE_HOME", &cache_home)
+            .env_remove("NICKEL_CACHE_DIR")
+            .output()
+            .expect("Nickel should be runnable")
+    };
+
+    // `std.trace` only prints when the expression is actually evaluated.
+    std::fs::write(
+        &main,
+        "{ a = std.trace \"computing a\" (1 + 1), b = std.trace \"computing b\" 3 }",
+    )
+    .unwrap();
+
+    let output = export();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("computing a") && stderr.contains("computing b"));
+
+    let output = export();
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("computing"));
+
+    // Only the field that changed is evaluated again.
+    std::fs::write(
+        &main,
+        "{ a = std.trace \"computing a\" (1 + 1), b = std.trace \"computing b\" 4 }",
+    )
+    .unwrap();
+
+    let output = export();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("computing a") && stderr.contains("computing b"));
+
+    // `--incremental` alone doesn't enable the cache of typechecking results.
+    assert!(cache_home.join("nickel").join("incremental").exists());
+    assert!(!cache_home.join("nickel").join("typecheck").exists());
+}
+
+#[test]
+fn cache_dir_typechecks_imports_with_wildcards() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let main = dir.path().join("main.ncl");
+    let cache_dir = dir.path().join("cache");
+
+    std::fs::write(&main, "(import \"lib.ncl\").double \"a\"").unwrap();
+    std::fs::write(
+        dir.path().join("lib.ncl"),
+        "{ double : _ = fun x => x * 2 }",
+    )
+    .unwrap();
+
+    // The contract generated from the wildcard must be applied even when the main file is found
+    // in the cache.
+    for _ in 0..2 {
+        let output = Command::new(nickel_bin)
+            .arg("export")
+            .arg(&main)
+            .arg("--cache-dir")
+            .arg(&cache_dir)
+            .output()
+            .expect("Nickel should be runnable");
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr)
+                .contains("contract broken by the caller of `double`")
+        );
+    }
+
+    // The library has wildcards, so only the main file is recorded.
+    let entries = std::fs::read_dir(cache_dir.join("typecheck"))
+        .unwrap()
+        .count();
+    assert_eq!(entries, 1);
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn diff_against_git_revision() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let repo = tempdir().expect("should be able to make a temporary directory");
+
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repo.path())
+            .output()
+            .expect("git should be runnable");
+        assert!(output.status.success());
+    };
+
+    git(&["init"]);
+    git(&["config", "user.email", "test-runner@example.com"]);
+    git(&["config", "user.name", "Test Runner"]);
+
+    // The imported file is taken from the same revision as the main file.
+    let main = repo.path().join("main.ncl");
+    std::fs::write(
+        &main,
+        "{ port = import \"port.ncl\", host = \"localhost\" }",
+    )
+    .unwrap();
+    std::fs::write(repo.path().join("port.ncl"), "8080").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-m", "initial"]);
+
+    std::fs::write(repo.path().join("port.ncl"), "9090").unwrap();
+
+    let output = Command::new(nickel_bin)
+        .arg("diff")
+        .arg("--rev")
+        .arg("HEAD")
+        .arg(&main)
+        .output()
+        .expect("Nickel should be runnable");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("~ port"));
+    assert!(stdout.contains("- 8080"));
+    assert!(stdout.contains("+ 9090"));
+    assert!(!stdout.contains("host"));
+}
@@ -567,7 +567,8 @@ impl From<&term::UnaryOp> for PrimOp {
             | term::UnaryOp::RecDefault
             | term::UnaryOp::RecForce
             | term::UnaryOp::ContractPostprocessResult
-            | term::UnaryOp::ContractAttachDefaultLabel) => {
+            | term::UnaryOp::ContractAttachDefaultLabel
+            | term::UnaryOp::NativeCall(_)) => {
                 panic!("didn't expect {op} at the parsing stage")
             }
         }
@@ -14,20 +14,24 @@ use crate::{
         compat::{ToAst, ToMainline},
     },
     closurize::Closurize as _,
-    error::{Error, ImportError, ImportErrorKind, ParseError, ParseErrors, TypecheckError},
+    error::{
+        Error, ImportError, ImportErrorKind, ParseError, ParseErrors, TypecheckError, Warning,
+    },
     eval::{self, cache::Cache as EvalCache, value::NickelValue},
     files::{FileId, Files},
-    identifier::LocIdent,
+    identifier::{Ident, LocIdent},
+    lint,
     metrics::measure_runtime,
     package::PackageMap,
     parser::{self, ErrorTolerantParser, ExtendedTerm, lexer::Lexer},
+    persistent_cache::PersistentCache,
     position::{PosIdx, PosTable, TermPos},
     program::FieldPath,
     serialize::yaml::Listify,
     stdlib::{self as nickel_stdlib, StdlibModule},
     term::{self},
     transform::{self, Wildcards, import_resolution},
-    traverse::TraverseOrder,
+    traverse::{TraverseControl, TraverseOrder},
     typ::UnboundTypeVariableError,
     typecheck::{self, HasApparentType, TypecheckMode, typecheck},
 };
@@ -228,6 +232,30 @@ impl TermCache {
 /// use it as the path: `format!({IN_MEMORY_SOURCE_PATH_PREFIX}{src_name})`.
 pub const IN_MEMORY_SOURCE_PATH_PREFIX: &str = "%inmem_src%:";
 
+/// A custom source of imported files, consulted before the filesystem. This makes it possible to
+/// import files that only exist in memory or in a database, for example.
+pub trait SourceLoader: Send + Sync {
+    /// Returns the content of the file at `path`, or `None` if this loader doesn't provide it.
+    ///
+    /// The path is normalized, but it's only absolute if the importing file has an absolute path
+    /// or if the import comes from an absolute import path.
+    fn load_file(&self, path: &Path) -> Option<io::Result<String>>;
+
+    /// Returns the directory of the package `id`, or `None` if this loader doesn't provide it, in
+    /// which case the package map is used. The main file of the package, `main.ncl` in this
+    /// directory, is then loaded like any other file.
+    fn package_dir(&self, _id: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// An error of [SourceCache::get_or_add_file]. Import resolution tries the next candidate directory
+/// on filesystem errors, but reports the errors of the loader.
+enum FileLookupError {
+    Loader(io::Error),
+    Filesystem(io::Error),
+}
+
 /// The source cache handles reading textual data from the file system or other sources and storing
 /// it in a [Files] instance.
 ///
@@ -250,6 +278,11 @@ pub struct SourceCache {
     pub packages: HashMap<FileId, PathBuf>,
     /// The map used to resolve package imports.
     pub package_map: Option<PackageMap>,
+    /// A custom source of imported files, which takes precedence over the filesystem.
+    pub loader: Option<Arc<dyn SourceLoader>>,
+    /// Whether imported files can be read from the filesystem. When this is disabled, importing a
+    /// file that isn't provided by [Self::loader] fails.
+    pub allow_filesystem: bool,
 }
 
 impl SourceCache {
@@ -263,6 +296,8 @@ impl SourceCache {
             import_paths: Vec::new(),
             packages: HashMap::new(),
             package_map: None,
+            loader: None,
+            allow_filesystem: true,
         }
     }
 
@@ -284,6 +319,66 @@ impl SourceCache {
         self.package_map = Some(map);
     }
 
+    /// Sets a custom source of imported files, which takes precedence over the filesystem.
+    pub fn set_loader(&mut self, loader: Arc<dyn SourceLoader>) {
+        self.loader = Some(loader);
+    }
+
+    /// Returns the directory of the package `id` imported from the file `parent`, as given by the
+    /// loader or, if the loader doesn't know about this package, by the package map.
+    fn package_dir(
+        &self,
+        parent: Option<FileId>,
+        id: Ident,
+        pos: TermPos,
+    ) -> Result<PathBuf, ImportErrorKind> {
+        if let Some(dir) = self
+            .loader
+            .as_ref()
+            .and_then(|loader| loader.package_dir(id.label()))
+        {
+            return Ok(dir);
+        }
+
+        let package_map = self
+            .package_map
+            .as_ref()
+            .ok_or(ImportErrorKind::NoPackageMap { pos })?;
+        let parent_path = parent
+            .and_then(|p| self.packages.get(&p))
+            .map(PathBuf::as_path);
+        Ok(package_map.get(parent_path, id, pos)?.to_owned())
+    }
+
+    /// Tries to load a file from the loader, if there's one. Returns `None` if the file should be
+    /// looked up in the filesystem instead.
+    fn get_or_load_file(
+        &mut self,
+        path: &Path,
+        format: InputFormat,
+    ) -> Option<io::Result<CacheOp<FileId>>> {
+        let loader = self.loader.clone()?;
+        // Unlike for the filesystem, relative paths don't make sense for the loader: it gets them
+        // as is.
+        let normalized = if path.is_relative() {
+            normalize_rel_path(path)
+        } else {
+            normalize_abs_path(path)
+        };
+        let source_path = SourcePath::Path(normalized.clone(), format);
+
+        if let Some(NameIdEntry {
+            id,
+            source: SourceKind::Memory,
+        }) = self.file_ids.get(&source_path)
+        {
+            return Some(Ok(CacheOp::Cached(*id)));
+        }
+
+        let contents = loader.load_file(&normalized)?;
+        Some(contents.map(|contents| CacheOp::Done(self.add_string(source_path, contents))))
+    }
+
     /// Same as [Self::add_file], but assumes that the path is already normalized and takes the
     /// timestamp as a parameter.
     fn add_normalized_file(
@@ -346,8 +441,32 @@ impl SourceCache {
         path: impl Into<OsString>,
         format: InputFormat,
     ) -> io::Result<CacheOp<FileId>> {
-        let path = path.into();
-        let normalized = normalize_path(&path)?;
+        self.lookup_file(path.into(), format)
+            .map_err(|(FileLookupError::Loader(err) | FileLookupError::Filesystem(err))| err)
+    }
+
+    /// Same as [Self::get_or_add_file], but only fails if the loader fails. Filesystem errors,
+    /// such as the file not existing, give `Ok(None)` instead, so that import resolution can go on
+    /// with the next candidate directory.
+    fn get_or_add_import(
+        &mut self,
+        path: &Path,
+        format: InputFormat,
+    ) -> io::Result<Option<CacheOp<FileId>>> {
+        match self.lookup_file(path.into(), format) {
+            Ok(op) => Ok(Some(op)),
+            Err(FileLookupError::Loader(err)) => Err(err),
+            Err(FileLookupError::Filesystem(_)) => Ok(None),
+        }
+    }
+
+    /// Implements [Self::get_or_add_file], keeping track of where the error comes from.
+    fn lookup_file(
+        &mut self,
+        path: OsString,
+        format: InputFormat,
+    ) -> Result<CacheOp<FileId>, FileLookupError> {
+        let normalized = normalize_path(&path).map_err(FileLookupError::Filesystem)?;
 
         // Try to fetch a generated source if the path starts with a hardcoded prefix
         let generated_entry = path
@@ -362,11 +481,26 @@ impl SourceCache {
             return Ok(CacheOp::Cached(entry.id));
         }
 
-        match self.id_or_new_timestamp_of(normalized.as_ref(), format)? {
+        if let Some(result) = self.get_or_load_file(path.as_ref(), format) {
+            return result.map_err(FileLookupError::Loader);
+        }
+
+        if !self.allow_filesystem {
+            return Err(FileLookupError::Filesystem(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "filesystem access is disabled",
+            )));
+        }
+
+        match self
+            .id_or_new_timestamp_of(normalized.as_ref(), format)
+            .map_err(FileLookupError::Filesystem)?
+        {
             SourceState::UpToDate(id) => Ok(CacheOp::Cached(id)),
             SourceState::Stale(timestamp) => self
                 .add_normalized_file(normalized, format, timestamp)
-                .map(CacheOp::Done),
+                .map(CacheOp::Done)
+                .map_err(FileLookupError::Filesystem),
         }
     }
 
@@ -517,6 +651,25 @@ impl SourceCache {
         }
     }
 
+    /// Returns the path of a source if it was loaded from the filesystem and hasn't been replaced
+    /// by an in-memory source since. Returns `None` for any other kind of source.
+    pub fn filesystem_path(&self, file_id: FileId) -> Option<&Path> {
+        let SourcePath::Path(path, format) = self.file_paths.get(&file_id)? else {
+            return None;
+        };
+
+        match self
+            .file_ids
+            .get(&SourcePath::Path(path.clone(), *format))?
+        {
+            NameIdEntry {
+                id,
+                source: SourceKind::Filesystem(_),
+            } if *id == file_id => Some(path),
+            _ => None,
+        }
+    }
+
     /// Gets a reference to the underlying files. Required by the WASM REPL error reporting code
     /// and LSP functions.
     pub fn files(&self) -> &Files {
@@ -585,6 +738,13 @@ impl SourceCache {
                     .map_err(|err| ParseError::from_serde_json(err, Some((file_id, &self.files))))
             }
             InputFormat::Text => Ok(NickelValue::string(source, pos_idx)),
+            InputFormat::Csv => {
+                crate::serialize::csv_deser::from_str(pos_table, source, file_id, b',')
+            }
+            InputFormat::Tsv => {
+                crate::serialize::csv_deser::from_str(pos_table, source, file_id, b'\t')
+            }
+            InputFormat::Ini => crate::serialize::ini_deser::from_str(pos_table, source, file_id),
         }
     }
 
@@ -594,6 +754,30 @@ impl SourceCache {
         self.files.is_stdlib(file)
     }
 
+    /// Returns true if a file belongs to a package dependency, that is if it's located in the
+    /// directory of one of the packages of the package map, or of a package provided by the
+    /// loader.
+    pub fn is_package_dependency(&self, file: FileId) -> bool {
+        if self.packages.contains_key(&file) {
+            return true;
+        }
+
+        let Some(path) = self
+            .file_paths
+            .get(&file)
+            .and_then(|path| <&OsStr>::try_from(path).ok())
+            .map(Path::new)
+        else {
+            return false;
+        };
+
+        self.package_map
+            .iter()
+            .flat_map(|map| map.top_level.values().chain(map.packages.values()))
+            .chain(self.packages.values())
+            .any(|package_dir| path.starts_with(package_dir))
+    }
+
     /// Retrieves the file id for a given standard library module.
     pub fn get_submodule_file_id(&self, module: StdlibModule) -> Option<FileId> {
         self.stdlib_modules()
@@ -757,6 +941,8 @@ pub struct CacheHub {
     pub asts: AstCache,
     pub wildcards: WildcardsCache,
     pub import_data: ImportData,
+    /// An optional on-disk cache of typechecking results, shared between runs.
+    pub persistent: Option<PersistentCache>,
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
     pub skip_stdlib: bool,
@@ -770,6 +956,7 @@ impl CacheHub {
             asts: AstCache::empty(),
             wildcards: WildcardsCache::new(),
             import_data: ImportData::new(),
+            persistent: None,
             #[cfg(debug_assertions)]
             skip_stdlib: false,
         }
@@ -1109,6 +1296,13 @@ impl CacheHub {
         asts.typecheck_stdlib(slice)
     }
 
+    /// Checks the lints on the Nickel sources of the cache which haven't been linted yet, except
+    /// for the standard library and package dependencies, and returns the warnings found. See
+    /// [crate::lint].
+    pub fn lint(&mut self) -> Vec<Warning> {
+        self.asts.lint(&self.sources)
+    }
+
     /// Loads, parses, compiles and applies program transformations to the standard library. We
     /// don't typecheck for performance reasons: this is done in the test suite.
     pub fn prepare_stdlib(&mut self, pos_table: &mut PosTable) -> Result<(), Error> {
@@ -1387,6 +1581,17 @@ impl CacheHub {
         self.asts.add_dyn_type_bindings(&self.sources, idents);
     }
 
+    /// Registers each given identifier in the typing environment with the given type. Used to
+    /// expose host-provided functions to the typechecker. See
+    /// [AstCache::add_static_type_bindings] for details.
+    pub fn add_static_type_bindings<'a, I>(&mut self, pos_table: &PosTable, bindings: I)
+    where
+        I: IntoIterator<Item = (crate::identifier::Ident, &'a crate::typ::Type)>,
+    {
+        self.asts
+            .add_static_type_bindings(&self.sources, pos_table, bindings);
+    }
+
     /// Converts an AST and all of its transitive dependencies to the runtime representation,
     /// populating the term cache. `file_id` and any of its Nickel dependencies must be present in
     /// the AST cache, or [CacheError::IncompatibleState] is returned. However, for non-Nickel
@@ -1543,6 +1748,7 @@ impl CacheHub {
             asts: AstCache::empty(),
             wildcards: self.wildcards.clone(),
             import_data: self.import_data.clone(),
+            persistent: None,
             #[cfg(debug_assertions)]
             skip_stdlib: self.skip_stdlib,
         }
@@ -1557,6 +1763,7 @@ impl CacheHub {
                 sources: &mut self.sources,
                 wildcards: &mut self.wildcards,
                 import_data: &mut self.import_data,
+                persistent: self.persistent.as_mut(),
                 #[cfg(debug_assertions)]
                 skip_stdlib: self.skip_stdlib,
             },
@@ -1580,6 +1787,7 @@ pub struct CacheHubView<'cache> {
     sources: &'cache mut SourceCache,
     wildcards: &'cache mut WildcardsCache,
     import_data: &'cache mut ImportData,
+    persistent: Option<&'cache mut PersistentCache>,
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
     skip_stdlib: bool,
@@ -1593,6 +1801,7 @@ impl CacheHubView<'_> {
             sources: self.sources,
             wildcards: self.wildcards,
             import_data: self.import_data,
+            persistent: self.persistent.as_deref_mut(),
             #[cfg(debug_assertions)]
             skip_stdlib: self.skip_stdlib,
         }
@@ -1613,6 +1822,9 @@ pub struct AstEntry<'ast> {
     pub ast: &'ast Ast<'ast>,
     pub state: AstEntryState,
     pub format: InputFormat,
+    /// Whether the lints have already been checked on this entry. Lints are checked independently
+    /// of the other stages, so this isn't part of [Self::state].
+    pub linted: bool,
 }
 
 impl<'ast> AstEntry<'ast> {
@@ -1622,6 +1834,7 @@ impl<'ast> AstEntry<'ast> {
             ast,
             state: AstEntryState::default(),
             format: InputFormat::default(),
+            linted: false,
         }
     }
 }
@@ -2017,19 +2230,11 @@ impl ImportResolver for CacheHub {
                 )
             }
             term::Import::Package { id } => {
-                let package_map = self
-                    .sources
-                    .package_map
-                    .as_ref()
-                    .ok_or(ImportErrorKind::NoPackageMap { pos })?;
-                let parent_path = parent
-                    .and_then(|p| self.sources.packages.get(&p))
-                    .map(PathBuf::as_path);
-                let pkg_path = package_map.get(parent_path, *id, pos)?;
+                let pkg_path = self.sources.package_dir(parent, *id, pos)?;
                 (
-                    vec![pkg_path.to_owned()],
+                    vec![pkg_path.clone()],
                     Path::new("main.ncl"),
-                    Some(pkg_path.to_owned()),
+                    Some(pkg_path),
                     // Packages are always in nickel format
                     InputFormat::Nickel,
                 )
@@ -2043,10 +2248,14 @@ impl ImportResolver for CacheHub {
                 let mut path_buf = parent.clone();
                 path_buf.push(path);
                 self.sources
-                    .get_or_add_file(&path_buf, format)
-                    .ok()
-                    .map(|x| (x, path_buf))
+                    .get_or_add_import(&path_buf, format)
+                    .map(|x| x.map(|x| (x, path_buf)))
+                    .transpose()
             })
+            .transpose()
+            .map_err(|err| {
+                ImportErrorKind::IOError(path.to_string_lossy().into_owned(), err.to_string(), pos)
+            })?
             .ok_or_else(|| {
                 let parents = possible_parents
                     .iter()
@@ -2306,19 +2515,11 @@ impl AstImportResolver for AstResolver<'_, '_> {
                 )
             }
             ast::Import::Package { id } => {
-                let package_map = self
-                    .sources
-                    .package_map
-                    .as_ref()
-                    .ok_or(ImportErrorKind::NoPackageMap { pos: *pos })?;
-                let parent_path = parent_id
-                    .and_then(|p| self.sources.packages.get(&p))
-                    .map(PathBuf::as_path);
-                let pkg_path = package_map.get(parent_path, *id, *pos)?;
+                let pkg_path = self.sources.package_dir(parent_id, *id, *pos)?;
                 (
-                    vec![pkg_path.to_owned()],
+                    vec![pkg_path.clone()],
                     Path::new("main.ncl"),
-                    Some(pkg_path.to_owned()),
+                    Some(pkg_path),
                     // Packages are always in nickel format
                     InputFormat::Nickel,
                 )
@@ -2331,8 +2532,14 @@ impl AstImportResolver for AstResolver<'_, '_> {
             .find_map(|parent| {
                 let mut path_buf = parent.clone();
                 path_buf.push(path);
-                self.sources.get_or_add_file(&path_buf, format).ok()
+                self.sources
+                    .get_or_add_import(&path_buf, format)
+                    .transpose()
             })
+            .transpose()
+            .map_err(|err| {
+                ImportErrorKind::IOError(path.to_string_lossy().into_owned(), err.to_string(), *pos)
+            })?
             .ok_or_else(|| {
                 let parents = possible_parents
                     .iter()
@@ -2678,6 +2885,31 @@ mod ast_cache {
             })
         }
 
+        /// Checks the lints on the entries which haven't been linted yet, except for the standard
+        /// library and package dependencies, and marks them as linted. The warnings are returned
+        /// in the order of the file ids, so that the output is deterministic.
+        pub fn lint(&mut self, sources: &SourceCache) -> Vec<Warning> {
+            self.with_asts_mut(|asts| {
+                let mut entries: Vec<_> = asts
+                    .iter_mut()
+                    .filter(|(file_id, entry)| {
+                        !entry.linted
+                            && !sources.is_stdlib_module(**file_id)
+                            && !sources.is_package_dependency(**file_id)
+                    })
+                    .collect();
+                entries.sort_by_key(|(file_id, _)| **file_id);
+
+                entries
+                    .into_iter()
+                    .flat_map(|(_, entry)| {
+                        entry.linted = true;
+                        lint::lint(entry.ast)
+                    })
+                    .collect()
+            })
+        }
+
         pub fn remove(&mut self, file_id: FileId) -> Option<AstEntry<'_>> {
             self.with_asts_mut(|asts| asts.remove(&file_id))
         }
@@ -2710,6 +2942,17 @@ mod ast_cache {
                 return Ok(CacheOp::Cached(()));
             }
 
+            // If a previous run already typechecked this file, and neither the file nor its
+            // imports have changed since, we might be able to skip typechecking the file itself.
+            // We still need to resolve its imports and to typecheck them, which is otherwise done
+            // by the typechecker. If an import fails to resolve, we fall back to typechecking,
+            // which reports the error.
+            let candidate = slice
+                .persistent
+                .as_deref_mut()
+                .and_then(|persistent| persistent.lookup(slice.sources, file_id, initial_mode))
+                .filter(|_| self.resolve_imports(slice.reborrow(), file_id).is_ok());
+
             // Protect against cycles in the import graph.
             // unwrap(): we checked at the beginning of this function that the term is in the
             // cache.
@@ -2719,21 +2962,10 @@ mod ast_cache {
 
             // Ensure the initial typing context is properly initialized.
             self.populate_type_ctxt(slice.sources);
-            self.with_mut(|slf| -> Result<(), AstCacheError<TypecheckError>> {
-                // unwrap(): we checked at the beginning of this function that the AST cache has an
-                // entry for `file_id`.
-                let ast = slf.asts.get(&file_id).unwrap().ast;
-
-                let mut resolver = AstResolver::new(slf.alloc, slf.asts, slice.reborrow());
-                let type_ctxt = slf.type_ctxt.clone();
-                let wildcards_map = measure_runtime!(
-                    "runtime:type_check",
-                    typecheck(slf.alloc, ast, type_ctxt, &mut resolver, initial_mode)?
-                );
-                slf.wildcards.insert(file_id, wildcards_map);
 
-                Ok(())
-            })?;
+            if candidate.is_none() {
+                self.typecheck_ast(file_id, slice.reborrow(), initial_mode)?;
+            }
 
             // Typecheck dependencies (files imported by this file).
             if let Some(imports) = slice.import_data.imports.get(&file_id) {
@@ -2758,6 +2990,27 @@ mod ast_cache {
                 }
             }
 
+            if let Some(candidate) = candidate {
+                // unwrap(): we only get a candidate if the persistent cache is enabled.
+                let confirmed = slice.persistent.as_deref_mut().unwrap().confirm(
+                    slice.sources,
+                    slice.import_data,
+                    file_id,
+                    candidate,
+                    |id| {
+                        self.get_wildcards(id)
+                            .is_some_and(|wildcards| !wildcards.is_empty())
+                    },
+                );
+
+                if !confirmed {
+                    self.typecheck_ast(file_id, slice.reborrow(), initial_mode)?;
+                    self.record_typechecked(slice.reborrow(), file_id, initial_mode);
+                }
+            } else {
+                self.record_typechecked(slice.reborrow(), file_id, initial_mode);
+            }
+
             // unwrap(): we checked at the beginning of this function that the AST is in the
             // cache.
             let _ = self
@@ -2767,6 +3020,95 @@ mod ast_cache {
             Ok(CacheOp::Done(()))
         }
 
+        /// Runs the typechecker on the AST of `file_id`, which must be in the cache, and stores
+        /// the inferred types of wildcards. The imports encountered along the way are resolved,
+        /// but not typechecked.
+        fn typecheck_ast(
+            &mut self,
+            file_id: FileId,
+            slice: CacheHubView<'_>,
+            initial_mode: TypecheckMode,
+        ) -> Result<(), AstCacheError<TypecheckError>> {
+            self.with_mut(|slf| {
+                // unwrap(): the caller ensures that the AST cache has an entry for `file_id`.
+                let ast = slf.asts.get(&file_id).unwrap().ast;
+
+                let mut resolver = AstResolver::new(slf.alloc, slf.asts, slice);
+                let type_ctxt = slf.type_ctxt.clone();
+                let wildcards_map = measure_runtime!(
+                    "runtime:type_check",
+                    typecheck(slf.alloc, ast, type_ctxt, &mut resolver, initial_mode)?
+                );
+                slf.wildcards.insert(file_id, wildcards_map);
+
+                Ok(())
+            })
+        }
+
+        /// Resolves the imports of `file_id`, which must be in the cache, without typechecking
+        /// it. This is what the typechecker would have done, had it been run.
+        fn resolve_imports(
+            &mut self,
+            slice: CacheHubView<'_>,
+            file_id: FileId,
+        ) -> Result<(), ImportErrorKind> {
+            self.with_mut(|slf| {
+                // unwrap(): the caller ensures that the AST cache has an entry for `file_id`.
+                let ast = slf.asts.get(&file_id).unwrap().ast;
+                let mut imports = Vec::new();
+
+                ast.traverse_ref(
+                    &mut |ast: &Ast<'_>, _: &()| {
+                        if let ast::Node::Import(import) = &ast.node {
+                            imports.push((import, ast.pos));
+                        }
+
+                        TraverseControl::<(), ()>::Continue
+                    },
+                    &(),
+                );
+
+                let mut resolver = AstResolver::new(slf.alloc, slf.asts, slice);
+
+                imports
+                    .into_iter()
+                    .try_for_each(|(import, pos)| resolver.resolve(import, &pos).map(|_| ()))
+            })
+        }
+
+        /// Records in the persistent cache, if enabled, that `file_id` and its imports have been
+        /// successfully typechecked.
+        fn record_typechecked(
+            &self,
+            slice: CacheHubView<'_>,
+            file_id: FileId,
+            initial_mode: TypecheckMode,
+        ) {
+            let Some(persistent) = slice.persistent else {
+                return;
+            };
+
+            let has_wildcards = |file_id| {
+                self.get_wildcards(file_id)
+                    .is_some_and(|wildcards| !wildcards.is_empty())
+            };
+
+            // The types inferred for wildcards are needed later to generate contracts, so we
+            // can't skip typechecking of a file which has some.
+            let persist = !has_wildcards(file_id);
+
+            // The persistent cache is only an optimization: failing to write to it shouldn't make
+            // typechecking fail.
+            let _ = persistent.record(
+                slice.sources,
+                slice.import_data,
+                file_id,
+                initial_mode,
+                has_wildcards,
+                persist,
+            );
+        }
+
         /// Typechecks the stdlib. This has to be public because it's used in benches. It probably
         /// does not have to be used for something else.
         pub fn typecheck_stdlib(
@@ -2884,6 +3226,28 @@ mod ast_cache {
             });
         }
 
+        /// Registers each given identifier in the typing environment with the given type, converted
+        /// from the runtime representation.
+        ///
+        /// As for [Self::add_dyn_type_bindings], the initial typing context is populated first.
+        pub fn add_static_type_bindings<'a, I>(
+            &mut self,
+            sources: &SourceCache,
+            pos_table: &PosTable,
+            bindings: I,
+        ) where
+            I: IntoIterator<Item = (crate::identifier::Ident, &'a crate::typ::Type)>,
+        {
+            self.populate_type_ctxt(sources);
+            self.with_mut(|slf| {
+                for (ident, typ) in bindings {
+                    let typ: ast::typ::Type<'_> = typ.to_ast(slf.alloc, pos_table);
+                    let uty = typecheck::UnifType::from_type(typ, &slf.type_ctxt.term_env);
+                    slf.type_ctxt.type_env.insert(ident, uty);
+                }
+            });
+        }
+
         /// Adds a binding to the type environment. The bound term is identified by its file id
         /// `file_id`.
         pub fn add_type_binding(
@@ -2985,6 +3349,31 @@ mod tests {
         assert_eq!(CacheOp::Cached(file_id), file);
     }
 
+    #[test]
+    fn lint_skips_package_dependencies() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("nickel-test-rootdir");
+        let mut cache = CacheHub::new();
+        cache.sources.set_package_map(PackageMap {
+            top_level: HashMap::from([(Ident::new("dep"), root.join("dep"))]),
+            packages: HashMap::new(),
+        });
+
+        let main = cache.sources.add_string(
+            SourcePath::Path(root.join("main.ncl"), InputFormat::Nickel),
+            "let unused = 1 in import dep".to_owned(),
+        );
+        let dep = cache.sources.add_string(
+            SourcePath::Path(root.join("dep").join("lib.ncl"), InputFormat::Nickel),
+            "let unused = 1 in 2".to_owned(),
+        );
+        cache.parse_to_ast(main).unwrap();
+        cache.parse_to_ast(dep).unwrap();
+
+        let warnings = cache.lint();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].pos().src_id(), Some(main));
+    }
+
     #[test]
     fn close_file() {
         let mut sources = SourceCache::new();
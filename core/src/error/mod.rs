@@ -2,7 +2,7 @@
 //!
 //! Define error types for different phases of the execution, together with functions to generate a
 //! [codespan](https://crates.io/crates/codespan-reporting) diagnostic from them.
-pub use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle};
+pub use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle, Severity};
 
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream, WriteColor};
 use malachite::base::num::conversion::traits::ToSci;
@@ -19,6 +19,7 @@ use crate::{
     cache::InputFormat,
     eval::{
         callstack::CallStack,
+        limits::Interruption,
         value::{EnumVariantData, NickelValue},
     },
     files::{FileId, Files},
@@ -107,6 +108,7 @@ pub enum Error {
     ExportError(ExportError),
     IOError(IOError),
     ReplError(ReplError),
+    ConversionError(ConversionError),
 }
 
 pub type EvalError = Box<EvalErrorData>;
@@ -306,12 +308,56 @@ pub enum EvalErrorKind {
         /// Evaluated expression
         value: NickelValue,
     },
+    /// The evaluation performed more steps than allowed by
+    /// [crate::eval::limits::EvalLimits::max_steps].
+    StepLimitExceeded {
+        limit: u64,
+        /// The position of the expression being evaluated when the limit was reached.
+        pos: PosIdx,
+    },
+    /// The evaluation allocated more thunks than allowed by
+    /// [crate::eval::limits::EvalLimits::max_allocations].
+    AllocationLimitExceeded {
+        limit: u64,
+        /// The position of the expression being evaluated when the limit was reached.
+        pos: PosIdx,
+    },
+    /// The evaluation stack grew larger than allowed by
+    /// [crate::eval::limits::EvalLimits::max_stack_depth].
+    StackDepthLimitExceeded {
+        limit: usize,
+        /// The position of the expression being evaluated when the limit was reached.
+        pos: PosIdx,
+    },
+    /// The evaluation took longer than allowed by [crate::eval::limits::EvalLimits::timeout].
+    Timeout {
+        limit: std::time::Duration,
+        /// The position of the expression being evaluated when the limit was reached.
+        pos: PosIdx,
+    },
+    /// The evaluation was cancelled through a [crate::eval::limits::CancellationHandle].
+    Cancelled,
     /// An unexpected internal error.
     InternalError(String, PosIdx),
     /// Errors occurring rarely enough to not deserve a dedicated variant.
     Other(String, PosIdx),
 }
 
+impl EvalErrorKind {
+    /// If this error was raised because a limit set by [crate::eval::limits::EvalLimits] was
+    /// reached, returns which one.
+    pub fn interruption(&self) -> Option<Interruption> {
+        match self {
+            EvalErrorKind::StepLimitExceeded { .. } => Some(Interruption::StepLimit),
+            EvalErrorKind::AllocationLimitExceeded { .. } => Some(Interruption::AllocationLimit),
+            EvalErrorKind::StackDepthLimitExceeded { .. } => Some(Interruption::StackDepthLimit),
+            EvalErrorKind::Timeout { .. } => Some(Interruption::Timeout),
+            EvalErrorKind::Cancelled => Some(Interruption::Cancelled),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum IllegalPolymorphicTailAction {
     FieldAccess { field: String },
@@ -676,6 +722,27 @@ pub enum ExportErrorKind {
     ExpectedArray {
         value: NickelValue,
     },
+    /// The export format expects the value to be a record.
+    ExpectedRecord {
+        format: ExportFormat,
+        value: NickelValue,
+    },
+    /// Encountered a record or an array for a flat format that only supports primitive values.
+    UnsupportedNestedValue {
+        format: ExportFormat,
+        value: NickelValue,
+    },
+    /// A field name isn't a valid environment variable name, when exporting to the env format.
+    InvalidEnvName {
+        name: String,
+        value: NickelValue,
+    },
+    /// A string contains a character that XML doesn't allow, such as most control characters,
+    /// when exporting to an XML property list.
+    InvalidXmlChar {
+        ch: char,
+        value: NickelValue,
+    },
     Other(String),
 }
 
@@ -683,6 +750,11 @@ pub enum ExportErrorKind {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IOError(pub String);
 
+/// An error occurring when converting a value of the host language to Nickel, for example because
+/// it's a map with non-string keys.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ConversionError(pub String);
+
 /// An error occurring during an REPL session.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ReplErrorKind {
@@ -762,6 +834,12 @@ impl From<IOError> for Error {
     }
 }
 
+impl From<ConversionError> for Error {
+    fn from(error: ConversionError) -> Error {
+        Error::ConversionError(error)
+    }
+}
+
 impl From<std::io::Error> for IOError {
     fn from(error: std::io::Error) -> IOError {
         IOError(error.to_string())
@@ -793,6 +871,7 @@ impl From<BuilderError> for Error {
                 error,
             } => Error::IOError(IOError(format!("source {}: {error}", p.display()))),
             BuilderError::Io { path: None, error } => Error::IOError(IOError(error.to_string())),
+            BuilderError::NativeFunctionType { error, .. } => Error::ParseErrors(error.into()),
         }
     }
 }
@@ -968,6 +1047,28 @@ fn cardinal(number: usize) -> String {
     format!("{number}{suffix}")
 }
 
+/// Builds the diagnostic of an evaluation stopped by one of its [resource
+/// limits][crate::eval::limits::EvalLimits].
+fn limit_exceeded_diagnostic(
+    pos_table: &PosTable,
+    pos: PosIdx,
+    message: &str,
+    note: String,
+) -> Vec<Diagnostic<FileId>> {
+    let labels = pos_table
+        .get(pos)
+        .as_opt_ref()
+        .map(|span| vec![primary(span).with_message("evaluation stopped here")])
+        .unwrap_or_default();
+
+    vec![
+        Diagnostic::error()
+            .with_message(message)
+            .with_labels(labels)
+            .with_notes(vec![note]),
+    ]
+}
+
 impl<T: IntoDiagnostics> IntoDiagnostics for Box<T> {
     fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
         (*self).into_diagnostics(files)
@@ -988,6 +1089,7 @@ impl IntoDiagnostics for Error {
             Error::ExportError(err) => err.into_diagnostics(files),
             Error::IOError(err) => err.into_diagnostics(files),
             Error::ReplError(err) => err.into_diagnostics(files),
+            Error::ConversionError(err) => err.into_diagnostics(files),
         }
     }
 }
@@ -1305,6 +1407,36 @@ impl IntoDiagnostics for EvalErrorData {
                         .with_labels(labels),
                 ]
             }
+            EvalErrorKind::StepLimitExceeded { limit, pos } => limit_exceeded_diagnostic(
+                &pos_table,
+                pos,
+                "evaluation step limit exceeded",
+                format!("the evaluation was stopped after {limit} steps"),
+            ),
+            EvalErrorKind::AllocationLimitExceeded { limit, pos } => limit_exceeded_diagnostic(
+                &pos_table,
+                pos,
+                "allocation limit exceeded",
+                format!("the evaluation was stopped after allocating {limit} thunks"),
+            ),
+            EvalErrorKind::StackDepthLimitExceeded { limit, pos } => limit_exceeded_diagnostic(
+                &pos_table,
+                pos,
+                "stack depth limit exceeded",
+                format!(
+                    "the evaluation stack grew larger than {limit} items, which usually \
+                        indicates a deep or unbounded recursion"
+                ),
+            ),
+            EvalErrorKind::Timeout { limit, pos } => limit_exceeded_diagnostic(
+                &pos_table,
+                pos,
+                "evaluation timed out",
+                format!("the evaluation was stopped after {limit:?}"),
+            ),
+            EvalErrorKind::Cancelled => {
+                vec![Diagnostic::error().with_message("evaluation cancelled")]
+            }
             EvalErrorKind::Other(msg, span_opt) => {
                 let labels = pos_table
                     .get(span_opt)
@@ -2140,7 +2272,7 @@ impl IntoDiagnostics for ParseError {
                 .with_message("unknown import format tag")
                 .with_labels(vec![primary(&span)])
                 .with_notes(vec![
-                    "Examples of valid format tags: 'Nickel, 'Json, 'Yaml, 'Toml, 'Text"
+                    "Examples of valid format tags: 'Nickel, 'Json, 'Yaml, 'Toml, 'Csv, 'Ini, 'Text"
                         .to_owned()
                 ]),
             ParseError::UnknownSigilSelector { selector, span } => {
@@ -2922,6 +3054,58 @@ impl IntoDiagnostics for ExportErrorData {
                         .with_notes(notes),
                 ]
             }
+            ExportErrorKind::ExpectedRecord { format, value } => {
+                vec![
+                    Diagnostic::error()
+                        .with_message(format!("{format} export expects a record"))
+                        .with_labels(vec![primary_term(&pos_table, &value, files)])
+                        .with_notes(notes),
+                ]
+            }
+            ExportErrorKind::UnsupportedNestedValue { format, value } => {
+                notes.push(format!(
+                    "The {format} format only supports strings, numbers, booleans and enum tags \
+                    as field values."
+                ));
+
+                vec![
+                    Diagnostic::error()
+                        .with_message(format!("{format} format doesn't support nested values"))
+                        .with_labels(vec![primary_term(&pos_table, &value, files)])
+                        .with_notes(notes),
+                ]
+            }
+            ExportErrorKind::InvalidEnvName { name, value } => {
+                notes.push(
+                    "Environment variable names must only contain ASCII letters, digits and \
+                    underscores, and can't start with a digit."
+                        .to_owned(),
+                );
+
+                vec![
+                    Diagnostic::error()
+                        .with_message(format!("`{name}` isn't a valid environment variable name"))
+                        .with_labels(vec![primary_term(&pos_table, &value, files)])
+                        .with_notes(notes),
+                ]
+            }
+            ExportErrorKind::InvalidXmlChar { ch, value } => {
+                notes.push(
+                    "XML documents can't contain control characters other than tabs and line \
+                    breaks, even escaped."
+                        .to_owned(),
+                );
+
+                vec![
+                    Diagnostic::error()
+                        .with_message(format!(
+                            "the character {} can't be exported to an XML property list",
+                            ch.escape_unicode()
+                        ))
+                        .with_labels(vec![primary_term(&pos_table, &value, files)])
+                        .with_notes(notes),
+                ]
+            }
         }
     }
 }
@@ -2934,6 +3118,16 @@ impl IntoDiagnostics for IOError {
     }
 }
 
+impl IntoDiagnostics for ConversionError {
+    fn into_diagnostics(self, _files: &mut Files) -> Vec<Diagnostic<FileId>> {
+        vec![
+            Diagnostic::error()
+                .with_message("couldn't convert a value to Nickel")
+                .with_notes(vec![self.0]),
+        ]
+    }
+}
+
 impl IntoDiagnostics for ReplErrorKind {
     fn into_diagnostics(self, files: &mut Files) -> Vec<Diagnostic<FileId>> {
         match self {
@@ -1,9 +1,16 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
 use codespan_reporting::diagnostic::Diagnostic;
 
 use crate::error::{IntoDiagnostics, primary, secondary};
 use crate::files::{FileId, Files};
+use crate::identifier::Ident;
+use crate::parser::lexer::{Lexer, NormalToken, Token};
 use crate::position::TermPos;
 
+/// The prefix of the comments silencing warnings in a source file. See [Warning::is_allowed_in].
+pub const ALLOW_DIRECTIVE: &str = "nickel-allow:";
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Warning {
     /// Applied a `fun label value => ...` (or match) as a contract directly,
@@ -14,11 +21,280 @@ pub enum Warning {
         /// The position of the thing that the contract was applied to.
         app_pos: TermPos,
     },
+    /// A variable bound by a let binding is never used.
+    UnusedBinding {
+        name: Ident,
+        /// The position of the variable in the binding.
+        pos: TermPos,
+    },
+    /// A let binding, a function argument or a pattern binds a variable which is already bound by
+    /// an enclosing let binding, function argument or pattern.
+    ShadowedIdentifier {
+        name: Ident,
+        /// The position of the new binding.
+        pos: TermPos,
+        /// The position of the binding being shadowed.
+        shadowed_pos: TermPos,
+    },
+    /// A field is given a value twice with the same priority in the same record literal. Unless
+    /// the values are mergeable records, this either fails at evaluation or is redundant.
+    DuplicateField {
+        /// The path of the field, as written in the record literal.
+        path: String,
+        /// The position of the second definition.
+        pos: TermPos,
+        /// The position of the first definition.
+        prev_pos: TermPos,
+    },
+    /// A match arm can never be selected, because it comes after an arm which matches any value.
+    UnreachableMatchArm {
+        /// The position of the pattern of the unreachable arm.
+        pos: TermPos,
+        /// The position of the pattern which matches any value.
+        catch_all_pos: TermPos,
+    },
+}
+
+/// The kind of a [Warning], which is identified by a stable code and can be configured
+/// independently of the other kinds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WarningKind {
+    NakedFunctionContract,
+    UnusedBinding,
+    ShadowedIdentifier,
+    DuplicateField,
+    /// Reserved for the use of deprecated functions of the standard library. The code is already
+    /// accepted wherever warnings are configured, but no warning of this kind is reported until
+    /// the standard library marks some of its functions as deprecated.
+    DeprecatedStdlib,
+    UnreachableMatchArm,
+}
+
+impl WarningKind {
+    /// All the kinds of warnings.
+    pub const ALL: &[WarningKind] = &[
+        WarningKind::NakedFunctionContract,
+        WarningKind::UnusedBinding,
+        WarningKind::ShadowedIdentifier,
+        WarningKind::DuplicateField,
+        WarningKind::DeprecatedStdlib,
+        WarningKind::UnreachableMatchArm,
+    ];
+
+    /// The stable code of this kind of warning, used in diagnostics and to configure the level of
+    /// warnings.
+    pub fn code(self) -> &'static str {
+        match self {
+            WarningKind::NakedFunctionContract => "naked-function-contract",
+            WarningKind::UnusedBinding => "unused-binding",
+            WarningKind::ShadowedIdentifier => "shadowed-identifier",
+            WarningKind::DuplicateField => "duplicate-field",
+            WarningKind::DeprecatedStdlib => "deprecated-stdlib",
+            WarningKind::UnreachableMatchArm => "unreachable-match-arm",
+        }
+    }
+
+    /// The level of this kind of warning when it isn't configured explicitly. Shadowing is
+    /// idiomatic in many functional programs, and is thus only reported on demand.
+    pub fn default_level(self) -> WarningLevel {
+        match self {
+            WarningKind::ShadowedIdentifier => WarningLevel::Allow,
+            _ => WarningLevel::Warn,
+        }
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// The error returned when parsing an unknown warning code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownWarningKind(pub String);
+
+impl fmt::Display for UnknownWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codes: Vec<_> = WarningKind::ALL.iter().map(|kind| kind.code()).collect();
+        write!(
+            f,
+            "unknown warning `{}` (expected one of {})",
+            self.0,
+            codes.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownWarningKind {}
+
+impl FromStr for WarningKind {
+    type Err = UnknownWarningKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WarningKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.code() == s)
+            .ok_or_else(|| UnknownWarningKind(s.to_owned()))
+    }
+}
+
+/// How a warning is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WarningLevel {
+    /// The warning is silenced.
+    Allow,
+    /// The warning is reported as a warning.
+    Warn,
+    /// The warning is reported as an error.
+    Deny,
+}
+
+/// The level of each kind of warning. Kinds which haven't been set explicitly have their
+/// [default level](WarningKind::default_level).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WarningLevels {
+    levels: HashMap<WarningKind, WarningLevel>,
+}
+
+impl WarningLevels {
+    /// Sets the level of a kind of warning, overriding any previous setting.
+    pub fn set(&mut self, kind: WarningKind, level: WarningLevel) {
+        self.levels.insert(kind, level);
+    }
+
+    /// Sets the level of all the kinds of warnings.
+    pub fn set_all(&mut self, level: WarningLevel) {
+        for kind in WarningKind::ALL {
+            self.set(*kind, level);
+        }
+    }
+
+    /// Returns the level of a kind of warning.
+    pub fn get(&self, kind: WarningKind) -> WarningLevel {
+        self.levels
+            .get(&kind)
+            .copied()
+            .unwrap_or_else(|| kind.default_level())
+    }
+
+    /// Returns the level at which a warning must be reported. A warning which has been silenced
+    /// in its source file is always allowed, whatever the level of its kind.
+    pub fn level_of(&self, warning: &Warning, files: &Files) -> WarningLevel {
+        if warning.is_allowed_in(files) {
+            WarningLevel::Allow
+        } else {
+            self.get(warning.kind())
+        }
+    }
+}
+
+impl Warning {
+    pub fn kind(&self) -> WarningKind {
+        match self {
+            Warning::NakedFunctionContract { .. } => WarningKind::NakedFunctionContract,
+            Warning::UnusedBinding { .. } => WarningKind::UnusedBinding,
+            Warning::ShadowedIdentifier { .. } => WarningKind::ShadowedIdentifier,
+            Warning::DuplicateField { .. } => WarningKind::DuplicateField,
+            Warning::UnreachableMatchArm { .. } => WarningKind::UnreachableMatchArm,
+        }
+    }
+
+    /// The position the warning is about, which is the position of its primary label.
+    pub fn pos(&self) -> TermPos {
+        match self {
+            Warning::NakedFunctionContract { func_pos, app_pos } => app_pos.or(*func_pos),
+            Warning::UnusedBinding { pos, .. }
+            | Warning::ShadowedIdentifier { pos, .. }
+            | Warning::DuplicateField { pos, .. }
+            | Warning::UnreachableMatchArm { pos, .. } => *pos,
+        }
+    }
+
+    /// Returns `true` if the warning has been silenced by a comment in its source file. A comment
+    /// of the form `# nickel-allow: <code>, <code>, ...` silences the warnings with the given codes
+    /// (or all of them, with `all`) on the line of the comment, if it follows some code, or on the
+    /// next line otherwise.
+    pub fn is_allowed_in(&self, files: &Files) -> bool {
+        let Some(span) = self.pos().into_opt() else {
+            return false;
+        };
+
+        let source = files.source(span.src_id);
+        let start = span.start.to_usize().min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |idx| start + idx);
+        let prev_line_start = (line_start > 0).then(|| {
+            source[..line_start - 1]
+                .rfind('\n')
+                .map_or(0, |idx| idx + 1)
+        });
+
+        for (comment_start, comment) in comments(source) {
+            if comment_start >= line_end {
+                break;
+            }
+
+            let Some(codes) = allow_directive(comment) else {
+                continue;
+            };
+
+            if comment_start >= line_start {
+                if self.is_allowed_by(codes) {
+                    return true;
+                }
+            }
+            // The directive might also stand on its own on the previous line.
+            else if let Some(prev_line_start) = prev_line_start
+                && comment_start >= prev_line_start
+                && source[prev_line_start..comment_start].trim().is_empty()
+                && self.is_allowed_by(codes)
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn is_allowed_by(&self, codes: &str) -> bool {
+        let kind = self.kind();
+
+        codes
+            .split(',')
+            .map(str::trim)
+            .any(|code| code == "all" || code == kind.code())
+    }
+}
+
+/// Returns the comments of a source, together with their starting offset. Comments following a
+/// lexing error are ignored.
+fn comments(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    Lexer::new(source)
+        .with_comments()
+        .map_while(Result::ok)
+        .filter_map(|(start, token, end)| match token {
+            Token::Normal(NormalToken::LineComment) => Some((start, &source[start..end])),
+            _ => None,
+        })
+}
+
+/// Returns the list of codes of a comment, if it's an allow directive.
+fn allow_directive(comment: &str) -> Option<&str> {
+    comment
+        .strip_prefix('#')?
+        .trim_start()
+        .strip_prefix(ALLOW_DIRECTIVE)
 }
 
 impl IntoDiagnostics for Warning {
     fn into_diagnostics(self, _files: &mut Files) -> Vec<Diagnostic<FileId>> {
-        match self {
+        let code = self.kind().code();
+
+        let diagnostic = match self {
             Warning::NakedFunctionContract { func_pos, app_pos } => {
                 let mut labels = vec![];
                 if let Some(span) = app_pos.into_opt() {
@@ -29,12 +305,183 @@ impl IntoDiagnostics for Warning {
                     labels.push(secondary(&func_span).with_message("this function"));
                 }
 
-                vec![Diagnostic::warning()
+                Diagnostic::warning()
                     .with_message("plain functions as contracts are deprecated")
                     .with_labels(labels)
                     .with_notes(vec!["wrap this function using one of the constructors in `std.contract` instead, like `std.contract.from_validator` or `std.contract.custom`".to_owned()])
-                ]
             }
+            Warning::UnusedBinding { name, pos } => Diagnostic::warning()
+                .with_message(format!("unused variable `{name}`"))
+                .with_labels(
+                    pos.into_opt()
+                        .map(|span| primary(&span).with_message("this variable is never used"))
+                        .into_iter()
+                        .collect(),
+                )
+                .with_notes(vec![format!(
+                    "If this is intentional, prefix the name with an underscore: `_{name}`."
+                )]),
+            Warning::ShadowedIdentifier {
+                name,
+                pos,
+                shadowed_pos,
+            } => {
+                let mut labels = vec![];
+                if let Some(span) = pos.into_opt() {
+                    labels.push(primary(&span).with_message("this binding"));
+                }
+
+                if let Some(span) = shadowed_pos.into_opt() {
+                    labels.push(secondary(&span).with_message("shadows this binding"));
+                }
+
+                Diagnostic::warning()
+                    .with_message(format!("`{name}` shadows an existing variable"))
+                    .with_labels(labels)
+            }
+            Warning::DuplicateField {
+                path,
+                pos,
+                prev_pos,
+            } => {
+                let mut labels = vec![];
+                if let Some(span) = pos.into_opt() {
+                    labels.push(primary(&span).with_message("defined again here"));
+                }
+
+                if let Some(span) = prev_pos.into_opt() {
+                    labels.push(secondary(&span).with_message("first defined here"));
+                }
+
+                Diagnostic::warning()
+                    .with_message(format!(
+                        "field `{path}` is defined twice with the same priority"
+                    ))
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        "Merging two different values with the same priority fails at evaluation, \
+                        unless both values are records."
+                            .to_owned(),
+                        "Use a merge priority annotation such as `| default` or `| force` to \
+                        override a value."
+                            .to_owned(),
+                    ])
+            }
+            Warning::UnreachableMatchArm { pos, catch_all_pos } => {
+                let mut labels = vec![];
+                if let Some(span) = pos.into_opt() {
+                    labels.push(primary(&span).with_message("this arm is unreachable"));
+                }
+
+                if let Some(span) = catch_all_pos.into_opt() {
+                    labels.push(
+                        secondary(&span).with_message("this pattern already matches any value"),
+                    );
+                }
+
+                Diagnostic::warning()
+                    .with_message("unreachable match arm")
+                    .with_labels(labels)
+            }
+        };
+
+        vec![diagnostic.with_code(code)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::RawSpan;
+
+    fn unused_binding_at(files: &mut Files, source: &str, var: &str) -> (Warning, FileId) {
+        let file_id = files.add("test.ncl", source.to_owned());
+        let start = source.find(var).unwrap();
+        let warning = Warning::UnusedBinding {
+            name: Ident::new(var),
+            pos: RawSpan::from_range(file_id, start..start + var.len()).into(),
+        };
+
+        (warning, file_id)
+    }
+
+    #[test]
+    fn allow_directive_on_same_line() {
+        let mut files = Files::empty();
+        let (warning, _) = unused_binding_at(
+            &mut files,
+            "let foo = 1 in # nickel-allow: unused-binding\n2",
+            "foo",
+        );
+        assert!(warning.is_allowed_in(&files));
+
+        let (warning, _) = unused_binding_at(
+            &mut files,
+            "let foo = 1 in # nickel-allow: duplicate-field\n2",
+            "foo",
+        );
+        assert!(!warning.is_allowed_in(&files));
+
+        // A `#` in a string doesn't start a comment.
+        let (warning, _) =
+            unused_binding_at(&mut files, "let foo = 1 in \"# nickel-allow: all\"", "foo");
+        assert!(!warning.is_allowed_in(&files));
+
+        let (warning, _) = unused_binding_at(
+            &mut files,
+            "let foo = \"#\" in # nickel-allow: all\n2",
+            "foo",
+        );
+        assert!(warning.is_allowed_in(&files));
+    }
+
+    #[test]
+    fn allow_directive_on_previous_line() {
+        let mut files = Files::empty();
+        let (warning, _) = unused_binding_at(
+            &mut files,
+            "# nickel-allow: duplicate-field, unused-binding\nlet foo = 1 in\n2",
+            "foo",
+        );
+        assert!(warning.is_allowed_in(&files));
+
+        let (warning, _) = unused_binding_at(
+            &mut files,
+            "# nickel-allow: all\n\nlet foo = 1 in\n2",
+            "foo",
+        );
+        assert!(!warning.is_allowed_in(&files));
+
+        // A trailing directive only applies to its own line.
+        let (warning, _) = unused_binding_at(
+            &mut files,
+            "let bar = 1 in # nickel-allow: all\nlet foo = 1 in\nbar",
+            "foo",
+        );
+        assert!(!warning.is_allowed_in(&files));
+    }
+
+    #[test]
+    fn levels() {
+        let mut levels = WarningLevels::default();
+        assert_eq!(levels.get(WarningKind::UnusedBinding), WarningLevel::Warn);
+        assert_eq!(
+            levels.get(WarningKind::ShadowedIdentifier),
+            WarningLevel::Allow
+        );
+
+        levels.set_all(WarningLevel::Deny);
+        levels.set(WarningKind::UnusedBinding, WarningLevel::Allow);
+        assert_eq!(levels.get(WarningKind::UnusedBinding), WarningLevel::Allow);
+        assert_eq!(levels.get(WarningKind::DuplicateField), WarningLevel::Deny);
+    }
+
+    #[test]
+    fn parse_codes() {
+        for kind in WarningKind::ALL {
+            assert_eq!(kind.code().parse::<WarningKind>(), Ok(*kind));
         }
+
+        assert!("unused".parse::<WarningKind>().is_err());
     }
 }
@@ -1,26 +1,169 @@
 //! Cross-evaluation incremental evaluation capabilities.
+//!
+//! # Persistence
+//!
+//! At the end of an evaluation, the cache can be [persisted][Cache::persist], and loaded back
+//! at the beginning of the next one. Only thunks whose value is plain data can be persisted: null,
+//! booleans, numbers, strings, enum tags, and arrays and records thereof. Records must not have
+//! metadata, pending contracts or recursive dependencies between fields, since the persisted
+//! version wouldn't behave the same as the original one when merged.
 
 use super::{
     Cache,
     lazy::{CBNCache, Thunk, ThunkState},
 };
 
-use crate::eval::semantic_hash::SemanticHash;
+use crate::{
+    eval::{
+        Closure,
+        semantic_hash::SemanticHash,
+        value::{ArrayData, Container, EnumVariantData, NickelValue, ValueContentRef},
+    },
+    identifier::{Ident, LocIdent},
+    position::PosIdx,
+    term::{
+        IndexMap, Number,
+        record::{Field, RecordAttrs, RecordData},
+    },
+};
+
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, hash_map::Entry},
     io,
+    str::FromStr,
 };
 
+/// The representation of a value of a persisted thunk.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum PersistedValue {
+    Null,
+    Bool(bool),
+    /// A number, as the string representation of an exact rational.
+    Number(String),
+    String(String),
+    EnumTag(String),
+    Array(Vec<PersistedValue>),
+    Record {
+        fields: Vec<(String, PersistedValue)>,
+        open: bool,
+    },
+}
+
+impl PersistedValue {
+    /// Converts an evaluated value to its persisted representation. Returns `None` if the value
+    /// isn't plain data, or if it's not fully evaluated.
+    fn from_value(value: &NickelValue) -> Option<Self> {
+        Some(match value.content_ref() {
+            ValueContentRef::Null => PersistedValue::Null,
+            ValueContentRef::Bool(b) => PersistedValue::Bool(b),
+            ValueContentRef::Number(n) => PersistedValue::Number(n.to_string()),
+            ValueContentRef::String(s) => PersistedValue::String(s.to_string()),
+            ValueContentRef::EnumVariant(EnumVariantData { tag, arg: None }) => {
+                PersistedValue::EnumTag(tag.label().to_owned())
+            }
+            ValueContentRef::Array(Container::Empty) => PersistedValue::Array(Vec::new()),
+            ValueContentRef::Array(Container::Alloc(ArrayData {
+                array,
+                pending_contracts,
+            })) if pending_contracts.is_empty() => PersistedValue::Array(
+                array
+                    .iter()
+                    .map(Self::from_value)
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            ValueContentRef::Record(Container::Empty) => PersistedValue::Record {
+                fields: Vec::new(),
+                open: false,
+            },
+            ValueContentRef::Record(Container::Alloc(record)) if record.sealed_tail.is_none() => {
+                let fields = record
+                    .fields
+                    .iter()
+                    .map(|(id, field)| {
+                        if !field.metadata.is_empty() || !field.pending_contracts.is_empty() {
+                            return None;
+                        }
+
+                        Some((
+                            id.label().to_owned(),
+                            Self::from_value(field.value.as_ref()?)?,
+                        ))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                PersistedValue::Record {
+                    fields,
+                    open: record.attrs.open,
+                }
+            }
+            ValueContentRef::Thunk(thunk)
+                if thunk.state() == ThunkState::Evaluated && thunk.deps().is_empty() =>
+            {
+                Self::from_value(&thunk.borrow().value)?
+            }
+            _ => return None,
+        })
+    }
+
+    /// Converts the persisted representation back to a value. Returns `None` if a number can't
+    /// be parsed, which can only happen if the cache has been tampered with.
+    fn to_value(&self) -> Option<NickelValue> {
+        Some(match self {
+            PersistedValue::Null => NickelValue::null(),
+            PersistedValue::Bool(b) => NickelValue::bool_value_posless(*b),
+            PersistedValue::Number(n) => NickelValue::number_posless(Number::from_str(n).ok()?),
+            PersistedValue::String(s) => NickelValue::string_posless(s.as_str()),
+            PersistedValue::EnumTag(tag) => NickelValue::enum_tag_posless(Ident::new(tag)),
+            PersistedValue::Array(elts) => NickelValue::array_posless(
+                elts.iter().map(Self::to_value).collect::<Option<_>>()?,
+                Vec::new(),
+            ),
+            PersistedValue::Record { fields, open } => {
+                let fields = fields
+                    .iter()
+                    .map(|(id, value)| {
+                        Some((
+                            LocIdent::from(Ident::new(id)),
+                            Field::from(value.to_value()?),
+                        ))
+                    })
+                    .collect::<Option<IndexMap<_, _>>>()?;
+
+                NickelValue::record_posless(RecordData::new(
+                    fields,
+                    RecordAttrs {
+                        open: *open,
+                        ..Default::default()
+                    },
+                    None,
+                ))
+            }
+        })
+    }
+}
+
 /// A thunk coming from a previous evaluation. Can be lazily loaded as a normal thunk if needed.
 #[derive(Clone)]
-pub struct LoadableThunk(Thunk);
+pub struct LoadableThunk(PersistedValue);
 
 impl LoadableThunk {
-    pub fn load(&self) -> Thunk {
-        self.0.clone()
+    /// Builds a thunk from the persisted value. Returns `None` if the persisted value is invalid.
+    pub fn load(&self) -> Option<Thunk> {
+        Some(Thunk::new(Closure::from(self.0.to_value()?), PosIdx::NONE))
     }
 }
 
+/// The content of a persisted cache.
+#[derive(Serialize, Deserialize)]
+struct PersistedCache {
+    /// The version of Nickel which persisted the cache. A cache coming from a different version is
+    /// ignored, as the semantics of the language, or the semantic hashing scheme, might have
+    /// changed in between.
+    version: String,
+    thunks: Vec<(SemanticHash, PersistedValue)>,
+}
+
 #[derive(Clone)]
 pub enum CacheEntry {
     /// The entry is coming from a previous evaluation round, but it hasn't been re-used yet.
@@ -33,13 +176,15 @@ pub enum CacheEntry {
 }
 
 impl CacheEntry {
-    pub fn get(&mut self) -> &Thunk {
+    /// Returns the thunk of this entry, loading it first if needed. Returns `None` if the entry
+    /// couldn't be loaded.
+    pub fn get(&mut self) -> Option<&Thunk> {
         match self {
             CacheEntry::Loadable(loadable_thunk) => {
-                *self = CacheEntry::Loaded(loadable_thunk.load());
+                *self = CacheEntry::Loaded(loadable_thunk.load()?);
                 self.get()
             }
-            CacheEntry::Loaded(thunk) | CacheEntry::Recorded(thunk) => thunk,
+            CacheEntry::Loaded(thunk) | CacheEntry::Recorded(thunk) => Some(thunk),
         }
     }
 }
@@ -68,20 +213,6 @@ pub struct IncrementalCache {
     thunks: HashMap<SemanticHash, CacheEntry>,
 }
 
-impl IncrementalCache {
-    /// Serialize the cache to a persistent storage (typically a file), to be re-used in a
-    /// subsequent evaluation. Only loaded and recorded [CacheEntry]s will be saved; loadable ones
-    /// (recorded from the previous evaluation but not used in the current one) are dropped.
-    pub fn persist(self, _out: impl io::Write) -> io::Result<()> {
-        unimplemented!()
-    }
-
-    /// Loads a persisted cache.
-    pub fn load(_src: impl io::Read) -> io::Result<Self> {
-        unimplemented!()
-    }
-}
-
 impl Cache for IncrementalCache {
     type UpdateIndex = <CBNCache as Cache>::UpdateIndex;
 
@@ -98,9 +229,9 @@ impl Cache for IncrementalCache {
         if let ThunkState::Suspended = idx.state()
             && idx.cui().is_some()
             && let Some(content_hash) = idx.semantic_hash()
-            && let Some(cached) = self.thunks.get_mut(&content_hash)
+            && let Some(cached) = self.thunks.get_mut(&content_hash).and_then(CacheEntry::get)
         {
-            idx.clone().update(cached.get().get_owned())
+            idx.clone().update(cached.get_owned())
         }
 
         self.cbn_cache.get_update_index(idx)
@@ -167,6 +298,10 @@ impl Cache for IncrementalCache {
         self.cbn_cache.revert(idx)
     }
 
+    fn allocations(&self) -> u64 {
+        self.cbn_cache.allocations()
+    }
+
     fn deps(&self, idx: &super::CacheIndex) -> Option<crate::term::record::FieldDeps> {
         self.cbn_cache.deps(idx)
     }
@@ -181,4 +316,132 @@ impl Cache for IncrementalCache {
     fn attach_cui(&mut self, idx: &super::CacheIndex, cui: SemanticHash) {
         idx.set_cui(cui);
     }
+
+    /// Only loaded and recorded [CacheEntry]s are saved; loadable ones (recorded from the previous
+    /// evaluation but not used in the current one) are dropped. Entries whose value isn't plain
+    /// data are dropped as well.
+    fn persist(&self, mut out: &mut dyn io::Write) -> io::Result<()> {
+        let thunks = self
+            .thunks
+            .iter()
+            .filter_map(|(hash, entry)| match entry {
+                CacheEntry::Loadable(_) => None,
+                CacheEntry::Loaded(thunk) | CacheEntry::Recorded(thunk) => {
+                    // Loaded thunks are never forced themselves (their content is copied in the
+                    // thunk being fetched from the cache), so we don't check their state.
+                    let value = PersistedValue::from_value(&thunk.borrow().value)?;
+                    Some((*hash, value))
+                }
+            })
+            .collect();
+
+        let persisted = PersistedCache {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            thunks,
+        };
+
+        bincode::serde::encode_into_std_write(persisted, &mut out, bincode::config::standard())
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn load_persisted(&mut self, mut src: &mut dyn io::Read) -> io::Result<()> {
+        let persisted: PersistedCache =
+            bincode::serde::decode_from_std_read(&mut src, bincode::config::standard())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        if persisted.version != env!("CARGO_PKG_VERSION") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "incremental cache persisted by Nickel {}, but this is Nickel {}",
+                    persisted.version,
+                    env!("CARGO_PKG_VERSION")
+                ),
+            ));
+        }
+
+        for (hash, value) in persisted.thunks {
+            self.thunks
+                .entry(hash)
+                .or_insert(CacheEntry::Loadable(LoadableThunk(value)));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::value::Array;
+
+    #[test]
+    fn persisted_values_roundtrip() {
+        let record = NickelValue::record_posless(RecordData::new(
+            [
+                (
+                    LocIdent::from(Ident::new("ratio")),
+                    Field::from(NickelValue::number_posless(
+                        Number::from_str("1/3").unwrap(),
+                    )),
+                ),
+                (
+                    LocIdent::from(Ident::new("tags")),
+                    Field::from(NickelValue::array_posless(
+                        Array::from_iter([
+                            NickelValue::enum_tag_posless(Ident::new("Foo")),
+                            NickelValue::null(),
+                            NickelValue::bool_value_posless(true),
+                            NickelValue::string_posless("bar"),
+                        ]),
+                        Vec::new(),
+                    )),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            RecordAttrs::default(),
+            None,
+        ));
+
+        let persisted = PersistedValue::from_value(&record).unwrap();
+        assert_eq!(
+            persisted.to_value().unwrap().to_string(),
+            record.to_string()
+        );
+        assert_eq!(
+            PersistedValue::from_value(&persisted.to_value().unwrap()),
+            Some(persisted)
+        );
+    }
+
+    #[test]
+    fn only_plain_data_is_persisted() {
+        let mut metadata = crate::term::record::FieldMetadata::new();
+        metadata.opt = true;
+
+        let with_metadata = NickelValue::record_posless(RecordData::new(
+            [(
+                LocIdent::from(Ident::new("a")),
+                Field {
+                    value: Some(NickelValue::null()),
+                    metadata: metadata.into(),
+                    pending_contracts: Vec::new(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            RecordAttrs::default(),
+            None,
+        ));
+
+        assert_eq!(PersistedValue::from_value(&with_metadata), None);
+        assert_eq!(
+            PersistedValue::from_value(&NickelValue::term_posless(crate::term::Term::Var(
+                LocIdent::from(Ident::new("x"))
+            ))),
+            None
+        );
+    }
 }
@@ -783,8 +783,13 @@ mod incremental {
 pub use incremental::*;
 
 /// Placeholder [Cache] for the call-by-need evaluation strategy.
+///
+/// Thunks manage their own memory, so the only state of this cache is the number of thunks it
+/// allocated, which is used to enforce [crate::eval::limits::EvalLimits::max_allocations].
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
-pub struct CBNCache {}
+pub struct CBNCache {
+    allocations: u64,
+}
 
 impl Cache for CBNCache {
     type UpdateIndex = ThunkUpdateFrame;
@@ -817,6 +822,7 @@ impl Cache for CBNCache {
 
     fn add(&mut self, clos: Closure, bty: BindingType) -> CacheIndex {
         let pos_idx = clos.value.pos_idx();
+        self.allocations += 1;
 
         match bty {
             BindingType::Normal => Thunk::new(clos, pos_idx),
@@ -841,7 +847,7 @@ impl Cache for CBNCache {
 
     #[inline]
     fn new() -> Self {
-        CBNCache {}
+        CBNCache::default()
     }
 
     #[inline]
@@ -860,6 +866,7 @@ impl Cache for CBNCache {
         idx: CacheIndex,
         fields: I,
     ) -> NickelValue {
+        self.allocations += 1;
         idx.saturate(fields)
     }
 
@@ -870,9 +877,15 @@ impl Cache for CBNCache {
 
     #[inline]
     fn revert(&mut self, idx: &CacheIndex) -> CacheIndex {
+        self.allocations += 1;
         idx.revert()
     }
 
+    #[inline]
+    fn allocations(&self) -> u64 {
+        self.allocations
+    }
+
     #[inline]
     fn make_update_index(
         &mut self,
@@ -80,6 +80,10 @@ pub trait Cache: Clone {
     /// Returns the dependencies of the element stored at index `idx`, if it has any.
     fn deps(&self, idx: &CacheIndex) -> Option<FieldDeps>;
 
+    /// Returns the number of elements allocated by this cache since its creation, including the
+    /// ones that have since been freed.
+    fn allocations(&self) -> u64;
+
     /// Checks whether the element at index `idx` is blackholed and returns a
     /// [BlackholedError] if it is. Otherwise, returns `idx`.
     fn make_update_index(
@@ -97,4 +101,24 @@ pub trait Cache: Clone {
     /// still need to have this method in the general cache interface.
     #[cfg(feature = "incremental-experimental")]
     fn attach_cui(&mut self, _idx: &CacheIndex, _cui: crate::eval::semantic_hash::SemanticHash) {}
+
+    /// Serializes the entries worth re-using in a subsequent evaluation to a persistent storage
+    /// (typically a file). They can be loaded back with [Self::load_persisted].
+    ///
+    /// The default implementation does nothing, which is the behavior for caches that don't
+    /// support incremental evaluation.
+    #[cfg(feature = "incremental-experimental")]
+    fn persist(&self, _out: &mut dyn std::io::Write) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Loads the entries persisted by a previous evaluation with [Self::persist]. They are only
+    /// used when an expression with the same semantic hash is evaluated.
+    ///
+    /// The default implementation does nothing, which is the behavior for caches that don't
+    /// support incremental evaluation.
+    #[cfg(feature = "incremental-experimental")]
+    fn load_persisted(&mut self, _src: &mut dyn std::io::Read) -> std::io::Result<()> {
+        Ok(())
+    }
 }
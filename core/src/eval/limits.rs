@@ -0,0 +1,174 @@
+//! Resource limits on evaluation, for embedders that evaluate untrusted programs.
+//!
+//! Limits are configured on a [super::VmContext] through [EvalLimits]. They apply separately to
+//! each [super::VirtualMachine] built from this context: in practice, to each evaluation request,
+//! such as one call to [crate::program::Program::eval_full]. Each limit produces a distinct
+//! [EvalErrorKind] when it's reached.
+//!
+//! Limits are checked once per step of the main evaluation loop. The clock and the cancellation
+//! flag are only polled every [POLL_INTERVAL] steps, so a timeout or a cancellation can be
+//! reported slightly late.
+use super::ErrorKind;
+use crate::{error::EvalErrorKind, position::PosIdx};
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// The number of evaluation steps between two checks of the clock and of the cancellation flag.
+pub const POLL_INTERVAL: u64 = 1024;
+
+/// Bounds on the work performed by one evaluation. All limits are disabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct EvalLimits {
+    /// The maximum number of steps of the evaluation loop.
+    pub max_steps: Option<u64>,
+    /// The maximum number of thunks allocated in the evaluation cache. Despite its name, this
+    /// limit only counts thunks, that is the shared suspended computations created for bindings,
+    /// record fields, array elements and function arguments. Values which aren't allocated
+    /// behind a thunk, such as the strings, arrays or records being built by a primitive
+    /// operation, aren't counted. The number of thunks grows with the amount of data an
+    /// evaluation creates, so this is a deterministic but coarse proxy for memory usage.
+    pub max_allocations: Option<u64>,
+    /// The maximum number of items on the evaluation stack, which grows with the depth of
+    /// recursive calls.
+    pub max_stack_depth: Option<usize>,
+    /// The maximum wall-clock duration of the evaluation.
+    pub timeout: Option<Duration>,
+    /// A handle to cancel the evaluation from another thread.
+    pub cancellation: Option<CancellationHandle>,
+}
+
+impl EvalLimits {
+    /// Returns `true` if no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_steps.is_none()
+            && self.max_allocations.is_none()
+            && self.max_stack_depth.is_none()
+            && self.timeout.is_none()
+            && self.cancellation.is_none()
+    }
+}
+
+/// The reason why an evaluation was stopped before completion. See
+/// [EvalErrorKind::interruption].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Interruption {
+    /// [EvalLimits::max_steps] was reached.
+    StepLimit,
+    /// [EvalLimits::max_allocations] was reached.
+    AllocationLimit,
+    /// [EvalLimits::max_stack_depth] was reached.
+    StackDepthLimit,
+    /// [EvalLimits::timeout] was reached.
+    Timeout,
+    /// The evaluation was cancelled through a [CancellationHandle].
+    Cancelled,
+}
+
+/// A thread-safe handle to cancel evaluations. Clones share the same state.
+///
+/// Cancellation is permanent: once [Self::cancel] has been called, all the evaluations using this
+/// handle, running or future, fail with [EvalErrorKind::Cancelled].
+#[derive(Clone, Debug, Default)]
+pub struct CancellationHandle(Arc<AtomicBool>);
+
+impl CancellationHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests the cancellation of the evaluations using this handle.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The resources consumed by an evaluation so far, checked against its limits.
+pub(super) struct Budget {
+    limits: EvalLimits,
+    steps: u64,
+    /// The number of allocations of the cache when the evaluation started. The cache can be shared
+    /// between several evaluations, so we only count the allocations that happened since then.
+    initial_allocations: u64,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    /// Starts tracking an evaluation, or returns `None` if there is nothing to track.
+    pub(super) fn new(limits: &EvalLimits, initial_allocations: u64) -> Option<Self> {
+        if limits.is_unlimited() {
+            return None;
+        }
+
+        Some(Budget {
+            limits: limits.clone(),
+            steps: 0,
+            initial_allocations,
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+        })
+    }
+
+    /// Accounts for one evaluation step, and fails if a limit is reached.
+    pub(super) fn step(
+        &mut self,
+        allocations: u64,
+        stack_depth: usize,
+        pos: PosIdx,
+    ) -> Result<(), ErrorKind> {
+        self.steps += 1;
+
+        if let Some(limit) = self.limits.max_steps
+            && self.steps > limit
+        {
+            return Err(Box::new(EvalErrorKind::StepLimitExceeded { limit, pos }));
+        }
+
+        if let Some(limit) = self.limits.max_allocations
+            && allocations.saturating_sub(self.initial_allocations) > limit
+        {
+            return Err(Box::new(EvalErrorKind::AllocationLimitExceeded {
+                limit,
+                pos,
+            }));
+        }
+
+        if let Some(limit) = self.limits.max_stack_depth
+            && stack_depth > limit
+        {
+            return Err(Box::new(EvalErrorKind::StackDepthLimitExceeded {
+                limit,
+                pos,
+            }));
+        }
+
+        // We also poll on the first step, so that an evaluation started with an already cancelled
+        // handle doesn't do any work.
+        if (self.steps - 1).is_multiple_of(POLL_INTERVAL) {
+            if self
+                .limits
+                .cancellation
+                .as_ref()
+                .is_some_and(CancellationHandle::is_cancelled)
+            {
+                return Err(Box::new(EvalErrorKind::Cancelled));
+            }
+
+            if let (Some(limit), Some(deadline)) = (self.limits.timeout, self.deadline)
+                && Instant::now() >= deadline
+            {
+                return Err(Box::new(EvalErrorKind::Timeout { limit, pos }));
+            }
+        }
+
+        Ok(())
+    }
+}
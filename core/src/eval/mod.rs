@@ -100,13 +100,16 @@ use std::{
     io::Write,
     mem::ManuallyDrop,
     ops::{Deref, DerefMut},
+    rc::Rc,
 };
 
 pub mod cache;
 pub mod callstack;
 pub mod contract_eq;
 pub mod fixpoint;
+pub mod limits;
 pub mod merge;
+pub mod native;
 pub mod operation;
 #[cfg(feature = "incremental-experimental")]
 pub mod semantic_hash;
@@ -114,6 +117,8 @@ pub mod stack;
 pub mod value;
 
 use callstack::*;
+use limits::{Budget, EvalLimits};
+use native::NativeFunction;
 #[cfg(feature = "incremental-experimental")]
 use semantic_hash::Register as _;
 use stack::{
@@ -150,6 +155,11 @@ pub struct VmContext<R: ImportResolver, C: Cache> {
     /// identifiers within the vector, the later entry wins. Configured via
     /// [Self::with_extend_env]; defaults to empty.
     pub extend_env: Vec<(Ident, NickelValue)>,
+    /// Native functions to expose in the initial environment, on top of [Self::extend_env]. See
+    /// [Self::add_native_function].
+    pub native_functions: Vec<Rc<NativeFunction>>,
+    /// Bounds on the work performed by each evaluation. See [limits].
+    pub eval_limits: EvalLimits,
     /// Whether incremental evaluation is enabled or not. Incremental evaluation is currently
     /// feature-gated, but even when compiled with the feature, it needs to be explicitly enabled
     /// through this feature.
@@ -183,6 +193,8 @@ impl<R: ImportResolver, C: Cache> VmContext<R, C> {
             cache: C::new(),
             pos_table,
             extend_env: Vec::new(),
+            native_functions: Vec::new(),
+            eval_limits: EvalLimits::default(),
             #[cfg(feature = "incremental-experimental")]
             enable_incremental_evaluation: false,
         }
@@ -197,12 +209,28 @@ impl<R: ImportResolver, C: Cache> VmContext<R, C> {
         self
     }
 
+    /// Bounds the work performed by each evaluation run by a [VirtualMachine] subsequently
+    /// constructed from this context. Calling this multiple times replaces the previous limits.
+    pub fn with_eval_limits(mut self, limits: EvalLimits) -> Self {
+        self.eval_limits = limits;
+        self
+    }
+
     /// Enable incremental evaluation for this VM.
     #[cfg(feature = "incremental-experimental")]
     pub fn with_incremental_evaluation(mut self) -> Self {
         self.enable_incremental_evaluation = true;
         self
     }
+
+    /// Exposes a native function in the initial environment of any [VirtualMachine] subsequently
+    /// constructed from this context. A previously registered function with the same name is
+    /// replaced.
+    pub fn add_native_function(&mut self, native: NativeFunction) {
+        self.native_functions
+            .retain(|other| other.name != native.name);
+        self.native_functions.push(Rc::new(native));
+    }
 }
 
 /// Many functions in [self] and submodules returns an error of type [EvalErrorKind]. However, this
@@ -224,23 +252,46 @@ impl From<PointedExportErrorData> for ErrorKind {
 
 impl<C: Cache> VmContext<ImportCaches, C> {
     /// Prepares the underlying program for evaluation (load the stdlib, typecheck, transform,
-    /// etc.).
+    /// etc.), and reports the warnings of the lints.
     pub fn prepare_eval(&mut self, main_id: FileId) -> Result<NickelValue, Error> {
-        self.prepare_eval_impl(main_id, true)
+        let prepared = self.prepare_eval_impl(main_id, true)?;
+        self.lint();
+        Ok(prepared)
     }
 
-    /// Same as [Self::prepare_eval], but skip typechecking.
+    /// Same as [Self::prepare_eval], but skip typechecking and linting.
     pub fn prepare_eval_only(&mut self, main_id: FileId) -> Result<NickelValue, Error> {
         self.prepare_eval_impl(main_id, false)
     }
 
+    /// Checks the lints on the sources loaded so far which haven't been linted yet, and reports
+    /// the resulting warnings.
+    pub fn lint(&mut self) {
+        let warnings = self.import_resolver.lint();
+
+        for warning in warnings {
+            self.reporter
+                .report((warning, self.import_resolver.files().clone()));
+        }
+    }
+
     /// Register every binding configured via [Self::with_extend_env] in the typing context as
-    /// `Dyn`. No-op if the extend env is empty. Idempotent: callable multiple times.
+    /// `Dyn`, and every native function with its declared type. No-op if there are no such
+    /// bindings. Idempotent: callable multiple times.
     pub fn register_extend_env_for_typecheck(&mut self) {
         if !self.extend_env.is_empty() {
             let idents: Vec<_> = self.extend_env.iter().map(|(id, _)| *id).collect();
             self.import_resolver.add_dyn_type_bindings(idents);
         }
+
+        if !self.native_functions.is_empty() {
+            let bindings = self
+                .native_functions
+                .iter()
+                .map(|native| (native.name, &native.typ));
+            self.import_resolver
+                .add_static_type_bindings(&self.pos_table, bindings);
+        }
     }
 
     fn prepare_eval_impl(
@@ -291,6 +342,8 @@ pub struct VirtualMachine<'ctxt, R: ImportResolver, C: Cache> {
     call_stack: CallStack,
     /// The initial environment containing stdlib and builtin functions accessible from anywhere
     initial_env: Environment,
+    /// The resources consumed so far, if the context sets [limits][VmContext::eval_limits].
+    budget: Option<Budget>,
 }
 
 impl<'ctxt, R: ImportResolver, C: Cache> Drop for VirtualMachine<'ctxt, R, C> {
@@ -303,8 +356,11 @@ impl<'ctxt, R: ImportResolver, C: Cache> VirtualMachine<'ctxt, R, C> {
     /// Creates a new VM with an empty initial environment. See [Self::new] for initialization of
     /// the initial environment when `R` is instantiated to [crate::cache::CacheHub].
     pub fn new_empty_env(context: &'ctxt mut VmContext<R, C>) -> Self {
+        let budget = Budget::new(&context.eval_limits, context.cache.allocations());
+
         VirtualMachine {
             context,
+            budget,
             call_stack: Default::default(),
             stack: Stack::new(),
             initial_env: Environment::new(),
@@ -710,6 +766,11 @@ impl<'ctxt, R: ImportResolver, C: Cache> VirtualMachine<'ctxt, R, C> {
         let result = loop {
             let Closure { value, mut env } = closure;
             let pos_idx = value.pos_idx();
+
+            if let Some(budget) = &mut self.budget {
+                budget.step(self.context.cache.allocations(), self.stack.len(), pos_idx)?;
+            }
+
             let has_cont_on_stack = self.stack.is_top_idx() || self.stack.is_top_cont();
 
             closure = match value.content_ref() {
@@ -1312,8 +1373,17 @@ impl<'ctxt, C: Cache> VirtualMachine<'ctxt, ImportCaches, C> {
             initial_env.insert(*id, idx);
         }
 
+        for native in &context.native_functions {
+            let value = native.to_value(&mut context.pos_table);
+            let idx = context.cache.add(value.into(), BindingType::Normal);
+            initial_env.insert(native.name, idx);
+        }
+
+        let budget = Budget::new(&context.eval_limits, context.cache.allocations());
+
         VirtualMachine {
             context,
+            budget,
             call_stack: Default::default(),
             stack: Stack::new(),
             initial_env,
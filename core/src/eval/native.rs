@@ -0,0 +1,188 @@
+//! Native functions, that is functions implemented in Rust by the host application and exposed to
+//! Nickel programs as ordinary bindings.
+//!
+//! A native function is registered together with a name and a Nickel type. It is exposed as a
+//! wrapper of the form:
+//!
+//! ```text
+//! (fun x1 .. xn => %native_call% (%force% [x1, .., xn])) | <type>
+//! ```
+//!
+//! where `n` is the number of arrows at the top-level of the type. Arguments are thus fully
+//! evaluated before the Rust implementation is called, and the contract derived from the type
+//! checks both the arguments and the return value. The typechecker sees the declared type, as
+//! for the functions of the standard library.
+use super::value::{Array, NickelValue};
+use crate::{
+    cache::{CacheHub, SourcePath},
+    error::ParseError,
+    identifier::{Ident, LocIdent},
+    label::{Label, Polarity, ty_path},
+    position::{PosIdx, PosTable},
+    term::{LabeledType, Term, TypeAnnotation, UnaryOp, make as mk_term},
+    typ::{Type, TypeF},
+};
+
+use std::{fmt, rc::Rc};
+
+/// The signature of the Rust implementation of a native function. The arguments are fully
+/// evaluated and don't have any free variable.
+pub type NativeFn = dyn Fn(&[NickelValue]) -> Result<NickelValue, NativeFunctionError>;
+
+/// An error raised by the implementation of a native function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NativeFunctionError {
+    /// An argument doesn't have the expected shape. This is reported like the type errors of the
+    /// primitive operations.
+    InvalidArgument {
+        /// The index of the argument, starting from zero.
+        index: usize,
+        /// A description of what was expected, such as `String` or `a non-empty array`.
+        expected: String,
+    },
+    /// An argument has the expected type, but is still invalid, such as a string that isn't a
+    /// valid URL. This is reported as a contract violation on the argument, as if the type of the
+    /// function was more precise.
+    Blame {
+        /// The index of the argument, starting from zero.
+        index: usize,
+        /// Why the argument was rejected.
+        message: String,
+    },
+    /// Any other error, reported with the given message.
+    Other(String),
+}
+
+/// A function implemented in Rust, exposed to Nickel programs under the name `name`.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: Ident,
+    /// The declared Nickel type of the function.
+    pub typ: Type,
+    fun: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: Ident,
+        typ: Type,
+        fun: impl Fn(&[NickelValue]) -> Result<NickelValue, NativeFunctionError> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name,
+            typ,
+            fun: Rc::new(fun),
+        }
+    }
+
+    /// Same as [Self::new], but parses the type from its Nickel source. The source is added to
+    /// `caches`, so that errors can point into it.
+    pub fn parse(
+        caches: &mut CacheHub,
+        pos_table: &mut PosTable,
+        name: Ident,
+        typ: &str,
+        fun: impl Fn(&[NickelValue]) -> Result<NickelValue, NativeFunctionError> + 'static,
+    ) -> Result<Self, ParseError> {
+        use crate::parser::{ErrorTolerantParserCompat, grammar::FixedTypeParser, lexer::Lexer};
+
+        let file_id = caches.replace_string(
+            SourcePath::Generated(format!("type of native function {name}")),
+            typ.to_owned(),
+        );
+
+        let typ = FixedTypeParser::new()
+            .parse_strict_compat(pos_table, file_id, Lexer::new(typ))
+            .map_err(|mut errs| {
+                errs.errors
+                    .pop()
+                    .expect("parsing failed, so the error list must be non-empty")
+            })?;
+
+        Ok(Self::new(name, typ, fun))
+    }
+
+    /// The number of arguments of the function, which is the number of arrows at the top-level of
+    /// its type, ignoring leading `forall`s.
+    pub fn arity(&self) -> usize {
+        let mut arity = 0;
+        let mut typ = &self.typ;
+
+        loop {
+            match &typ.typ {
+                TypeF::Forall { body, .. } => typ = body,
+                TypeF::Arrow(_, codomain) => {
+                    arity += 1;
+                    typ = codomain;
+                }
+                _ => break arity,
+            }
+        }
+    }
+
+    /// Calls the Rust implementation.
+    pub fn call(&self, args: &[NickelValue]) -> Result<NickelValue, NativeFunctionError> {
+        (self.fun)(args)
+    }
+
+    /// Builds a label blaming the argument `index` for violating the contract of this function,
+    /// as the contract derived from its type would do.
+    pub fn blame_label(&self, pos_table: &mut PosTable, index: usize, message: String) -> Label {
+        let mut path: ty_path::Path = std::iter::repeat_n(ty_path::Elem::Codomain, index).collect();
+        path.push(ty_path::Elem::Domain);
+
+        Label {
+            typ: Rc::new(self.typ.clone()),
+            span: pos_table.push(self.typ.pos),
+            polarity: Polarity::Negative,
+            path,
+            ..Default::default()
+        }
+        .with_diagnostic_message(message)
+    }
+
+    /// Builds the Nickel wrapper of this function, as described in the [module
+    /// documentation][self].
+    pub fn to_value(self: &Rc<Self>, pos_table: &mut PosTable) -> NickelValue {
+        let params: Vec<_> = (0..self.arity()).map(|_| LocIdent::fresh()).collect();
+        let args = Array::from_iter(params.iter().map(|param| mk_term::var(*param)));
+
+        let call = mk_term::op1(
+            UnaryOp::NativeCall(Rc::clone(self)),
+            mk_term::op1(
+                UnaryOp::Force {
+                    ignore_not_exported: false,
+                },
+                NickelValue::array_posless(args, Vec::new()),
+            ),
+        );
+
+        let fun = params.into_iter().rev().fold(call, |body, param| {
+            NickelValue::term_posless(Term::fun(param, body))
+        });
+
+        let span = pos_table.push(self.typ.pos);
+        let annot = TypeAnnotation {
+            typ: None,
+            contracts: vec![LabeledType::new(self.typ.clone(), span)],
+        };
+
+        NickelValue::term(Term::annotated(annot, fun), PosIdx::NONE)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("typ", &self.typ)
+            .finish_non_exhaustive()
+    }
+}
+
+// Two native functions are only equal if they share the same implementation.
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fun, &other.fun)
+    }
+}
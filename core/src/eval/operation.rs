@@ -11,6 +11,7 @@ use super::{
     cache::lazy::Thunk,
     contract_eq::contract_eq,
     merge::{self, MergeMode, split},
+    native::NativeFunctionError,
     stack::{EqItem, Op1ContItem, Op2FirstContItem, Op2SecondContItem, PrimopAppInfo, StrAccItem},
     subst,
     value::{
@@ -118,6 +119,7 @@ struct OpNEvalData {
 /// A string representation of the type of the first argument of serialization-related primitive
 /// operations. This is a Nickel enum of the supported serialization formats.
 static ENUM_FORMAT: &str = "[| 'Json, 'Toml, 'Yaml, 'YamlDocuments |]";
+static ENUM_SERIALIZE_FORMAT: &str = "[| 'Env, 'Json, 'Plist, 'Toml, 'Yaml, 'YamlDocuments |]";
 
 impl<'ctxt, R: ImportResolver, C: Cache> VirtualMachine<'ctxt, R, C> {
     /// Proceeds to the next step of the evaluation of a primitive operation.
@@ -1468,6 +1470,58 @@ impl<'ctxt, R: ImportResolver, C: Cache> VirtualMachine<'ctxt, R, C> {
                     pos_op,
                 },
             ),
+            UnaryOp::NativeCall(native) => {
+                let op_name = format!("native function `{}`", native.name);
+
+                if value.as_array().is_none() {
+                    return mk_type_error!(op_name = op_name, "Array");
+                }
+
+                // The arguments have been forced by the wrapper, but native functions expect
+                // closed values.
+                let args = subst(
+                    &self.context.pos_table,
+                    &self.context.cache,
+                    value,
+                    &Environment::new(),
+                    &env,
+                );
+                let args: Vec<_> = args
+                    .as_array()
+                    .into_iter()
+                    .flat_map(|container| container.iter().cloned().collect::<Vec<_>>())
+                    .collect();
+
+                match native.call(&args) {
+                    Ok(result) => Ok(result.with_pos_idx(pos_op_inh).into()),
+                    Err(NativeFunctionError::InvalidArgument { index, expected }) => {
+                        Err(Box::new(EvalErrorKind::NAryPrimopTypeError {
+                            primop: op_name,
+                            expected,
+                            arg_number: index + 1,
+                            pos_arg: orig_pos_arg,
+                            arg_evaluated: args.get(index).cloned().unwrap_or_default(),
+                            pos_op,
+                        }))
+                    }
+                    Err(NativeFunctionError::Blame { index, message }) => {
+                        let mut label =
+                            native.blame_label(&mut self.context.pos_table, index, message);
+                        let evaluated_arg = args.get(index).cloned();
+                        label.arg_pos = evaluated_arg
+                            .as_ref()
+                            .map_or(PosIdx::NONE, NickelValue::pos_idx);
+
+                        Err(Box::new(EvalErrorKind::BlameError {
+                            evaluated_arg,
+                            label,
+                        }))
+                    }
+                    Err(NativeFunctionError::Other(message)) => Err(Box::new(
+                        EvalErrorKind::Other(format!("{op_name} failed: {message}"), pos_op),
+                    )),
+                }
+            }
             UnaryOp::RecDefault => unimplemented!(),
             UnaryOp::RecForce => unimplemented!(),
         }
@@ -2602,7 +2656,7 @@ impl<'ctxt, R: ImportResolver, C: Cache> VirtualMachine<'ctxt, R, C> {
                 Ok(NickelValue::string(result, pos_op_inh).into())
             }
             BinaryOp::Serialize => {
-                let mk_err_fst = || mk_type_error!(ENUM_FORMAT, 1, value1.clone());
+                let mk_err_fst = || mk_type_error!(ENUM_SERIALIZE_FORMAT, 1, value1.clone());
 
                 let Some(enum_data) = value1.as_enum_variant() else {
                     return mk_err_fst();
@@ -2627,6 +2681,8 @@ impl<'ctxt, R: ImportResolver, C: Cache> VirtualMachine<'ctxt, R, C> {
                     "Yaml" => ExportFormat::Yaml,
                     "YamlDocuments" => ExportFormat::YamlDocuments,
                     "Toml" => ExportFormat::Toml,
+                    "Env" => ExportFormat::Env,
+                    "Plist" => ExportFormat::Plist,
                     _ => return mk_err_fst(),
                 };
 
@@ -44,6 +44,15 @@
 //! The interpreter picks so-called _thunks of interest_, which are thunks that are worth caching
 //! across evaluations (as hashing, recording and persisting has a cost). This is the thunks for
 //! which we compute the CUI.
+//!
+//! # Current scheme
+//!
+//! The CUI of an expression is currently the hash of its pretty-printed representation, which
+//! is independent from positions: moving an expression around in a file, or editing unrelated
+//! parts of it, doesn't change its CUI. Since semantic hashes are persisted on disk and compared
+//! across runs, all the hashes of this module are computed with a [StableHasher], which doesn't
+//! depend on the process or on the version of the Rust standard library, and identifiers are
+//! hashed through their label, which doesn't depend on the interning order.
 
 use super::{
     Closure,
@@ -53,14 +62,119 @@ use super::{
     },
 };
 
-use std::hash::{DefaultHasher, Hash, Hasher};
+use crate::{
+    identifier::Ident,
+    stdlib::StdlibModule,
+    term::{BinaryOp, Term},
+    transform::free_vars::CollectFreeVars as _,
+    traverse::Traverse as _,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::{Hash, Hasher},
+};
 
 /// A semantic hash for re-using previous computations in the incremental evaluation mode.
-#[derive(Copy, Debug, PartialEq, Eq, Clone, Hash)]
+#[derive(Copy, Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
 pub struct SemanticHash(pub u64);
 
-pub fn cui(_v: &NickelValue) -> SemanticHash {
-    unimplemented!()
+/// A [Hasher] whose result only depends on the data being hashed, and not on the process or on the
+/// version of the Rust toolchain, unlike [std::hash::DefaultHasher].
+#[derive(Default, Clone)]
+struct StableHasher(Sha256);
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        // unwrap(): a SHA-256 digest is 32 bytes long.
+        u64::from_le_bytes(digest[..8].try_into().unwrap())
+    }
+}
+
+/// Computes the Cross-evaluation Unique Identifier of an expression, which is the hash of its
+/// pretty-printed representation. `v` must be [of interest][is_of_interest]: the pretty-printed
+/// representation of other values might not be faithful.
+pub fn cui(v: &NickelValue) -> SemanticHash {
+    let mut hasher = StableHasher::default();
+    v.to_string().hash(&mut hasher);
+    SemanticHash(hasher.finish())
+}
+
+/// Returns `true` if `v` contains a node which the pretty-printer doesn't represent faithfully, or
+/// whose meaning depends on something else than the expression itself and its free variables.
+/// Such values can't be identified by their CUI.
+fn has_opaque_parts(v: &NickelValue) -> bool {
+    v.find_map(|v: &NickelValue| {
+        let opaque = match v.content_ref() {
+            ValueContentRef::Thunk(_)
+            | ValueContentRef::Label(_)
+            | ValueContentRef::ForeignId(_)
+            | ValueContentRef::SealingKey(_)
+            | ValueContentRef::CustomContract(_) => true,
+            ValueContentRef::Array(Container::Alloc(array_data)) => {
+                !array_data.pending_contracts.is_empty()
+            }
+            ValueContentRef::Record(Container::Alloc(record)) => {
+                record.sealed_tail.is_some()
+                    || record
+                        .fields
+                        .values()
+                        .any(|field| !field.pending_contracts.is_empty())
+            }
+            // The content of an import can change while the import expression stays the same.
+            ValueContentRef::Term(
+                Term::Import(_)
+                | Term::ResolvedImport(_)
+                | Term::Closurize(_)
+                | Term::Sealed(_)
+                | Term::ParseError(_)
+                | Term::RuntimeError(_),
+            ) => true,
+            // Record insertions are generated by the interpreter, and their metadata aren't
+            // printed.
+            ValueContentRef::Term(Term::Op2(data)) => {
+                matches!(data.op, BinaryOp::RecordInsert { .. })
+            }
+            _ => false,
+        };
+
+        opaque.then_some(())
+    })
+    .is_some()
+}
+
+/// Returns the free variables of `v`, or `None` if they can't be computed because `v` contains
+/// closures.
+fn free_vars(v: &NickelValue) -> Option<HashSet<Ident>> {
+    let has_thunks = v
+        .find_map(|v: &NickelValue| v.as_thunk().map(|_| ()))
+        .is_some();
+
+    if has_thunks {
+        return None;
+    }
+
+    // Collecting free variables also annotates recursive records with their dependencies, which
+    // requires a mutable value. We work on a copy to leave the original value untouched.
+    let mut free_vars = HashSet::new();
+    v.clone().collect_free_vars(&mut free_vars);
+    Some(free_vars)
+}
+
+/// Returns `true` if `id` is bound in the initial environment by the standard library, which is
+/// the same across all evaluations performed by a given version of Nickel.
+fn is_stdlib_binding(id: Ident) -> bool {
+    let label = id.label();
+    // Bindings of the internals module start with `$`, which is forbidden in user identifiers.
+    label == StdlibModule::Std.name() || label.starts_with('$')
 }
 
 /// In the context of incremental evaluation, decides if an expression put in thunk should be
@@ -77,9 +191,15 @@ pub fn cui(_v: &NickelValue) -> SemanticHash {
 /// be cheaper to recompute them from scratch) and have good chances of surviving successive
 /// changes (e.g focusing on top-level configurations fields rather than local variables).
 ///
-/// Currently, the decision algorithm for thunks of interest is unimplemented.
-pub fn is_of_interest(_v: &NickelValue) -> bool {
-    unimplemented!()
+/// Currently, any expression which needs to be evaluated (that is, which isn't a constant, a
+/// function or a mere variable), and whose CUI can be computed faithfully, is of interest.
+pub fn is_of_interest(v: &NickelValue) -> bool {
+    let needs_eval = match v.content_ref() {
+        ValueContentRef::Term(term) => !matches!(term, Term::Fun(_) | Term::Var(_)),
+        _ => false,
+    };
+
+    needs_eval && !has_opaque_parts(v)
 }
 
 pub trait Register<C: Cache> {
@@ -105,10 +225,14 @@ impl<C: Cache> Register<C> for NickelValue {
 
 impl<C: Cache> Register<C> for Thunk {
     fn register(&self, cache: &mut C) {
-        let content = &self.borrow().value;
+        // The borrow must be released before attaching the CUI, which mutably borrows the thunk.
+        let cui = {
+            let content = &self.borrow().value;
+            is_of_interest(content).then(|| cui(content))
+        };
 
-        if is_of_interest(content) {
-            cache.attach_cui(self, cui(content));
+        if let Some(cui) = cui {
+            cache.attach_cui(self, cui);
         }
     }
 }
@@ -140,15 +264,28 @@ impl<C: Cache> Register<C> for ArrayData {
 /// If no CUI is provided, the closure to hash is a dependency of a thunk of interest, but isn't
 /// itself of interest. We don't want to override the original decision of the interpreter so we
 /// don't compute its CUI, but we still try a shallow, fast hash that works on simple values.
+///
+/// Only the free variables of the expression are taken into account, such that unrelated bindings
+/// don't prevent re-use. Free variables which aren't bound in the closure's environment must be
+/// bound by the standard library; otherwise, they come from elsewhere (e.g. additional bindings
+/// provided by the embedder), and the hash is undefined.
 pub fn semantic_hash(closure: &Closure, cui: Option<SemanticHash>) -> Option<SemanticHash> {
-    // TODO: For now, we're being stupid, and hash the whole environment. What we should do is
-    // 1. Compute the free variables of each expression of interest
-    // 2. Only retrieve the free variables as dependencies from the environment
-    let mut hasher = DefaultHasher::new();
-
-    for (id, thunk) in closure.env.iter_elems() {
-        id.hash(&mut hasher);
-        thunk.semantic_hash()?.hash(&mut hasher);
+    let mut hasher = StableHasher::default();
+
+    // We sort the dependencies by name, as the iteration order of the environment isn't stable.
+    let deps: BTreeMap<&str, Option<&Thunk>> = free_vars(&closure.value)?
+        .into_iter()
+        .map(|id| (id.label(), closure.env.get(&id)))
+        .collect();
+
+    for (label, thunk) in deps {
+        label.hash(&mut hasher);
+
+        match thunk {
+            Some(thunk) => thunk.semantic_hash()?.hash(&mut hasher),
+            None if is_stdlib_binding(Ident::new(label)) => (),
+            None => return None,
+        }
     }
 
     if let Some(cui) = cui {
@@ -164,7 +301,7 @@ pub fn semantic_hash(closure: &Closure, cui: Option<SemanticHash>) -> Option<Sem
             ValueContentRef::Number(n) => n.hash(&mut hasher),
             ValueContentRef::String(s) => s.hash(&mut hasher),
             ValueContentRef::EnumVariant(EnumVariantData { tag, arg: None }) => {
-                tag.hash(&mut hasher)
+                tag.label().hash(&mut hasher)
             }
             ValueContentRef::Array(Container::Empty)
             | ValueContentRef::Record(Container::Empty) => 0.hash(&mut hasher),
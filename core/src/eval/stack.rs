@@ -312,6 +312,9 @@ pub(crate) enum SealedCont {
 /// local variable), which is properly aligned.
 pub(crate) struct Stack<C: Cache> {
     data: Vec<u8>,
+    /// The number of items currently on the stack. Since items have different sizes, we can't
+    /// derive it from the length of [Self::data].
+    len: usize,
     phantom: std::marker::PhantomData<C>,
 }
 
@@ -319,6 +322,7 @@ impl<C: Cache> Default for Stack<C> {
     fn default() -> Self {
         Stack {
             data: Vec::default(),
+            len: 0,
             phantom: std::marker::PhantomData,
         }
     }
@@ -340,6 +344,7 @@ impl<C: Cache> Stack<C> {
     pub(crate) fn with_capacity(capacity: usize) -> Self {
         Stack {
             data: Vec::with_capacity(capacity),
+            len: 0,
             phantom: std::marker::PhantomData,
         }
     }
@@ -378,6 +383,8 @@ impl<C: Cache> Stack<C> {
                 .set_len(prev_len + size_value + mem::size_of::<Marker>());
         }
 
+        self.len += 1;
+
         // Since we've copied the value onto the eval stack, we will materialize it again at pop
         // time: we mustn't run any clean up code now. In some sense, we've moved the value from
         // the Rust stack into the eval stack, albeit as a bunch of untyped bytes instead of a
@@ -399,6 +406,11 @@ impl<C: Cache> Stack<C> {
         unsafe { Some(self.pop_unchecked()) }
     }
 
+    /// Returns the number of items on the stack.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
     /// Peeks the top marker of the stack, or returns `None` if the stack is empty.
     pub(crate) fn top_marker(&self) -> Option<Marker> {
         self.data
@@ -434,6 +446,7 @@ impl<C: Cache> Stack<C> {
         // this computation can't underflow at this point
         self.data
             .truncate(self.data.len() - mem::size_of::<Marker>() - mem::size_of::<T>());
+        self.len -= 1;
 
         item
     }
@@ -18,14 +18,24 @@ impl Display for FormatError {
 }
 
 /// Format a Nickel file being read from `input`, writing the result to `output`.
-pub fn format(mut input: impl Read, mut output: impl Write) -> Result<(), FormatError> {
+pub fn format(input: impl Read, output: impl Write) -> Result<(), FormatError> {
+    format_with_indent(input, output, None)
+}
+
+/// Format a Nickel file being read from `input`, writing the result to `output`, using `indent`
+/// for each level of indentation instead of the default two spaces.
+pub fn format_with_indent(
+    mut input: impl Read,
+    mut output: impl Write,
+    indent: Option<&str>,
+) -> Result<(), FormatError> {
     let grammar = tree_sitter_nickel::LANGUAGE.into();
     let query = TopiaryQuery::new(&grammar, topiary_queries::nickel()).map_err(FormatError)?;
     let language = Language {
         name: "nickel".to_owned(),
         query,
         grammar,
-        indent: None,
+        indent: indent.map(ToOwned::to_owned),
     };
 
     formatter(
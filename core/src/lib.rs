@@ -5,13 +5,17 @@ pub mod ast;
 pub mod cache;
 pub mod closurize;
 pub mod deserialize;
+#[cfg(feature = "doc")]
+pub mod doctest;
 pub mod error;
 pub mod eval;
 pub mod label;
+pub mod lint;
 #[cfg(feature = "nix-experimental")]
 pub mod nix_ffi;
 pub mod package;
 pub mod parser;
+pub mod persistent_cache;
 pub mod position;
 pub mod pretty;
 pub mod program;
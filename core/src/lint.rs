@@ -0,0 +1,389 @@
+//! Static lints, which are checked on the AST of a program before its evaluation.
+//!
+//! Lints find code which is valid but most probably not what the user meant, such as variables
+//! which are never used or match arms which can never be selected. They are reported as
+//! [warnings](crate::error::Warning), whose level can be configured by the user (see
+//! [crate::error::warning::WarningLevels]).
+use std::ops::Range;
+
+use crate::{
+    ast::{
+        Ast, Match, Node,
+        pattern::{Pattern, bindings::Bindings as _},
+        record::{FieldPathElem, Record},
+    },
+    environment::Environment as GenericEnvironment,
+    error::Warning,
+    identifier::{Ident, LocIdent},
+    position::TermPos,
+    pretty::ident_quoted,
+    term::pattern::compile::is_catch_all,
+    traverse::{TraverseAlloc, TraverseControl},
+};
+
+/// Maps variables to their index in [Linter::bindings], or to `None` for the variables which
+/// aren't subject to lints, such as record fields.
+type Environment = GenericEnvironment<Ident, Option<usize>>;
+
+/// A variable bound by a let binding, a function argument or a pattern.
+struct Binding {
+    id: LocIdent,
+    /// Only let-bound variables are reported when unused. Function arguments are often unused on
+    /// purpose, typically to respect the interface expected by a higher-order function.
+    check_unused: bool,
+    used: bool,
+}
+
+impl Binding {
+    /// Bindings which start with an underscore, or which have been generated by the parser, are
+    /// never linted.
+    fn is_ignored(&self) -> bool {
+        self.id.label().starts_with('_') || !self.id.pos.is_def()
+    }
+}
+
+/// Checks the lints on an AST and returns the warnings found.
+pub fn lint<'ast>(ast: &'ast Ast<'ast>) -> Vec<Warning> {
+    Linter::new().lint(ast)
+}
+
+struct Linter {
+    bindings: Vec<Binding>,
+    warnings: Vec<Warning>,
+}
+
+impl Linter {
+    fn new() -> Self {
+        Linter {
+            bindings: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn lint<'ast>(mut self, ast: &'ast Ast<'ast>) -> Vec<Warning> {
+        self.walk(ast, &Environment::new());
+        self.warnings
+    }
+
+    /// Binds a new variable in `env`, checking that it doesn't shadow another binding.
+    fn bind(&mut self, env: &mut Environment, id: LocIdent, check_unused: bool) {
+        let binding = Binding {
+            id,
+            check_unused,
+            used: false,
+        };
+
+        if let Some(Some(idx)) = env.get(&id.ident())
+            && !binding.is_ignored()
+        {
+            self.warnings.push(Warning::ShadowedIdentifier {
+                name: id.ident(),
+                pos: id.pos,
+                shadowed_pos: self.bindings[*idx].id.pos,
+            });
+        }
+
+        env.insert(id.ident(), Some(self.bindings.len()));
+        self.bindings.push(binding);
+    }
+
+    /// Reports the unused bindings among the given range of [Self::bindings]. Must be called once
+    /// the scope of these bindings has been fully walked.
+    fn check_unused(&mut self, range: Range<usize>) {
+        for binding in &self.bindings[range] {
+            if binding.check_unused && !binding.used && !binding.is_ignored() {
+                self.warnings.push(Warning::UnusedBinding {
+                    name: binding.id.ident(),
+                    pos: binding.id.pos,
+                });
+            }
+        }
+    }
+
+    fn use_var(&mut self, id: Ident, env: &Environment) {
+        if let Some(Some(idx)) = env.get(&id) {
+            self.bindings[*idx].used = true;
+        }
+    }
+
+    /// Walks the ASTs nested in another syntactic element, such as the contracts of a type or the
+    /// default values of a pattern.
+    fn walk_nested<'ast, T>(&mut self, node: &'ast T, env: &Environment)
+    where
+        T: TraverseAlloc<'ast, Ast<'ast>>,
+    {
+        node.traverse_ref::<_, ()>(
+            &mut |ast: &'ast Ast<'ast>, env: &Environment| {
+                self.walk(ast, env);
+                TraverseControl::SkipBranch
+            },
+            env,
+        );
+    }
+
+    fn walk<'ast>(&mut self, ast: &'ast Ast<'ast>, env: &Environment) {
+        ast.traverse_ref::<_, ()>(
+            &mut |ast: &'ast Ast<'ast>, env: &Environment| match &ast.node {
+                Node::Var(id) => {
+                    self.use_var(id.ident(), env);
+                    TraverseControl::Continue
+                }
+                Node::Fun { args, body } => {
+                    let mut fun_env = env.clone();
+
+                    for arg in args.iter() {
+                        self.walk_nested(arg, env);
+                    }
+
+                    for binding in args.iter().flat_map(|arg| arg.bindings()) {
+                        self.bind(&mut fun_env, binding.id, false);
+                    }
+
+                    self.walk(body, &fun_env);
+                    TraverseControl::SkipBranch
+                }
+                Node::Let {
+                    bindings,
+                    body,
+                    rec,
+                } => {
+                    let first = self.bindings.len();
+                    let mut let_env = env.clone();
+
+                    for binding in bindings.iter() {
+                        for pat_binding in binding.pattern.bindings() {
+                            self.bind(&mut let_env, pat_binding.id, true);
+                        }
+                    }
+
+                    let let_bindings = first..self.bindings.len();
+
+                    let bound_env = if *rec { &let_env } else { env };
+
+                    for binding in bindings.iter() {
+                        self.walk_nested(&binding.pattern, bound_env);
+                        self.walk_nested(&binding.metadata.annotation, bound_env);
+                        self.walk(&binding.value, bound_env);
+                    }
+
+                    self.walk(body, &let_env);
+                    self.check_unused(let_bindings);
+                    TraverseControl::SkipBranch
+                }
+                Node::Record(record) => {
+                    self.walk_record(record, env);
+                    TraverseControl::SkipBranch
+                }
+                Node::Match(data) => {
+                    self.walk_match(data, env);
+                    TraverseControl::SkipBranch
+                }
+                _ => TraverseControl::Continue,
+            },
+            env,
+        );
+    }
+
+    fn walk_record<'ast>(&mut self, record: &'ast Record<'ast>, env: &Environment) {
+        // Included variables come from the enclosing scope.
+        for include in record.includes.iter() {
+            self.use_var(include.ident.ident(), env);
+            self.walk_nested(&include.metadata.annotation, env);
+        }
+
+        // Fields are recursively in scope in the whole record, and shadow the enclosing scope.
+        let mut rec_env = env.clone();
+        let fields = record
+            .field_defs
+            .iter()
+            .filter_map(|def| def.path.first()?.try_as_ident())
+            .chain(record.includes.iter().map(|include| include.ident));
+
+        for id in fields {
+            rec_env.insert(id.ident(), None);
+        }
+
+        for def in record.field_defs.iter() {
+            for elem in def.path.iter() {
+                if let FieldPathElem::Expr(expr) = elem {
+                    self.walk(expr, env);
+                }
+            }
+
+            self.walk_nested(&def.metadata.annotation, &rec_env);
+
+            if let Some(value) = &def.value {
+                self.walk(value, &rec_env);
+            }
+        }
+
+        self.check_duplicate_fields(record);
+    }
+
+    /// Checks that the same field isn't defined twice with the same priority. Values which are
+    /// record literals are ignored, as they can be merged together.
+    fn check_duplicate_fields(&mut self, record: &Record<'_>) {
+        let mut defined: Vec<(Vec<Ident>, _, TermPos)> = Vec::new();
+
+        for def in record.field_defs.iter() {
+            let Some(value) = &def.value else { continue };
+
+            if matches!(value.node, Node::Record(_)) {
+                continue;
+            }
+
+            let Some(path) = def
+                .path
+                .iter()
+                .map(|elem| elem.try_as_ident().map(|id| id.ident()))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            // unwrap(): field paths are never empty
+            let pos = def.path[0].pos().fuse(def.path.last().unwrap().pos());
+
+            let prev = defined.iter().find(|(prev_path, prev_priority, _)| {
+                *prev_path == path && *prev_priority == &def.metadata.priority
+            });
+
+            if let Some((_, _, prev_pos)) = prev {
+                self.warnings.push(Warning::DuplicateField {
+                    path: path
+                        .iter()
+                        .map(|id| ident_quoted(*id))
+                        .collect::<Vec<_>>()
+                        .join("."),
+                    pos,
+                    prev_pos: *prev_pos,
+                });
+            } else {
+                defined.push((path, &def.metadata.priority, pos));
+            }
+        }
+    }
+
+    fn walk_match<'ast>(&mut self, data: &'ast Match<'ast>, env: &Environment) {
+        let mut catch_all: Option<&Pattern<'_>> = None;
+
+        for branch in data.branches.iter() {
+            if let Some(catch_all) = catch_all {
+                self.warnings.push(Warning::UnreachableMatchArm {
+                    pos: branch.pattern.pos,
+                    catch_all_pos: catch_all.pos,
+                });
+            } else if is_catch_all(&branch.pattern) && branch.guard.is_none() {
+                catch_all = Some(&branch.pattern);
+            }
+
+            let mut branch_env = env.clone();
+
+            self.walk_nested(&branch.pattern, env);
+
+            for binding in branch.pattern.bindings() {
+                self.bind(&mut branch_env, binding.id, false);
+            }
+
+            if let Some(guard) = &branch.guard {
+                self.walk(guard, &branch_env);
+            }
+
+            self.walk(&branch.body, &branch_env);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::AstAlloc,
+        error::warning::WarningKind,
+        files::Files,
+        parser::{ErrorTolerantParser as _, grammar, lexer::Lexer},
+    };
+
+    fn lint_kinds(source: &str) -> Vec<WarningKind> {
+        let mut files = Files::empty();
+        let file_id = files.add("<test>", source.to_owned());
+        let alloc = AstAlloc::new();
+        let ast = grammar::TermParser::new()
+            .parse_strict(&alloc, file_id, Lexer::new(source))
+            .unwrap();
+
+        Linter::new().lint(&ast).iter().map(Warning::kind).collect()
+    }
+
+    #[test]
+    fn unused_bindings() {
+        assert_eq!(
+            lint_kinds("let x = 1 in 2"),
+            vec![WarningKind::UnusedBinding]
+        );
+        assert_eq!(lint_kinds("let x = 1 in x"), vec![]);
+        assert_eq!(lint_kinds("let _x = 1 in 2"), vec![]);
+        assert_eq!(
+            lint_kinds("let {a, b} = {a = 1, b = 2} in a"),
+            vec![WarningKind::UnusedBinding]
+        );
+        // Function arguments aren't reported.
+        assert_eq!(lint_kinds("fun x y => x"), vec![]);
+        // A field shadows an outer let binding.
+        assert_eq!(
+            lint_kinds("let x = 1 in { x = 2, y = x }"),
+            vec![WarningKind::UnusedBinding]
+        );
+        assert_eq!(lint_kinds("let x = 1 in { include x }"), vec![]);
+        assert_eq!(lint_kinds("let x = 1 in { y | Number = x }"), vec![]);
+        assert_eq!(lint_kinds("let C = Number in { y | C = 1 }"), vec![]);
+        assert_eq!(lint_kinds("let rec f = fun n => f n in f 1"), vec![]);
+    }
+
+    #[test]
+    fn shadowed_identifiers() {
+        assert_eq!(
+            lint_kinds("let x = 1 in let x = x in x"),
+            vec![WarningKind::ShadowedIdentifier]
+        );
+        assert_eq!(
+            lint_kinds("fun x => match { x => x }"),
+            vec![WarningKind::ShadowedIdentifier]
+        );
+        // Fields aren't variables.
+        assert_eq!(lint_kinds("{ x = 1, f = fun x => x }"), vec![]);
+    }
+
+    #[test]
+    fn duplicate_fields() {
+        assert_eq!(
+            lint_kinds("{ a = 1, a = 2 }"),
+            vec![WarningKind::DuplicateField]
+        );
+        assert_eq!(
+            lint_kinds("{ a.b = 1, a.b = 2 }"),
+            vec![WarningKind::DuplicateField]
+        );
+        assert_eq!(lint_kinds("{ a | default = 1, a = 2 }"), vec![]);
+        assert_eq!(lint_kinds("{ a = { b = 1 }, a = { c = 2 } }"), vec![]);
+        assert_eq!(lint_kinds("{ a.b = 1, a.c = 2 }"), vec![]);
+        assert_eq!(lint_kinds("{ a | Number, a = 2 }"), vec![]);
+    }
+
+    #[test]
+    fn unreachable_match_arms() {
+        assert_eq!(
+            lint_kinds("match { x => 1, 'Foo => 2 }"),
+            vec![WarningKind::UnreachableMatchArm]
+        );
+        assert_eq!(
+            lint_kinds("match { _ => 1, 'Foo => 2, 'Bar => 3 }"),
+            vec![
+                WarningKind::UnreachableMatchArm,
+                WarningKind::UnreachableMatchArm
+            ]
+        );
+        assert_eq!(lint_kinds("match { x if x > 0 => 1, _ => 2 }"), vec![]);
+        assert_eq!(lint_kinds("match { 'Foo => 1, _ => 2 }"), vec![]);
+    }
+}
@@ -9,6 +9,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::{error::ImportErrorKind, identifier::Ident, position::TermPos};
 
 /// Maps package imports to filesystem locations.
@@ -20,7 +22,7 @@ use crate::{error::ImportErrorKind, identifier::Ident, position::TermPos};
 /// Package names are package-local, in the sense that if you import `foo`
 /// and `bar`, and then `foo` also imports `bar`, then those two `bar`s
 /// are not necessarily the same package.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PackageMap {
     /// The top-level name-to-path map. If the main file imports the name `foo`,
     /// it gets looked up here.
@@ -0,0 +1,461 @@
+//! An opt-in, on-disk cache of typechecking results, shared between runs of Nickel.
+//!
+//! Typechecking a large configuration, and in particular the libraries it imports, can take a
+//! significant share of the total evaluation time, although those files rarely change from one
+//! run to the next. When a persistent cache is enabled, each Nickel file loaded from the
+//! filesystem that typechecks successfully is recorded in the cache directory, together with the
+//! files its imports resolved to and the content hash of all the files it transitively imports.
+//! On subsequent runs, a file whose source and transitive imports are unchanged, and whose imports
+//! still resolve to the same files, is marked as typechecked without running the typechecker on
+//! it. Its imports are still resolved and looked up in the cache in turn. The recorded hashes are
+//! compared against the sources already loaded in the [SourceCache], each of which is hashed at
+//! most once per run.
+//!
+//! Only the typechecking phase is cached: files are still parsed, compiled and transformed as
+//! usual on each run. Caching transformed runtime terms as well is tracked in the design note
+//! `notes/persistent-cache.md`, which explains what's missing to do so.
+//!
+//! Files whose typechecking infers the type of wildcards are never recorded, because those types
+//! are used later on to generate contracts and would be missing if typechecking were skipped.
+//! Whether each import had wildcards is part of the entry of the importing file. Similarly, files
+//! importing sources that aren't on the filesystem (such as standard input) are never recorded,
+//! since there's no way to tell if such an import has changed.
+//!
+//! Entries are keyed by a hash of the file's path and content, the typechecking mode, the version
+//! of Nickel, and a salt provided by the user of the cache, which should account for anything else
+//! that influences typechecking (the import paths, the bindings of the initial environment, etc.).
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    ast::InputFormat,
+    cache::{ImportData, SourceCache},
+    files::FileId,
+    typecheck::TypecheckMode,
+};
+
+/// The name of the subdirectory of the cache directory holding the typechecking entries.
+const TYPECHECK_DIR: &str = "typecheck";
+
+type Hash = [u8; 32];
+
+fn hash_source(source: &str) -> Hash {
+    Sha256::digest(source.as_bytes()).into()
+}
+
+fn hex(hash: &Hash) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// A file that a cached file transitively depends on, together with the hash of its content at
+/// the time the entry was recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Dependency {
+    path: PathBuf,
+    hash: Hash,
+}
+
+/// A direct import of a cached file, as resolved when the entry was recorded.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct ResolvedImport {
+    path: PathBuf,
+    format: String,
+    /// Whether typechecking the import inferred the type of some wildcards.
+    wildcards: bool,
+}
+
+/// An entry of the cache, recording that a file has been successfully typechecked.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    /// The direct imports of the file, sorted.
+    imports: Vec<ResolvedImport>,
+    /// The transitive dependencies of the file.
+    deps: Vec<Dependency>,
+}
+
+/// An entry found in the cache by [PersistentCache::lookup], which must be confirmed by
+/// [PersistentCache::confirm] once the imports of the file have been resolved and typechecked.
+#[derive(Debug)]
+pub struct Candidate {
+    entry: Entry,
+}
+
+/// A persistent cache of typechecking results stored in a directory. See the [module-level
+/// documentation](self).
+#[derive(Debug, Clone)]
+pub struct PersistentCache {
+    dir: PathBuf,
+    salt: String,
+    /// The transitive dependencies of the files that have been typechecked or found in the cache
+    /// during this session. A file that isn't in this map can't be the dependency of a cached
+    /// file, because we can't tell if it's up to date.
+    deps: HashMap<FileId, Vec<Dependency>>,
+    /// The hashes of the sources of the files that have been computed during this session.
+    hashes: HashMap<FileId, Hash>,
+}
+
+impl PersistentCache {
+    /// Creates a new persistent cache stored in `dir`. The directory is created lazily when the
+    /// first entry is recorded. `salt` must identify any external parameter which could change the
+    /// outcome of typechecking, as entries recorded with a different salt are ignored.
+    pub fn new(dir: impl Into<PathBuf>, salt: impl Into<String>) -> Self {
+        PersistentCache {
+            dir: dir.into(),
+            salt: salt.into(),
+            deps: HashMap::new(),
+            hashes: HashMap::new(),
+        }
+    }
+
+    /// Returns the directory where the cache is stored.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, path: &Path, source: &str, mode: TypecheckMode) -> PathBuf {
+        let mut hasher = Sha256::new();
+
+        for part in [
+            self.salt.as_bytes(),
+            env!("CARGO_PKG_VERSION").as_bytes(),
+            format!("{mode:?}").as_bytes(),
+            path.as_os_str().as_encoded_bytes(),
+            source.as_bytes(),
+        ] {
+            // We prefix each part with its length, so that moving bytes from one part to the
+            // next can't produce the same key.
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+
+        self.dir
+            .join(TYPECHECK_DIR)
+            .join(format!("{}.bin", hex(&hasher.finalize().into())))
+    }
+
+    fn read_entry(path: &Path) -> Option<Entry> {
+        let mut file = io::BufReader::new(fs::File::open(path).ok()?);
+        bincode::serde::decode_from_std_read(&mut file, bincode::config::standard()).ok()
+    }
+
+    /// Returns the hash of the source of `file_id`, which is only computed the first time.
+    fn hash(&mut self, sources: &SourceCache, file_id: FileId) -> Hash {
+        *self
+            .hashes
+            .entry(file_id)
+            .or_insert_with(|| hash_source(sources.source(file_id)))
+    }
+
+    /// Looks up `file_id` in the cache. Returns a candidate entry if the file, with its current
+    /// source, has been successfully typechecked in `mode` by a previous run. Always returns
+    /// `None` for files that haven't been loaded from the filesystem.
+    ///
+    /// The file can't be considered typechecked yet: the candidate must be confirmed with
+    /// [Self::confirm] once the imports of the file have been resolved and typechecked, which
+    /// checks that they haven't changed either.
+    pub fn lookup(
+        &mut self,
+        sources: &SourceCache,
+        file_id: FileId,
+        mode: TypecheckMode,
+    ) -> Option<Candidate> {
+        let path = sources.filesystem_path(file_id)?;
+        let entry = Self::read_entry(&self.entry_path(path, sources.source(file_id), mode))?;
+
+        Some(Candidate { entry })
+    }
+
+    /// Confirms a candidate entry for `file_id`, which must have been returned by [Self::lookup]
+    /// for the same file. The imports of `file_id` must have been resolved and typechecked, and
+    /// `has_wildcards` must tell if typechecking a file inferred the type of some wildcards.
+    ///
+    /// Returns `true` if the imports of the file still resolve to the same files as when the
+    /// entry was recorded, with the same wildcards, and if none of its transitive imports has
+    /// changed since, in which case the file doesn't need to be typechecked again.
+    pub fn confirm(
+        &mut self,
+        sources: &SourceCache,
+        import_data: &ImportData,
+        file_id: FileId,
+        candidate: Candidate,
+        has_wildcards: impl Fn(FileId) -> bool,
+    ) -> bool {
+        let Candidate { entry } = candidate;
+
+        if Self::resolved_imports(sources, import_data, file_id, has_wildcards)
+            .is_none_or(|imports| imports != entry.imports)
+            || self
+                .transitive_deps(sources, import_data, file_id)
+                .is_none_or(|deps| deps != entry.deps)
+        {
+            return false;
+        }
+
+        self.deps.insert(file_id, entry.deps);
+        true
+    }
+
+    /// Returns the transitive dependencies of `file_id`, with the hashes of their sources loaded
+    /// during this session, or `None` if one of the imports of `file_id` hasn't been loaded from
+    /// the filesystem, or hasn't been typechecked or found in the cache yet.
+    fn transitive_deps(
+        &mut self,
+        sources: &SourceCache,
+        import_data: &ImportData,
+        file_id: FileId,
+    ) -> Option<Vec<Dependency>> {
+        let mut deps = BTreeMap::new();
+
+        for target in import_data.imports.get(&file_id).into_iter().flatten() {
+            let dep_path = sources.filesystem_path(target.file_id)?;
+            deps.insert(dep_path.to_owned(), self.hash(sources, target.file_id));
+
+            // Non-Nickel files don't have imports of their own.
+            if let InputFormat::Nickel = target.format {
+                // If the import isn't known, it's either not on the filesystem or it's part of an
+                // import cycle that is still being typechecked. In both cases, we bail out.
+                let transitive = self.deps.get(&target.file_id)?;
+                deps.extend(transitive.iter().map(|dep| (dep.path.clone(), dep.hash)));
+            }
+        }
+
+        Some(
+            deps.into_iter()
+                .map(|(path, hash)| Dependency { path, hash })
+                .collect(),
+        )
+    }
+
+    /// Returns the direct imports of `file_id`, sorted, or `None` if one of them hasn't been
+    /// loaded from the filesystem.
+    fn resolved_imports(
+        sources: &SourceCache,
+        import_data: &ImportData,
+        file_id: FileId,
+        has_wildcards: impl Fn(FileId) -> bool,
+    ) -> Option<Vec<ResolvedImport>> {
+        let mut imports = import_data
+            .imports
+            .get(&file_id)
+            .into_iter()
+            .flatten()
+            .map(|target| {
+                Some(ResolvedImport {
+                    path: sources.filesystem_path(target.file_id)?.to_owned(),
+                    format: target.format.to_string(),
+                    wildcards: has_wildcards(target.file_id),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        imports.sort();
+        Some(imports)
+    }
+
+    /// Records that `file_id` and all of its imports have been successfully typechecked in
+    /// `mode`. `has_wildcards` must tell if typechecking a file inferred the type of some
+    /// wildcards. If `persist` is `false`, the result isn't written to the cache directory, but the
+    /// dependencies of `file_id` are still remembered, so that the files importing `file_id` can be
+    /// recorded.
+    ///
+    /// The file isn't recorded if it, or one of its transitive imports, hasn't been loaded from
+    /// the filesystem. Failing to write the entry isn't considered an error, as the cache is only
+    /// an optimization, and is reported through the returned value for information.
+    pub fn record(
+        &mut self,
+        sources: &SourceCache,
+        import_data: &ImportData,
+        file_id: FileId,
+        mode: TypecheckMode,
+        has_wildcards: impl Fn(FileId) -> bool,
+        persist: bool,
+    ) -> io::Result<()> {
+        let Some(path) = sources.filesystem_path(file_id) else {
+            return Ok(());
+        };
+
+        let Some(imports) = Self::resolved_imports(sources, import_data, file_id, has_wildcards)
+        else {
+            return Ok(());
+        };
+
+        let Some(deps) = self.transitive_deps(sources, import_data, file_id) else {
+            return Ok(());
+        };
+
+        let entry_path = self.entry_path(path, sources.source(file_id), mode);
+        self.deps.insert(file_id, deps.clone());
+
+        if !persist {
+            return Ok(());
+        }
+
+        // We write to a temporary file first and then rename it, so that concurrent runs never
+        // observe a partially written entry.
+        // unwrap(): `entry_path` is always a file in a subdirectory of the cache directory.
+        let entry_dir = entry_path.parent().unwrap();
+        fs::create_dir_all(entry_dir)?;
+        let tmp_path = entry_path.with_extension(format!("tmp.{}", std::process::id()));
+        let mut tmp_file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+
+        bincode::serde::encode_into_std_write(
+            Entry { imports, deps },
+            &mut tmp_file,
+            bincode::config::standard(),
+        )
+        .map_err(io::Error::other)?;
+
+        drop(tmp_file);
+        fs::rename(tmp_path, entry_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::ImportTarget;
+
+    fn no_wildcards(_: FileId) -> bool {
+        false
+    }
+
+    /// Writes a main file importing a library, loads both into a fresh source cache, and returns
+    /// the corresponding file ids.
+    fn load(dir: &Path, lib_content: &str) -> (SourceCache, ImportData, FileId, FileId) {
+        fs::write(dir.join("lib.ncl"), lib_content).unwrap();
+        fs::write(dir.join("main.ncl"), "(import \"lib.ncl\").x").unwrap();
+
+        let mut sources = SourceCache::new();
+        let main = sources
+            .add_file(dir.join("main.ncl"), InputFormat::Nickel)
+            .unwrap();
+        let lib = sources
+            .add_file(dir.join("lib.ncl"), InputFormat::Nickel)
+            .unwrap();
+
+        let mut import_data = ImportData::new();
+        import_data
+            .imports
+            .entry(main)
+            .or_default()
+            .insert(ImportTarget {
+                file_id: lib,
+                format: InputFormat::Nickel,
+            });
+
+        (sources, import_data, main, lib)
+    }
+
+    #[test]
+    fn hit_and_invalidation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mode = TypecheckMode::Walk;
+
+        let (sources, import_data, main, lib) = load(tmp.path(), "{ x = 1 }");
+        let mut cache = PersistentCache::new(&cache_dir, "salt");
+        assert!(cache.lookup(&sources, main, mode).is_none());
+        cache
+            .record(&sources, &import_data, lib, mode, no_wildcards, true)
+            .unwrap();
+        cache
+            .record(&sources, &import_data, main, mode, no_wildcards, true)
+            .unwrap();
+
+        // Imports are confirmed before the files importing them.
+        let mut cache = PersistentCache::new(&cache_dir, "salt");
+        let candidate = cache.lookup(&sources, lib, mode).unwrap();
+        assert!(cache.confirm(&sources, &import_data, lib, candidate, no_wildcards));
+        let candidate = cache.lookup(&sources, main, mode).unwrap();
+        assert!(cache.confirm(&sources, &import_data, main, candidate, no_wildcards));
+        assert!(
+            cache
+                .lookup(&sources, main, TypecheckMode::Enforce)
+                .is_none()
+        );
+        assert!(
+            PersistentCache::new(&cache_dir, "other")
+                .lookup(&sources, main, mode)
+                .is_none()
+        );
+
+        // Changing the library invalidates the main file, although its content is the same.
+        let (sources, import_data, main, lib) = load(tmp.path(), "{ x = 2 }");
+        let mut cache = PersistentCache::new(&cache_dir, "salt");
+        assert!(cache.lookup(&sources, lib, mode).is_none());
+        cache
+            .record(&sources, &import_data, lib, mode, no_wildcards, true)
+            .unwrap();
+        let candidate = cache.lookup(&sources, main, mode).unwrap();
+        assert!(!cache.confirm(&sources, &import_data, main, candidate, no_wildcards));
+    }
+
+    #[test]
+    fn imports_are_part_of_the_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mode = TypecheckMode::Walk;
+
+        let (sources, import_data, main, lib) = load(tmp.path(), "{ x = 1 }");
+        let mut cache = PersistentCache::new(&cache_dir, "salt");
+        cache
+            .record(&sources, &import_data, lib, mode, no_wildcards, true)
+            .unwrap();
+        cache
+            .record(&sources, &import_data, main, mode, no_wildcards, true)
+            .unwrap();
+
+        // The import now has wildcards.
+        let mut cache = PersistentCache::new(&cache_dir, "salt");
+        let candidate = cache.lookup(&sources, main, mode).unwrap();
+        assert!(!cache.confirm(&sources, &import_data, main, candidate, |id| id == lib));
+
+        // The import now resolves to a different file, although the sources are unchanged.
+        let other = tmp.path().join("other");
+        fs::create_dir(&other).unwrap();
+        fs::write(other.join("lib.ncl"), "{ x = 1 }").unwrap();
+        let mut sources = sources;
+        let other_lib = sources
+            .add_file(other.join("lib.ncl"), InputFormat::Nickel)
+            .unwrap();
+        let mut import_data = ImportData::new();
+        import_data
+            .imports
+            .entry(main)
+            .or_default()
+            .insert(ImportTarget {
+                file_id: other_lib,
+                format: InputFormat::Nickel,
+            });
+
+        let candidate = cache.lookup(&sources, main, mode).unwrap();
+        assert!(!cache.confirm(&sources, &import_data, main, candidate, no_wildcards));
+    }
+
+    #[test]
+    fn unknown_imports_are_not_recorded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mode = TypecheckMode::Walk;
+
+        let (sources, import_data, main, _) = load(tmp.path(), "{ x = 1 }");
+        let mut cache = PersistentCache::new(&cache_dir, "salt");
+        // The library has not been recorded, so the main file can't be either.
+        cache
+            .record(&sources, &import_data, main, mode, no_wildcards, true)
+            .unwrap();
+
+        assert!(
+            PersistentCache::new(&cache_dir, "salt")
+                .lookup(&sources, main, mode)
+                .is_none()
+        );
+        assert!(!cache_dir.exists());
+    }
+}
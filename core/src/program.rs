@@ -31,6 +31,8 @@ use crate::{
     eval::{
         Closure, VirtualMachine, VmContext,
         cache::Cache as EvalCache,
+        limits::EvalLimits,
+        native::{NativeFn, NativeFunction, NativeFunctionError},
         value::{Container, NickelValue, ValueContent},
     },
     files::{FileId, Files},
@@ -38,6 +40,7 @@ use crate::{
     label::Label,
     metrics::{increment, measure_runtime},
     package::PackageMap,
+    persistent_cache::PersistentCache,
     position::{PosIdx, PosTable, RawSpan},
     term::{
         BinaryOp, Import, MergePriority, RuntimeContract, Term,
@@ -258,6 +261,10 @@ pub struct Program<EC: EvalCache> {
     /// Extra contracts to apply to the main program source. Note that the contract is applied to
     /// the whole value before fields are extracted.
     pub contracts: Vec<ProgramContract>,
+    /// The file where the incremental evaluation cache is persisted between runs, if any. See
+    /// [Self::persist_incremental_cache].
+    #[cfg(feature = "incremental-experimental")]
+    incremental_cache_path: Option<PathBuf>,
 }
 
 impl<EC: EvalCache> Program<EC> {
@@ -536,6 +543,33 @@ impl<EC: EvalCache> Program<EC> {
         Ok(self.new_vm().eval_closure(closure)?.value)
     }
 
+    /// Saves the values computed by the incremental evaluator, so that they can be re-used by
+    /// subsequent runs on the same program. This is a no-op unless both incremental evaluation and
+    /// the incremental cache have been enabled when building the program (see
+    /// [ProgramBuilder::with_incremental_cache]).
+    ///
+    /// This should only be called after a successful evaluation.
+    #[cfg(feature = "incremental-experimental")]
+    pub fn persist_incremental_cache(&self) -> io::Result<()> {
+        let Some(path) = &self.incremental_cache_path else {
+            return Ok(());
+        };
+
+        // unwrap(): the incremental cache path is always a file in a subdirectory of the cache
+        // directory.
+        std::fs::create_dir_all(path.parent().unwrap())?;
+
+        // We write to a temporary file first and then rename it, so that a concurrent run never
+        // loads a partially written cache.
+        let tmp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+        let mut out = io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+        self.vm_ctxt.cache.persist(&mut out)?;
+        out.flush()?;
+        drop(out);
+
+        std::fs::rename(tmp_path, path)
+    }
+
     /// Same as `eval`, but proceeds to a full evaluation.
     pub fn eval_full(&mut self) -> Result<NickelValue, Error> {
         let prepared = self.prepare_eval()?;
@@ -562,6 +596,15 @@ impl<EC: EvalCache> Program<EC> {
         Ok(self.new_vm().eval_full_for_export_closure(prepared)?)
     }
 
+    /// Same as `eval_full_for_export`, but takes a closure, such as a part of the program
+    /// returned by [Self::eval_closurized_record_spine], instead of the whole program.
+    pub fn eval_full_for_export_closure(
+        &mut self,
+        closure: Closure,
+    ) -> Result<NickelValue, EvalError> {
+        self.new_vm().eval_full_for_export_closure(closure)
+    }
+
     /// Same as `eval_full`, but does not substitute all variables.
     pub fn eval_deep(&mut self) -> Result<NickelValue, Error> {
         let prepared = self.prepare_eval()?;
@@ -587,7 +630,7 @@ impl<EC: EvalCache> Program<EC> {
     }
 
     /// Load, parse, and typecheck the program (together with additional contracts) and the
-    /// standard library, if not already done.
+    /// standard library, if not already done. The warnings of the lints are reported as well.
     pub fn typecheck(&mut self, initial_mode: TypecheckMode) -> Result<(), Error> {
         // If the main file is known to not be Nickel, we don't bother parsing it into an AST
         // (`cache.typecheck()` will ignore it anyway)
@@ -635,6 +678,8 @@ impl<EC: EvalCache> Program<EC> {
             }
         }
 
+        self.vm_ctxt.lint();
+
         Ok(())
     }
 
@@ -949,6 +994,42 @@ impl<EC: EvalCache> Program<EC> {
     pub fn pos_table(&self) -> &PosTable {
         &self.vm_ctxt.pos_table
     }
+
+    /// Returns the filesystem paths of the sources this program depends on: the main input, the
+    /// contracts provided as source files, and all the files they transitively import. Standard
+    /// library modules and in-memory sources are left out.
+    ///
+    /// Imports are only known once they have been resolved, so this method should be called after
+    /// the program has been prepared or evaluated. If preparation failed midway, the result only
+    /// includes the imports that were resolved before the failure.
+    pub fn source_paths(&self) -> Vec<PathBuf> {
+        let cache = &self.vm_ctxt.import_resolver;
+
+        let roots =
+            std::iter::once(self.main_id).chain(self.contracts.iter().filter_map(|contract| {
+                match contract {
+                    ProgramContract::Source(file_id) => Some(*file_id),
+                    ProgramContract::Term(_) => None,
+                }
+            }));
+
+        let mut file_ids: Vec<FileId> = Vec::new();
+
+        for root in roots {
+            file_ids.push(root);
+            file_ids.extend(cache.import_data.transitive_imports(root));
+        }
+
+        let mut paths: Vec<PathBuf> = file_ids
+            .into_iter()
+            .filter_map(|file_id| cache.sources.filesystem_path(file_id))
+            .map(PathBuf::from)
+            .collect();
+
+        paths.sort();
+        paths.dedup();
+        paths
+    }
 }
 
 /// An error that occurred during a call to [`ProgramBuilder::build`].
@@ -961,6 +1042,8 @@ pub enum BuilderError {
         path: Option<PathBuf>,
         error: std::io::Error,
     },
+    /// The type of a native function couldn't be parsed.
+    NativeFunctionType { name: Ident, error: ParseError },
 }
 
 impl std::error::Error for BuilderError {}
@@ -976,10 +1059,20 @@ impl std::fmt::Display for BuilderError {
                 }
             }
             Self::NoInputs => write!(f, "ProgramBuilder::build: no inputs were added"),
+            Self::NativeFunctionType { name, .. } => {
+                write!(f, "invalid type for the native function `{name}`")
+            }
         }
     }
 }
 
+/// A native function added to a [`ProgramBuilder`], whose type hasn't been parsed yet.
+struct PendingNativeFunction {
+    name: Ident,
+    typ: String,
+    fun: Box<NativeFn>,
+}
+
 /// An input source held by a [`ProgramBuilder`].
 ///
 /// The concrete `Read` implementation is erased to `Box<dyn Read>` so that builders can mix paths
@@ -1028,8 +1121,13 @@ pub struct ProgramBuilder<R = NullReporter, W = io::Sink> {
     import_paths: Vec<PathBuf>,
     package_map: Option<PackageMap>,
     extra_env: Vec<(Ident, NickelValue)>,
+    native_functions: Vec<PendingNativeFunction>,
+    eval_limits: EvalLimits,
+    persistent_cache_dir: Option<PathBuf>,
     #[cfg(feature = "incremental-experimental")]
     enable_incremental_evaluation: bool,
+    #[cfg(feature = "incremental-experimental")]
+    incremental_cache_dir: Option<PathBuf>,
     trace: W,
     reporter: R,
 }
@@ -1053,8 +1151,13 @@ impl ProgramBuilder {
             import_paths: Vec::new(),
             package_map: None,
             extra_env: Vec::new(),
+            native_functions: Vec::new(),
+            eval_limits: EvalLimits::default(),
+            persistent_cache_dir: None,
             #[cfg(feature = "incremental-experimental")]
             enable_incremental_evaluation: false,
+            #[cfg(feature = "incremental-experimental")]
+            incremental_cache_dir: None,
             trace: io::sink(),
             reporter: NullReporter {},
         }
@@ -1176,8 +1279,13 @@ impl<R, W> ProgramBuilder<R, W> {
             import_paths: self.import_paths,
             package_map: self.package_map,
             extra_env: self.extra_env,
+            native_functions: self.native_functions,
+            eval_limits: self.eval_limits,
+            persistent_cache_dir: self.persistent_cache_dir,
             #[cfg(feature = "incremental-experimental")]
             enable_incremental_evaluation: self.enable_incremental_evaluation,
+            #[cfg(feature = "incremental-experimental")]
+            incremental_cache_dir: self.incremental_cache_dir,
             trace,
             reporter: self.reporter,
         }
@@ -1197,8 +1305,13 @@ impl<R, W> ProgramBuilder<R, W> {
             import_paths: self.import_paths,
             package_map: self.package_map,
             extra_env: self.extra_env,
+            native_functions: self.native_functions,
+            eval_limits: self.eval_limits,
+            persistent_cache_dir: self.persistent_cache_dir,
             #[cfg(feature = "incremental-experimental")]
             enable_incremental_evaluation: self.enable_incremental_evaluation,
+            #[cfg(feature = "incremental-experimental")]
+            incremental_cache_dir: self.incremental_cache_dir,
             trace: self.trace,
             reporter,
         }
@@ -1226,12 +1339,60 @@ impl<R, W> ProgramBuilder<R, W> {
         self
     }
 
+    /// Expose a function implemented in Rust to the program under the name `name`, with the Nickel
+    /// type `typ`. The type is parsed at [`Self::build`] time.
+    ///
+    /// The function behaves like a function of the standard library: it is typechecked against
+    /// `typ`, and the contract corresponding to `typ` is applied to it at runtime. Its arguments
+    /// are fully evaluated before `fun` is called. See [crate::eval::native] for more details.
+    pub fn add_native_function(
+        mut self,
+        name: impl Into<Ident>,
+        typ: impl Into<String>,
+        fun: impl Fn(&[NickelValue]) -> Result<NickelValue, NativeFunctionError> + 'static,
+    ) -> Self {
+        self.native_functions.push(PendingNativeFunction {
+            name: name.into(),
+            typ: typ.into(),
+            fun: Box::new(fun),
+        });
+        self
+    }
+
+    /// Enable the persistent cache of typechecking results, stored in `dir`. Disabled by default.
+    ///
+    /// Files that have been typechecked by a previous run using the same cache directory, and
+    /// which haven't changed since (nor have their transitive imports), aren't typechecked again.
+    /// Only typechecking is cached: parsing and program transformations still happen on each run.
+    /// See [crate::persistent_cache] for more details.
+    pub fn with_persistent_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.persistent_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Bound the work performed by each evaluation of the program, e.g. to evaluate untrusted
+    /// code. No limit is set by default. See [crate::eval::limits] for more details.
+    pub fn with_eval_limits(mut self, limits: EvalLimits) -> Self {
+        self.eval_limits = limits;
+        self
+    }
+
     /// Enable incremental evaluation (experimental). Disabled by default.
     #[cfg(feature = "incremental-experimental")]
     pub fn with_incremental_evaluation(mut self) -> Self {
         self.enable_incremental_evaluation = true;
         self
     }
+
+    /// Save the values computed by incremental evaluation in `dir` at the end of a run (see
+    /// [Program::persist_incremental_cache]), and re-use the ones saved by previous runs
+    /// (experimental). This has no effect unless incremental evaluation is enabled, and is
+    /// independent from [Self::with_persistent_cache]. Disabled by default.
+    #[cfg(feature = "incremental-experimental")]
+    pub fn with_incremental_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.incremental_cache_dir = Some(dir.into());
+        self
+    }
 }
 
 impl<R, W> ProgramBuilder<R, W>
@@ -1255,8 +1416,13 @@ where
             import_paths,
             package_map,
             extra_env,
+            native_functions,
+            eval_limits,
+            persistent_cache_dir,
             #[cfg(feature = "incremental-experimental")]
             enable_incremental_evaluation,
+            #[cfg(feature = "incremental-experimental")]
+            incremental_cache_dir,
             trace,
             reporter,
         } = self;
@@ -1296,6 +1462,24 @@ where
             )
         };
 
+        #[cfg(feature = "incremental-experimental")]
+        let incremental_cache_path = incremental_cache_dir
+            .as_ref()
+            .filter(|_| enable_incremental_evaluation)
+            .map(|dir| incremental_cache_path(dir, &cache, main_id));
+
+        cache.persistent = persistent_cache_dir.map(|dir| {
+            PersistentCache::new(
+                dir,
+                persistent_cache_salt(
+                    &import_paths,
+                    package_map.as_ref(),
+                    &extra_env,
+                    &native_functions,
+                ),
+            )
+        });
+
         cache.sources.add_import_paths(import_paths.into_iter());
         if let Some(map) = package_map {
             cache.sources.set_package_map(map);
@@ -1313,23 +1497,113 @@ where
         }
 
         #[allow(unused_mut)]
-        let mut vm_ctxt = VmContext::new(cache, trace, reporter).with_extend_env(extra_env);
+        let mut vm_ctxt: VmContext<_, EC> = VmContext::new(cache, trace, reporter)
+            .with_extend_env(extra_env)
+            .with_eval_limits(eval_limits);
+
+        for PendingNativeFunction { name, typ, fun } in native_functions {
+            let native = NativeFunction::parse(
+                &mut vm_ctxt.import_resolver,
+                &mut vm_ctxt.pos_table,
+                name,
+                &typ,
+                fun,
+            )
+            .map_err(|error| BuilderError::NativeFunctionType { name, error })?;
+            vm_ctxt.add_native_function(native);
+        }
 
         #[cfg(feature = "incremental-experimental")]
         if enable_incremental_evaluation {
             vm_ctxt = vm_ctxt.with_incremental_evaluation();
         }
 
+        // A missing, outdated or corrupted incremental cache isn't an error: we just start from
+        // scratch.
+        #[cfg(feature = "incremental-experimental")]
+        if let Some(path) = &incremental_cache_path
+            && let Ok(file) = std::fs::File::open(path)
+        {
+            let _ = vm_ctxt.cache.load_persisted(&mut io::BufReader::new(file));
+        }
+
         Ok(Program {
             main_id,
             vm_ctxt,
             overrides,
             field,
             contracts,
+            #[cfg(feature = "incremental-experimental")]
+            incremental_cache_path,
         })
     }
 }
 
+/// Returns the file where the incremental evaluation cache of the program `main_id` is persisted,
+/// within the incremental cache directory `dir`.
+#[cfg(feature = "incremental-experimental")]
+fn incremental_cache_path(dir: &std::path::Path, cache: &CacheHub, main_id: FileId) -> PathBuf {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(cache.sources.name(main_id).as_encoded_bytes());
+
+    // The main source of a program with several inputs is generated, and it's its content
+    // that identifies the program.
+    if cache.sources.filesystem_path(main_id).is_none() {
+        hasher.update(cache.sources.source(main_id).as_bytes());
+    }
+
+    let name: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    dir.join("incremental").join(format!("{name}.bin"))
+}
+
+/// Computes the salt of the persistent cache, which must change whenever the options of the
+/// builder could change the outcome of typechecking a file that is otherwise unchanged.
+fn persistent_cache_salt(
+    import_paths: &[PathBuf],
+    package_map: Option<&PackageMap>,
+    extra_env: &[(Ident, NickelValue)],
+    native_functions: &[PendingNativeFunction],
+) -> String {
+    // The order of import paths matters, but the order of the other items doesn't.
+    let mut unordered: Vec<String> = extra_env
+        .iter()
+        .map(|(id, _)| format!("env:{id}"))
+        .chain(
+            native_functions
+                .iter()
+                .map(|native| format!("native:{}:{}", native.name, native.typ)),
+        )
+        .collect();
+
+    if let Some(map) = package_map {
+        unordered.extend(
+            map.top_level
+                .iter()
+                .map(|(id, path)| format!("package:{id}={}", path.display())),
+        );
+        unordered.extend(map.packages.iter().map(|((parent, id), path)| {
+            format!("package:{}:{id}={}", parent.display(), path.display())
+        }));
+    }
+
+    unordered.sort();
+    unordered.dedup();
+
+    import_paths
+        .iter()
+        .map(|path| format!("import-path:{}", path.display()))
+        .chain(unordered)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 /// Register a single [`BuilderInput`] in the cache and return its [`FileId`]. Used for the
 /// single-input path of [`ProgramBuilder::build`], which doesn't need to synthesize a `Merge`
 /// term.
@@ -1735,7 +2009,10 @@ mod doc {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::eval::cache::CacheImpl;
+    use crate::{
+        eval::{cache::CacheImpl, limits::CancellationHandle},
+        label::{Polarity, ty_path},
+    };
     use assert_matches::assert_matches;
 
     fn build_test_program(s: &str) -> Result<Program<CacheImpl>, BuilderError> {
@@ -1894,4 +2171,204 @@ mod tests {
             .expect_err("typecheck must fail without extras");
         assert_matches!(err, Error::TypecheckError(_));
     }
+
+    fn double(args: &[NickelValue]) -> Result<NickelValue, NativeFunctionError> {
+        let n = args[0]
+            .as_number()
+            .ok_or_else(|| NativeFunctionError::InvalidArgument {
+                index: 0,
+                expected: "Number".to_owned(),
+            })?;
+        Ok(NickelValue::number_posless(
+            n * crate::term::Number::from(2),
+        ))
+    }
+
+    #[test]
+    fn builder_native_function() {
+        let mut prog: Program<CacheImpl> = ProgramBuilder::new()
+            .add_source_string("double (1 + 2) + 1", "<native>")
+            .add_native_function("double", "Number -> Number", double)
+            .build()
+            .unwrap();
+
+        let v = prog.eval_full().unwrap();
+        assert_eq!(v.without_pos(), mk_term::integer(7));
+    }
+
+    #[test]
+    fn builder_native_function_typecheck() {
+        let mut prog: Program<CacheImpl> = ProgramBuilder::new()
+            .add_source_string("(double \"a\" : Number)", "<native-typecheck>")
+            .add_native_function("double", "Number -> Number", double)
+            .build()
+            .unwrap();
+
+        let err = prog
+            .typecheck(TypecheckMode::Walk)
+            .expect_err("typecheck must use the declared type of native functions");
+        assert_matches!(err, Error::TypecheckError(_));
+    }
+
+    #[test]
+    fn builder_native_function_invalid_type() {
+        let result: Result<Program<CacheImpl>, _> = ProgramBuilder::new()
+            .add_source_string("double 1", "<native-invalid>")
+            .add_native_function("double", "Number ->", double)
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(BuilderError::NativeFunctionType { .. })
+        ));
+    }
+
+    #[test]
+    fn builder_native_function_blame() {
+        let mut prog: Program<CacheImpl> = ProgramBuilder::new()
+            .add_source_string("repeat \"a\" (-1)", "<native-blame>")
+            .add_native_function("repeat", "String -> Number -> String", |_| {
+                Err(NativeFunctionError::Blame {
+                    index: 1,
+                    message: "expected a non-negative count".to_owned(),
+                })
+            })
+            .build()
+            .unwrap();
+
+        match prog.eval_full() {
+            Err(Error::EvalError(err)) => match err.error {
+                EvalErrorKind::BlameError { label, .. } => {
+                    assert_eq!(label.polarity, Polarity::Negative);
+                    assert_eq!(
+                        label.path.iter().collect::<Vec<_>>(),
+                        [&ty_path::Elem::Codomain, &ty_path::Elem::Domain]
+                    );
+                }
+                err => panic!("expected a blame error, got {err:?}"),
+            },
+            result => panic!("expected a blame error, got {result:?}"),
+        }
+    }
+
+    fn eval_with_limits(src: &str, limits: EvalLimits) -> Result<NickelValue, Error> {
+        let mut prog: Program<CacheImpl> = ProgramBuilder::new()
+            .add_source_string(src, "<limits>")
+            .with_eval_limits(limits)
+            .build()
+            .unwrap();
+
+        prog.eval_full()
+    }
+
+    fn limit_error(result: Result<NickelValue, Error>) -> EvalErrorKind {
+        match result {
+            Err(Error::EvalError(err)) => err.error,
+            Err(err) => panic!("expected an evaluation error, got {err:?}"),
+            Ok(_) => panic!("expected the evaluation to reach a limit"),
+        }
+    }
+
+    const INFINITE_LOOP: &str =
+        "let rec loop = fun n => if n < 0 then n else loop (n + 1) in loop 0";
+
+    const SUM: &str = "std.array.fold_left (+) 0 (std.array.range 0 10000)";
+
+    const DEEP: &str = "let rec f = fun n => if n == 0 then 0 else 1 + f (n - 1) in f 1000";
+
+    const ALLOC: &str = "std.array.map (fun x => x + 1) (std.array.range 0 10000)";
+
+    #[test]
+    fn builder_eval_step_limit() {
+        let err = limit_error(eval_with_limits(
+            SUM,
+            EvalLimits {
+                max_steps: Some(1000),
+                ..Default::default()
+            },
+        ));
+        assert_matches!(err, EvalErrorKind::StepLimitExceeded { limit: 1000, .. });
+    }
+
+    #[test]
+    fn builder_eval_stack_depth_limit() {
+        let err = limit_error(eval_with_limits(
+            DEEP,
+            EvalLimits {
+                max_stack_depth: Some(100),
+                ..Default::default()
+            },
+        ));
+        assert_matches!(
+            err,
+            EvalErrorKind::StackDepthLimitExceeded { limit: 100, .. }
+        );
+    }
+
+    #[test]
+    fn builder_eval_allocation_limit() {
+        let err = limit_error(eval_with_limits(
+            ALLOC,
+            EvalLimits {
+                max_allocations: Some(1000),
+                ..Default::default()
+            },
+        ));
+        assert_matches!(
+            err,
+            EvalErrorKind::AllocationLimitExceeded { limit: 1000, .. }
+        );
+    }
+
+    #[test]
+    fn builder_eval_timeout() {
+        let err = limit_error(eval_with_limits(
+            INFINITE_LOOP,
+            EvalLimits {
+                timeout: Some(std::time::Duration::from_millis(50)),
+                ..Default::default()
+            },
+        ));
+        assert_matches!(err, EvalErrorKind::Timeout { .. });
+    }
+
+    #[test]
+    fn builder_eval_within_limits() {
+        let limits = EvalLimits {
+            max_steps: Some(10_000_000),
+            max_allocations: Some(10_000_000),
+            max_stack_depth: Some(1_000_000),
+            timeout: Some(std::time::Duration::from_secs(60)),
+            cancellation: Some(CancellationHandle::new()),
+        };
+
+        assert_eq!(
+            eval_with_limits(SUM, limits.clone()).unwrap().without_pos(),
+            mk_term::integer(49995000)
+        );
+        assert!(eval_with_limits(DEEP, limits.clone()).is_ok());
+        assert!(eval_with_limits(ALLOC, limits).is_ok());
+    }
+
+    #[test]
+    fn builder_eval_cancellation() {
+        let handle = CancellationHandle::new();
+        let limits = EvalLimits {
+            cancellation: Some(handle.clone()),
+            ..Default::default()
+        };
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            handle.cancel();
+        });
+
+        let err = limit_error(eval_with_limits(INFINITE_LOOP, limits.clone()));
+        assert_matches!(err, EvalErrorKind::Cancelled);
+        canceller.join().unwrap();
+
+        // Cancellation is permanent.
+        let err = limit_error(eval_with_limits("1 + 1", limits));
+        assert_matches!(err, EvalErrorKind::Cancelled);
+    }
 }
@@ -0,0 +1,235 @@
+//! Support for parsing CSV and TSV and converting them to Nickel.
+//!
+//! A CSV file is converted to an array of records, one per row, whose field names are taken from
+//! the header row. Cells are always converted to strings: CSV has no notion of types, and guessing
+//! them would for example turn a zip code such as `01234` into the number `1234`.
+//!
+//! The `csv` crate doesn't give us the location of individual fields within the source, only the
+//! start of each row. We thus recover the span of each cell by scanning the raw text of the row
+//! ourselves, which is enough to point back to the original file when a contract fails on an
+//! imported cell.
+
+use std::{collections::HashSet, ops::Range};
+
+use crate::{
+    ast::{
+        Ast, AstAlloc, Node,
+        record::{FieldDef, FieldMetadata, FieldPathElem},
+    },
+    error::ParseError,
+    eval::value::NickelValue,
+    files::FileId,
+    identifier::LocIdent,
+    position::{PosTable, RawSpan, TermPos},
+};
+
+fn mk_pos(file_id: FileId, range: Range<usize>) -> TermPos {
+    RawSpan::from_range(file_id, range).into()
+}
+
+fn format_name(delimiter: u8) -> &'static str {
+    if delimiter == b'\t' { "tsv" } else { "csv" }
+}
+
+fn csv_error(err: csv::Error, file_id: FileId, delimiter: u8) -> ParseError {
+    let span = err.position().map(|pos| {
+        let start = pos.byte() as usize;
+        RawSpan::from_range(file_id, start..start + 1)
+    });
+
+    ParseError::ExternalFormatError(format_name(delimiter).to_owned(), err.to_string(), span)
+}
+
+/// Computes the byte ranges of the fields of the row starting at the beginning of `raw`. The
+/// ranges include the quotes surrounding quoted fields.
+fn field_spans(raw: &[u8], delimiter: u8) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut idx = 0;
+
+    loop {
+        let start = idx;
+
+        if raw.get(idx) == Some(&b'"') {
+            idx += 1;
+
+            while idx < raw.len() {
+                if raw[idx] == b'"' {
+                    idx += 1;
+
+                    // A doubled quote is an escaped quote, which doesn't end the field.
+                    if raw.get(idx) != Some(&b'"') {
+                        break;
+                    }
+                }
+
+                idx += 1;
+            }
+        }
+
+        while idx < raw.len() && !matches!(raw[idx], b'\n' | b'\r') && raw[idx] != delimiter {
+            idx += 1;
+        }
+
+        spans.push(start..idx);
+
+        if raw.get(idx) == Some(&delimiter) {
+            idx += 1;
+        } else {
+            return spans;
+        }
+    }
+}
+
+/// Parses a CSV source whose fields are separated by `delimiter` into an [`Ast`], which is an
+/// array of records keyed by the header row.
+pub fn ast_from_str<'ast>(
+    alloc: &'ast AstAlloc,
+    s: &str,
+    file_id: FileId,
+    delimiter: u8,
+) -> Result<Ast<'ast>, ParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .from_reader(s.as_bytes());
+
+    // We read all the rows first, because the span of a row is only known once we know where the
+    // next one starts.
+    let rows = reader
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| csv_error(err, file_id, delimiter))?;
+
+    let row_spans = rows.iter().map(|row| {
+        // unwrap(): records coming from a reader always have a position
+        let start = row.position().unwrap().byte() as usize;
+        field_spans(&s.as_bytes()[start..], delimiter)
+            .into_iter()
+            .map(|span| span.start + start..span.end + start)
+            .collect::<Vec<_>>()
+    });
+
+    let mut rows = rows.iter().zip(row_spans);
+
+    let Some((header, header_spans)) = rows.next() else {
+        return Ok(Ast::from(alloc.array(Vec::new())).with_pos(mk_pos(file_id, 0..s.len())));
+    };
+
+    let mut seen = HashSet::new();
+    let columns = header
+        .iter()
+        .zip(header_spans)
+        .map(|(name, span)| {
+            if !seen.insert(name) {
+                return Err(ParseError::ExternalFormatError(
+                    format_name(delimiter).to_owned(),
+                    format!("duplicate column `{name}` in the header row"),
+                    mk_pos(file_id, span).into_opt(),
+                ));
+            }
+
+            Ok(LocIdent::new_with_pos(name, mk_pos(file_id, span)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let records = rows
+        .map(|(row, spans)| {
+            let row_pos = match (spans.first(), spans.last()) {
+                (Some(first), Some(last)) => mk_pos(file_id, first.start..last.end),
+                _ => TermPos::None,
+            };
+
+            let fields = columns
+                .iter()
+                .zip(row.iter().zip(spans))
+                .map(|(column, (cell, span))| FieldDef {
+                    path: FieldPathElem::single_ident_path(alloc, *column),
+                    metadata: FieldMetadata::default(),
+                    value: Some(Ast::from(alloc.string(cell)).with_pos(mk_pos(file_id, span))),
+                    pos: TermPos::default(),
+                })
+                .collect::<Vec<_>>();
+
+            Ast::from(Node::Record(alloc.record_data([], fields, false))).with_pos(row_pos)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Ast::from(alloc.array(records)).with_pos(mk_pos(file_id, 0..s.len())))
+}
+
+/// Parses a CSV source whose fields are separated by `delimiter` into a [`NickelValue`]. See
+/// [ast_from_str].
+pub fn from_str(
+    pos_table: &mut PosTable,
+    s: &str,
+    file_id: FileId,
+    delimiter: u8,
+) -> Result<NickelValue, ParseError> {
+    let alloc = AstAlloc::new();
+    Ok(super::yaml::ast_to_term(
+        pos_table,
+        ast_from_str(&alloc, s, file_id, delimiter)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::Files;
+
+    fn csv_to_ncl(s: &str) -> String {
+        let mut files = Files::empty();
+        let file_id = files.add("<test>", s);
+        let alloc = AstAlloc::new();
+        ast_from_str(&alloc, s, file_id, b',').unwrap().to_string()
+    }
+
+    #[test]
+    fn field_spans_quoted() {
+        let raw = br#"a,"b,""c""",,d
+next"#;
+        assert_eq!(field_spans(raw, b','), vec![0..1, 2..11, 12..12, 13..14]);
+        assert_eq!(field_spans(b"x\ty", b'\t'), vec![0..1, 2..3]);
+    }
+
+    #[test]
+    fn rows_are_keyed_by_header() {
+        assert_eq!(csv_to_ncl(""), "[  ]");
+        assert_eq!(csv_to_ncl("name,port\n"), "[  ]");
+        assert_eq!(
+            csv_to_ncl("name,port\nweb,80\n\"db, primary\",5432\n"),
+            r#"[ { name = "web", port = "80" }, { name = "db, primary", port = "5432" } ]"#
+        );
+    }
+
+    #[test]
+    fn cell_positions() {
+        let source = "name,port\nweb,\"80\"\n";
+        let mut files = Files::empty();
+        let file_id = files.add("<test>", source);
+        let alloc = AstAlloc::new();
+        let ast = ast_from_str(&alloc, source, file_id, b',').unwrap();
+
+        let Node::Array(rows) = ast.node else {
+            panic!("expected an array")
+        };
+        let Node::Record(record) = rows[0].node else {
+            panic!("expected a record")
+        };
+
+        let port = record.field_defs[1].value.as_ref().unwrap();
+        let span = port.pos.unwrap();
+        assert_eq!(&source[span.to_range()], "\"80\"");
+    }
+
+    #[test]
+    fn invalid_csv() {
+        let source = "name,port\nweb\n";
+        let mut files = Files::empty();
+        let file_id = files.add("<test>", source);
+        let alloc = AstAlloc::new();
+
+        assert!(ast_from_str(&alloc, source, file_id, b',').is_err());
+        assert!(ast_from_str(&alloc, "a,a\n1,2\n", file_id, b',').is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! Serialization to env files, as consumed by dotenv libraries or systemd's `EnvironmentFile`.
+//!
+//! An env file is a flat list of `NAME=value` assignments, so only records whose fields are
+//! strings, numbers, booleans or enum tags can be exported to this format. Values are written
+//! unquoted when they only contain characters that are safe for all the common consumers, and
+//! double-quoted otherwise. Note that `docker --env-file` doesn't handle quotes, so it only reads
+//! the unquoted values correctly.
+use super::ExportFormat;
+use crate::{
+    error::ExportErrorKind,
+    eval::value::{Container, EnumVariantData, NickelValue, ValueContentRef},
+};
+
+use std::io;
+
+/// Returns `true` if `name` is a valid environment variable name, that is if it's a non-empty
+/// sequence of ASCII letters, digits and underscores which doesn't start with a digit.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns `true` if `s` can be written as is, without quotes, on the right hand side of an
+/// assignment.
+fn is_bare(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '_' | '-' | '.' | '/' | ':' | '@' | '%' | '+' | ',')
+        })
+}
+
+/// Renders a string value, quoting and escaping it if needed.
+fn render_str(s: &str) -> String {
+    if is_bare(s) {
+        return s.to_owned();
+    }
+
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' | '\\' | '$' | '`' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+/// Serializes a record to the env format. The value must have been validated with
+/// [super::validate] first.
+pub fn to_writer(mut writer: impl io::Write, value: &NickelValue) -> Result<(), ExportErrorKind> {
+    let record = match value.content_ref() {
+        ValueContentRef::Record(Container::Empty) => return Ok(()),
+        ValueContentRef::Record(Container::Alloc(record)) => record,
+        _ => {
+            return Err(ExportErrorKind::ExpectedRecord {
+                format: ExportFormat::Env,
+                value: value.clone(),
+            });
+        }
+    };
+
+    let mut entries = record
+        .iter_serializable()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|missing_def_err| {
+            ExportErrorKind::Other(format!(
+                "missing field definition for `{}`",
+                missing_def_err.id
+            ))
+        })?;
+
+    entries.sort_by_key(|(k, _)| *k);
+
+    for (id, value) in entries {
+        let rendered = match value.content_ref() {
+            ValueContentRef::String(s) => render_str(s),
+            ValueContentRef::EnumVariant(EnumVariantData { tag, arg: None }) => {
+                render_str(tag.label())
+            }
+            ValueContentRef::Bool(b) => b.to_string(),
+            ValueContentRef::Number(n) => super::number_to_string(n),
+            _ => {
+                return Err(ExportErrorKind::UnsupportedNestedValue {
+                    format: ExportFormat::Env,
+                    value: value.clone(),
+                });
+            }
+        };
+
+        writeln!(writer, "{id}={rendered}")
+            .map_err(|err| ExportErrorKind::Other(err.to_string()))?;
+    }
+
+    Ok(())
+}
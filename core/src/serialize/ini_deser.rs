@@ -0,0 +1,274 @@
+//! Support for parsing INI files and converting them to Nickel.
+//!
+//! INI has no formal specification, so we follow the common subset also accepted by Python's
+//! `configparser` in strict mode:
+//!
+//! - a section starts with a `[name]` header and extends up to the next header,
+//! - entries are written `key = value` or `key: value`, and the value is always a string (matching
+//!   surrounding quotes are removed),
+//! - lines starting with `;` or `#` are comments, and comments can't follow an entry on the same
+//!   line,
+//! - sections and keys can't be defined twice.
+//!
+//! An INI file is converted to a record with one field per section, each section being a record of
+//! strings. Entries appearing before the first header are put directly in the top-level record.
+
+use std::{collections::HashSet, ops::Range};
+
+use crate::{
+    ast::{
+        Ast, AstAlloc, Node,
+        record::{FieldDef, FieldMetadata, FieldPathElem},
+    },
+    error::ParseError,
+    eval::value::NickelValue,
+    files::FileId,
+    identifier::LocIdent,
+    position::{PosTable, RawSpan, TermPos},
+};
+
+/// An entry `key = value` of an INI file, together with the span of the value.
+struct Entry<'a> {
+    key: LocIdent,
+    value: &'a str,
+    span: Range<usize>,
+}
+
+/// A section of an INI file. The section without a name holds the entries appearing before the
+/// first header.
+struct Section<'a> {
+    name: Option<LocIdent>,
+    span: Range<usize>,
+    entries: Vec<Entry<'a>>,
+    keys: HashSet<&'a str>,
+}
+
+impl<'a> Section<'a> {
+    fn new(name: Option<LocIdent>, span: Range<usize>) -> Self {
+        Section {
+            name,
+            span,
+            entries: Vec::new(),
+            keys: HashSet::new(),
+        }
+    }
+}
+
+/// Returns the range of `sub` within `source`. `sub` must be a subslice of `source`.
+fn range_of(source: &str, sub: &str) -> Range<usize> {
+    let start = sub.as_ptr() as usize - source.as_ptr() as usize;
+    start..start + sub.len()
+}
+
+/// Removes a pair of matching quotes surrounding `value`, if any.
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|rest| rest.strip_suffix(*quote))
+        })
+        .unwrap_or(value)
+}
+
+fn mk_pos(file_id: FileId, range: Range<usize>) -> TermPos {
+    RawSpan::from_range(file_id, range).into()
+}
+
+fn ini_error(msg: impl Into<String>, file_id: FileId, range: Range<usize>) -> ParseError {
+    ParseError::ExternalFormatError(
+        String::from("ini"),
+        msg.into(),
+        Some(RawSpan::from_range(file_id, range)),
+    )
+}
+
+fn parse_sections(s: &str, file_id: FileId) -> Result<Vec<Section<'_>>, ParseError> {
+    let mut sections = vec![Section::new(None, 0..0)];
+    let mut top_level_names = HashSet::new();
+
+    for line in s.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        let line_range = range_of(s, line);
+
+        if let Some(header) = line.strip_prefix('[') {
+            let Some(name) = header.strip_suffix(']').map(str::trim) else {
+                return Err(ini_error(
+                    "unterminated section header, expected `]`",
+                    file_id,
+                    line_range,
+                ));
+            };
+
+            if name.is_empty() {
+                return Err(ini_error("empty section name", file_id, line_range));
+            }
+
+            if !top_level_names.insert(name) {
+                return Err(ini_error(
+                    format!("section `{name}` is defined more than once"),
+                    file_id,
+                    line_range,
+                ));
+            }
+
+            let name = LocIdent::new_with_pos(name, mk_pos(file_id, range_of(s, name)));
+            sections.push(Section::new(Some(name), line_range));
+            continue;
+        }
+
+        let Some(sep) = line.find(['=', ':']) else {
+            return Err(ini_error(
+                "expected an entry of the form `key = value`",
+                file_id,
+                line_range,
+            ));
+        };
+
+        let key = line[..sep].trim_end();
+        let value = unquote(line[sep + 1..].trim_start());
+
+        if key.is_empty() {
+            return Err(ini_error("missing key before `=`", file_id, line_range));
+        }
+
+        // unwrap(): we always start with the unnamed section, and never remove sections.
+        let section = sections.last_mut().unwrap();
+
+        // Top-level entries share their namespace with section names.
+        let is_duplicate = if section.name.is_none() {
+            !top_level_names.insert(key)
+        } else {
+            !section.keys.insert(key)
+        };
+
+        if is_duplicate {
+            return Err(ini_error(
+                format!("key `{key}` is defined more than once"),
+                file_id,
+                range_of(s, key),
+            ));
+        }
+
+        section.entries.push(Entry {
+            key: LocIdent::new_with_pos(key, mk_pos(file_id, range_of(s, key))),
+            value,
+            span: range_of(s, value),
+        });
+        section.span.end = line_range.end;
+    }
+
+    Ok(sections)
+}
+
+fn entry_defs<'ast>(
+    alloc: &'ast AstAlloc,
+    file_id: FileId,
+    entries: Vec<Entry<'_>>,
+) -> impl Iterator<Item = FieldDef<'ast>> {
+    entries.into_iter().map(move |entry| FieldDef {
+        path: FieldPathElem::single_ident_path(alloc, entry.key),
+        metadata: FieldMetadata::default(),
+        value: Some(Ast::from(alloc.string(entry.value)).with_pos(mk_pos(file_id, entry.span))),
+        pos: TermPos::default(),
+    })
+}
+
+/// Parses an INI source into an [`Ast`], which is a record of sections.
+pub fn ast_from_str<'ast>(
+    alloc: &'ast AstAlloc,
+    s: &str,
+    file_id: FileId,
+) -> Result<Ast<'ast>, ParseError> {
+    let mut field_defs = Vec::new();
+
+    for section in parse_sections(s, file_id)? {
+        match section.name {
+            None => field_defs.extend(entry_defs(alloc, file_id, section.entries)),
+            Some(name) => {
+                let fields = entry_defs(alloc, file_id, section.entries).collect::<Vec<_>>();
+
+                field_defs.push(FieldDef {
+                    path: FieldPathElem::single_ident_path(alloc, name),
+                    metadata: FieldMetadata::default(),
+                    value: Some(
+                        Ast::from(Node::Record(alloc.record_data([], fields, false)))
+                            .with_pos(mk_pos(file_id, section.span)),
+                    ),
+                    pos: TermPos::default(),
+                });
+            }
+        }
+    }
+
+    Ok(
+        Ast::from(Node::Record(alloc.record_data([], field_defs, false)))
+            .with_pos(mk_pos(file_id, 0..s.len())),
+    )
+}
+
+/// Parses an INI source into a [`NickelValue`]. See [ast_from_str].
+pub fn from_str(
+    pos_table: &mut PosTable,
+    s: &str,
+    file_id: FileId,
+) -> Result<NickelValue, ParseError> {
+    let alloc = AstAlloc::new();
+    Ok(super::yaml::ast_to_term(
+        pos_table,
+        ast_from_str(&alloc, s, file_id)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::Files;
+
+    fn parse(s: &str) -> Result<String, ParseError> {
+        let mut files = Files::empty();
+        let file_id = files.add("<test>", s);
+        let alloc = AstAlloc::new();
+        ast_from_str(&alloc, s, file_id).map(|ast| ast.to_string())
+    }
+
+    #[test]
+    fn sections_and_entries() {
+        assert_eq!(parse("").unwrap(), "{}");
+        assert_eq!(
+            parse(
+                "; global settings\nname = inventory\n\n[db]\nhost: localhost\nport = \"5432\"\n# done\n[empty]\n"
+            )
+            .unwrap(),
+            r#"{ name = "inventory", db = { host = "localhost", port = "5432" }, empty = {} }"#
+        );
+    }
+
+    #[test]
+    fn value_positions() {
+        let source = "[db]\nport = 5432\n";
+        let mut files = Files::empty();
+        let file_id = files.add("<test>", source);
+        let sections = parse_sections(source, file_id).unwrap();
+
+        assert_eq!(&source[sections[1].entries[0].span.clone()], "5432");
+        assert_eq!(&source[sections[1].span.clone()], "[db]\nport = 5432");
+    }
+
+    #[test]
+    fn invalid_ini() {
+        assert!(parse("[db\nport = 1").is_err());
+        assert!(parse("[]").is_err());
+        assert!(parse("port 1").is_err());
+        assert!(parse("= 1").is_err());
+        assert!(parse("[db]\nport = 1\nport = 2").is_err());
+        assert!(parse("[db]\n[db]").is_err());
+        assert!(parse("db = 1\n[db]").is_err());
+    }
+}
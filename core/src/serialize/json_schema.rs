@@ -0,0 +1,855 @@
+//! Generation of Nickel contracts from a [JSON Schema](https://json-schema.org/).
+//!
+//! A schema is converted to a Nickel expression evaluating to an equivalent contract: object
+//! schemas become record contracts (non-required properties being optional fields, and
+//! descriptions being documentation), string enumerations become enum types, and the other
+//! constraints are expressed with the combinators of `std.contract`. The definitions of the schema
+//! (found under `$defs` or `definitions`) are bound in a recursive let block, so that they can
+//! refer to each other, and references to them become variables.
+//!
+//! The conversion is best-effort: keywords without a direct counterpart are ignored, which makes
+//! the generated contract more permissive than the original schema. In particular:
+//!
+//! - `format`, `uniqueItems`, `patternProperties`, `propertyNames`, `minProperties`,
+//!   `maxProperties`, `dependentRequired`, `dependentSchemas` and conditional schemas (`if`,
+//!   `then` and `else`) aren't checked,
+//! - constraints specific to strings, numbers or arrays (such as `pattern` or `minimum`) are only
+//!   checked when the schema also specifies the corresponding `type`,
+//! - an `additionalProperties` schema is only enforced if the object schema has no `properties`.
+//!   Otherwise, the record contract is simply open,
+//! - `oneOf` is treated like `anyOf`,
+//! - references which don't point to the root schema or to one of its definitions are replaced
+//!   with `Dyn`.
+
+use std::collections::{HashMap, HashSet};
+
+use nickel_lang_parser::{fun, primop_app};
+
+use crate::{
+    ast::{
+        Ast, AstAlloc, LetBinding, LetMetadata, MergePriority, Node,
+        builder::{self, Record},
+        pattern::Pattern,
+        primop::PrimOp,
+        record::FieldPathElem,
+        typ::{EnumRow, EnumRows, EnumRowsUnr, Type},
+    },
+    error::ParseError,
+    files::FileId,
+    identifier::{Ident, LocIdent},
+    pretty::ident_quoted,
+    term::Number,
+    typ::{DictTypeFlavour, EnumRowsF, TypeF},
+};
+
+/// The name of the variable bound by the predicates generated for validation keywords.
+const PREDICATE_ARG: &str = "value";
+
+fn schema_error(msg: impl Into<String>, ast: &Ast<'_>) -> ParseError {
+    ParseError::ExternalFormatError("json-schema".to_owned(), msg.into(), ast.pos.into_opt())
+}
+
+/// Returns the fields of a JSON object, or `None` if `ast` isn't an object.
+fn entries<'ast>(ast: &Ast<'ast>) -> Option<impl Iterator<Item = (LocIdent, &'ast Ast<'ast>)>> {
+    let Node::Record(record) = &ast.node else {
+        return None;
+    };
+
+    Some(
+        record
+            .field_defs
+            .iter()
+            .filter_map(|def| match (def.path, def.value.as_ref()) {
+                ([FieldPathElem::Ident(id)], Some(value)) => Some((*id, value)),
+                _ => None,
+            }),
+    )
+}
+
+/// Returns the value of the keyword `key` of a schema, if any.
+fn get<'ast>(schema: &Ast<'ast>, key: &str) -> Option<&'ast Ast<'ast>> {
+    entries(schema)?.find_map(|(id, value)| (id.label() == key).then_some(value))
+}
+
+fn expect_str<'ast>(ast: &Ast<'ast>, keyword: &str) -> Result<&'ast str, ParseError> {
+    match &ast.node {
+        Node::String(s) => Ok(s),
+        _ => Err(schema_error(
+            format!("expected the value of `{keyword}` to be a string"),
+            ast,
+        )),
+    }
+}
+
+fn expect_array<'ast>(ast: &Ast<'ast>, keyword: &str) -> Result<&'ast [Ast<'ast>], ParseError> {
+    match &ast.node {
+        Node::Array(elts) => Ok(elts),
+        _ => Err(schema_error(
+            format!("expected the value of `{keyword}` to be an array"),
+            ast,
+        )),
+    }
+}
+
+fn expect_number<'ast>(ast: &'ast Ast<'ast>, keyword: &str) -> Result<&'ast Ast<'ast>, ParseError> {
+    match &ast.node {
+        Node::Number(_) => Ok(ast),
+        _ => Err(schema_error(
+            format!("expected the value of `{keyword}` to be a number"),
+            ast,
+        )),
+    }
+}
+
+/// Returns the values of an enumeration as enum tags, if they're all strings.
+fn string_enum(values: &[Ast<'_>]) -> Option<Vec<LocIdent>> {
+    values
+        .iter()
+        .map(|value| match &value.node {
+            Node::String(s) => Some(LocIdent::from(*s)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Escapes a name to be used as a segment of a JSON pointer, as specified by RFC 6901.
+fn escape_pointer(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+struct Converter<'ast> {
+    alloc: &'ast AstAlloc,
+    /// The variables bound to the definitions of the schema, indexed by the corresponding
+    /// reference (such as `#/$defs/Name`).
+    definitions: HashMap<String, LocIdent>,
+    /// The names of the variables already bound in the generated code.
+    names: HashSet<String>,
+    /// The variable bound to the root schema, which is only needed if the root is referenced
+    /// somewhere in the schema.
+    root: Option<LocIdent>,
+}
+
+impl<'ast> Converter<'ast> {
+    /// Returns a valid Nickel identifier derived from `name`, and distinct from all the names
+    /// returned before.
+    fn fresh_name(&mut self, name: &str) -> LocIdent {
+        let mut base: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        // Identifiers must start with a letter, possibly preceded by underscores.
+        if !base
+            .trim_start_matches('_')
+            .starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            base.insert_str(0, "Schema_");
+        }
+
+        let mut candidate = base.clone();
+        let mut counter = 1;
+
+        // We also avoid `std`, so that the generated code can still refer to the standard library.
+        while candidate == "std"
+            || ident_quoted(Ident::new(&candidate)) != candidate
+            || self.names.contains(&candidate)
+        {
+            counter += 1;
+            candidate = format!("{base}_{counter}");
+        }
+
+        self.names.insert(candidate.clone());
+        LocIdent::from(candidate)
+    }
+
+    /// Builds an access to an element of the standard library, such as `std.contract.Equal`.
+    fn std(&self, path: &[&str]) -> Ast<'ast> {
+        path.iter().fold(builder::var("std"), |record, field| {
+            primop_app!(
+                self.alloc,
+                PrimOp::RecordStatAccess(LocIdent::from(*field)),
+                record
+            )
+        })
+    }
+
+    fn app(&self, head: Ast<'ast>, args: impl IntoIterator<Item = Ast<'ast>>) -> Ast<'ast> {
+        let args: Vec<_> = args.into_iter().collect();
+        self.alloc.app(head, args).into()
+    }
+
+    fn contract(&self, ast: Ast<'ast>) -> Type<'ast> {
+        TypeF::Contract(self.alloc.alloc(ast)).into()
+    }
+
+    /// Converts a type to the equivalent expression, to be used as an argument of a contract
+    /// combinator.
+    fn type_to_ast(&self, typ: Type<'ast>) -> Ast<'ast> {
+        match typ.typ {
+            TypeF::Contract(ast) => ast.clone(),
+            _ => self.alloc.typ(typ).into(),
+        }
+    }
+
+    /// Builds the contract `std.contract.from_predicate (fun value => body)`.
+    fn predicate(&self, body: Ast<'ast>) -> Type<'ast> {
+        self.contract(self.app(
+            self.std(&["contract", "from_predicate"]),
+            [fun!(self.alloc, PREDICATE_ARG, body)],
+        ))
+    }
+
+    /// Builds a predicate comparing `lhs`, an expression depending on the argument of the
+    /// predicate, to the value of a numeric keyword.
+    fn comparison(&self, lhs: Ast<'ast>, op: PrimOp, bound: &Ast<'ast>) -> Type<'ast> {
+        self.predicate(primop_app!(self.alloc, op, lhs, bound.clone()))
+    }
+
+    /// Converts the bounds of a value: `keywords` maps each bound keyword to the corresponding
+    /// comparison operator, and `measure` computes the measured quantity (the length of a string,
+    /// the value of a number, etc.) from the argument of the predicate.
+    fn bounds(
+        &self,
+        schema: &Ast<'ast>,
+        keywords: &[(&str, PrimOp)],
+        measure: impl Fn(Ast<'ast>) -> Ast<'ast>,
+    ) -> Result<Vec<Type<'ast>>, ParseError> {
+        let mut contracts = Vec::new();
+
+        for (keyword, op) in keywords {
+            if let Some(bound) = get(schema, keyword) {
+                let bound = expect_number(bound, keyword)?;
+                contracts.push(self.comparison(measure(builder::var(PREDICATE_ARG)), *op, bound));
+            }
+        }
+
+        Ok(contracts)
+    }
+
+    /// Converts the bounds of a number. `exclusiveMinimum` and `exclusiveMaximum` are either bounds
+    /// on their own (since draft 6), or booleans making `minimum` and `maximum` exclusive (draft 4).
+    fn numeric_bounds(&self, schema: &Ast<'ast>) -> Result<Vec<Type<'ast>>, ParseError> {
+        let mut contracts = Vec::new();
+
+        for (keyword, exclusive_keyword, op, exclusive_op) in [
+            (
+                "minimum",
+                "exclusiveMinimum",
+                PrimOp::GreaterOrEq,
+                PrimOp::GreaterThan,
+            ),
+            (
+                "maximum",
+                "exclusiveMaximum",
+                PrimOp::LessOrEq,
+                PrimOp::LessThan,
+            ),
+        ] {
+            let exclusive = get(schema, exclusive_keyword);
+            let is_exclusive = matches!(
+                exclusive,
+                Some(Ast {
+                    node: Node::Bool(true),
+                    ..
+                })
+            );
+
+            if let Some(bound) = get(schema, keyword) {
+                let bound = expect_number(bound, keyword)?;
+                let op = if is_exclusive { exclusive_op } else { op };
+                contracts.push(self.comparison(builder::var(PREDICATE_ARG), op, bound));
+            }
+
+            match exclusive {
+                Some(Ast {
+                    node: Node::Bool(_),
+                    ..
+                })
+                | None => (),
+                Some(bound) => {
+                    let bound = expect_number(bound, exclusive_keyword)?;
+                    contracts.push(self.comparison(
+                        builder::var(PREDICATE_ARG),
+                        exclusive_op,
+                        bound,
+                    ));
+                }
+            }
+        }
+
+        Ok(contracts)
+    }
+
+    /// Combines several contracts into one, which requires all of them to hold.
+    fn all_of(&self, mut contracts: Vec<Type<'ast>>) -> Type<'ast> {
+        match contracts.len() {
+            0 => TypeF::Dyn.into(),
+            // unwrap(): we just checked that there is exactly one element
+            1 => contracts.pop().unwrap(),
+            _ => {
+                let contracts: Vec<_> = contracts
+                    .into_iter()
+                    .map(|typ| self.type_to_ast(typ))
+                    .collect();
+                self.contract(self.app(
+                    self.std(&["contract", "all_of"]),
+                    [self.alloc.array(contracts).into()],
+                ))
+            }
+        }
+    }
+
+    /// Combines several contracts into one, which requires at least one of them to hold.
+    fn any_of(&self, contracts: Vec<Type<'ast>>) -> Type<'ast> {
+        let contracts: Vec<_> = contracts
+            .into_iter()
+            .map(|typ| self.type_to_ast(typ))
+            .collect();
+        self.contract(self.app(
+            self.std(&["contract", "any_of"]),
+            [self.alloc.array(contracts).into()],
+        ))
+    }
+
+    /// Converts a schema to a single contract.
+    fn convert(&mut self, schema: &Ast<'ast>) -> Result<Type<'ast>, ParseError> {
+        let contracts = self.contracts(schema)?;
+        Ok(self.all_of(contracts))
+    }
+
+    /// Converts a schema to a list of contracts, which must all hold for a value to be valid.
+    fn contracts(&mut self, schema: &Ast<'ast>) -> Result<Vec<Type<'ast>>, ParseError> {
+        match &schema.node {
+            Node::Bool(true) => return Ok(Vec::new()),
+            Node::Bool(false) => {
+                return Ok(vec![self.contract(self.app(
+                    self.std(&["contract", "from_predicate"]),
+                    [fun!(self.alloc, PREDICATE_ARG, Node::Bool(false))],
+                ))]);
+            }
+            Node::Record(_) => (),
+            _ => {
+                return Err(schema_error(
+                    "expected a schema, that is an object or a boolean",
+                    schema,
+                ));
+            }
+        }
+
+        let mut contracts = Vec::new();
+
+        if let Some(reference) = get(schema, "$ref") {
+            contracts.extend(self.reference(reference)?);
+        }
+
+        if let Some(value) = get(schema, "const") {
+            contracts
+                .push(self.contract(self.app(self.std(&["contract", "Equal"]), [value.clone()])));
+        }
+
+        if let Some(values) = get(schema, "enum") {
+            contracts.extend(self.enumeration(values)?);
+        }
+
+        match get(schema, "type") {
+            Some(
+                typ @ Ast {
+                    node: Node::Array(types),
+                    ..
+                },
+            ) => {
+                let alternatives = types
+                    .iter()
+                    .map(|typ| {
+                        let contracts = self.typed(expect_str(typ, "type")?, typ, schema)?;
+                        Ok::<_, ParseError>(self.all_of(contracts))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match alternatives.len() {
+                    0 => return Err(schema_error("`type` can't be empty", typ)),
+                    1 => contracts.extend(alternatives),
+                    _ => contracts.push(self.any_of(alternatives)),
+                }
+            }
+            Some(typ) => contracts.extend(self.typed(expect_str(typ, "type")?, typ, schema)?),
+            // Without a type, we can still recognize object and array schemas from their
+            // keywords.
+            None if ["properties", "required", "additionalProperties"]
+                .iter()
+                .any(|keyword| get(schema, keyword).is_some()) =>
+            {
+                contracts.push(self.object(schema)?)
+            }
+            None if get(schema, "items").is_some() => {
+                contracts.extend(self.typed("array", schema, schema)?)
+            }
+            None => (),
+        }
+
+        if let Some(schemas) = get(schema, "allOf") {
+            for sub_schema in expect_array(schemas, "allOf")? {
+                contracts.extend(self.contracts(sub_schema)?);
+            }
+        }
+
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(schemas) = get(schema, keyword) {
+                let alternatives = expect_array(schemas, keyword)?
+                    .iter()
+                    .map(|sub_schema| self.convert(sub_schema))
+                    .collect::<Result<Vec<_>, _>>()?;
+                contracts.push(self.any_of(alternatives));
+            }
+        }
+
+        if let Some(negated) = get(schema, "not") {
+            let negated = self.convert(negated)?;
+            contracts.push(
+                self.contract(
+                    self.app(self.std(&["contract", "not"]), [self.type_to_ast(negated)]),
+                ),
+            );
+        }
+
+        Ok(contracts)
+    }
+
+    /// Converts a `$ref` keyword.
+    fn reference(&mut self, reference: &Ast<'ast>) -> Result<Option<Type<'ast>>, ParseError> {
+        let target = expect_str(reference, "$ref")?;
+
+        let id = if target == "#" {
+            match self.root {
+                Some(id) => id,
+                None => {
+                    let id = self.fresh_name("Schema");
+                    self.root = Some(id);
+                    id
+                }
+            }
+        } else if let Some(id) = self.definitions.get(target) {
+            *id
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            TypeF::Contract(self.alloc.alloc(builder::var(id))).into(),
+        ))
+    }
+
+    /// Converts an `enum` keyword. An enumeration of strings is converted to an enum type, such
+    /// that both enum tags and strings are accepted.
+    fn enumeration(&self, values: &Ast<'ast>) -> Result<Vec<Type<'ast>>, ParseError> {
+        let values = expect_array(values, "enum")?;
+
+        if let Some(tags) = string_enum(values) {
+            let rows = tags.into_iter().rev().fold(
+                EnumRows(EnumRowsF::Empty),
+                |tail: EnumRows<'ast>, id| {
+                    EnumRows(EnumRowsUnr::Extend {
+                        row: EnumRow { id, typ: None },
+                        tail: self.alloc.alloc(tail),
+                    })
+                },
+            );
+
+            Ok(vec![
+                self.contract(self.std(&["enum", "TagOrString"])),
+                TypeF::Enum(rows).into(),
+            ])
+        } else {
+            let values = self.alloc.array(values.iter().cloned()).into();
+            Ok(vec![self.predicate(self.app(
+                self.std(&["array", "elem"]),
+                [builder::var(PREDICATE_ARG), values],
+            ))])
+        }
+    }
+
+    /// Converts a schema whose `type` is `typ`. `typ_ast` is the value of the `type` keyword,
+    /// used to report errors.
+    fn typed(
+        &mut self,
+        typ: &str,
+        typ_ast: &Ast<'ast>,
+        schema: &Ast<'ast>,
+    ) -> Result<Vec<Type<'ast>>, ParseError> {
+        let contracts = match typ {
+            "null" => vec![self.contract(self.app(
+                self.std(&["contract", "Equal"]),
+                [Node::Null.into()],
+            ))],
+            "boolean" => vec![TypeF::Bool.into()],
+            // The values of a string enumeration are converted to enum tags, which aren't strings.
+            // The enumeration is more precise than the other string keywords anyway.
+            "string"
+                if get(schema, "enum").is_some_and(|values| {
+                    matches!(&values.node, Node::Array(values) if string_enum(values).is_some())
+                }) =>
+            {
+                Vec::new()
+            }
+            "string" => {
+                let mut contracts = vec![TypeF::String.into()];
+
+                if let Some(pattern) = get(schema, "pattern") {
+                    let pattern = expect_str(pattern, "pattern")?;
+                    contracts.push(self.contract(self.app(
+                        self.std(&["contract", "from_predicate"]),
+                        [self.app(
+                            self.std(&["string", "is_match"]),
+                            [self.alloc.string(pattern).into()],
+                        )],
+                    )));
+                }
+
+                contracts.extend(self.bounds(
+                    schema,
+                    &[
+                        ("minLength", PrimOp::GreaterOrEq),
+                        ("maxLength", PrimOp::LessOrEq),
+                    ],
+                    |value| self.app(self.std(&["string", "length"]), [value]),
+                )?);
+                contracts
+            }
+            "number" | "integer" => {
+                let mut contracts = vec![if typ == "number" {
+                    TypeF::Number.into()
+                } else {
+                    self.contract(self.std(&["number", "Integer"]))
+                }];
+
+                contracts.extend(self.numeric_bounds(schema)?);
+
+                if let Some(divisor) = get(schema, "multipleOf") {
+                    let divisor = expect_number(divisor, "multipleOf")?;
+                    let modulo = primop_app!(
+                        self.alloc,
+                        PrimOp::Modulo,
+                        builder::var(PREDICATE_ARG),
+                        divisor.clone()
+                    );
+                    contracts.push(self.predicate(primop_app!(
+                        self.alloc,
+                        PrimOp::Eq,
+                        modulo,
+                        self.alloc.number(Number::from(0))
+                    )));
+                }
+
+                contracts
+            }
+            "array" => {
+                let items = match get(schema, "items") {
+                    // Tuple validation (an array of schemas) isn't supported.
+                    Some(Ast {
+                        node: Node::Array(_),
+                        ..
+                    })
+                    | None => TypeF::Dyn.into(),
+                    Some(items) => self.convert(items)?,
+                };
+
+                let mut contracts = vec![TypeF::Array(self.alloc.type_data(items.typ, items.pos)).into()];
+
+                contracts.extend(self.bounds(
+                    schema,
+                    &[
+                        ("minItems", PrimOp::GreaterOrEq),
+                        ("maxItems", PrimOp::LessOrEq),
+                    ],
+                    |value| self.app(self.std(&["array", "length"]), [value]),
+                )?);
+                contracts
+            }
+            "object" => vec![self.object(schema)?],
+            _ => {
+                return Err(schema_error(format!("unknown type `{typ}`"), typ_ast));
+            }
+        };
+
+        Ok(contracts)
+    }
+
+    /// Converts an object schema to a record contract, or to a dictionary contract if the schema
+    /// only constrains the type of its properties through `additionalProperties`.
+    fn object(&mut self, schema: &Ast<'ast>) -> Result<Type<'ast>, ParseError> {
+        let required = match get(schema, "required") {
+            Some(required) => expect_array(required, "required")?
+                .iter()
+                .map(|name| expect_str(name, "required"))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+
+        let additional = get(schema, "additionalProperties");
+
+        let Some(properties) = get(schema, "properties") else {
+            if required.is_empty() {
+                let type_fields = match additional {
+                    Some(additional) => self.convert(additional)?,
+                    None => TypeF::Dyn.into(),
+                };
+
+                return Ok(TypeF::Dict {
+                    type_fields: self.alloc.type_data(type_fields.typ, type_fields.pos),
+                    flavour: DictTypeFlavour::Contract,
+                }
+                .into());
+            }
+
+            let record = required
+                .iter()
+                .fold(Record::new(), |record, name| {
+                    record.field(name).no_value(self.alloc)
+                })
+                .set_open(!matches!(
+                    additional.map(|ast| &ast.node),
+                    Some(Node::Bool(false))
+                ));
+
+            return Ok(self.contract(record.build(self.alloc)));
+        };
+
+        let Some(properties) = entries(properties) else {
+            return Err(schema_error(
+                "expected the value of `properties` to be an object",
+                properties,
+            ));
+        };
+
+        let mut record = Record::new();
+        let mut defined = HashSet::new();
+
+        for (name, property) in properties {
+            defined.insert(name.label());
+
+            let contracts = self.contracts(property)?;
+            let doc = get(property, "description").or_else(|| get(property, "title"));
+            let doc = doc.map(|doc| expect_str(doc, "description")).transpose()?;
+            let default = get(property, "default");
+
+            let field = record
+                .field(name.label())
+                .contracts(contracts)
+                .some_doc(doc)
+                .optional(default.is_none() && !required.contains(&name.label()));
+
+            record = match default {
+                Some(default) => field
+                    .priority(MergePriority::Bottom)
+                    .value(self.alloc, default.clone()),
+                None => field.no_value(self.alloc),
+            };
+        }
+
+        // Required properties might not be described in `properties`, in which case they can have
+        // any value.
+        for name in required {
+            if !defined.contains(name) {
+                record = record.field(name).no_value(self.alloc);
+            }
+        }
+
+        let open = !matches!(additional.map(|ast| &ast.node), Some(Node::Bool(false)));
+        Ok(self.contract(record.set_open(open).build(self.alloc)))
+    }
+}
+
+/// Converts a JSON schema into an [`Ast`] of a Nickel contract. See the [module-level
+/// documentation](self).
+pub fn ast_from_str<'ast>(
+    alloc: &'ast AstAlloc,
+    s: &str,
+    file_id: FileId,
+) -> Result<Ast<'ast>, ParseError> {
+    let schema = super::yaml::load_json(alloc, s, Some(file_id))?;
+
+    let mut converter = Converter {
+        alloc,
+        definitions: HashMap::new(),
+        names: HashSet::new(),
+        root: None,
+    };
+
+    let mut definitions = Vec::new();
+
+    for keyword in ["$defs", "definitions"] {
+        let Some(defs) = get(&schema, keyword) else {
+            continue;
+        };
+
+        let Some(defs) = entries(defs) else {
+            return Err(schema_error(
+                format!("expected the value of `{keyword}` to be an object"),
+                defs,
+            ));
+        };
+
+        for (name, def) in defs {
+            let id = converter.fresh_name(name.label());
+            converter
+                .definitions
+                .insert(format!("#/{keyword}/{}", escape_pointer(name.label())), id);
+            definitions.push((id, def));
+        }
+    }
+
+    let mut bindings = definitions
+        .into_iter()
+        .map(|(id, def)| {
+            let doc = get(def, "description")
+                .map(|doc| expect_str(doc, "description"))
+                .transpose()?;
+            let contract = converter.convert(def)?;
+
+            Ok(LetBinding {
+                pattern: Pattern::any(id),
+                metadata: LetMetadata {
+                    doc,
+                    annotation: Default::default(),
+                },
+                value: converter.type_to_ast(contract),
+            })
+        })
+        .collect::<Result<Vec<_>, ParseError>>()?;
+
+    let root = converter.convert(&schema)?;
+    let root = converter.type_to_ast(root);
+
+    let body = match converter.root {
+        Some(id) => {
+            bindings.push(LetBinding {
+                pattern: Pattern::any(id),
+                metadata: Default::default(),
+                value: root,
+            });
+            builder::var(id)
+        }
+        None => root,
+    };
+
+    if bindings.is_empty() {
+        Ok(body)
+    } else {
+        Ok(alloc.let_block(bindings, body, true).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::Files;
+
+    fn convert(s: &str) -> Result<String, ParseError> {
+        let mut files = Files::empty();
+        let file_id = files.add("<test>", s);
+        let alloc = AstAlloc::new();
+        ast_from_str(&alloc, s, file_id).map(|ast| ast.to_string())
+    }
+
+    #[test]
+    fn scalar_types() {
+        assert_eq!(convert(r#"{ "type": "string" }"#).unwrap(), "String");
+        assert_eq!(convert("true").unwrap(), "Dyn");
+        assert_eq!(
+            convert(r#"{ "type": ["boolean", "null"] }"#).unwrap(),
+            "std.contract.any_of [ Bool, std.contract.Equal null ]"
+        );
+        assert_eq!(
+            convert(r#"{ "enum": ["debug", "info"] }"#).unwrap(),
+            "std.contract.all_of [ std.enum.TagOrString, [| 'debug, 'info |] ]"
+        );
+    }
+
+    #[test]
+    fn objects() {
+        assert_eq!(
+            convert(
+                r#"{
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string", "description": "The name" },
+                        "port": { "type": "integer", "minimum": 1, "default": 80 }
+                    },
+                    "required": ["name"],
+                    "additionalProperties": false
+                }"#
+            )
+            .unwrap(),
+            r#"{
+  name | String | doc "The name",
+  port
+    | std.number.Integer
+    | std.contract.from_predicate (fun value => value >= 1)
+    | default = 80
+}"#
+        );
+        assert_eq!(
+            convert(r#"{ "type": "object", "additionalProperties": { "type": "number" } }"#)
+                .unwrap(),
+            "{ _ | Number }"
+        );
+    }
+
+    #[test]
+    fn definitions() {
+        assert_eq!(
+            convert(
+                r##"{
+                    "$defs": { "let": { "type": "string" } },
+                    "type": "array",
+                    "items": { "$ref": "#/$defs/let" }
+                }"##
+            )
+            .unwrap(),
+            "let rec let_2 = String in Array let_2"
+        );
+    }
+
+    #[test]
+    fn exclusive_bounds() {
+        let expected = r#"std.contract.all_of
+  [
+    Number,
+    std.contract.from_predicate (fun value => value > 0),
+    std.contract.from_predicate (fun value => value <= 10)
+  ]"#;
+
+        assert_eq!(
+            convert(r#"{ "type": "number", "exclusiveMinimum": 0, "maximum": 10 }"#).unwrap(),
+            expected
+        );
+        // Draft 4 form, where the exclusive keywords are booleans.
+        assert_eq!(
+            convert(
+                r#"{
+                    "type": "number",
+                    "minimum": 0,
+                    "exclusiveMinimum": true,
+                    "maximum": 10,
+                    "exclusiveMaximum": false
+                }"#
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        assert!(convert(r#"{ "type": "str" }"#).is_err());
+        assert!(convert(r#"{ "properties": [] }"#).is_err());
+        assert!(convert(r#"{ "type": "string", "pattern": 1 }"#).is_err());
+        assert!(convert("1").is_err());
+    }
+}
@@ -23,6 +23,11 @@ use once_cell::sync::Lazy;
 
 use std::{fmt, io};
 
+pub mod csv_deser;
+pub mod env;
+pub mod ini_deser;
+pub mod json_schema;
+pub mod plist;
 pub mod yaml;
 
 /// Available export formats.
@@ -41,6 +46,13 @@ pub enum ExportFormat {
     /// converted into its own document.
     YamlDocuments,
     Toml,
+    /// A list of `NAME=value` assignments, as used by dotenv libraries or systemd's
+    /// `EnvironmentFile`. The output data must be a record of strings, numbers, booleans or enum
+    /// tags.
+    #[cfg_attr(feature = "clap", value(alias("dotenv")))]
+    Env,
+    /// An XML property list, as used for macOS preferences or launchd jobs.
+    Plist,
 }
 
 impl fmt::Display for ExportFormat {
@@ -51,6 +63,8 @@ impl fmt::Display for ExportFormat {
             Self::Yaml => write!(f, "yaml"),
             Self::YamlDocuments => write!(f, "yaml-documents"),
             Self::Toml => write!(f, "toml"),
+            Self::Env => write!(f, "env"),
+            Self::Plist => write!(f, "plist"),
         }
     }
 }
@@ -115,6 +129,28 @@ where
         .serialize(serializer)
 }
 
+/// Returns the value of a number if it's an integer that fits either in an `i64` or in a `u64`.
+fn as_int64(n: &Number) -> Option<i128> {
+    if !n.is_integer() {
+        return None;
+    }
+
+    i64::try_from(n)
+        .map(i128::from)
+        .or_else(|_| u64::try_from(n).map(i128::from))
+        .ok()
+}
+
+/// Textual representation of a number, for the export formats that don't go through serde. As
+/// for [serialize_num], integers that fit in 64 bits are represented exactly, and other numbers
+/// are approximated by the nearest `f64`.
+fn number_to_string(n: &Number) -> String {
+    match as_int64(n) {
+        Some(i) => i.to_string(),
+        None => f64::rounding_from(n, RoundingMode::Nearest).0.to_string(),
+    }
+}
+
 /// Helper function to convert a serialized primitive float to a Nickel number. Return a deserialize error if
 /// the floating point value is either NaN or infinity.
 fn number_from_float<F: PrimitiveFloat, E: serde::de::Error>(float_value: F) -> Result<Number, E>
@@ -520,6 +556,55 @@ pub fn validate(format: ExportFormat, value: &NickelValue) -> Result<(), Pointed
         }
     }
 
+    // Env files are flat: we check the top-level record and its fields here, and delegate the
+    // validation of each field value to `do_validate`.
+    fn validate_env(value: &NickelValue) -> Result<(), PointedExportErrorData> {
+        let record = match value.content_ref() {
+            ValueContentRef::Record(Container::Empty) => return Ok(()),
+            ValueContentRef::Record(Container::Alloc(record)) => record,
+            _ => {
+                return Err(ExportErrorKind::ExpectedRecord {
+                    format: ExportFormat::Env,
+                    value: value.clone(),
+                }
+                .into());
+            }
+        };
+
+        record.iter_serializable().try_for_each(|binding| {
+            // unwrap(): see `do_validate`
+            let (id, value) = binding.unwrap_or_else(|err| {
+                panic!(
+                    "encountered field without definition `{}` \
+                    during pre-serialization validation",
+                    err.id
+                )
+            });
+
+            let result = if !env::is_valid_name(id.label()) {
+                Err(ExportErrorKind::InvalidEnvName {
+                    name: id.label().to_owned(),
+                    value: value.clone(),
+                }
+                .into())
+            } else if matches!(
+                value.content_ref(),
+                ValueContentRef::Record(_) | ValueContentRef::Array(_)
+            ) {
+                Err(ExportErrorKind::UnsupportedNestedValue {
+                    format: ExportFormat::Env,
+                    value: value.clone(),
+                }
+                .into())
+            } else {
+                do_validate(ExportFormat::Env, value)
+            };
+
+            result
+                .map_err(|err: PointedExportErrorData| err.with_elem(NickelPointerElem::Field(id)))
+        })
+    }
+
     if format == ExportFormat::Text {
         if value.as_string().is_some() {
             Ok(())
@@ -527,7 +612,11 @@ pub fn validate(format: ExportFormat, value: &NickelValue) -> Result<(), Pointed
             Err(ExportErrorKind::NotAString(value.clone()).into())
         }
     } else {
-        let mut result = do_validate(format, value);
+        let mut result = if format == ExportFormat::Env {
+            validate_env(value)
+        } else {
+            do_validate(format, value)
+        };
 
         if let Err(PointedExportErrorData { path, .. }) = &mut result {
             path.0.reverse();
@@ -609,6 +698,8 @@ where
                     .write_all(s.as_bytes())
                     .map_err(|err| ExportErrorKind::Other(err.to_string()))
             }),
+        ExportFormat::Env => env::to_writer(writer, value),
+        ExportFormat::Plist => plist::to_writer(writer, value),
         ExportFormat::Text => match value.as_string() {
             Some(s) => writer
                 .write_all(s.as_bytes())
@@ -670,6 +761,51 @@ pub mod toml_deser {
         })
     }
 
+    fn fuse_ranges(r1: Range<usize>, r2: Range<usize>) -> Range<usize> {
+        r1.start.min(r2.start)..r1.end.max(r2.end)
+    }
+
+    /// `toml_edit` only records the span of the header of a table (and none at all for implicit
+    /// tables, such as `foo` in `[foo.bar]`). We extend it to cover the entries of the table, so
+    /// that errors on a table point to its whole definition.
+    fn table_span(table: &toml_edit::Table) -> Option<Range<usize>> {
+        table
+            .iter()
+            .filter_map(|(_, item)| item_span(item))
+            .chain(table.span())
+            .reduce(fuse_ranges)
+    }
+
+    fn item_span(item: &toml_edit::Item) -> Option<Range<usize>> {
+        match item {
+            toml_edit::Item::Table(t) => table_span(t),
+            toml_edit::Item::ArrayOfTables(ts) => {
+                ts.iter().filter_map(table_span).reduce(fuse_ranges)
+            }
+            toml_edit::Item::Value(val) => value_span(val),
+            toml_edit::Item::None => None,
+        }
+    }
+
+    /// Same as [table_span], but for inline tables, whose implicit subtables (such as `b` in
+    /// `a = { b.c = 1 }`) don't have a span either.
+    fn value_span(val: &Value) -> Option<Range<usize>> {
+        match val {
+            Value::InlineTable(t) => t
+                .iter()
+                .filter_map(|(_, val)| value_span(val))
+                .chain(t.span())
+                .reduce(fuse_ranges),
+            _ => val.span(),
+        }
+    }
+
+    /// Returns the identifier corresponding to the key `key` of a table, positioned at the
+    /// definition of the key.
+    fn key_ident(key: &str, toml_key: Option<&toml_edit::Key>, src_id: FileId) -> LocIdent {
+        LocIdent::new(key).with_pos(range_pos(toml_key.and_then(toml_edit::Key::span), src_id))
+    }
+
     // Add `to_value` method to `toml_edit` types.
     trait ToNickelValue {
         fn to_value(&self, pos_table: &mut PosTable, src_id: FileId) -> NickelValue;
@@ -691,8 +827,9 @@ pub mod toml_deser {
                 self.iter()
                     .map(|(key, val)| {
                         (
-                            LocIdent::new(key),
-                            val.to_value_with_pos(pos_table, val.span(), src_id).into(),
+                            key_ident(key, self.key(key), src_id),
+                            val.to_value_with_pos(pos_table, item_span(val), src_id)
+                                .into(),
                         )
                     })
                     .collect(),
@@ -711,7 +848,7 @@ pub mod toml_deser {
                 Value::Boolean(b) => NickelValue::bool_value_posless(*b.value()),
                 Value::Array(vs) => NickelValue::array_posless(
                     vs.iter()
-                        .map(|val| val.to_value_with_pos(pos_table, val.span(), src_id))
+                        .map(|val| val.to_value_with_pos(pos_table, value_span(val), src_id))
                         .collect(),
                     Vec::new(),
                 ),
@@ -719,8 +856,9 @@ pub mod toml_deser {
                     t.iter()
                         .map(|(key, val)| {
                             (
-                                LocIdent::new(key),
-                                val.to_value_with_pos(pos_table, val.span(), src_id).into(),
+                                key_ident(key, t.key(key), src_id),
+                                val.to_value_with_pos(pos_table, value_span(val), src_id)
+                                    .into(),
                             )
                         })
                         .collect(),
@@ -741,7 +879,7 @@ pub mod toml_deser {
                 toml_edit::Item::Table(t) => t.to_value(pos_table, src_id),
                 toml_edit::Item::ArrayOfTables(ts) => NickelValue::array_posless(
                     ts.iter()
-                        .map(|val| val.to_value_with_pos(pos_table, val.span(), src_id))
+                        .map(|val| val.to_value_with_pos(pos_table, table_span(val), src_id))
                         .collect(),
                     Vec::new(),
                 ),
@@ -767,7 +905,7 @@ pub mod toml_deser {
         fn to_ast<'ast>(&self, alloc: &'ast AstAlloc, file_id: FileId) -> Ast<'ast>;
     }
 
-    fn to_record<'a, 'ast, T: ToAst + 'a, I: Iterator<Item = (&'a str, &'a T)>>(
+    fn to_record<'a, 'ast, T: ToAst + 'a, I: Iterator<Item = (LocIdent, &'a T)>>(
         alloc: &'ast AstAlloc,
         file_id: FileId,
         iter: I,
@@ -776,7 +914,7 @@ pub mod toml_deser {
             alloc.record_data(
                 [],
                 iter.map(|(key, val)| FieldDef {
-                    path: FieldPathElem::single_ident_path(alloc, LocIdent::new(key)),
+                    path: FieldPathElem::single_ident_path(alloc, key),
                     metadata: FieldMetadata::default(),
                     value: Some(val.to_ast(alloc, file_id)),
                     pos: TermPos::default(),
@@ -789,14 +927,18 @@ pub mod toml_deser {
 
     impl ToAst for toml_edit::Table {
         fn to_ast<'ast>(&self, alloc: &'ast AstAlloc, file_id: FileId) -> Ast<'ast> {
-            Ast::from(to_record(alloc, file_id, self.iter()))
-                .with_pos(range_pos(self.span(), file_id))
+            let fields = self
+                .iter()
+                .map(|(key, val)| (key_ident(key, self.key(key), file_id), val));
+
+            Ast::from(to_record(alloc, file_id, fields))
+                .with_pos(range_pos(table_span(self), file_id))
         }
     }
 
     impl ToAst for toml_edit::Item {
         fn to_ast<'ast>(&self, alloc: &'ast AstAlloc, file_id: FileId) -> Ast<'ast> {
-            let pos = range_pos(self.span(), file_id);
+            let pos = range_pos(item_span(self), file_id);
             match self {
                 toml_edit::Item::None => Ast::from(Node::Null).with_pos(pos),
                 toml_edit::Item::Value(val) => val.to_ast(alloc, file_id),
@@ -815,7 +957,7 @@ pub mod toml_deser {
 
     impl ToAst for toml_edit::Value {
         fn to_ast<'ast>(&self, alloc: &'ast AstAlloc, file_id: FileId) -> Ast<'ast> {
-            let pos = range_pos(self.span(), file_id);
+            let pos = range_pos(value_span(self), file_id);
             let node = match self {
                 Value::String(s) => alloc.string(s.value()),
                 Value::Integer(i) => alloc.number((*i.value()).into()),
@@ -828,7 +970,12 @@ pub mod toml_deser {
                         .map(|v| v.to_ast(alloc, file_id))
                         .collect::<Vec<_>>(),
                 ),
-                Value::InlineTable(t) => to_record(alloc, file_id, t.iter()),
+                Value::InlineTable(t) => to_record(
+                    alloc,
+                    file_id,
+                    t.iter()
+                        .map(|(key, val)| (key_ident(key, t.key(key), file_id), val)),
+                ),
             };
             Ast::from(node).with_pos(pos)
         }
@@ -1013,6 +1160,81 @@ mod tests {
         );
         assert_pass_validation(ExportFormat::Json, "{foo = null}");
         assert_fail_validation(ExportFormat::Toml, "{foo = null}");
+        assert_fail_validation(ExportFormat::Plist, "{foo = null}");
+    }
+
+    #[test]
+    fn env_validation() {
+        assert_pass_validation(
+            ExportFormat::Env,
+            "{HOST = \"localhost\", PORT = 80, DEBUG = false, MODE = 'prod}",
+        );
+        assert_pass_validation(ExportFormat::Env, "{}");
+        assert_fail_validation(ExportFormat::Env, "[1, 2]");
+        assert_fail_validation(ExportFormat::Env, "{FOO = {BAR = 1}}");
+        assert_fail_validation(ExportFormat::Env, "{FOO = [1]}");
+        assert_fail_validation(ExportFormat::Env, "{FOO = null}");
+        assert_fail_validation(ExportFormat::Env, "{\"1FOO\" = 1}");
+        assert_fail_validation(ExportFormat::Env, "{\"FOO-BAR\" = 1}");
+    }
+
+    #[test]
+    fn env_output() {
+        assert_eq!(
+            to_string(
+                ExportFormat::Env,
+                &eval("{B = 1.5, A = \"plain\", C = \"a \\\"quoted\\\" $value\\n\", D = true}")
+            )
+            .unwrap(),
+            "A=plain\nB=1.5\nC=\"a \\\"quoted\\\" \\$value\\n\"\nD=true\n"
+        );
+    }
+
+    #[test]
+    fn plist_output() {
+        assert_eq!(
+            to_string(
+                ExportFormat::Plist,
+                &eval("{label = \"a & b\", args = [1, 0.5, false], env = {}}")
+            )
+            .unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>args</key>
+	<array>
+		<integer>1</integer>
+		<real>0.5</real>
+		<false/>
+	</array>
+	<key>env</key>
+	<dict/>
+	<key>label</key>
+	<string>a &amp; b</string>
+</dict>
+</plist>
+"#
+        );
+    }
+
+    #[test]
+    fn plist_invalid_chars() {
+        for src in [
+            "{label = \"a\\u{0}b\"}",
+            "{label = \"\\u{8}\"}",
+            "{\"bad\\u{1b}key\" = 1}",
+        ] {
+            assert!(matches!(
+                to_string(ExportFormat::Plist, &eval(src)),
+                Err(PointedExportErrorData {
+                    error: ExportErrorKind::InvalidXmlChar { .. },
+                    ..
+                })
+            ));
+        }
+
+        assert!(to_string(ExportFormat::Plist, &eval("{label = \"a\\tb\\nc\"}")).is_ok());
     }
 
     #[test]
@@ -1022,4 +1244,54 @@ mod tests {
         assert_involutory("{val = [\"a\", 3, []]}");
         assert_involutory("{a.foo.bar = \"2\", b = false, c = [{d = \"e\"}, {d = \"f\"}]}");
     }
+
+    #[test]
+    fn toml_positions() {
+        use crate::ast::{AstAlloc, Node};
+
+        let source = "\
+name = \"web\"
+
+[nested]
+inner = \"x\"
+
+[[servers]]
+ip = \"1\"
+[[servers]]
+ip = \"2\"
+";
+        let mut files = crate::files::Files::empty();
+        let file_id = files.add("<test>", source);
+        let alloc = AstAlloc::new();
+        let ast = toml_deser::ast_from_str(&alloc, source, file_id).unwrap();
+
+        let Node::Record(record) = &ast.node else {
+            panic!("expected a record");
+        };
+
+        let field_text = |name: &str| {
+            let def = record
+                .field_defs
+                .iter()
+                .find(|def| def.path[0].try_as_ident().unwrap().label() == name)
+                .unwrap();
+            let key_span = def.path[0].try_as_ident().unwrap().pos.unwrap();
+            let value_span = def.value.as_ref().unwrap().pos.unwrap();
+
+            (
+                &source[key_span.to_range::<usize>()],
+                &source[value_span.to_range::<usize>()],
+            )
+        };
+
+        assert_eq!(field_text("name"), ("name", "\"web\""));
+        assert_eq!(field_text("nested"), ("nested", "[nested]\ninner = \"x\""));
+        assert_eq!(
+            field_text("servers"),
+            (
+                "servers",
+                "[[servers]]\nip = \"1\"\n[[servers]]\nip = \"2\""
+            )
+        );
+    }
 }
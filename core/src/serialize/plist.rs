@@ -0,0 +1,145 @@
+//! Serialization to XML property lists, as used for macOS preferences or launchd jobs.
+//!
+//! Records are mapped to `<dict>`, arrays to `<array>`, strings and enum tags to `<string>`,
+//! booleans to `<true/>` and `<false/>`, and numbers to `<integer>` or `<real>` depending on
+//! whether they are integers. Property lists have no equivalent of `null`, which is thus rejected
+//! during validation.
+use super::ExportFormat;
+use crate::{
+    error::ExportErrorKind,
+    eval::value::{ArrayData, Container, EnumVariantData, NickelValue, ValueContentRef},
+};
+
+use std::io;
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+"#;
+
+const FOOTER: &str = "</plist>\n";
+
+/// Returns `true` if `c` may appear in an XML 1.0 document. Most control characters can't, even
+/// escaped.
+fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}')
+        || c >= '\u{10000}'
+}
+
+/// Escapes the characters that have a special meaning in XML character data. Fails on the
+/// characters that XML doesn't allow, reporting `value` as the culprit.
+fn escape(s: &str, value: &NickelValue) -> Result<String, ExportErrorKind> {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c if !is_xml_char(c) => {
+                return Err(ExportErrorKind::InvalidXmlChar {
+                    ch: c,
+                    value: value.clone(),
+                });
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    Ok(escaped)
+}
+
+/// Serializes a value to an XML property list. The value must have been validated with
+/// [super::validate] first.
+pub fn to_writer(mut writer: impl io::Write, value: &NickelValue) -> Result<(), ExportErrorKind> {
+    let mut out = String::from(HEADER);
+    write_value(&mut out, value, 0)?;
+    out.push_str(FOOTER);
+
+    writer
+        .write_all(out.as_bytes())
+        .map_err(|err| ExportErrorKind::Other(err.to_string()))
+}
+
+fn indent(out: &mut String, level: usize) {
+    out.extend(std::iter::repeat_n('\t', level));
+}
+
+/// Writes a single element on its own line, e.g. `<string>hello</string>`.
+fn write_leaf(out: &mut String, level: usize, tag: &str, content: &str) {
+    indent(out, level);
+    out.push_str(&format!("<{tag}>{content}</{tag}>\n"));
+}
+
+fn write_value(out: &mut String, value: &NickelValue, level: usize) -> Result<(), ExportErrorKind> {
+    match value.content_ref() {
+        ValueContentRef::Bool(b) => {
+            indent(out, level);
+            out.push_str(if b { "<true/>\n" } else { "<false/>\n" });
+        }
+        ValueContentRef::Number(n) => {
+            let tag = if super::as_int64(n).is_some() {
+                "integer"
+            } else {
+                "real"
+            };
+            write_leaf(out, level, tag, &super::number_to_string(n));
+        }
+        ValueContentRef::String(s) => write_leaf(out, level, "string", &escape(s, value)?),
+        ValueContentRef::EnumVariant(EnumVariantData { tag, arg: None }) => {
+            write_leaf(out, level, "string", &escape(tag.label(), value)?)
+        }
+        ValueContentRef::Record(Container::Empty) => {
+            indent(out, level);
+            out.push_str("<dict/>\n");
+        }
+        ValueContentRef::Record(Container::Alloc(record)) => {
+            let mut entries = record
+                .iter_serializable()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|missing_def_err| {
+                    ExportErrorKind::Other(format!(
+                        "missing field definition for `{}`",
+                        missing_def_err.id
+                    ))
+                })?;
+
+            entries.sort_by_key(|(k, _)| *k);
+
+            indent(out, level);
+            out.push_str("<dict>\n");
+
+            for (id, value) in entries {
+                write_leaf(out, level + 1, "key", &escape(id.label(), value)?);
+                write_value(out, value, level + 1)?;
+            }
+
+            indent(out, level);
+            out.push_str("</dict>\n");
+        }
+        ValueContentRef::Array(Container::Empty) => {
+            indent(out, level);
+            out.push_str("<array/>\n");
+        }
+        ValueContentRef::Array(Container::Alloc(ArrayData { array, .. })) => {
+            indent(out, level);
+            out.push_str("<array>\n");
+
+            for elt in array.iter() {
+                write_value(out, elt, level + 1)?;
+            }
+
+            indent(out, level);
+            out.push_str("</array>\n");
+        }
+        ValueContentRef::Null => {
+            return Err(ExportErrorKind::UnsupportedNull(
+                ExportFormat::Plist,
+                value.clone(),
+            ));
+        }
+        _ => return Err(ExportErrorKind::NonSerializable(value.clone())),
+    }
+
+    Ok(())
+}
@@ -85,6 +85,20 @@ impl<'ast> Node<'ast> {
         }
     }
 
+    /// Returns the end of the last child of a container, if any.
+    fn last_child_end(&self) -> Option<ByteIndex> {
+        let last_pos = match self {
+            Node::Array { array, .. } => array.last().map(|elt| elt.pos),
+            Node::Map { map, .. } => map
+                .last()
+                .and_then(|field_def| field_def.value.as_ref())
+                .map(|value| value.pos),
+            Node::Scalar(_) => None,
+        };
+
+        last_pos.and_then(TermPos::into_opt).map(|span| span.end)
+    }
+
     fn with_end_pos(mut self, end: ByteIndex) -> Self {
         match &mut self {
             Node::Array { pos, .. } | Node::Map { pos, .. } => {
@@ -164,8 +178,9 @@ impl<'ast> AnchorMap<'ast> {
                 .map
                 .entry(aid)
                 .or_insert((Ident::fresh(), ast::Node::Null.into()));
+            let pos = ast.pos;
             *slot = ast;
-            ast::Node::Var((*ident).into()).into()
+            Ast::from(ast::Node::Var((*ident).into())).with_pos(pos)
         } else {
             ast
         }
@@ -178,9 +193,11 @@ impl<'ast> AnchorMap<'ast> {
 /// This is designed mainly as an implementor of the
 /// `saphyr_parser::SpannedEventReceiver` trait. We adapt it for JSON by a
 /// little shim that translates `json_scanner` events to `saphyr_parser` events.
-struct Loader<'ast> {
+struct Loader<'src, 'ast> {
     /// For error reporting, do we claim to be loading YAML or JSON?
     format_name: &'static str,
+    /// The source being loaded.
+    source: &'src str,
     alloc: &'ast AstAlloc,
     /// Keeps track of the anchors we've encountered so far.
     anchor_map: AnchorMap<'ast>,
@@ -216,7 +233,7 @@ struct Loader<'ast> {
     char_index_map: Vec<usize>,
 }
 
-impl<'ast> Loader<'ast> {
+impl<'ast> Loader<'_, 'ast> {
     fn push_node(&mut self, node: Node<'ast>, anchor_id: Option<NonZeroUsize>) {
         if let Some((parent, _)) = self.doc_stack.last_mut() {
             match parent {
@@ -390,6 +407,29 @@ impl<'ast> Loader<'ast> {
         }
     }
 
+    /// Converts an index reported by the parser to a byte index in the source.
+    fn byte_index(&self, index: usize) -> ByteIndex {
+        ByteIndex::from(self.char_index_map.get(index).copied().unwrap_or(index) as u32)
+    }
+
+    /// Block scalars (`|` and `>`) extend up to the next token, and thus include the trailing
+    /// line breaks and indentation. This removes them from the position of a scalar.
+    fn trim_trailing_whitespace(&self, pos: TermPos) -> TermPos {
+        pos.map(|span| {
+            let start = span.start.to_usize();
+            let end = span.end.to_usize();
+            let trimmed_len = self
+                .source
+                .get(start..end)
+                .map_or(end - start, |text| text.trim_end().len());
+
+            RawSpan {
+                end: ByteIndex::from((start + trimmed_len) as u32),
+                ..span
+            }
+        })
+    }
+
     /// If we're expecting the next item to be a map key, return somewhere to put it.
     ///
     /// The goal here is to delay parsing so that we can interpret map keys as strings as much as
@@ -423,7 +463,7 @@ impl<'ast> Loader<'ast> {
     }
 }
 
-impl<'input, 'ast> SpannedEventReceiver<'input> for Loader<'ast> {
+impl<'input, 'ast> SpannedEventReceiver<'input> for Loader<'_, 'ast> {
     fn on_event(&mut self, ev: saphyr_parser::Event<'input>, span: saphyr_parser::Span) {
         // saphyr-parser doesn't provide a way for the loader to signal an
         // error. So we store an error in our internal state and just refuse to
@@ -456,7 +496,7 @@ impl<'input, 'ast> SpannedEventReceiver<'input> for Loader<'ast> {
                 // unwrap: saphyr uses id zero to represent no id, so it should
                 // never be the payload of the Alias variant.
                 let id = NonZeroUsize::new(id).unwrap();
-                self.push_node(Node::Scalar(self.anchor_map.var(id)), None);
+                self.push_node(Node::Scalar(self.anchor_map.var(id).with_pos(pos)), None);
             }
             Scalar(value, style, anchor_id, tag) => {
                 if let Some(key_slot) = self.key_slot() {
@@ -464,6 +504,12 @@ impl<'input, 'ast> SpannedEventReceiver<'input> for Loader<'ast> {
                     *key_slot = Some(key);
                 } else {
                     let aid = NonZeroUsize::new(anchor_id);
+                    let pos = match style {
+                        ScalarStyle::Literal | ScalarStyle::Folded => {
+                            self.trim_trailing_whitespace(pos)
+                        }
+                        _ => pos,
+                    };
                     self.push_scalar(&value, style, aid, tag.as_ref(), pos);
                 }
             }
@@ -487,8 +533,17 @@ impl<'input, 'ast> SpannedEventReceiver<'input> for Loader<'ast> {
                 self.doc_stack.push((node, anchor_id));
             }
             SequenceEnd | MappingEnd => {
-                let end_idx = ByteIndex::from(span.end.index() as u32);
                 let (node, anchor_id) = self.doc_stack.pop().unwrap();
+                // The end of a flow collection is its closing bracket. A block collection has no
+                // closing token, and saphyr reports an empty end span at the beginning of the
+                // following token instead, which might be a few lines below. In that case, we
+                // rather end the collection with its last element.
+                let end_idx = if span.start.index() == span.end.index() {
+                    node.last_child_end()
+                        .unwrap_or_else(|| self.byte_index(span.end.index()))
+                } else {
+                    self.byte_index(span.end.index())
+                };
                 let node = node.with_end_pos(end_idx);
                 self.push_node(node, anchor_id);
             }
@@ -531,6 +586,7 @@ pub fn load_json<'ast>(
     let mut parser = json_scanner::Parser::new(s.as_bytes());
     let mut loader = Loader {
         format_name: "json",
+        source: s,
         file_id,
         alloc,
         anchor_map: Default::default(),
@@ -640,6 +696,7 @@ fn load<'ast>(
 ) -> Result<Ast<'ast>, ParseError> {
     let mut loader = Loader {
         format_name,
+        source: s,
         file_id,
         alloc,
         anchor_map: Default::default(),
@@ -742,7 +799,7 @@ fn load<'ast>(
 // The mainline conversion creates `RecRecords`, but since they came from data we know they're just
 // normal Records. This is important for std.deserialize, since it expects deserialized data to be
 // evaluated.
-fn ast_to_term(pos_table: &mut PosTable, ast: Ast<'_>) -> NickelValue {
+pub(crate) fn ast_to_term(pos_table: &mut PosTable, ast: Ast<'_>) -> NickelValue {
     let value: NickelValue = ast.to_mainline(pos_table);
     value
         .traverse::<_, Infallible>(
@@ -815,6 +872,51 @@ mod tests {
         assert_eq!(&yaml_to_ncl(multi_doc), "[ 1, 2 ]");
     }
 
+    #[test]
+    fn positions() {
+        let source = "\
+name: \"wéb\"
+tags:
+  - a
+  - 1
+nested:
+  inner: x
+# comment
+flow: {a: 1, b: [1, 2]}
+multi: |
+  line
+
+";
+        let mut files = crate::files::Files::empty();
+        let file_id = files.add("<test>", source);
+        let alloc = AstAlloc::new();
+        let ast = load_yaml(&alloc, source, Some(file_id), Listify::Auto).unwrap();
+
+        let ast::Node::Record(record) = &ast.node else {
+            panic!("expected a record");
+        };
+
+        let field_text = |name: &str| {
+            let field = record
+                .field_defs
+                .iter()
+                .find(|def| def.path[0].try_as_ident().unwrap().label() == name)
+                .unwrap();
+            let span = field.value.as_ref().unwrap().pos.unwrap();
+            &source[span.to_range::<usize>()]
+        };
+
+        assert_eq!(field_text("name"), "\"wéb\"");
+        assert_eq!(field_text("tags"), "- a\n  - 1");
+        assert_eq!(field_text("nested"), "inner: x");
+        assert_eq!(field_text("flow"), "{a: 1, b: [1, 2]}");
+        assert_eq!(field_text("multi"), "line");
+        assert_eq!(
+            &source[ast.pos.unwrap().to_range::<usize>()],
+            source.trim_end()
+        );
+    }
+
     #[test]
     fn yaml_refs() {
         let basic_ref = r#"
@@ -12,7 +12,7 @@ use crate::{
     cache::InputFormat,
     combine::Combine,
     error::{EvalErrorKind, ParseError},
-    eval::{Environment, contract_eq, value::NickelValue},
+    eval::{Environment, contract_eq, native::NativeFunction, value::NickelValue},
     files::FileId,
     identifier::{Ident, LocIdent},
     impl_display_from_pretty,
@@ -1188,8 +1188,16 @@ pub enum UnaryOp {
 
     /// The tangent function.
     NumberTan,
+
+    /// Call a native function on an array of arguments. The arguments must have been fully
+    /// evaluated, which is ensured by the wrapper built by [NativeFunction::to_value].
+    NativeCall(Rc<NativeFunction>),
 }
 
+// Unary operators are stored inline in terms, so we make sure they don't grow by accident.
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(std::mem::size_of::<UnaryOp>() == 40);
+
 impl fmt::Display for UnaryOp {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use UnaryOp::*;
@@ -1260,6 +1268,8 @@ impl fmt::Display for UnaryOp {
             NumberCos => write!(f, "number/cos"),
             NumberSin => write!(f, "number/sin"),
             NumberTan => write!(f, "number/tan"),
+
+            NativeCall(_) => write!(f, "native_call"),
         }
     }
 }
@@ -788,6 +788,12 @@ impl<'ast> CompilePart for EnumPattern<'ast> {
     }
 }
 
+/// Returns `true` if `pattern` matches any value. Unless it has a guard, the branches coming after
+/// such a pattern in a match expression are unreachable.
+pub fn is_catch_all(pattern: &Pattern<'_>) -> bool {
+    matches!(pattern.data, PatternData::Wildcard | PatternData::Any(_))
+}
+
 pub trait Compile {
     /// Compile a match expression to a Nickel expression matching `value`.
     fn compile(self, pos_table: &mut PosTable, value: NickelValue, pos_idx: PosIdx) -> NickelValue;
@@ -835,7 +841,7 @@ impl<'ast> Compile for MatchData<'ast> {
                         body,
                     },
                 )| {
-                    if matches!((&pattern.data, guard), (PatternData::Wildcard, None)) {
+                    if is_catch_all(pattern) && guard.is_none() {
                         Some((idx, body.clone()))
                     } else {
                         None
@@ -403,12 +403,12 @@ impl PrimOpType for PrimOp {
                 ],
                 mk_uniftype::str(),
             ),
-            // forall a. [| 'Json, 'Toml, 'Yaml, 'YamlDocuments |] -> a -> String
+            // forall a. [| 'Env, 'Json, 'Plist, 'Toml, 'Yaml, 'YamlDocuments |] -> a -> String
             PrimOp::Serialize => {
                 let ty_input = state.table.fresh_type_uvar(var_level);
                 (
                     vec![
-                        mk_uty_enum!("Json", "Toml", "Yaml", "YamlDocuments"),
+                        mk_uty_enum!("Env", "Json", "Plist", "Toml", "Yaml", "YamlDocuments"),
                         ty_input,
                     ],
                     mk_uniftype::str(),
@@ -1,8 +1,10 @@
 use nickel_lang_core::{
+    cache::normalize_path,
     error::Sink,
     eval::cache::lazy::CBNCache,
     program::{Program, ProgramBuilder},
 };
+use nickel_lang_utils::project_root::project_root;
 
 // Regression test for https://github.com/tweag/nickel/issues/2362
 #[test]
@@ -17,3 +19,24 @@ fn multiple_inputs_non_paths() {
 
     assert_eq!(&prog.eval_full_for_export().unwrap().to_string(), "{}");
 }
+
+#[test]
+fn source_paths_include_transitive_imports() {
+    let root =
+        normalize_path(project_root().join("core/tests/integration/inputs/imports")).unwrap();
+    let mut prog: Program<CBNCache> = ProgramBuilder::new()
+        .add_path(root.join("nested.ncl"))
+        .build()
+        .unwrap();
+
+    prog.eval_full().unwrap();
+
+    assert_eq!(
+        prog.source_paths(),
+        vec![
+            root.join("imported/nested.ncl"),
+            root.join("imported/two.ncl"),
+            root.join("nested.ncl"),
+        ]
+    );
+}
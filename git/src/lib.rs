@@ -9,7 +9,10 @@ use gix::{
     remote::{self, Direction, fetch, fetch::refmap},
     worktree::state::checkout,
 };
-use std::{num::NonZero, path::Path};
+use std::{
+    num::NonZero,
+    path::{Path, PathBuf},
+};
 
 /// An error that occurred during a git operation.
 #[derive(thiserror::Error, Debug)]
@@ -23,6 +26,9 @@ pub enum Error {
     #[error("target `{target}` not found in `{url}`")]
     TargetNotFound { url: Box<gix::Url>, target: Target },
 
+    #[error("revision `{rev}` not found in the repository at {}", path.display())]
+    RevisionNotFound { rev: String, path: PathBuf },
+
     #[error("{0:?}")]
     Internal(#[from] anyhow::Error),
 }
@@ -199,6 +205,74 @@ pub fn fetch(spec: &Spec, dir: impl AsRef<Path>) -> Result<ObjectId> {
     }
     let object_id = source_object_id(&outcome.ref_map.mappings[0].remote)?;
 
+    let (tree_id, mut index) = checkout_object(&repo, object_id, dir)?;
+    index.write(Default::default()).wrap_err()?;
+
+    Ok(tree_id)
+}
+
+/// A revision of a local repository, checked out by [`checkout_local`].
+#[derive(Clone, Debug)]
+pub struct LocalCheckout {
+    /// The id of the commit that was checked out.
+    pub commit: ObjectId,
+    /// The root of the working tree of the repository.
+    pub workdir: PathBuf,
+}
+
+/// Checks out a revision of the local repository containing `path` into a directory.
+///
+/// `path` can be any file or directory within the working tree of the repository, which is
+/// looked up in the same way as the git CLI does. `rev` is anything understood by `git
+/// rev-parse` that points to a commit, such as `HEAD~2`, a branch name or an abbreviated commit
+/// hash.
+///
+/// As for [`fetch`], only the contents of the revision are written to the given directory, which
+/// will be created if it doesn't exist yet. The repository itself, including its index, is left
+/// untouched.
+pub fn checkout_local(
+    path: impl AsRef<Path>,
+    rev: &str,
+    dir: impl AsRef<Path>,
+) -> Result<LocalCheckout> {
+    let path = path.as_ref();
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).with_path(dir)?;
+
+    // Discovery starts from a directory. We canonicalize the path first, because the parent of a
+    // relative path such as `config.ncl` is empty.
+    let path = path.canonicalize().with_path(path)?;
+    let start = if path.is_dir() {
+        path.as_path()
+    } else {
+        path.parent().unwrap_or(&path)
+    };
+    let repo = gix::discover(start).wrap_err()?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("the repository at {} is bare", repo.git_dir().display()))?
+        .to_owned();
+
+    let commit = repo
+        .rev_parse_single(rev)
+        .map_err(|_| Error::RevisionNotFound {
+            rev: rev.to_owned(),
+            path: workdir.clone(),
+        })?
+        .detach();
+
+    checkout_object(&repo, commit, dir)?;
+
+    Ok(LocalCheckout { commit, workdir })
+}
+
+/// Writes the tree of the given object (typically a commit) to `dir`, returning the id of the
+/// tree and the corresponding index.
+fn checkout_object(
+    repo: &gix::Repository,
+    object_id: ObjectId,
+    dir: &Path,
+) -> Result<(ObjectId, gix::index::File)> {
     let object = repo.find_object(object_id).wrap_err()?;
     let tree_id = object.peel_to_tree().wrap_err()?.id();
     let mut index = repo.index_from_tree(&tree_id).wrap_err()?;
@@ -216,7 +290,6 @@ pub fn fetch(spec: &Spec, dir: impl AsRef<Path>) -> Result<ObjectId> {
         },
     )
     .wrap_err()?;
-    index.write(Default::default()).wrap_err()?;
 
-    Ok(tree_id.detach())
+    Ok((tree_id.detach(), index))
 }
@@ -101,3 +101,54 @@ fn fetch_targets() {
     // gix doesn't give us back a useful structured error: we get a
     // FetchResponse(UploadPack(..)) with a string error message.
 }
+
+// Test that local checkouts resolve revisions like `git rev-parse` and leave the repository alone.
+#[test]
+fn checkout_local_revisions() {
+    let repo = tempdir().unwrap();
+
+    let run = |cmd: &mut Command| {
+        let output = cmd.current_dir(repo.path()).output().unwrap();
+        assert!(output.status.success());
+        output.stdout
+    };
+
+    run(Command::new("git").arg("init"));
+    run(Command::new("git").args(["config", "user.email", "test-runner@example.com"]));
+    run(Command::new("git").args(["config", "user.name", "Test Runner"]));
+    run(Command::new("git").args(["branch", "-m", "main"]));
+
+    write_contents(repo.path(), "main");
+    write_contents(repo.path(), "other_branch");
+    std::fs::write(repo.path().join("contents.txt"), "uncommitted").unwrap();
+
+    let check = |rev: &str, contents: &str| {
+        let out_dir = tempdir().unwrap();
+        let checkout =
+            nickel_lang_git::checkout_local(repo.path().join("contents.txt"), rev, out_dir.path())
+                .unwrap();
+
+        assert_eq!(
+            checkout.workdir.canonicalize().unwrap(),
+            repo.path().canonicalize().unwrap()
+        );
+        let actual = std::fs::read_to_string(out_dir.path().join("contents.txt")).unwrap();
+        assert_eq!(contents, actual);
+    };
+
+    check("HEAD", "other_branch");
+    check("HEAD~1", "main");
+    check("main", "main");
+
+    let out_dir = tempdir().unwrap();
+    assert!(matches!(
+        nickel_lang_git::checkout_local(repo.path(), "not_a_rev", out_dir.path()),
+        Err(nickel_lang_git::Error::RevisionNotFound { .. })
+    ));
+
+    let status = run(Command::new("git").args(["status", "--porcelain"]));
+    assert_eq!(
+        std::str::from_utf8(&status).unwrap().trim(),
+        "M contents.txt"
+    );
+}
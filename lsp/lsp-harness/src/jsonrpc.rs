@@ -13,8 +13,9 @@ use lsp_types::{
     request::{ExecuteCommand, GotoDefinition, Initialize, Request as LspRequest, Shutdown},
     CancelParams, ClientCapabilities, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
     ExecuteCommandParams, GotoDefinitionParams, GotoDefinitionResponse, InitializeParams,
-    InitializedParams, Position, TextDocumentContentChangeEvent, TextDocumentIdentifier,
-    TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier, WorkDoneProgressParams,
+    InitializedParams, Position, Range, ServerCapabilities, TextDocumentContentChangeEvent,
+    TextDocumentIdentifier, TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+    WorkDoneProgressParams, WorkspaceFolder,
 };
 use std::{
     io::{BufRead, BufReader, Read, Write},
@@ -41,6 +42,8 @@ pub struct Server {
     /// yet delivered to the client.
     pending_notifications: Vec<Notification>,
     pause_request_ids: Vec<u32>,
+    /// The capabilities advertised by the language server on initialization.
+    pub capabilities: ServerCapabilities,
 }
 
 /// A dynamically typed message from the LSP server.
@@ -118,8 +121,17 @@ impl Drop for Server {
 impl Server {
     /// Similar to `new`, but allows passing custom stuff
     pub fn new_with_options(
+        cmd: std::process::Command,
+        initialization_options: Option<serde_json::Value>,
+    ) -> Result<Server> {
+        Server::new_with_workspace(cmd, initialization_options, None)
+    }
+
+    /// Similar to `new_with_options`, but also sends workspace folders to the language server.
+    pub fn new_with_workspace(
         mut cmd: std::process::Command,
         initialization_options: Option<serde_json::Value>,
+        workspace_folders: Option<Vec<WorkspaceFolder>>,
     ) -> Result<Server> {
         let mut lsp = cmd
             .stdin(Stdio::piped())
@@ -136,9 +148,10 @@ impl Server {
             pending_notifications: Vec::new(),
             pause_request_ids: Vec::new(),
             id: 0,
+            capabilities: ServerCapabilities::default(),
         };
 
-        lsp.initialize(initialization_options)?;
+        lsp.initialize(initialization_options, workspace_folders)?;
 
         Ok(lsp)
     }
@@ -175,6 +188,18 @@ impl Server {
         })
     }
 
+    /// Replace a range of a file with new text.
+    pub fn edit_file(&mut self, uri: Url, version: i32, range: Range, text: &str) -> Result<()> {
+        self.send_notification::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(range),
+                range_length: None,
+                text: text.to_owned(),
+            }],
+            text_document: VersionedTextDocumentIdentifier { uri, version },
+        })
+    }
+
     /// Send a GotoDefinition request to the language server.
     pub fn goto_def(&mut self, uri: Url, pos: Position) -> Result<Option<GotoDefinitionResponse>> {
         self.send_request::<GotoDefinition>(GotoDefinitionParams {
@@ -187,23 +212,28 @@ impl Server {
         })
     }
 
-    fn initialize(&mut self, initialization_options: Option<serde_json::Value>) -> Result<()> {
+    fn initialize(
+        &mut self,
+        initialization_options: Option<serde_json::Value>,
+        workspace_folders: Option<Vec<WorkspaceFolder>>,
+    ) -> Result<()> {
         // `root_path` is deprecated, but we need ot initialize the struct
         // somehow. There is no `Default` implementation for `InitializeParams`
         // in versions of `lsp-types` compatible with `codespan-lsp`
         #[allow(deprecated)]
-        self.send_request::<Initialize>(InitializeParams {
+        let result = self.send_request::<Initialize>(InitializeParams {
             process_id: None,
             root_path: None,
             root_uri: None,
             initialization_options,
             capabilities: ClientCapabilities::default(),
             trace: None,
-            workspace_folders: None,
+            workspace_folders,
             client_info: None,
             locale: None,
             work_done_progress_params: WorkDoneProgressParams::default(),
         })?;
+        self.capabilities = result.capabilities;
         self.send_notification::<Initialized>(InitializedParams {})
     }
 
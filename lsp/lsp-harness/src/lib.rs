@@ -1,19 +1,31 @@
 mod jsonrpc;
 mod output;
 
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::Write as _,
+};
 
 use assert_cmd::prelude::CommandCargoExt;
 pub use jsonrpc::Server;
 use lsp_types::{
     notification::{Notification, PublishDiagnostics},
     request::{
-        Completion, DocumentDiagnosticRequest, DocumentSymbolRequest, ExecuteCommand, Formatting,
-        GotoDefinition, HoverRequest, References, Rename, Request as LspRequest,
+        CodeActionRequest, CodeActionResolveRequest, CodeLensRequest, Completion,
+        DocumentDiagnosticRequest, DocumentHighlightRequest, DocumentLinkRequest,
+        DocumentSymbolRequest, ExecuteCommand, FoldingRangeRequest, Formatting, GotoDefinition,
+        HoverRequest, InlayHintRequest, References, Rename, Request as LspRequest,
+        SelectionRangeRequest, SemanticTokensFullRequest, SemanticTokensRangeRequest,
+        SignatureHelpRequest, WorkspaceSymbolRequest,
     },
-    CompletionParams, DocumentDiagnosticParams, DocumentFormattingParams, DocumentSymbolParams,
-    ExecuteCommandParams, GotoDefinitionParams, HoverParams, PublishDiagnosticsParams,
-    ReferenceParams, RenameParams, TextDocumentIdentifier, Url,
+    CodeActionOrCommand, CodeActionParams, CodeLensParams, CompletionParams,
+    DocumentDiagnosticParams, DocumentFormattingParams, DocumentHighlightParams,
+    DocumentLinkParams, DocumentSymbolParams, ExecuteCommandParams, FoldingRangeParams,
+    GotoDefinitionParams, HoverParams, InlayHintParams, PublishDiagnosticsParams, Range,
+    ReferenceParams, RenameParams, SelectionRangeParams, SemanticToken, SemanticTokensParams,
+    SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult,
+    SemanticTokensServerCapabilities, SignatureHelpParams, TextDocumentIdentifier, Url,
+    WorkspaceFolder, WorkspaceSymbolParams,
 };
 pub use output::LspDebug;
 use serde::Deserialize;
@@ -48,6 +60,18 @@ pub enum Request {
     Hover(HoverParams),
     Rename(RenameParams),
     Symbols(DocumentSymbolParams),
+    SemanticTokens(SemanticTokensParams),
+    SemanticTokensRange(SemanticTokensRangeParams),
+    InlayHint(InlayHintParams),
+    SignatureHelp(SignatureHelpParams),
+    CodeAction(CodeActionParams),
+    WorkspaceSymbol(WorkspaceSymbolParams),
+    DocumentLink(DocumentLinkParams),
+    CodeLens(CodeLensParams),
+    ExecuteCommand(ExecuteCommandParams),
+    FoldingRange(FoldingRangeParams),
+    SelectionRange(SelectionRangeParams),
+    DocumentHighlight(DocumentHighlightParams),
 }
 
 #[derive(Deserialize, Debug, Default)]
@@ -129,6 +153,50 @@ fn modify_request_uri(req: &mut Request) {
         Request::Symbols(params) => {
             params.text_document.uri = file_url(&params.text_document.uri);
         }
+        Request::SemanticTokens(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::SemanticTokensRange(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::InlayHint(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::SignatureHelp(params) => {
+            params.text_document_position_params.text_document.uri =
+                file_url(&params.text_document_position_params.text_document.uri);
+        }
+        Request::CodeAction(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::WorkspaceSymbol(_) => {}
+        Request::DocumentLink(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::CodeLens(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::FoldingRange(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::SelectionRange(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::DocumentHighlight(params) => {
+            params.text_document_position_params.text_document.uri =
+                file_url(&params.text_document_position_params.text_document.uri);
+        }
+        Request::ExecuteCommand(params) => {
+            // By convention, the document is the first argument of our commands.
+            if let Some(uri) = params
+                .arguments
+                .first_mut()
+                .and_then(|arg| arg.get_mut("uri"))
+            {
+                let url = file_url(&Url::parse(uri.as_str().unwrap()).unwrap());
+                *uri = serde_json::Value::String(url.to_string());
+            }
+        }
     }
 }
 
@@ -211,6 +279,26 @@ impl TestHarness {
         }
     }
 
+    /// Creates a new test harness with background evaluation disabled, whose workspace is the
+    /// given directory.
+    pub fn new_with_workspace(root: Url) -> Self {
+        let cmd = std::process::Command::cargo_bin("nls").unwrap();
+        let options = serde_json::json!({
+                "eval_config": {
+                    "disable": true,
+                },
+        });
+        let folder = WorkspaceFolder {
+            uri: root,
+            name: "workspace".to_owned(),
+        };
+        let srv = Server::new_with_workspace(cmd, Some(options), Some(vec![folder])).unwrap();
+        Self {
+            srv,
+            out: Vec::new(),
+        }
+    }
+
     /// Creates a new test harness with background evaluation disabled.
     ///
     /// Background evaluation is annoying for tests because it has some timing-sensitive parts.
@@ -233,6 +321,13 @@ impl TestHarness {
     }
 
     // Send a request to the language server and immediately cancel it.
+    /// Sends a request and returns the response without checking it for errors.
+    pub fn request_raw<T: LspRequest>(&mut self, params: T::Params) -> jsonrpc::Response {
+        self.srv
+            .send_request_with_options::<T>(params, false)
+            .unwrap()
+    }
+
     pub fn request_and_cancel<T: LspRequest>(&mut self, params: T::Params) -> jsonrpc::Response {
         self.srv
             .send_request_with_options::<T>(params, true)
@@ -248,6 +343,95 @@ impl TestHarness {
             Request::References(r) => self.request::<References>(r),
             Request::Rename(r) => self.request::<Rename>(r),
             Request::Symbols(s) => self.request::<DocumentSymbolRequest>(s),
+            Request::SemanticTokens(s) => {
+                let tokens = match self
+                    .srv
+                    .send_request::<SemanticTokensFullRequest>(s)
+                    .unwrap()
+                {
+                    Some(SemanticTokensResult::Tokens(tokens)) => tokens.data,
+                    Some(SemanticTokensResult::Partial(partial)) => partial.data,
+                    None => Vec::new(),
+                };
+                self.semantic_tokens(&tokens);
+            }
+            Request::SemanticTokensRange(s) => {
+                let tokens = match self
+                    .srv
+                    .send_request::<SemanticTokensRangeRequest>(s)
+                    .unwrap()
+                {
+                    Some(SemanticTokensRangeResult::Tokens(tokens)) => tokens.data,
+                    Some(SemanticTokensRangeResult::Partial(partial)) => partial.data,
+                    None => Vec::new(),
+                };
+                self.semantic_tokens(&tokens);
+            }
+            Request::InlayHint(h) => self.request::<InlayHintRequest>(h),
+            Request::SignatureHelp(s) => self.request::<SignatureHelpRequest>(s),
+            Request::CodeAction(a) => {
+                let mut actions = self.srv.send_request::<CodeActionRequest>(a).unwrap();
+                // Like clients do, resolve the actions that come without an edit.
+                for action in actions.iter_mut().flatten() {
+                    match action {
+                        CodeActionOrCommand::CodeAction(action)
+                            if action.edit.is_none() && action.data.is_some() =>
+                        {
+                            *action = self
+                                .srv
+                                .send_request::<CodeActionResolveRequest>(action.clone())
+                                .unwrap();
+                        }
+                        _ => {}
+                    }
+                }
+                actions.debug(&mut self.out).unwrap();
+                self.out.push(b'\n');
+            }
+            Request::WorkspaceSymbol(s) => self.request::<WorkspaceSymbolRequest>(s),
+            Request::DocumentLink(l) => self.request::<DocumentLinkRequest>(l),
+            Request::CodeLens(l) => self.request::<CodeLensRequest>(l),
+            Request::ExecuteCommand(c) => self.request::<ExecuteCommand>(c),
+            Request::FoldingRange(f) => self.request::<FoldingRangeRequest>(f),
+            Request::SelectionRange(s) => self.request::<SelectionRangeRequest>(s),
+            Request::DocumentHighlight(h) => self.request::<DocumentHighlightRequest>(h),
+        }
+    }
+
+    /// Decodes semantic tokens using the legend advertised by the server, and prints one token per
+    /// line with its absolute position.
+    fn semantic_tokens(&mut self, tokens: &[SemanticToken]) {
+        let Some(SemanticTokensServerCapabilities::SemanticTokensOptions(options)) =
+            &self.srv.capabilities.semantic_tokens_provider
+        else {
+            panic!("the server doesn't support semantic tokens");
+        };
+        let legend = &options.legend;
+
+        let mut line = 0;
+        let mut start = 0;
+        for token in tokens {
+            if token.delta_line > 0 {
+                start = 0;
+            }
+            line += token.delta_line;
+            start += token.delta_start;
+
+            let modifiers: Vec<_> = legend
+                .token_modifiers
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| token.token_modifiers_bitset & (1 << i) != 0)
+                .map(|(_, modifier)| modifier.as_str())
+                .collect();
+
+            writeln!(
+                self.out,
+                "{line}:{start}-{}: {} {modifiers:?}",
+                start + token.length,
+                legend.token_types[token.token_type as usize].as_str(),
+            )
+            .unwrap();
         }
     }
 
@@ -304,6 +488,10 @@ impl TestHarness {
         self.srv.send_file(uri.clone(), contents).unwrap();
     }
 
+    pub fn edit_file(&mut self, uri: Url, version: i32, range: Range, text: &str) {
+        self.srv.edit_file(uri, version, range, text).unwrap();
+    }
+
     // Waits (until forever, if necessary) for the first diagnostics, and then
     // returns them.
     pub fn wait_for_diagnostics(&mut self) -> PublishDiagnosticsParams {
@@ -319,6 +507,10 @@ impl TestHarness {
         }
     }
 
+    pub fn send_notification<T: Notification>(&mut self, params: T::Params) {
+        self.srv.send_notification::<T>(params).unwrap();
+    }
+
     /// Sends a request to pause the language server and returns without waiting for a response.
     /// This can be used to test queuing and cancellation behavior.
     pub fn pause_language_server(&mut self) {
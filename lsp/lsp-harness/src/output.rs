@@ -153,6 +153,167 @@ impl LspDebug for lsp_types::TextEdit {
     }
 }
 
+impl LspDebug for lsp_types::CodeActionOrCommand {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        match self {
+            lsp_types::CodeActionOrCommand::Command(cmd) => write!(w, "command {}", cmd.title),
+            lsp_types::CodeActionOrCommand::CodeAction(action) => {
+                write!(w, "{}", action.title)?;
+                if let Some(disabled) = &action.disabled {
+                    write!(w, " (disabled: {})", disabled.reason)?;
+                }
+                if let Some(edit) = &action.edit {
+                    write!(w, " ")?;
+                    edit.debug(w)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl LspDebug for lsp_types::WorkspaceSymbolResponse {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        let mut symbols: Vec<_> = match self {
+            lsp_types::WorkspaceSymbolResponse::Flat(symbols) => symbols
+                .iter()
+                .map(|s| (s.name.clone(), s.container_name.clone(), s.location.clone()))
+                .collect(),
+            lsp_types::WorkspaceSymbolResponse::Nested(symbols) => symbols
+                .iter()
+                .filter_map(|s| match &s.location {
+                    lsp_types::OneOf::Left(loc) => {
+                        Some((s.name.clone(), s.container_name.clone(), loc.clone()))
+                    }
+                    lsp_types::OneOf::Right(_) => None,
+                })
+                .collect(),
+        };
+        symbols.sort_by_key(|(_, _, loc)| (loc.uri.clone(), loc.range.start));
+
+        for (name, container, loc) in symbols {
+            let name = match container {
+                Some(container) => format!("{container}.{name}"),
+                None => name,
+            };
+            writeln!(w, "{name} {}", loc.debug_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl LspDebug for lsp_types::DocumentLink {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{} -> ", self.range.debug_str())?;
+        match &self.target {
+            Some(target) => write!(w, "{target}"),
+            None => write!(w, "None"),
+        }
+    }
+}
+
+impl LspDebug for lsp_types::CodeLens {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{}", self.range.debug_str())?;
+        if let Some(command) = &self.command {
+            write!(w, " {} ({}", command.title, command.command)?;
+            for arg in command.arguments.iter().flatten() {
+                write!(w, " {arg}")?;
+            }
+            write!(w, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl LspDebug for lsp_types::FoldingRange {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{}-{}", self.start_line, self.end_line)?;
+        if let Some(kind) = &self.kind {
+            write!(w, " {kind:?}")?;
+        }
+        Ok(())
+    }
+}
+
+impl LspDebug for lsp_types::SelectionRange {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{}", self.range.debug_str())?;
+        if let Some(parent) = &self.parent {
+            write!(w, " < ")?;
+            parent.debug(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl LspDebug for lsp_types::DocumentHighlight {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{}", self.range.debug_str())?;
+        if let Some(kind) = self.kind {
+            let kind = if kind == lsp_types::DocumentHighlightKind::WRITE {
+                "write"
+            } else if kind == lsp_types::DocumentHighlightKind::READ {
+                "read"
+            } else {
+                "text"
+            };
+            write!(w, " {kind}")?;
+        }
+        Ok(())
+    }
+}
+
+impl LspDebug for lsp_types::InlayHint {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        let label = match &self.label {
+            lsp_types::InlayHintLabel::String(s) => s.clone(),
+            lsp_types::InlayHintLabel::LabelParts(parts) => {
+                parts.iter().map(|part| part.value.as_str()).collect()
+            }
+        };
+        write!(
+            w,
+            "{}:{}: {label}",
+            self.position.line, self.position.character
+        )
+    }
+}
+
+impl LspDebug for lsp_types::SignatureHelp {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        for sig in &self.signatures {
+            write!(w, "{}", sig.label)?;
+
+            let active = sig.active_parameter.or(self.active_parameter);
+            let param = active.and_then(|idx| sig.parameters.as_ref()?.get(idx as usize));
+            if let Some(param) = param {
+                let label = match &param.label {
+                    lsp_types::ParameterLabel::Simple(s) => s.clone(),
+                    lsp_types::ParameterLabel::LabelOffsets([start, end]) => {
+                        // The offsets are in UTF-16 code units.
+                        let utf16: Vec<u16> = sig.label.encode_utf16().collect();
+                        String::from_utf16_lossy(&utf16[*start as usize..*end as usize])
+                    }
+                };
+                write!(w, " [{label}]")?;
+            }
+
+            if let Some(doc) = &sig.documentation {
+                let doc = match doc {
+                    lsp_types::Documentation::String(s) => s,
+                    lsp_types::Documentation::MarkupContent(lsp_types::MarkupContent {
+                        value,
+                        ..
+                    }) => value,
+                };
+                write!(w, "\n{doc}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl LspDebug for lsp_types::Hover {
     fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
         write!(w, "<{}>", self.range.debug_str())?;
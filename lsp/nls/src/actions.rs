@@ -1,7 +1,35 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+};
+
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{CodeActionOrCommand, CodeActionParams};
+use lsp_types::{
+    CodeAction, CodeActionDisabled, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    Diagnostic, Position, Range, TextEdit, Url, WorkspaceEdit,
+};
+use nickel_lang_core::{
+    ast::{Ast, Node, primop::PrimOp, typ::Type},
+    cache::{CacheHub, InputFormat},
+    error::suggest::find_best_match,
+    files::FileId,
+    identifier::Ident,
+    position::RawSpan,
+    pretty::ident_quoted,
+    traverse::{TraverseAlloc, TraverseControl},
+    typ::TypeF,
+    typecheck::TypecheckMode,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::server::Server;
+use crate::{
+    codespan_lsp::{byte_span_to_range, position_to_byte_index},
+    diagnostic::FixData,
+    field_walker::FieldResolver,
+    files::uri_to_path,
+    server::Server,
+    world::World,
+};
 
 pub fn handle_code_action(
     params: CodeActionParams,
@@ -9,15 +37,578 @@ pub fn handle_code_action(
     server: &mut Server,
 ) -> Result<(), ResponseError> {
     let mut actions = Vec::new();
+    let uri = &params.text_document.uri;
 
-    if server.world.file_id(&params.text_document.uri)?.is_some() {
+    if let Some(file_id) = server.world.file_id(uri)? {
         actions.push(CodeActionOrCommand::Command(lsp_types::Command {
             title: "evaluate term".to_owned(),
             command: "eval".to_owned(),
             arguments: Some(vec![serde_json::to_value(&params.text_document).unwrap()]),
         }));
+
+        // Clients send the diagnostics overlapping the requested range, but we also look at the
+        // ones we published last, in case the client didn't.
+        let mut diagnostics = params.context.diagnostics.clone();
+        let published = server.last_diagnostics.get(uri).into_iter().flatten();
+        for diag in published {
+            if overlaps(&diag.range, &params.range) && !diagnostics.contains(diag) {
+                diagnostics.push(diag.clone());
+            }
+        }
+
+        let fixer = QuickFixer {
+            world: &server.world,
+            file_id,
+            uri,
+        };
+
+        // All the diagnostics of an error carry its fix data, so different diagnostics can lead
+        // to the same fix.
+        let mut fixes: Vec<CodeAction> = Vec::new();
+        for fix in diagnostics.iter().flat_map(|diag| fixer.fixes(diag)) {
+            if !fixes
+                .iter()
+                .any(|other| other.title == fix.title && other.edit == fix.edit)
+            {
+                fixes.push(fix);
+            }
+        }
+        actions.extend(fixes.into_iter().map(CodeActionOrCommand::CodeAction));
+
+        actions.extend(
+            fixer
+                .static_annotation(&params.range)
+                .map(CodeActionOrCommand::CodeAction),
+        );
     }
 
     server.reply(Response::new_ok(req, Some(actions)));
     Ok(())
 }
+
+pub fn handle_resolve_code_action(
+    mut action: CodeAction,
+    req: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let data = action
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value::<StaticAnnotation>(data).ok());
+
+    if let Some(StaticAnnotation { uri, bar }) = data
+        && let Some(file_id) = server.world.file_id(&uri)?
+    {
+        let fixer = QuickFixer {
+            world: &server.world,
+            file_id,
+            uri: &uri,
+        };
+
+        match fixer.resolve_static_annotation(bar) {
+            Some(edit) => action.edit = Some(edit),
+            None => {
+                action.disabled = Some(CodeActionDisabled {
+                    reason: "the term doesn't typecheck with a static type annotation".to_owned(),
+                })
+            }
+        }
+    }
+
+    server.reply(Response::new_ok(req, action));
+    Ok(())
+}
+
+/// The data of a static annotation action, which is sent back by the client to resolve it.
+#[derive(Serialize, Deserialize)]
+struct StaticAnnotation {
+    uri: Url,
+    /// The range of the `|` to replace.
+    bar: Range,
+}
+
+fn overlaps(left: &Range, right: &Range) -> bool {
+    left.start <= right.end && right.start <= left.end
+}
+
+/// Returns a value of the given type to use as the definition of a new field, or `null` if we
+/// can't come up with a better one.
+fn placeholder(typ: Option<&Type<'_>>) -> &'static str {
+    match typ.map(|typ| &typ.typ) {
+        Some(TypeF::Number) => "0",
+        Some(TypeF::String) => "\"\"",
+        Some(TypeF::Bool) => "false",
+        Some(TypeF::Array(_)) => "[]",
+        Some(TypeF::Record(_) | TypeF::Dict { .. }) => "{}",
+        _ => "null",
+    }
+}
+
+/// Returns `true` if `source` is a variable or a chain of field accesses, which don't need to be
+/// parenthesized when passed as an argument.
+fn is_path(source: &str) -> bool {
+    source
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '\'' | '.'))
+}
+
+fn quick_fix(title: String, uri: &Url, edit: TextEdit, diag: &Diagnostic) -> CodeAction {
+    CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diag.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Computes the code actions fixing the diagnostics of a file.
+struct QuickFixer<'a> {
+    world: &'a World,
+    file_id: FileId,
+    uri: &'a Url,
+}
+
+impl<'a> QuickFixer<'a> {
+    fn source(&self) -> &'a str {
+        self.world.sources.files().source(self.file_id)
+    }
+
+    fn range(&self, span: RawSpan) -> Option<Range> {
+        byte_span_to_range(self.world.sources.files(), span.src_id, span.to_range()).ok()
+    }
+
+    fn span(&self, range: &Range) -> Option<RawSpan> {
+        let files = self.world.sources.files();
+        let start = position_to_byte_index(files, self.file_id, &range.start).ok()?;
+        let end = position_to_byte_index(files, self.file_id, &range.end).ok()?;
+
+        Some(RawSpan {
+            src_id: self.file_id,
+            start: (start as u32).into(),
+            end: (end as u32).into(),
+        })
+    }
+
+    /// Finds the AST node of the current file spanning exactly `range`, and satisfying `pred`.
+    fn ast_at(&self, range: &Range, pred: impl Fn(&Ast<'a>) -> bool) -> Option<&'a Ast<'a>> {
+        let span = self.span(range)?;
+        let analysis = self.world.file_analysis(self.file_id).ok()?;
+
+        analysis.ast().traverse_ref(
+            &mut |ast: &'a Ast<'a>, _: &()| {
+                if ast.pos.into_opt() == Some(span) && pred(ast) {
+                    TraverseControl::Return(ast)
+                } else {
+                    TraverseControl::Continue
+                }
+            },
+            &(),
+        )
+    }
+
+    fn fixes(&self, diag: &Diagnostic) -> Vec<CodeAction> {
+        let Some(data) = diag
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value(data).ok())
+        else {
+            return Vec::new();
+        };
+
+        match data {
+            FixData::MissingField { name, suggestion } => self
+                .missing_field(diag, &name, suggestion)
+                .into_iter()
+                .collect(),
+            FixData::UnboundIdentifier { name } => {
+                let mut fixes: Vec<_> = self
+                    .unbound_identifier_typo(diag, &name)
+                    .into_iter()
+                    .collect();
+                fixes.extend(self.import_sibling(diag, &name));
+                fixes
+            }
+            FixData::ExtraField { name } => {
+                self.extra_field_typo(diag, &name).into_iter().collect()
+            }
+            FixData::NakedFunctionContract { func } => {
+                self.wrap_naked_contract(diag, func.0).into_iter().collect()
+            }
+        }
+    }
+
+    /// Wraps a function used directly as a contract in `std.contract.from_validator`.
+    fn wrap_naked_contract(&self, diag: &Diagnostic, func: Range) -> Option<CodeAction> {
+        let span = self.span(&func)?;
+        let source = self.source().get(span.to_range())?;
+        let new_text = if is_path(source) {
+            format!("std.contract.from_validator {source}")
+        } else {
+            format!("std.contract.from_validator ({source})")
+        };
+
+        Some(quick_fix(
+            "Wrap in `std.contract.from_validator`".to_owned(),
+            self.uri,
+            TextEdit {
+                range: func,
+                new_text,
+            },
+            diag,
+        ))
+    }
+
+    /// Fixes a missing field, either by defining it in the record that lacks it or, if the field
+    /// is accessed, by replacing it with the closest existing field.
+    fn missing_field(
+        &self,
+        diag: &Diagnostic,
+        name: &str,
+        suggestion: Option<String>,
+    ) -> Option<CodeAction> {
+        let ast = self.ast_at(&diag.range, |ast| {
+            matches!(
+                &ast.node,
+                Node::Record(_)
+                    | Node::PrimOpApp {
+                        op: PrimOp::RecordStatAccess(_),
+                        ..
+                    }
+            )
+        })?;
+
+        match &ast.node {
+            Node::PrimOpApp {
+                op: PrimOp::RecordStatAccess(id),
+                args: [record],
+            } if id.label() == name => {
+                let resolver = FieldResolver::new(self.world);
+                let fields: Vec<_> = resolver
+                    .resolve_record(record)
+                    .iter()
+                    .flat_map(|record| record.completion_items())
+                    .map(|item| item.label)
+                    .collect();
+                let best = suggestion
+                    .or_else(|| find_best_match(&fields, &name).map(ToOwned::to_owned))?;
+
+                Some(quick_fix(
+                    format!("Replace with `{best}`"),
+                    self.uri,
+                    TextEdit {
+                        range: self.range(id.pos.into_opt()?)?,
+                        new_text: best,
+                    },
+                    diag,
+                ))
+            }
+            Node::Record(_) => self.add_field(diag, ast, name),
+            _ => None,
+        }
+    }
+
+    /// Adds a definition for a field required by a type or a contract to a record literal.
+    fn add_field(&self, diag: &Diagnostic, record: &'a Ast<'a>, name: &str) -> Option<CodeAction> {
+        let span = record.pos.into_opt()?;
+        let text = self.source().get(span.to_range())?;
+        let body = text.strip_prefix('{')?.strip_suffix('}')?;
+        let content = body.trim_end();
+
+        let field = format!(
+            "{} = {}",
+            ident_quoted(Ident::new(name)),
+            placeholder(self.required_type(record, name).as_ref()),
+        );
+
+        let (offset, new_text) = if content.trim().is_empty() {
+            (1, format!(" {field} "))
+        } else {
+            let sep = if content.ends_with(',') { "" } else { "," };
+
+            if body.contains('\n') {
+                let last_line = content.lines().next_back().unwrap_or_default();
+                let indent = &last_line[..last_line.len() - last_line.trim_start().len()];
+                let trailing = if content.ends_with(',') { "," } else { "" };
+                (
+                    1 + content.len(),
+                    format!("{sep}\n{indent}{field}{trailing}"),
+                )
+            } else {
+                (1 + content.len(), format!("{sep} {field}"))
+            }
+        };
+
+        let at = span.start.to_usize() + offset;
+        let position = self.range(RawSpan {
+            src_id: self.file_id,
+            start: (at as u32).into(),
+            end: (at as u32).into(),
+        })?;
+
+        Some(quick_fix(
+            format!("Add the missing field `{name}`"),
+            self.uri,
+            TextEdit {
+                range: position,
+                new_text,
+            },
+            diag,
+        ))
+    }
+
+    /// Returns the type that the annotations of a record literal require for one of its fields.
+    fn required_type(&self, record: &'a Ast<'a>, name: &str) -> Option<Type<'a>> {
+        let mut ancestors = self.world.analysis_reg.get_parent_chain(record)?;
+        let Node::Annotated { annot, .. } = &ancestors.next()?.node else {
+            return None;
+        };
+
+        let resolver = FieldResolver::new(self.world);
+        let label = ident_quoted(Ident::new(name));
+
+        annot
+            .typ
+            .iter()
+            .chain(annot.contracts)
+            .flat_map(|typ| resolver.resolve_type_record(typ))
+            .flat_map(|record| record.completion_items())
+            .filter(|item| item.label == label)
+            .flat_map(|item| item.metadata)
+            .find_map(|metadata| {
+                let annot = &metadata.annotation;
+                annot.typ.iter().chain(annot.contracts).next().cloned()
+            })
+    }
+
+    /// Replaces an unbound identifier by the closest variable in scope.
+    fn unbound_identifier_typo(&self, diag: &Diagnostic, name: &str) -> Option<CodeAction> {
+        let var = self.ast_at(&diag.range, |ast| matches!(ast.node, Node::Var(_)))?;
+        let env = self.world.analysis_reg.get_env(var)?;
+        let names: Vec<_> = env
+            .iter_elems()
+            .map(|(id, _)| id.label())
+            .filter(|label| *label != name)
+            .collect();
+        let best = find_best_match(&names, &name)?;
+
+        Some(quick_fix(
+            format!("Replace with `{best}`"),
+            self.uri,
+            TextEdit {
+                range: diag.range,
+                new_text: best.to_owned(),
+            },
+            diag,
+        ))
+    }
+
+    /// Imports a file of the same directory whose name is the unbound identifier, such as
+    /// `foo.ncl` for `foo`.
+    fn import_sibling(&self, diag: &Diagnostic, name: &str) -> Vec<CodeAction> {
+        let Ok(path) = uri_to_path(self.uri) else {
+            return Vec::new();
+        };
+        let Some(dir) = path.parent() else {
+            return Vec::new();
+        };
+
+        let on_disk = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()));
+        let open = self
+            .world
+            .file_uris
+            .values()
+            .filter_map(|uri| uri_to_path(uri).ok());
+
+        let siblings: BTreeSet<PathBuf> = on_disk
+            .chain(open)
+            .filter(|candidate| {
+                candidate != &path
+                    && candidate.parent() == Some(dir)
+                    && candidate.file_stem().is_some_and(|stem| stem == name)
+                    && InputFormat::from_path(candidate).is_some()
+            })
+            .collect();
+
+        siblings
+            .iter()
+            .filter_map(|sibling| {
+                let file_name = sibling.file_name()?.to_str()?;
+                Some(quick_fix(
+                    format!("Import `{file_name}`"),
+                    self.uri,
+                    TextEdit {
+                        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                        new_text: format!("let {name} = import \"{file_name}\" in\n"),
+                    },
+                    diag,
+                ))
+            })
+            .collect()
+    }
+
+    /// Renames a field which isn't allowed by a record contract to the closest field of the
+    /// contract which isn't defined yet.
+    fn extra_field_typo(&self, diag: &Diagnostic, name: &str) -> Option<CodeAction> {
+        let record = self.ast_at(&diag.range, |ast| matches!(ast.node, Node::Record(_)))?;
+        let Node::Record(data) = &record.node else {
+            return None;
+        };
+
+        let id = data
+            .field_defs
+            .iter()
+            .filter_map(|def| def.path.first()?.try_as_ident())
+            .find(|id| id.label() == name)?;
+        let defined: Vec<_> = data
+            .field_defs
+            .iter()
+            .filter_map(|def| def.path.first()?.try_as_ident())
+            .map(|id| ident_quoted(id.ident()))
+            .collect();
+
+        let mut ancestors = self.world.analysis_reg.get_parent_chain(record)?;
+        let Node::Annotated { annot, .. } = &ancestors.next()?.node else {
+            return None;
+        };
+
+        let resolver = FieldResolver::new(self.world);
+        let expected: Vec<_> = annot
+            .typ
+            .iter()
+            .chain(annot.contracts)
+            .flat_map(|typ| resolver.resolve_type_record(typ))
+            .flat_map(|record| record.completion_items())
+            .map(|item| item.label)
+            .filter(|label| !defined.contains(label))
+            .collect();
+        let best = find_best_match(&expected, &name)?;
+
+        Some(quick_fix(
+            format!("Rename to `{best}`"),
+            self.uri,
+            TextEdit {
+                range: self.range(id.pos.into_opt()?)?,
+                new_text: best.to_owned(),
+            },
+            diag,
+        ))
+    }
+
+    /// Converts the contract annotation `| Type` under the cursor into a static type annotation
+    /// `: Type`. The edit is only computed by [Self::resolve_static_annotation], since the
+    /// annotated term (and thus the whole file) must still typecheck afterwards.
+    fn static_annotation(&self, range: &Range) -> Option<CodeAction> {
+        let cursor = self.span(range)?.start;
+        let analysis = self.world.file_analysis(self.file_id).ok()?;
+        let mut candidate: Option<&Type<'_>> = None;
+
+        let mut consider = |contracts: &'a [Type<'a>]| {
+            for contract in contracts {
+                if contract.pos.into_opt().is_some_and(|span| {
+                    span.src_id == self.file_id && span.start <= cursor && cursor <= span.end
+                }) {
+                    candidate = Some(contract);
+                }
+            }
+        };
+
+        analysis.ast().traverse_ref(
+            &mut |ast: &'a Ast<'a>, _: &()| {
+                match &ast.node {
+                    Node::Annotated { annot, .. } if annot.typ.is_none() => {
+                        consider(annot.contracts)
+                    }
+                    Node::Let { bindings, .. } => {
+                        for binding in bindings.iter() {
+                            if binding.metadata.annotation.typ.is_none() {
+                                consider(binding.metadata.annotation.contracts);
+                            }
+                        }
+                    }
+                    Node::Record(data) => {
+                        for def in data.field_defs.iter() {
+                            if def.metadata.annotation.typ.is_none() {
+                                consider(def.metadata.annotation.contracts);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                TraverseControl::<(), ()>::Continue
+            },
+            &(),
+        );
+
+        let contract_start = candidate?.pos.into_opt()?.start.to_usize();
+        let bar = self.source()[..contract_start].trim_end();
+        let bar = bar.strip_suffix('|').map(str::len)?;
+        let bar = self.range(RawSpan {
+            src_id: self.file_id,
+            start: (bar as u32).into(),
+            end: (bar as u32 + 1).into(),
+        })?;
+
+        // Checking that the file still typechecks is costly, so we only do it once the client
+        // resolves the action, which usually happens when the user picks it.
+        Some(CodeAction {
+            title: "Convert to a static type annotation".to_owned(),
+            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+            data: Some(
+                serde_json::to_value(StaticAnnotation {
+                    uri: self.uri.clone(),
+                    bar,
+                })
+                .ok()?,
+            ),
+            ..Default::default()
+        })
+    }
+
+    /// Computes the edit of a static annotation action returned by [Self::static_annotation], or
+    /// returns `None` if the file doesn't typecheck with a static annotation.
+    fn resolve_static_annotation(&self, bar: Range) -> Option<WorkspaceEdit> {
+        let span = self.span(&bar)?.to_range();
+        let mut contents = self.source().to_owned();
+        if contents.get(span.clone())? != "|" {
+            return None;
+        }
+        contents.replace_range(span, ":");
+
+        if !self.typechecks_with(contents) {
+            return None;
+        }
+
+        Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                self.uri.clone(),
+                vec![TextEdit {
+                    range: bar,
+                    new_text: ":".to_owned(),
+                }],
+            )])),
+            ..Default::default()
+        })
+    }
+    /// Checks if the current file typechecks once its contents are replaced by `contents`. This is
+    /// done in a fresh cache so that the state of the world isn't affected.
+    fn typechecks_with(&self, contents: String) -> bool {
+        let Some(path) = self.world.sources.file_paths.get(&self.file_id) else {
+            return false;
+        };
+
+        let mut cache = CacheHub::new();
+        cache.sources = self.world.sources.clone();
+        let file_id = cache.replace_string(path.clone(), contents);
+
+        cache.load_stdlib().is_ok()
+            && cache.parse_to_ast(file_id).is_ok()
+            && cache.typecheck(file_id, TypecheckMode::Walk).is_ok()
+    }
+}
@@ -39,7 +39,7 @@ use crate::{
     position::PositionLookup,
     term::AstPtr,
     usage::{Environment, UsageLookup},
-    world::{ImportTargets, StdlibResolver, WorldImportResolver},
+    world::{ImportTargets, PackageMaps, ProjectSettingsMap, StdlibResolver, WorldImportResolver},
 };
 
 /// The parent of an AST node.
@@ -708,6 +708,8 @@ impl<'std> PackedAnalysis<'std> {
         import_data: &'a mut ImportData,
         import_targets: &'a mut ImportTargets,
         file_uris: &'a mut HashMap<FileId, Url>,
+        package_maps: &'a PackageMaps,
+        project_settings: &'a ProjectSettingsMap,
         reg: AnalysisRegistryRef<'a, 'std>,
     ) -> (Vec<AnalysisTarget<'std>>, Result<(), Vec<TypecheckError>>) {
         self.with_mut(move |slf| {
@@ -729,6 +731,8 @@ impl<'std> PackedAnalysis<'std> {
                 import_data,
                 import_targets,
                 file_uris,
+                package_maps,
+                project_settings,
             };
 
             let typecheck_result = typecheck_visit(
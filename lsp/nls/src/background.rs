@@ -10,10 +10,10 @@ use crossbeam::channel::{Receiver, RecvTimeoutError, Sender, bounded};
 use log::warn;
 use lsp_types::Url;
 use nickel_lang_core::cache::{InputFormat, SourcePath};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
 use crate::{
-    config::{self, LspConfig},
+    config::{self, LspConfig, ProjectSettings, SETTINGS_FILE_NAME},
     diagnostic::SerializableDiagnostic,
     files::uri_to_path,
     world::World,
@@ -31,6 +31,9 @@ struct Eval {
     eval: Url,
     /// Mark the evaluation as high priority.
     high_priority: bool,
+    /// The import paths of the file to evaluate, including the ones from the settings of its
+    /// project.
+    import_paths: Vec<PathBuf>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,6 +45,7 @@ pub struct Diagnostics {
 pub struct BackgroundJobs {
     receiver: Option<Receiver<Diagnostics>>,
     sender: Sender<Eval>,
+    config: config::LspEvalConfig,
 }
 
 fn run_with_timeout<T: Send + 'static, F: FnOnce() -> T + Send + 'static>(
@@ -56,15 +60,67 @@ fn run_with_timeout<T: Send + 'static, F: FnOnce() -> T + Send + 'static>(
     rx.recv_timeout(timeout)
 }
 
+/// Sends `input` to a new worker process, started with the command-line flag `flag`, and waits
+/// for its output, blocking until it completes or times out.
+///
+/// The current implementation uses a background process per invocation, which is not the most
+/// efficient thing but it allows for cancellation and prevents memory leaks. It also keeps the
+/// server alive if the evaluation crashes, for example by overflowing the stack.
+pub fn run_worker<I: Serialize, O: DeserializeOwned + Send + 'static>(
+    flag: &str,
+    envs: &[(&str, String)],
+    input: &I,
+    timeout: Duration,
+) -> anyhow::Result<O> {
+    let path = std::env::current_exe()?;
+    let mut child = std::process::Command::new(path)
+        .envs(envs.iter().cloned())
+        .arg(flag)
+        .stdout(std::process::Stdio::piped())
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+
+    let tx = child.stdin.take();
+    let rx = child.stdout.take();
+
+    scopeguard::defer! {
+        // If we successfully deserialized the response, the child should be just about done anyway
+        // (and killing an already-finished process isn't an error).
+        // Otherwise, we might have timed out waiting for the child, so kill it to reclaim resources.
+        if child.kill().is_ok() {
+            // We should wait on the child process to avoid having zombies, but if the
+            // kill failed then we skip waiting because we don't actually want to block.
+            let _ = child.wait();
+        }
+    }
+
+    let mut tx = tx.ok_or_else(|| anyhow!("failed to get worker stdin"))?;
+    let mut rx = rx.ok_or_else(|| anyhow!("failed to get worker stdout"))?;
+
+    bincode::serde::encode_into_std_write(input, &mut tx, bincode::config::standard())?;
+
+    let result = run_with_timeout(
+        move || bincode::serde::decode_from_std_read(&mut rx, bincode::config::standard()),
+        timeout,
+    );
+
+    Ok(result??)
+}
+
 // The entry point of the background worker. This background worker
 // reads an `Eval` (in bincode) from stdin, performs the evaluation, and
 // writes a `Diagnostics` (in bincode) to stdout.
 pub fn worker_main() -> anyhow::Result<()> {
-    let mut world = World::new_without_contract_configs(LspConfig::default());
     let eval: Eval = bincode::serde::decode_from_std_read(
         &mut std::io::stdin().lock(),
         bincode::config::standard(),
     )?;
+    // This world has no workspace folders, so it doesn't look for project settings: they are
+    // already accounted for in the import paths.
+    let mut world = World::new_without_contract_configs(LspConfig {
+        import_paths: eval.import_paths,
+        ..LspConfig::default()
+    });
     for (uri, text) in eval.contents {
         world.add_file(uri, text.to_string())?;
     }
@@ -92,6 +148,88 @@ pub fn worker_main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The project settings of a directory, loaded by [SettingsLoader]. `settings` is `None` if the
+/// directory has no settings file or if it couldn't be loaded.
+#[derive(Debug)]
+pub struct LoadedSettings {
+    pub dir: PathBuf,
+    pub settings: Option<ProjectSettings>,
+}
+
+/// Loads project settings in the background.
+///
+/// Settings files are Nickel programs, which may take arbitrarily long to evaluate, so we evaluate
+/// them in worker processes with the same timeout as background evaluation, one at a time.
+pub struct SettingsLoader {
+    sender: Sender<PathBuf>,
+    receiver: Receiver<LoadedSettings>,
+}
+
+impl SettingsLoader {
+    pub fn new(timeout: Duration) -> Self {
+        let (dir_tx, dir_rx) = crossbeam::channel::unbounded::<PathBuf>();
+        let (settings_tx, settings_rx) = crossbeam::channel::unbounded();
+
+        std::thread::spawn(move || {
+            for dir in dir_rx {
+                let path = dir.join(SETTINGS_FILE_NAME);
+                let settings = if path.is_file() {
+                    run_worker::<_, Result<ProjectSettings, String>>(
+                        "--background-settings",
+                        &[],
+                        &path,
+                        timeout,
+                    )
+                    .map_err(|e| e.to_string())
+                    .and_then(|settings| settings)
+                    .inspect_err(|e| warn!("failed to read the settings {}: {e}", path.display()))
+                    .ok()
+                } else {
+                    None
+                };
+
+                // If the main process has exited, just exit quietly.
+                if settings_tx.send(LoadedSettings { dir, settings }).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            sender: dir_tx,
+            receiver: settings_rx,
+        }
+    }
+
+    /// Schedules the loading of the settings of `dir`.
+    pub fn load(&self, dir: PathBuf) {
+        let _ = self.sender.send(dir);
+    }
+
+    pub fn receiver(&self) -> &Receiver<LoadedSettings> {
+        &self.receiver
+    }
+}
+
+// The entry point of the settings worker, which reads the path of a settings file (in bincode)
+// from stdin and writes the settings, or the error that prevented reading them, to stdout.
+pub fn settings_worker_main() -> anyhow::Result<()> {
+    let path: PathBuf = bincode::serde::decode_from_std_read(
+        &mut std::io::stdin().lock(),
+        bincode::config::standard(),
+    )?;
+    let settings = ProjectSettings::from_file(&path).map_err(|e| e.to_string());
+
+    // If this fails, the main process has already exited. No need for a loud error in that case.
+    let _ = bincode::serde::encode_into_std_write(
+        settings,
+        &mut std::io::stdout().lock(),
+        bincode::config::standard(),
+    );
+
+    Ok(())
+}
+
 struct SupervisorState {
     eval_rx: Receiver<Eval>,
     response_tx: Sender<Diagnostics>,
@@ -126,46 +264,16 @@ impl SupervisorState {
     }
 
     // Evaluate the nickel file with the given uri, blocking until it completes or times out.
-    //
-    // The current implementation uses a background process per invocation, which is not the
-    // most efficient thing but it allows for cancellation and prevents memory leaks.
     fn eval(&self, eval: &Eval) -> anyhow::Result<Diagnostics> {
-        let path = std::env::current_exe()?;
-        let mut child = std::process::Command::new(path)
-            .env(
+        run_worker(
+            "--background-eval",
+            &[(
                 RECURSION_LIMIT_ENV_VAR_NAME,
                 self.config.eval_limits.recursion_limit.to_string(),
-            )
-            .arg("--background-eval")
-            .stdout(std::process::Stdio::piped())
-            .stdin(std::process::Stdio::piped())
-            .spawn()?;
-
-        let tx = child.stdin.take();
-        let rx = child.stdout.take();
-
-        scopeguard::defer! {
-            // If we successfully deserialized the response, the child should be just about done anyway
-            // (and killing an already-finished process isn't an error).
-            // Otherwise, we might have timed out waiting for the child, so kill it to reclaim resources.
-            if child.kill().is_ok() {
-                // We should wait on the child process to avoid having zombies, but if the
-                // kill failed then we skip waiting because we don't actually want to block.
-                let _ = child.wait();
-            }
-        }
-
-        let mut tx = tx.ok_or_else(|| anyhow!("failed to get worker stdin"))?;
-        let mut rx = rx.ok_or_else(|| anyhow!("failed to get worker stdout"))?;
-
-        bincode::serde::encode_into_std_write(eval, &mut tx, bincode::config::standard())?;
-
-        let result = run_with_timeout(
-            move || bincode::serde::decode_from_std_read(&mut rx, bincode::config::standard()),
+            )],
+            eval,
             self.config.eval_limits.timeout,
-        );
-
-        Ok(result??)
+        )
     }
 
     fn handle_eval(&mut self, eval: Eval) {
@@ -243,9 +351,10 @@ impl BackgroundJobs {
             Self {
                 sender: eval_tx,
                 receiver: None,
+                config,
             }
         } else {
-            match SupervisorState::new(eval_rx, diag_tx, config) {
+            match SupervisorState::new(eval_rx, diag_tx, config.clone()) {
                 Ok(mut sup) => {
                     std::thread::spawn(move || {
                         sup.run();
@@ -259,6 +368,7 @@ impl BackgroundJobs {
             Self {
                 sender: eval_tx,
                 receiver: Some(diag_rx),
+                config,
             }
         }
     }
@@ -283,11 +393,16 @@ impl BackgroundJobs {
     }
 
     fn eval_file_with_priority(&mut self, uri: Url, world: &World, high_priority: bool) {
+        let Ok(Some(file_id)) = world.file_id(&uri) else {
+            return;
+        };
+
         if let Some(contents) = self.contents(&uri, world) {
             let _ = self.sender.send(Eval {
                 eval: uri,
                 contents,
                 high_priority,
+                import_paths: world.import_paths_for(file_id),
             });
         };
     }
@@ -306,4 +421,8 @@ impl BackgroundJobs {
     pub fn receiver(&self) -> Option<&Receiver<Diagnostics>> {
         self.receiver.as_ref()
     }
+
+    pub fn config(&self) -> &config::LspEvalConfig {
+        &self.config
+    }
 }
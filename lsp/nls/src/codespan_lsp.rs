@@ -117,8 +117,11 @@ where
     let source = files.source(file_id)?;
     let source = source.as_ref();
 
-    let line_span = files.line_range(file_id, position.line as usize).unwrap();
-    let line_str = source.get(line_span.clone()).unwrap();
+    let line_span = files.line_range(file_id, position.line as usize)?;
+    let line_str = source.get(line_span.clone()).ok_or(Error::IndexTooLarge {
+        given: line_span.end,
+        max: source.len(),
+    })?;
 
     let byte_offset = character_to_line_offset(line_str, position.character)?;
 
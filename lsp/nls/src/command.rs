@@ -1,7 +1,91 @@
-use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{ExecuteCommandParams, TextDocumentIdentifier, Url};
+use std::ffi::OsString;
 
-use crate::{error::Error, server::Server};
+use anyhow::anyhow;
+use crossbeam::channel::RecvTimeoutError;
+use lsp_server::{Notification, RequestId, Response, ResponseError};
+use lsp_types::{
+    ExecuteCommandParams, MessageType, ShowMessageParams, TextDocumentIdentifier, Url,
+    notification::{Notification as _, ShowMessage},
+};
+use nickel_lang_core::{
+    doctest::{TestFailure, TestName, TestObserver, prepare_tests, run_tests},
+    error::{
+        IntoDiagnostics,
+        report::{ColorOpt, report_as_str},
+    },
+    eval::cache::CacheImpl,
+    identifier::LocIdent,
+    package::PackageMap,
+    program::{FieldPath, Program, ProgramBuilder},
+    serialize::{self, ExportFormat},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{background::run_worker, error::Error, server::Server};
+
+/// The commands supported by the server.
+pub const COMMANDS: &[&str] = &["eval", "export", "test"];
+
+/// The result of the `export` command: the exported field, to be shown in a new document.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportResult {
+    pub content: String,
+    /// The language identifier of the content, such as `json`.
+    pub language: String,
+}
+
+/// The outcome of a single doctest, as returned by the `test` command.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TestResult {
+    /// The name of the test, such as `foo.bar/0` for the first test of the field `foo.bar`.
+    pub name: String,
+    /// The reason of the failure, or `None` if the test passed.
+    pub failure: Option<String>,
+}
+
+/// The arguments of the `export` command.
+#[derive(Deserialize)]
+struct ExportArgs(TextDocumentIdentifier, Vec<String>, String);
+
+/// The arguments of the `test` command.
+#[derive(Deserialize)]
+struct TestArgs(TextDocumentIdentifier, Vec<String>);
+
+/// A command evaluating a document, which is run in a separate process by [worker_main].
+#[derive(Debug, Serialize, Deserialize)]
+enum Job {
+    Export { path: Vec<String>, format: String },
+    Test { path: Vec<String> },
+}
+
+/// What the command worker needs to know to run a [Job].
+#[derive(Debug, Serialize, Deserialize)]
+struct JobInput {
+    job: Job,
+    /// The current contents of the document.
+    source: String,
+    /// The name of the document, which is its path for documents on disk.
+    name: OsString,
+    import_paths: Vec<std::path::PathBuf>,
+    package_map: Option<PackageMap>,
+}
+
+/// The output of a [Job], or the report of the error that made it fail.
+#[derive(Debug, Serialize, Deserialize)]
+enum JobOutput {
+    Export(ExportResult),
+    Test(Vec<TestResult>),
+    Failed(String),
+}
+
+fn arguments<T: DeserializeOwned>(params: ExecuteCommandParams) -> Result<T, Error> {
+    serde_json::from_value(serde_json::Value::Array(params.arguments)).map_err(|e| {
+        Error::InvalidCommandArguments {
+            command: params.command,
+            details: e.to_string(),
+        }
+    })
+}
 
 pub fn handle_command(
     params: ExecuteCommandParams,
@@ -16,6 +100,27 @@ pub fn handle_command(
             server.reply(Response::new_ok(req, None::<()>));
             Ok(())
         }
+        "export" => {
+            let ExportArgs(doc, path, format) = arguments(params)?;
+            // Check the format before starting a worker.
+            export_format(&format)?;
+            match run_job(server, &doc.uri, Job::Export { path, format })? {
+                JobOutput::Export(result) => server.reply(Response::new_ok(req, result)),
+                output => return Err(unexpected_output(&doc.uri, output).into()),
+            }
+            Ok(())
+        }
+        "test" => {
+            let TestArgs(doc, path) = arguments(params)?;
+            match run_job(server, &doc.uri, Job::Test { path })? {
+                JobOutput::Test(results) => {
+                    show_test_summary(server, &results);
+                    server.reply(Response::new_ok(req, results));
+                }
+                output => return Err(unexpected_output(&doc.uri, output).into()),
+            }
+            Ok(())
+        }
         #[cfg(debug_assertions)]
         "pause" => {
             // This command is only used by integration tests. It is intended to simulate
@@ -39,3 +144,226 @@ fn eval(server: &mut Server, uri: &Url) -> Result<(), Error> {
     }
     Ok(())
 }
+
+/// Runs a command on the current contents of a document in a separate process.
+///
+/// Commands evaluate arbitrary code, so running them in the server could make it hang or crash.
+/// The worker is killed if it doesn't complete within the timeout of the evaluation
+/// configuration.
+fn run_job(server: &Server, uri: &Url, job: Job) -> Result<JobOutput, Error> {
+    let world = &server.world;
+    let file_id = world
+        .file_id(uri)?
+        .ok_or_else(|| Error::FileNotFound(uri.clone()))?;
+    // Use the path of the document as the name of the source, so that relative imports are
+    // resolved as for the file on disk.
+    let name = uri
+        .to_file_path()
+        .map(|path| path.into_os_string())
+        .unwrap_or_else(|()| uri.path().into());
+    let input = JobInput {
+        job,
+        source: world.sources.files().source(file_id).to_owned(),
+        name,
+        import_paths: world.import_paths_for(file_id),
+        package_map: world
+            .package_maps
+            .for_file(&world.sources, file_id)
+            .cloned(),
+    };
+
+    let timeout = world.config.eval_config.eval_limits.timeout;
+    let output = run_worker("--background-command", &[], &input, timeout).map_err(|e| {
+        let details = if e.is::<RecvTimeoutError>() {
+            format!("the command didn't complete within {timeout:?}")
+        } else {
+            e.to_string()
+        };
+        Error::CommandFailed {
+            file: uri.clone(),
+            details,
+        }
+    })?;
+
+    match output {
+        JobOutput::Failed(details) => Err(Error::CommandFailed {
+            file: uri.clone(),
+            details,
+        }),
+        output => Ok(output),
+    }
+}
+
+fn unexpected_output(uri: &Url, output: JobOutput) -> Error {
+    Error::CommandFailed {
+        file: uri.clone(),
+        details: format!("unexpected output from the command worker: {output:?}"),
+    }
+}
+
+// The entry point of the command worker. It reads a `JobInput` (in bincode) from stdin, runs the
+// job, and writes a `JobOutput` (in bincode) to stdout.
+pub fn worker_main() -> anyhow::Result<()> {
+    let input: JobInput = bincode::serde::decode_from_std_read(
+        &mut std::io::stdin().lock(),
+        bincode::config::standard(),
+    )?;
+
+    let output = match &input.job {
+        Job::Export { path, format } => export(&input, path, format).map(JobOutput::Export),
+        Job::Test { path } => test(&input, path).map(JobOutput::Test),
+    };
+
+    bincode::serde::encode_into_std_write(
+        output.unwrap_or_else(JobOutput::Failed),
+        &mut std::io::stdout().lock(),
+        bincode::config::standard(),
+    )
+    .map_err(|e| anyhow!("failed to send the output of the command: {e}"))?;
+
+    Ok(())
+}
+
+/// Builds a program from the contents of a document, focused on the given field.
+///
+/// The program is independent from the world: it has its own cache and re-reads imports from the
+/// disk.
+fn program(input: &JobInput, field: &[String]) -> Result<Program<CacheImpl>, String> {
+    let field = FieldPath(field.iter().map(|id| LocIdent::from(id.as_str())).collect());
+
+    let mut builder = ProgramBuilder::new()
+        .add_source_string(input.source.clone(), input.name.clone())
+        .add_import_paths(input.import_paths.clone())
+        .with_field_path(field);
+    if let Some(map) = &input.package_map {
+        builder = builder.with_package_map(map.clone());
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+fn report(program: &Program<CacheImpl>, error: impl IntoDiagnostics) -> String {
+    report_as_str(&mut program.files(), error, ColorOpt::Never)
+}
+
+fn export_format(format: &str) -> Result<ExportFormat, Error> {
+    match format {
+        "json" => Ok(ExportFormat::Json),
+        "yaml" => Ok(ExportFormat::Yaml),
+        "toml" => Ok(ExportFormat::Toml),
+        "text" | "raw" => Ok(ExportFormat::Text),
+        _ => Err(Error::InvalidCommandArguments {
+            command: "export".to_owned(),
+            details: format!("unsupported export format `{format}`"),
+        }),
+    }
+}
+
+/// Evaluates the field at `path` and serializes it to the given format, like `nickel export
+/// --field`.
+fn export(input: &JobInput, path: &[String], format: &str) -> Result<ExportResult, String> {
+    let format = export_format(format).map_err(|e| e.to_string())?;
+    let mut program = program(input, path)?;
+    let value = program
+        .eval_full_for_export()
+        .map_err(|e| report(&program, e))?;
+    let content = serialize::to_string(format, &value).map_err(|e| {
+        let error = nickel_lang_core::error::Error::export_error(program.pos_table().clone(), e);
+        report(&program, error)
+    })?;
+
+    let language = match format {
+        ExportFormat::Text => "plaintext".to_owned(),
+        format => format.to_string(),
+    };
+    Ok(ExportResult { content, language })
+}
+
+/// Collects the results of the doctests of a single field.
+struct FieldTests<'a> {
+    path: &'a [String],
+    results: Vec<(TestName, Option<TestFailure>)>,
+}
+
+impl TestObserver for FieldTests<'_> {
+    fn selects(&self, path: &[LocIdent]) -> bool {
+        path.len() == self.path.len()
+            && path
+                .iter()
+                .zip(self.path)
+                .all(|(id, expected)| id.label() == expected)
+    }
+
+    fn finished(&mut self, test: TestName, failure: Option<TestFailure>) {
+        self.results.push((test, failure));
+    }
+}
+
+/// Runs the doctests of the field at `path`, like `nickel test` does for a whole file.
+fn test(input: &JobInput, path: &[String]) -> Result<Vec<TestResult>, String> {
+    // The tests are inserted next to the field they document, so we need the enclosing record
+    // and can't just focus the program on the field.
+    let mut program = program(input, &[])?;
+    let (spine, registry) = prepare_tests(&mut program).map_err(|e| report(&program, e))?;
+
+    let mut observer = FieldTests {
+        path,
+        results: Vec::new(),
+    };
+    run_tests(
+        &mut program,
+        &spine,
+        &registry,
+        ColorOpt::Never,
+        &mut observer,
+    );
+
+    Ok(observer
+        .results
+        .into_iter()
+        .map(|(test, failure)| {
+            let failure = failure.map(|failure| match failure {
+                TestFailure::UnexpectedSuccess { result } => {
+                    format!("succeeded (evaluated to {result}), but it should have failed")
+                }
+                TestFailure::WrongTestFailure { message, expected } => {
+                    format!("failed, but the error didn't contain \"{expected}\":\n{message}")
+                }
+                TestFailure::UnexpectedFailure { error } => {
+                    format!("failed:\n{}", report(&program, *error))
+                }
+            });
+
+            TestResult {
+                name: test.to_string(),
+                failure,
+            }
+        })
+        .collect())
+}
+
+/// Tells the user how the tests went, since the result of a command isn't shown by editors.
+fn show_test_summary(server: &mut Server, results: &[TestResult]) {
+    let failures: Vec<_> = results
+        .iter()
+        .filter_map(|res| Some((&res.name, res.failure.as_ref()?)))
+        .collect();
+
+    let params = if let Some((name, failure)) = failures.first() {
+        ShowMessageParams {
+            typ: MessageType::ERROR,
+            message: format!(
+                "{} of {} doctests failed. Test {name} {failure}",
+                failures.len(),
+                results.len()
+            ),
+        }
+    } else {
+        ShowMessageParams {
+            typ: MessageType::INFO,
+            message: format!("{} doctests passed", results.len()),
+        }
+    };
+
+    server.notify(Notification::new(ShowMessage::METHOD.to_owned(), params));
+}
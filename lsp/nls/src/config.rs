@@ -1,13 +1,19 @@
 //! Configuration for the Nickel Language Server
 use serde::{Deserialize, Serialize};
 
-use std::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The name of the file holding the settings of a project.
+pub const SETTINGS_FILE_NAME: &str = "Nls-settings.ncl";
 
 /// Limits to apply to evaluation in the LSP.
 ///
 /// If a background evaluation reaches one of these limits, it will be canceled
 /// and the offending file will be temporarily blacklisted.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(default)]
 pub struct LspEvalLimits {
     /// Time out at which to cancel the background evaluation
@@ -25,7 +31,7 @@ impl Default for LspEvalLimits {
 }
 
 /// The configuration of the LSP evaluator
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(default)]
 pub struct LspEvalConfig {
     /// Disable background evaluation altogether.
@@ -46,9 +52,176 @@ impl Default for LspEvalConfig {
     }
 }
 
+/// The kinds of inlay hints shown by the LSP
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LspInlayHintsConfig {
+    /// Show the types inferred for let-bound variables and function parameters.
+    pub types: bool,
+    /// Show the types and contracts applied to a field by the records it's merged with.
+    pub contracts: bool,
+}
+
+impl Default for LspInlayHintsConfig {
+    fn default() -> Self {
+        LspInlayHintsConfig {
+            types: true,
+            contracts: true,
+        }
+    }
+}
+
+/// The configuration of the formatter
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct LspFormatConfig {
+    /// Don't format documents, for example to leave them to another tool.
+    pub disable: bool,
+    /// The number of spaces of an indentation level. The formatter uses two spaces by default.
+    pub indent_width: Option<usize>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Default)]
 #[serde(default)]
 pub struct LspConfig {
     /// Configuration for the background evaluator in the LSP
     pub eval_config: LspEvalConfig,
+    /// Configuration for the inlay hints
+    pub inlay_hints: LspInlayHintsConfig,
+    /// Additional directories to look for imports in, on top of the ones from
+    /// `NICKEL_IMPORT_PATH`
+    pub import_paths: Vec<PathBuf>,
+    /// Configuration for the formatter
+    pub format: LspFormatConfig,
+}
+
+impl LspConfig {
+    /// Updates the configuration with the settings sent by the client, which may only set some of
+    /// the fields. Returns `true` if the configuration changed.
+    pub fn merge(&mut self, settings: serde_json::Value) -> serde_json::Result<bool> {
+        fn merge_values(base: &mut serde_json::Value, update: serde_json::Value) {
+            match (base, update) {
+                (serde_json::Value::Object(base), serde_json::Value::Object(update)) => {
+                    for (key, value) in update {
+                        merge_values(base.entry(key).or_insert(serde_json::Value::Null), value);
+                    }
+                }
+                (base, update) => *base = update,
+            }
+        }
+
+        let current = serde_json::to_value(&*self)?;
+        let mut merged = current.clone();
+        merge_values(&mut merged, settings);
+        *self = serde_json::from_value(merged)?;
+
+        // Compare the configurations rather than the settings, which may contain unknown fields.
+        Ok(serde_json::to_value(&*self)? != current)
+    }
+}
+
+/// The settings of a project, read from a [`SETTINGS_FILE_NAME`] file at its root. They apply to
+/// the files below this directory and take precedence over the configuration sent by the editor.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ProjectSettings {
+    /// Additional directories to look for imports in. Relative paths are relative to the settings
+    /// file.
+    pub import_paths: Vec<PathBuf>,
+    /// The manifest of the package to resolve package imports with. By default, we use the
+    /// closest manifest in the ancestors of the file being edited.
+    pub package_manifest: Option<PathBuf>,
+    /// The lock file of the package, if it's not the default one next to the manifest.
+    pub package_lock_file: Option<PathBuf>,
+    /// Enable or disable background evaluation for the files of the project.
+    pub background_eval: Option<bool>,
+    /// The configuration of the formatter for the files of the project.
+    pub format: Option<LspFormatConfig>,
+}
+
+impl ProjectSettings {
+    /// Reads project settings from a file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let settings: ProjectSettings = nickel_lang_core::deserialize::from_path(path)
+            // Eagerly format the error, because anyhow requires `Send` errors and ours aren't
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        let parent_path = path
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("settings {} have no parent", path.display()))?;
+        Ok(settings.relative_to(parent_path))
+    }
+
+    fn relative_to(self, parent_path: &Path) -> Self {
+        ProjectSettings {
+            import_paths: self
+                .import_paths
+                .into_iter()
+                .map(|path| parent_path.join(path))
+                .collect(),
+            package_manifest: self.package_manifest.map(|path| parent_path.join(path)),
+            package_lock_file: self.package_lock_file.map(|path| parent_path.join(path)),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_client_settings() {
+        let mut config = LspConfig::default();
+        config.inlay_hints.types = false;
+
+        // Settings we don't know about, like the ones of the VS Code extension, are ignored.
+        let changed = config
+            .merge(serde_json::json!({ "server": { "path": "nls" } }))
+            .unwrap();
+        assert!(!changed);
+
+        let changed = config
+            .merge(serde_json::json!({
+                "import_paths": ["/lib"],
+                "format": { "indent_width": 4 },
+            }))
+            .unwrap();
+        assert!(changed);
+        assert!(!config.inlay_hints.types);
+        assert_eq!(config.import_paths, vec![PathBuf::from("/lib")]);
+        assert_eq!(
+            config.format,
+            LspFormatConfig {
+                disable: false,
+                indent_width: Some(4),
+            }
+        );
+    }
+
+    #[test]
+    fn project_settings() {
+        let settings: ProjectSettings = nickel_lang_core::deserialize::from_str(
+            r#"{
+                import_paths = ["lib", "/opt/nickel"],
+                package_manifest = "pkg/Nickel-pkg.ncl",
+                background_eval = false,
+                format.indent_width = 4,
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.relative_to(Path::new("/project")),
+            ProjectSettings {
+                import_paths: vec![PathBuf::from("/project/lib"), PathBuf::from("/opt/nickel")],
+                package_manifest: Some(PathBuf::from("/project/pkg/Nickel-pkg.ncl")),
+                package_lock_file: None,
+                background_eval: Some(false),
+                format: Some(LspFormatConfig {
+                    disable: false,
+                    indent_width: Some(4),
+                }),
+            }
+        );
+    }
 }
@@ -2,9 +2,12 @@ use std::ops::Range;
 
 use codespan_reporting::diagnostic::{self, Diagnostic, LabelStyle};
 use lsp_types::{DiagnosticRelatedInformation, NumberOrString};
-use nickel_lang_core::error::IntoDiagnostics;
+use nickel_lang_core::error::{
+    EvalError, EvalErrorKind, ImportErrorKind, IntoDiagnostics, ParseErrors, TypecheckError,
+    TypecheckErrorKind, Warning, suggest::find_best_match,
+};
 use nickel_lang_core::files::{FileId, Files};
-use nickel_lang_core::{error::UNKNOWN_SOURCE_NAME, position::RawSpan};
+use nickel_lang_core::{error::UNKNOWN_SOURCE_NAME, position::RawSpan, typecheck::error::RowKind};
 use serde::{Deserialize, Serialize};
 
 use crate::codespan_lsp::byte_span_to_range;
@@ -25,6 +28,7 @@ pub struct SerializableDiagnostic {
     pub code: Option<String>,
     pub message: String,
     pub related_information: Option<Vec<OrdDiagnosticRelatedInformation>>,
+    pub data: Option<FixData>,
 }
 
 impl SerializableDiagnostic {
@@ -36,14 +40,128 @@ impl SerializableDiagnostic {
     ///
     /// `current_file` is the file that we're currently reporting diagnostics for. (LSP reports
     /// diagnostics per-file.)
-    pub fn from<E: IntoDiagnostics>(e: E, files: &mut Files, current_file: FileId) -> Vec<Self> {
+    ///
+    /// All the diagnostics produced from `e` carry its [FixData], if any.
+    pub fn from<E: IntoDiagnostics + HasFixData>(
+        e: E,
+        files: &mut Files,
+        current_file: FileId,
+    ) -> Vec<Self> {
+        let data = e.fix_data(files, current_file);
+
         e.into_diagnostics(files)
             .into_iter()
             .flat_map(|d| SerializableDiagnostic::from_codespan(current_file, d, files))
+            .map(|diag| SerializableDiagnostic {
+                data: data.clone(),
+                ..diag
+            })
             .collect()
     }
 }
 
+/// Structured information about an error or a warning, attached to its diagnostics so that code
+/// actions can fix them without having to parse their messages. It's sent to the client in the
+/// `data` field of the diagnostics, which clients send back as is in code action requests.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum FixData {
+    /// A field is required, either by a field access or by a type or a contract, but isn't
+    /// defined.
+    MissingField {
+        name: String,
+        /// The closest existing field, if we know of one.
+        suggestion: Option<String>,
+    },
+    /// A variable isn't in scope.
+    UnboundIdentifier { name: String },
+    /// A record contract doesn't allow a field.
+    ExtraField { name: String },
+    /// A plain function is used as a contract. `func` is the range of the function, which is only
+    /// known if it's in the file the diagnostics are reported for.
+    NakedFunctionContract { func: OrdRange },
+}
+
+/// Errors and warnings that code actions know how to fix.
+pub trait HasFixData {
+    /// Returns the data needed to fix `self`, if any. `current_file` is the file that the
+    /// diagnostics are reported for.
+    fn fix_data(&self, _files: &Files, _current_file: FileId) -> Option<FixData> {
+        None
+    }
+}
+
+impl HasFixData for ParseErrors {}
+
+impl HasFixData for ImportErrorKind {}
+
+impl HasFixData for EvalError {
+    fn fix_data(&self, _files: &Files, _current_file: FileId) -> Option<FixData> {
+        match &self.error {
+            EvalErrorKind::FieldMissing {
+                id, field_names, ..
+            } => Some(FixData::MissingField {
+                name: id.label().to_owned(),
+                suggestion: find_best_match(field_names, id).map(ToOwned::to_owned),
+            }),
+            EvalErrorKind::UnboundIdentifier(id, _) => Some(FixData::UnboundIdentifier {
+                name: id.label().to_owned(),
+            }),
+            // Record contracts report missing and extra fields through the message of the
+            // contract error, which is the only data we get from them.
+            EvalErrorKind::BlameError { label, .. } => {
+                let message = label.current_diagnostic()?.message.as_deref()?;
+                let (kind, rest) = message.split_once(" field `")?;
+                let name = rest.strip_suffix('`')?.to_owned();
+
+                match kind {
+                    "missing" => Some(FixData::MissingField {
+                        name,
+                        suggestion: None,
+                    }),
+                    "extra" => Some(FixData::ExtraField { name }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl HasFixData for TypecheckError {
+    fn fix_data(&self, _files: &Files, _current_file: FileId) -> Option<FixData> {
+        match self.borrow_error() {
+            TypecheckErrorKind::MissingRow {
+                id,
+                kind: RowKind::Record,
+                ..
+            } => Some(FixData::MissingField {
+                name: id.label().to_owned(),
+                suggestion: None,
+            }),
+            TypecheckErrorKind::UnboundIdentifier(id) => Some(FixData::UnboundIdentifier {
+                name: id.label().to_owned(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl HasFixData for Warning {
+    fn fix_data(&self, files: &Files, current_file: FileId) -> Option<FixData> {
+        match self {
+            Warning::NakedFunctionContract { func_pos, .. } => {
+                let span = func_pos
+                    .into_opt()
+                    .filter(|span| span.src_id == current_file)?;
+                Some(FixData::NakedFunctionContract {
+                    func: OrdRange(lsp_types::Range::from_span(&span, files)?),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Default, Deserialize, Serialize)]
 pub struct OrdRange(pub lsp_types::Range);
 
@@ -95,6 +213,7 @@ impl From<SerializableDiagnostic> for lsp_types::Diagnostic {
             related_information: d
                 .related_information
                 .map(|xs| xs.into_iter().map(|x| x.0).collect()),
+            data: d.data.and_then(|data| serde_json::to_value(data).ok()),
             ..Default::default()
         }
     }
@@ -213,6 +332,7 @@ impl DiagnosticCompat for SerializableDiagnostic {
                             })
                             .collect(),
                     ),
+                    data: None,
                 });
             }
         }
@@ -231,6 +351,7 @@ impl DiagnosticCompat for SerializableDiagnostic {
                 severity: Some(lsp_types::DiagnosticSeverity::HINT),
                 code: code.clone(),
                 related_information: None,
+                data: None,
             })
         }));
         diagnostics
@@ -28,6 +28,12 @@ pub enum Error {
     #[error("Command not supported: {0}")]
     CommandNotFound(String),
 
+    #[error("invalid arguments for command {command}: {details}")]
+    InvalidCommandArguments { command: String, details: String },
+
+    #[error("command failed for file {file}: {details}")]
+    CommandFailed { details: String, file: Url },
+
     #[error("formatting failed for file {file}: {details}")]
     FormattingFailed { details: String, file: Url },
 
@@ -51,7 +57,9 @@ impl From<Error> for ResponseError {
             Error::SchemeNotSupported(_) => ErrorCode::InvalidParams,
             Error::InvalidPath(_) => ErrorCode::InvalidParams,
             Error::CommandNotFound(_) => ErrorCode::InvalidParams,
+            Error::InvalidCommandArguments { .. } => ErrorCode::InvalidParams,
             Error::MethodNotFound => ErrorCode::MethodNotFound,
+            Error::CommandFailed { .. } => ErrorCode::RequestFailed,
             Error::FormattingFailed { .. } => ErrorCode::InternalError,
             Error::Nickel(_) => ErrorCode::InternalError,
         };
@@ -752,6 +752,12 @@ impl<'ast> FieldResolver<'ast> {
         filter_records(self.resolve_container(ast))
     }
 
+    /// If this type resolves to one or more records, either record types or record contracts,
+    /// return them all.
+    pub fn resolve_type_record(&self, typ: &'ast Type<'ast>) -> Vec<Record<'ast>> {
+        filter_records(self.resolve_type(typ))
+    }
+
     /// Finds all the containers that are descended from `ast` at the given path.
     ///
     /// The path can mix field access and array "accesses". The array accesses are only used
@@ -4,7 +4,8 @@ use anyhow::{Result, anyhow};
 use log::info;
 use lsp_server::RequestId;
 use lsp_types::{
-    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, Url,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, Position,
+    TextDocumentContentChangeEvent, Url,
     notification::{DidCloseTextDocument, DidOpenTextDocument, Notification},
 };
 use nickel_lang_core::files::FileId;
@@ -81,8 +82,12 @@ pub fn run_diagnostics_on_file(server: &mut Server, req: DiagnosticsRequest) ->
         .world
         .file_id(&req.uri)?
         .ok_or_else(|| anyhow!("Could not find a matching File ID for {}", req.uri))?;
-    let diags = server.world.parse_and_typecheck(file_id);
+    let mut diags = server.world.parse_and_typecheck(file_id);
+    diags.extend(server.world.lint_diagnostics(file_id));
     server.issue_diagnostics(file_id, diags);
+    if !server.world.background_eval_enabled(&req.uri) {
+        return Ok(());
+    }
     match req.priority {
         Priority::High => server
             .background_jobs
@@ -92,27 +97,146 @@ pub fn run_diagnostics_on_file(server: &mut Server, req: DiagnosticsRequest) ->
     Ok(())
 }
 
+/// Converts a position of a text document change to a byte offset in `contents`. As required by
+/// the LSP specification, a position past the end of a line refers to the end of that line, and a
+/// position past the last line refers to the end of the contents.
+fn clamped_byte_index(contents: &str, position: Position) -> usize {
+    let mut line_start = 0;
+
+    for _ in 0..position.line {
+        match contents[line_start..].find('\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return contents.len(),
+        }
+    }
+
+    let line_end = contents[line_start..]
+        .find('\n')
+        .map_or(contents.len(), |idx| line_start + idx);
+    let line = &contents[line_start..line_end];
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    // Characters are counted in UTF-16 code units. A position in the middle of a character is
+    // moved to the end of this character.
+    let mut character = 0;
+
+    for (idx, ch) in line.char_indices() {
+        if character >= position.character {
+            return line_start + idx;
+        }
+
+        character += ch.len_utf16() as u32;
+    }
+
+    line_start + line.len()
+}
+
+/// Applies the changes of a `textDocument/didChange` notification to the contents of a file, in
+/// order. A change without a range replaces the whole contents. Out-of-range positions are clamped
+/// to the contents.
+fn apply_changes(
+    mut contents: String,
+    changes: Vec<TextDocumentContentChangeEvent>,
+) -> Result<String> {
+    for change in changes {
+        let Some(range) = change.range else {
+            contents = change.text;
+            continue;
+        };
+
+        // Positions refer to the contents as they are after the previous changes, so we need to
+        // convert them one change at a time.
+        let start = clamped_byte_index(&contents, range.start);
+        let end = clamped_byte_index(&contents, range.end);
+
+        if start > end {
+            return Err(anyhow!("invalid range in text document change: {range:?}"));
+        }
+
+        contents.replace_range(start..end, &change.text);
+    }
+
+    Ok(contents)
+}
+
 /// Returns a list of open files that were potentially invalidated by the changes.
 pub fn handle_save(server: &mut Server, params: DidChangeTextDocumentParams) -> Result<Vec<Url>> {
-    let id: RequestId = format!(
-        "{}#{}",
-        params.text_document.uri, params.text_document.version
-    )
-    .into();
+    let uri = params.text_document.uri;
+    let id: RequestId = format!("{}#{}", uri, params.text_document.version).into();
 
     Trace::receive(id.clone(), DidOpenTextDocument::METHOD);
-    Trace::enrich(
-        &id,
-        FileUpdate {
-            content: &params.content_changes[0].text,
-        },
-    );
 
-    let (_, invalid) = server.world.update_file(
-        params.text_document.uri.clone(),
-        params.content_changes[0].text.clone(),
-    )?;
+    let current = server
+        .world
+        .file_id(&uri)?
+        .map(|file_id| server.world.sources.source(file_id).to_owned())
+        .unwrap_or_default();
+    let contents = apply_changes(current, params.content_changes)?;
+
+    Trace::enrich(&id, FileUpdate { content: &contents });
+
+    let (_, invalid) = server.world.update_file(uri, contents)?;
 
     Trace::reply(id);
     Ok(server.world.uris(invalid).cloned().collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Range;
+
+    use super::*;
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|((l1, c1), (l2, c2))| Range {
+                start: Position::new(l1, c1),
+                end: Position::new(l2, c2),
+            }),
+            range_length: None,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn full_change() {
+        let contents = apply_changes("1 + 1".to_owned(), vec![change(None, "2")]).unwrap();
+        assert_eq!(contents, "2");
+    }
+
+    #[test]
+    fn incremental_changes() {
+        let changes = vec![
+            change(Some(((0, 2), (0, 5))), "bar"),
+            change(Some(((0, 9), (0, 9))), ",\n  baz = 2"),
+            // The positions of a change are relative to the result of the previous ones.
+            change(Some(((0, 10), (1, 2))), " "),
+        ];
+        let contents = apply_changes("{ foo = 1 }".to_owned(), changes).unwrap();
+        assert_eq!(contents, "{ bar = 1, baz = 2 }");
+    }
+
+    #[test]
+    fn utf16_positions() {
+        // `𐐀` is two UTF-16 code units but four bytes.
+        let changes = vec![change(Some(((0, 3), (0, 4))), "x")];
+        let contents = apply_changes("\"𐐀a\"".to_owned(), changes).unwrap();
+        assert_eq!(contents, "\"𐐀x\"");
+    }
+
+    #[test]
+    fn out_of_range_changes() {
+        let changes = vec![
+            // Past the end of a line.
+            change(Some(((0, 4), (0, 100))), "2"),
+            // Past the last line.
+            change(Some(((5, 0), (7, 3))), "\n"),
+            change(Some(((1, 2), (1, 2))), "# end"),
+        ];
+        let contents = apply_changes("1 + 1\n".to_owned(), changes).unwrap();
+        assert_eq!(contents, "1 + 2\n# end\n");
+    }
+}
@@ -23,6 +23,8 @@ mod field_walker;
 mod files;
 mod identifier;
 mod incomplete;
+#[cfg(feature = "package-experimental")]
+mod package;
 mod position;
 mod requests;
 mod server;
@@ -31,6 +33,7 @@ use server::Server;
 mod term;
 mod trace;
 mod usage;
+mod workspace;
 mod world;
 
 // Default stack size is 1MB on Windows, which is too small. We make it 8MB, which is the default
@@ -64,6 +67,15 @@ struct Options {
     /// If set, this process runs a background evaluation job instead of setting up a language server.
     #[arg(long)]
     background_eval: bool,
+
+    /// If set, this process runs a command, such as `export`, instead of setting up a language
+    /// server.
+    #[arg(long)]
+    background_command: bool,
+
+    /// If set, this process reads project settings instead of setting up a language server.
+    #[arg(long)]
+    background_settings: bool,
 }
 
 fn main() -> Result<()> {
@@ -86,6 +98,14 @@ fn run() -> Result<()> {
         return background::worker_main();
     }
 
+    if options.background_command {
+        return command::worker_main();
+    }
+
+    if options.background_settings {
+        return background::settings_worker_main();
+    }
+
     if let Some(file) = options.trace {
         let absolute_path = path::absolute(&file)?;
         debug!("Writing trace to {absolute_path:?}");
@@ -112,7 +132,9 @@ fn run() -> Result<()> {
 
     debug!("Parsed InitializeParams: {config:?}");
 
-    let _server = Server::new(connection, config).run();
+    let mut server = Server::new(connection, config);
+    server.index_workspace(workspace::workspace_roots(&initialize_params));
+    let _server = server.run();
 
     Ok(())
 }
@@ -0,0 +1,49 @@
+//! Package support, which amounts to finding the package map to resolve package imports with.
+
+use std::path::{Path, PathBuf};
+
+use nickel_lang_core::package::PackageMap;
+use nickel_lang_package::{
+    ManifestFile, config::Config, error::Error, index::PackageIndex, lock::LockFile,
+    manifest::MANIFEST_NAME, resolve, snapshot::Snapshot,
+};
+
+/// Finds the manifest of the package containing `dir`, if any.
+pub fn find_manifest(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(MANIFEST_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Computes the package map of a package from its lock file, which is the default one next to the
+/// manifest unless `lock_path` is provided.
+///
+/// Unlike the CLI, we never (re)generate the lock file because it may require fetching packages,
+/// which isn't something we want to do behind the user's back while they're editing. If the lock
+/// file is missing or out of date, we return `None` and package imports are left unresolved.
+pub fn package_map(
+    manifest_path: &Path,
+    lock_path: Option<&Path>,
+) -> Result<Option<PackageMap>, Box<Error>> {
+    let manifest = ManifestFile::from_path(manifest_path)?;
+    let lock_path = match lock_path {
+        Some(path) => path.to_owned(),
+        None => manifest.default_lockfile_path()?,
+    };
+
+    if !lock_path.is_file() {
+        return Ok(None);
+    }
+
+    let lock = LockFile::from_path(&lock_path)?;
+    let config = Config::new()?;
+    let snapshot = Snapshot::new_with_lock(&config, &manifest.parent_dir, &manifest, &lock)?;
+
+    if !manifest.is_lock_file_up_to_date(&snapshot, &lock) {
+        return Ok(None);
+    }
+
+    let index = PackageIndex::shared(config.clone())?;
+    let resolution = resolve::copy_from_lock(&lock, snapshot, index, config)?;
+    Ok(Some(resolution.package_map(&manifest)?))
+}
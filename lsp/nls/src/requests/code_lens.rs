@@ -0,0 +1,176 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{CodeLens, CodeLensParams, Command, TextDocumentIdentifier, Url};
+use nickel_lang_core::{
+    ast::{
+        Ast, Node,
+        record::{FieldDef, Record as RecordData},
+    },
+    doctest::extract_doctests,
+    files::FileId,
+    position::RawSpan,
+};
+use serde_json::json;
+
+use crate::{
+    codespan_lsp::byte_span_to_range,
+    error::Error,
+    field_walker::{FieldDefPiece, FieldResolver, Record},
+    requests::semantic_tokens::is_function_field,
+    server::Server,
+    world::World,
+};
+
+/// The formats offered by the export lenses, with their titles.
+const EXPORT_FORMATS: &[(&str, &str)] = &[("json", "Export to JSON"), ("yaml", "Export to YAML")];
+
+fn lens(
+    world: &World,
+    span: RawSpan,
+    title: String,
+    command: &str,
+    args: Vec<serde_json::Value>,
+) -> Option<CodeLens> {
+    let range = byte_span_to_range(world.sources.files(), span.src_id, span.to_range()).ok()?;
+    Some(CodeLens {
+        range,
+        command: Some(Command {
+            title,
+            command: command.to_owned(),
+            arguments: Some(args),
+        }),
+        data: None,
+    })
+}
+
+/// Returns the lenses exporting each top-level field of `record`, except for functions, which
+/// can't be exported.
+fn export_lenses(
+    world: &World,
+    uri: &Url,
+    file_id: FileId,
+    record: &RecordData<'_>,
+) -> Vec<CodeLens> {
+    let doc = TextDocumentIdentifier { uri: uri.clone() };
+
+    record
+        .group_by_field_id()
+        .into_iter()
+        .filter(|(_, defs)| {
+            !defs.iter().any(|def| {
+                is_function_field(&FieldDefPiece {
+                    index: 0,
+                    field_def: def,
+                })
+            })
+        })
+        .filter_map(|(id, defs)| {
+            // Put the lens on the first piece of the definition from this file.
+            defs.iter()
+                .filter_map(|def| def.root_as_ident()?.pos.into_opt())
+                .find(|span| span.src_id == file_id)
+                .map(|span| (id, span))
+        })
+        .flat_map(|(id, span)| {
+            let doc = &doc;
+            EXPORT_FORMATS.iter().filter_map(move |(format, title)| {
+                lens(
+                    world,
+                    span,
+                    title.to_string(),
+                    "export",
+                    vec![json!(doc), json!([id.label()]), json!(format)],
+                )
+            })
+        })
+        .collect()
+}
+
+/// Collects the lenses running the doctests of the fields of `record` and of their subfields,
+/// `prefix` being the path of `record` itself.
+fn doctest_lenses(
+    world: &World,
+    uri: &Url,
+    file_id: FileId,
+    record: &RecordData<'_>,
+    prefix: &[String],
+    lenses: &mut Vec<CodeLens>,
+) {
+    for def in record.field_defs.iter() {
+        // We can only address fields with a static path.
+        let Some(path) = def
+            .path
+            .iter()
+            .map(|elem| Some(elem.try_as_ident()?.label().to_owned()))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let path: Vec<_> = prefix.iter().cloned().chain(path).collect();
+
+        if let Some(lens) = doctest_lens(world, uri, file_id, def, &path) {
+            lenses.push(lens);
+        }
+
+        if let Some(Ast {
+            node: Node::Record(record),
+            ..
+        }) = &def.value
+        {
+            doctest_lenses(world, uri, file_id, record, &path, lenses);
+        }
+    }
+}
+
+fn doctest_lens(
+    world: &World,
+    uri: &Url,
+    file_id: FileId,
+    def: &FieldDef<'_>,
+    path: &[String],
+) -> Option<CodeLens> {
+    let count = extract_doctests(def.metadata.doc?).len();
+    let span = def.path.first()?.pos().into_opt()?;
+
+    if count == 0 || span.src_id != file_id {
+        return None;
+    }
+
+    let title = if count == 1 {
+        "Run doctest".to_owned()
+    } else {
+        format!("Run {count} doctests")
+    };
+    let doc = TextDocumentIdentifier { uri: uri.clone() };
+    lens(world, span, title, "test", vec![json!(doc), json!(path)])
+}
+
+pub fn handle_code_lens(
+    params: CodeLensParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let uri = &params.text_document.uri;
+    let file_id = server
+        .world
+        .file_id(uri)?
+        .ok_or_else(|| Error::FileNotFound(uri.clone()))?;
+
+    let world = &server.world;
+    let analysis = world.file_analysis(file_id)?;
+    let resolver = FieldResolver::new(world);
+    let mut lenses = Vec::new();
+
+    for record in resolver.resolve_path(analysis.ast(), [].into_iter()) {
+        if let Record::Term(record) = record {
+            lenses.extend(export_lenses(world, uri, file_id, record));
+            doctest_lenses(world, uri, file_id, record, &[], &mut lenses);
+        }
+    }
+
+    // Sort so the response is deterministic. The sort is stable, so lenses on the same field keep
+    // their relative order.
+    lenses.sort_by_key(|lens| lens.range.start);
+
+    server.reply(Response::new_ok(id, lenses));
+    Ok(())
+}
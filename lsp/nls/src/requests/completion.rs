@@ -17,7 +17,7 @@ use std::{
     ffi::OsStr,
     io,
     iter::Extend,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use crate::{
@@ -467,9 +467,24 @@ fn handle_import_completion(
         .unwrap();
     let current_file = cache::normalize_path(current_file)?;
 
-    let mut current_path = current_file.clone();
-    current_path.pop();
-    current_path.push(import);
+    // When completing `lib/k8`, we list the entries of the `lib` directory and let the editor
+    // filter them.
+    let import = Path::new(import);
+    let typed_dir = if import.as_os_str().is_empty() || import.to_string_lossy().ends_with('/') {
+        import
+    } else {
+        import.parent().unwrap_or(Path::new(""))
+    };
+
+    // Imports are looked up relative to the importing file first, and then in the import path.
+    let current_dir = current_file
+        .parent()
+        .map(Path::to_owned)
+        .unwrap_or_default();
+    let search_dirs: Vec<PathBuf> = std::iter::once(current_dir)
+        .chain(server.world.sources.import_paths.iter().cloned())
+        .map(|dir| dir.join(typed_dir))
+        .collect();
 
     #[derive(Eq, PartialEq, Hash)]
     struct Entry {
@@ -479,8 +494,10 @@ fn handle_import_completion(
 
     let mut entries = HashSet::new();
 
-    let dir = std::fs::read_dir(&current_path)?;
-    let dir_entries = dir
+    let dir_entries = search_dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
         .filter_map(|i| i.ok().and_then(|d| d.file_type().ok().zip(Some(d))))
         .map(|(file_type, entry)| Entry {
             path: entry.path(),
@@ -492,13 +509,13 @@ fn handle_import_completion(
         .file_uris
         .values()
         .filter_map(|uri| uri.to_file_path().ok())
-        .filter(|path| path.starts_with(&current_path))
+        .filter(|path| search_dirs.iter().any(|dir| path.parent() == Some(dir)))
         .map(|path| Entry { path, file: true });
 
     entries.extend(dir_entries);
     entries.extend(cached_entries);
 
-    let completions = entries
+    let mut completions = entries
         .iter()
         .filter(|Entry { path, file }| {
             // don't try to import a file into itself
@@ -524,5 +541,8 @@ fn handle_import_completion(
             }
         })
         .collect::<Vec<_>>();
+    // The same name can be found in several directories of the import path.
+    completions.sort_by(|c1, c2| c1.label.cmp(&c2.label));
+    completions.dedup_by(|c1, c2| c1.label == c2.label);
     Ok(completions)
 }
@@ -0,0 +1,56 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams};
+use nickel_lang_core::position::RawSpan;
+
+use crate::{codespan_lsp::byte_span_to_range, server::Server};
+
+pub fn handle_document_highlight(
+    params: DocumentHighlightParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let world = &server.world;
+    let pos = world.position(&params.text_document_position_params)?;
+    let ident_data = world.ident_data_at(pos)?;
+
+    // Like for references, we find the definitions of the symbol and then their usages, except
+    // that we only care about the current file.
+    let mut defs = world
+        .ast_at(pos)?
+        .map(|ast| world.get_defs(ast, ident_data.as_ref()))
+        .unwrap_or_default();
+    defs.extend(ident_data.and_then(|data| data.ident.pos.into_opt()));
+
+    let usages = defs
+        .iter()
+        .flat_map(|def| world.analysis_reg.get_usages(def))
+        .filter_map(|id| id.pos.into_opt())
+        .chain(defs.iter().flat_map(|def| world.get_field_refs(*def)));
+
+    let mut highlights: Vec<(RawSpan, DocumentHighlightKind)> = defs
+        .iter()
+        .map(|def| (*def, DocumentHighlightKind::WRITE))
+        .chain(usages.map(|span| (span, DocumentHighlightKind::READ)))
+        .filter(|(span, _)| span.src_id == pos.src_id)
+        .collect();
+
+    // A definition can also show up as a usage, for example for a field referring to itself
+    // through a recursive record: keep the first occurrence, which is the definition.
+    highlights.sort_by_key(|(span, _)| (span.start, span.end));
+    highlights.dedup_by_key(|(span, _)| *span);
+
+    let highlights: Vec<_> = highlights
+        .into_iter()
+        .filter_map(|(span, kind)| {
+            let range =
+                byte_span_to_range(world.sources.files(), span.src_id, span.to_range()).ok()?;
+            Some(DocumentHighlight {
+                range,
+                kind: Some(kind),
+            })
+        })
+        .collect();
+
+    server.reply(Response::new_ok(id, highlights));
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{DocumentLink, DocumentLinkParams};
+use nickel_lang_core::{
+    ast::{Ast, Node},
+    position::RawSpan,
+    traverse::{TraverseAlloc, TraverseControl},
+};
+
+use crate::{codespan_lsp::byte_span_to_range, error::Error, server::Server, world::World};
+
+/// Returns the span of the path or of the package name in an import expression, such as
+/// `"lib/k8s.ncl"` in `import "lib/k8s.ncl" as 'Nickel'`. This is the part of the import that the
+/// editor underlines.
+fn import_target_span(world: &World, span: RawSpan) -> Option<RawSpan> {
+    let source = world.sources.files().source(span.src_id);
+    let text = source.get(span.to_range())?;
+    let rest = text.strip_prefix("import")?;
+    let offset = text.len() - rest.trim_start().len();
+    let rest = &text[offset..];
+
+    let len = if let Some(path) = rest.strip_prefix('"') {
+        // Find the closing quote, skipping escaped characters.
+        let mut chars = path.char_indices();
+        let mut end = None;
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => {
+                    end = Some(idx + 2);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        end?
+    } else {
+        rest.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '\'')))
+            .unwrap_or(rest.len())
+    };
+
+    let start = span.start.to_usize() + offset;
+    Some(RawSpan {
+        src_id: span.src_id,
+        start: (start as u32).into(),
+        end: ((start + len) as u32).into(),
+    })
+}
+
+fn import_link(world: &World, ast: &Ast<'_>) -> Option<DocumentLink> {
+    let target = world.get_import_target(ast.pos)?;
+    let uri = world.file_uris.get(&target)?;
+    let span = import_target_span(world, ast.pos.into_opt()?)?;
+    let range = byte_span_to_range(world.sources.files(), span.src_id, span.to_range()).ok()?;
+
+    Some(DocumentLink {
+        range,
+        target: Some(uri.clone()),
+        tooltip: None,
+        data: None,
+    })
+}
+
+pub fn handle_document_links(
+    params: DocumentLinkParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server
+        .world
+        .file_id(&params.text_document.uri)?
+        .ok_or_else(|| Error::FileNotFound(params.text_document.uri.clone()))?;
+
+    let world = &server.world;
+    let analysis = world.file_analysis(file_id)?;
+    let mut links = Vec::new();
+
+    analysis.ast().traverse_ref(
+        &mut |ast: &Ast<'_>, _: &()| {
+            if let Node::Import(_) = &ast.node {
+                links.extend(import_link(world, ast));
+            }
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    // Sort so the response is deterministic.
+    links.sort_by_key(|link| link.range.start);
+
+    server.reply(Response::new_ok(id, links));
+    Ok(())
+}
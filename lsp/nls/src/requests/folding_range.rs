@@ -0,0 +1,132 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{FoldingRange, FoldingRangeKind, FoldingRangeParams};
+use nickel_lang_core::{
+    ast::{Ast, Node, record::FieldDef},
+    parser::lexer::{Lexer, NormalToken, Token},
+    position::RawSpan,
+    traverse::{TraverseAlloc, TraverseControl},
+};
+
+use crate::{codespan_lsp::byte_span_to_range, error::Error, server::Server, world::World};
+
+fn folding_range(
+    world: &World,
+    span: RawSpan,
+    kind: Option<FoldingRangeKind>,
+) -> Option<FoldingRange> {
+    let range = byte_span_to_range(world.sources.files(), span.src_id, span.to_range()).ok()?;
+
+    // There's nothing to fold in a single line.
+    (range.end.line > range.start.line).then_some(FoldingRange {
+        start_line: range.start.line,
+        start_character: Some(range.start.character),
+        end_line: range.end.line,
+        end_character: Some(range.end.character),
+        kind,
+        collapsed_text: None,
+    })
+}
+
+/// Returns the span of the string literal of the `doc` annotation of a field definition, if any.
+///
+/// Documentation strings don't have a position in the AST, so we look for the literal among the
+/// tokens of the field definition, which skips over comments and the content of other strings.
+fn doc_span(world: &World, def: &FieldDef<'_>) -> Option<RawSpan> {
+    def.metadata.doc?;
+
+    let span = def.pos.into_opt()?;
+    let source = world.sources.files().source(span.src_id);
+    let text = source.get(span.to_range())?;
+
+    let mut lexer = Lexer::new(text);
+    let mut after_pipe = false;
+    let mut after_doc = false;
+
+    let start = loop {
+        let (start, token, _) = lexer.next()?.ok()?;
+
+        if after_doc
+            && let Token::Normal(NormalToken::DoubleQuote | NormalToken::MultiStringStart(_)) =
+                token
+        {
+            break start;
+        }
+
+        after_doc = after_pipe && token == Token::Normal(NormalToken::Doc);
+        after_pipe = token == Token::Normal(NormalToken::Pipe);
+    };
+
+    // The lexer enters a new mode at the opening delimiter of the string, and goes back to the
+    // previous one at the closing delimiter. Interpolated expressions push more modes in between.
+    let depth = lexer.modes.len();
+    let end = loop {
+        let (_, _, end) = lexer.next()?.ok()?;
+
+        if lexer.modes.len() < depth {
+            break end;
+        }
+    };
+
+    let offset = span.start.to_usize();
+    Some(RawSpan {
+        src_id: span.src_id,
+        start: ((offset + start) as u32).into(),
+        end: ((offset + end) as u32).into(),
+    })
+}
+
+pub fn handle_folding_ranges(
+    params: FoldingRangeParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server
+        .world
+        .file_id(&params.text_document.uri)?
+        .ok_or_else(|| Error::FileNotFound(params.text_document.uri.clone()))?;
+
+    let world = &server.world;
+    let analysis = world.file_analysis(file_id)?;
+    let mut ranges = Vec::new();
+
+    analysis.ast().traverse_ref(
+        &mut |ast: &Ast<'_>, _: &()| {
+            let span = ast.pos.into_opt();
+
+            match &ast.node {
+                Node::Record(record) => {
+                    ranges.extend(span.and_then(|span| folding_range(world, span, None)));
+                    ranges.extend(record.field_defs.iter().filter_map(|def| {
+                        folding_range(
+                            world,
+                            doc_span(world, def)?,
+                            Some(FoldingRangeKind::Comment),
+                        )
+                    }));
+                }
+                Node::Array(_) | Node::String(_) | Node::StringChunks(_) => {
+                    ranges.extend(span.and_then(|span| folding_range(world, span, None)));
+                }
+                Node::Match(data) => {
+                    ranges.extend(span.and_then(|span| folding_range(world, span, None)));
+                    ranges.extend(data.branches.iter().filter_map(|branch| {
+                        let start = branch.pattern.pos.into_opt()?;
+                        let end = branch.body.pos.into_opt()?;
+                        folding_range(world, start.fuse(end)?, None)
+                    }));
+                }
+                _ => {}
+            }
+
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    // Sort so the response is deterministic.
+    ranges.sort_by_key(|range| (range.start_line, range.start_character, range.end_line));
+    ranges.dedup();
+
+    server.reply(Response::new_ok(id, ranges));
+    Ok(())
+}
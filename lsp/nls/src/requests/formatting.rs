@@ -12,6 +12,13 @@ pub fn handle_format_document(
     id: RequestId,
     server: &mut Server,
 ) -> Result<(), ResponseError> {
+    let config = server.world.format_config(&params.text_document.uri);
+    if config.disable {
+        server.reply(Response::new_ok(id, None::<Vec<TextEdit>>));
+        return Ok(());
+    }
+    let indent = config.indent_width.map(|width| " ".repeat(width));
+
     let path = uri_to_path(&params.text_document.uri)?;
     let Some(file_id) = server
         .world
@@ -27,11 +34,14 @@ pub fn handle_format_document(
     let document_length = text.lines().count() as u32;
 
     let mut formatted: Vec<u8> = Vec::new();
-    nickel_lang_core::format::format(text.as_bytes(), &mut formatted).map_err(|err| {
-        Error::FormattingFailed {
-            details: format!("{err}"),
-            file: params.text_document.uri.clone(),
-        }
+    nickel_lang_core::format::format_with_indent(
+        text.as_bytes(),
+        &mut formatted,
+        indent.as_deref(),
+    )
+    .map_err(|err| Error::FormattingFailed {
+        details: format!("{err}"),
+        file: params.text_document.uri.clone(),
     })?;
 
     let formatted = String::from_utf8(formatted).map_err(|_err| Error::FormattingFailed {
@@ -8,7 +8,6 @@ use nickel_lang_core::{
     combine::Combine,
     identifier::Ident,
     position::RawSpan,
-    typecheck::AnnotSeqRef,
 };
 
 use serde_json::Value;
@@ -32,16 +31,33 @@ fn dedup<T: std::hash::Hash + Eq + Clone>(xs: &mut Vec<T>) {
 }
 
 #[derive(Debug, Default)]
-struct HoverData<'ast> {
+pub(crate) struct HoverData<'ast> {
     /// A list of values contributing to the definition of the hovered term. Values are currently
     /// used only for their annotation, in order to aggregate even more type information.
-    values: Vec<&'ast Ast<'ast>>,
+    pub(crate) values: Vec<&'ast Ast<'ast>>,
     /// A list of metadata contributing to the definition of the hovered term.
-    metadata: Vec<Cow<'ast, FieldMetadata<'ast>>>,
-    span: Option<RawSpan>,
+    pub(crate) metadata: Vec<Cow<'ast, FieldMetadata<'ast>>>,
+    pub(crate) span: Option<RawSpan>,
     /// The distinguished type of the hovered term, if any. This is the one inferred by the
     /// typechecker.
-    ty: Option<&'ast Type<'ast>>,
+    pub(crate) ty: Option<&'ast Type<'ast>>,
+}
+
+impl<'ast> HoverData<'ast> {
+    /// All the type and contract annotations we can find, either in the metadata or directly on
+    /// the values. They may contain duplicates.
+    pub(crate) fn annotations(&self) -> impl Iterator<Item = &Type<'ast>> {
+        self.metadata
+            .iter()
+            .flat_map(|m| m.annotation.typ.iter().chain(m.annotation.contracts))
+            .chain(self.values.iter().flat_map(|ast| annotated_contracts(ast)))
+    }
+
+    /// The documentation of the hovered term, if any.
+    pub(crate) fn doc(&self) -> Option<&str> {
+        // Not sure how to do documentation merging yet, so pick the first non-empty one.
+        self.metadata.iter().find_map(|m| m.doc)
+    }
 }
 
 impl Combine for HoverData<'_> {
@@ -88,7 +104,7 @@ fn values_and_metadata_from_field(
     (values, metadata)
 }
 
-fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData<'_>> {
+pub(crate) fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData<'_>> {
     let ty = world.analysis_reg.get_ident_type(&ident);
     let span = ident.pos.into_opt()?;
     let mut ret = HoverData {
@@ -148,7 +164,10 @@ fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData<'_>> {
     Some(ret)
 }
 
-fn term_hover<'ast>(ast: &'ast Ast<'ast>, world: &'ast World) -> Option<HoverData<'ast>> {
+pub(crate) fn term_hover<'ast>(
+    ast: &'ast Ast<'ast>,
+    world: &'ast World,
+) -> Option<HoverData<'ast>> {
     let ty = world.analysis_reg.get_type(ast);
     let span = ast.pos.into_opt();
 
@@ -209,18 +228,7 @@ pub fn handle(
         // Collect all the type and contract annotations we can find. We don't distinguish between them
         // (and we deduplicate annotations if they're present as both types and contracts). However, we
         // do give some special attention to the inferred static type if there is one: we list it first.
-        let mut annotations: Vec<_> = hover
-            .metadata
-            .iter()
-            .flat_map(|m| m.annotation.iter().map(|typ| typ.to_string()))
-            .chain(
-                hover
-                    .values
-                    .iter()
-                    .flat_map(|ast| annotated_contracts(ast))
-                    .map(|contract| contract.to_string()),
-            )
-            .collect();
+        let mut annotations: Vec<_> = hover.annotations().map(Type::to_string).collect();
         dedup(&mut annotations);
 
         let ty = hover
@@ -240,9 +248,7 @@ pub fn handle(
 
         contents.extend(annotations.into_iter().map(nickel_string));
 
-        // Not sure how to do documentation merging yet, so pick the first non-empty one.
-        let doc = hover.metadata.iter().find_map(|m| m.doc.as_ref());
-        if let Some(doc) = doc {
+        if let Some(doc) = hover.doc() {
             contents.push(MarkedString::String(doc.to_string()));
         }
 
@@ -0,0 +1,202 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, InlayHintParams, Position};
+use nickel_lang_core::{
+    ast::{
+        Ast, Node,
+        pattern::{Pattern, PatternData},
+        record::FieldMetadata,
+        typ::Type,
+    },
+    files::FileId,
+    identifier::Ident,
+    position::TermPos,
+    traverse::{TraverseAlloc, TraverseControl},
+    typ::TypeF,
+};
+
+use crate::{
+    codespan_lsp::byte_index_to_position, config::LspInlayHintsConfig, error::Error,
+    field_walker::FieldResolver, server::Server, world::World,
+};
+
+/// Collects the inlay hints of a file.
+struct HintCollector<'a> {
+    world: &'a World,
+    resolver: FieldResolver<'a>,
+    config: &'a LspInlayHintsConfig,
+    file_id: FileId,
+    hints: Vec<InlayHint>,
+}
+
+/// Returns the type and contract annotations of a field definition, as they would be written in
+/// the source. Merge priorities aren't included, since they only apply to the definition they're
+/// written on and aren't propagated by merging.
+fn annotation_labels(metadata: &FieldMetadata<'_>) -> Vec<String> {
+    let annot = &metadata.annotation;
+
+    annot
+        .typ
+        .iter()
+        .chain(annot.contracts)
+        .map(Type::to_string)
+        .collect()
+}
+
+impl<'a> HintCollector<'a> {
+    /// Adds a hint right after the item at `pos`.
+    fn push(&mut self, pos: TermPos, label: String, kind: Option<InlayHintKind>) {
+        let Some(span) = pos.into_opt().filter(|span| span.src_id == self.file_id) else {
+            return;
+        };
+
+        let Ok(position) =
+            byte_index_to_position(self.world.sources.files(), span.src_id, span.end.to_usize())
+        else {
+            return;
+        };
+
+        self.hints.push(InlayHint {
+            position,
+            label: InlayHintLabel::String(label),
+            kind,
+            text_edits: None,
+            tooltip: None,
+            padding_left: Some(kind.is_none()),
+            padding_right: None,
+            data: None,
+        });
+    }
+
+    /// Adds the type inferred for the variable bound by a pattern, if the pattern is a bare
+    /// variable. Types which are not informative, such as `Dyn`, aren't shown.
+    fn inferred_type(&mut self, pattern: &Pattern<'_>) {
+        let PatternData::Any(id) = pattern.data else {
+            return;
+        };
+
+        let Some(typ) = self.world.analysis_reg.get_ident_type(&id.into()) else {
+            return;
+        };
+
+        if matches!(typ.typ, TypeF::Dyn | TypeF::Wildcard(_)) {
+            return;
+        }
+
+        self.push(id.pos, format!(": {typ}"), Some(InlayHintKind::TYPE));
+    }
+
+    /// Adds the types and contracts applied to each field defined by `record` by other records
+    /// merged with it. Annotations that are already written on the field itself
+    /// aren't repeated.
+    fn merged_annotations(&mut self, record: &'a Ast<'a>) {
+        let Node::Record(data) = &record.node else {
+            return;
+        };
+
+        for field_def in data.field_defs.iter() {
+            let mut prefix: Vec<Ident> = Vec::new();
+
+            for (index, elem) in field_def.path.iter().enumerate() {
+                // We can't resolve the cousins of a field after a dynamic path element.
+                let Some(id) = elem.try_as_ident() else {
+                    break;
+                };
+
+                // The annotations written on the field itself, which we don't need to repeat.
+                let own = (index + 1 == field_def.path.len()).then_some(&field_def.metadata);
+                let mut annots: Vec<String> = own
+                    .into_iter()
+                    .flat_map(|metadata| annotation_labels(metadata))
+                    .collect();
+                let own_count = annots.len();
+
+                let cousins =
+                    self.resolver
+                        .cousin_defs_at_path(record, prefix.iter().copied(), id.ident());
+
+                for metadata in cousins.iter().filter_map(|cousin| cousin.metadata()) {
+                    for annot in annotation_labels(metadata) {
+                        if !annots.contains(&annot) {
+                            annots.push(annot);
+                        }
+                    }
+                }
+
+                let annots = &annots[own_count..];
+
+                if !annots.is_empty() {
+                    let label = annots
+                        .iter()
+                        .map(|annot| format!("| {annot}"))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    self.push(id.pos, label, None);
+                }
+
+                prefix.push(id.ident());
+            }
+        }
+    }
+
+    fn ast(&mut self, ast: &'a Ast<'a>) {
+        match &ast.node {
+            Node::Let { bindings, .. } if self.config.types => {
+                for binding in bindings.iter() {
+                    if binding.metadata.annotation.typ.is_none() {
+                        self.inferred_type(&binding.pattern);
+                    }
+                }
+            }
+            Node::Fun { args, .. } if self.config.types => {
+                for arg in args.iter() {
+                    self.inferred_type(arg);
+                }
+            }
+            Node::Record(_) if self.config.contracts => self.merged_annotations(ast),
+            _ => {}
+        }
+    }
+}
+
+pub fn handle_inlay_hints(
+    params: InlayHintParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server
+        .world
+        .file_id(&params.text_document.uri)?
+        .ok_or_else(|| Error::FileNotFound(params.text_document.uri.clone()))?;
+
+    let world = &server.world;
+    let analysis = world.file_analysis(file_id)?;
+    let mut collector = HintCollector {
+        world,
+        resolver: FieldResolver::new(world),
+        config: &world.config.inlay_hints,
+        file_id,
+        hints: Vec::new(),
+    };
+
+    analysis.ast().traverse_ref(
+        &mut |ast, _: &()| {
+            collector.ast(ast);
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    let range = params.range;
+    let in_range = |pos: &Position| range.start <= *pos && *pos <= range.end;
+
+    let mut hints: Vec<_> = collector
+        .hints
+        .into_iter()
+        .filter(|hint| in_range(&hint.position))
+        .collect();
+    // Sort so the response is deterministic.
+    hints.sort_by_key(|hint| hint.position);
+
+    server.reply(Response::new_ok(id, hints));
+    Ok(())
+}
@@ -1,6 +1,14 @@
+pub mod code_lens;
 pub mod completion;
+pub mod document_highlight;
+pub mod document_link;
+pub mod folding_range;
 pub mod formatting;
 pub mod goto;
 pub mod hover;
+pub mod inlay_hints;
 pub mod rename;
+pub mod selection_range;
+pub mod semantic_tokens;
+pub mod signature_help;
 pub mod symbols;
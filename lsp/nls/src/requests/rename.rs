@@ -10,6 +10,9 @@ pub fn handle_rename(
     id: RequestId,
     server: &mut Server,
 ) -> Result<(), ResponseError> {
+    // Files that aren't open can also refer to this symbol.
+    server.analyze_pending_files();
+
     let pos = server.world.position(&params.text_document_position)?;
 
     let ident_data = server.world.ident_data_at(pos)?;
@@ -0,0 +1,111 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{
+    SelectionRange, SelectionRangeParams, TextDocumentIdentifier, TextDocumentPositionParams,
+};
+use nickel_lang_core::{
+    ast::{Ast, Node},
+    position::RawSpan,
+};
+
+use crate::{codespan_lsp::byte_span_to_range, server::Server, world::World};
+
+fn contains(outer: &RawSpan, inner: &RawSpan) -> bool {
+    outer.src_id == inner.src_id && outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Returns the spans enclosing `pos`, from the innermost to the outermost: the identifier under
+/// the cursor if there is one, then the most specific AST node and each of its ancestors. Field
+/// definitions don't have their own AST node, so they're inserted between a field's value and the
+/// enclosing record.
+fn enclosing_spans(world: &World, pos: nickel_lang_core::position::RawPos) -> Vec<RawSpan> {
+    let mut spans = Vec::new();
+
+    if let Ok(Some(ident)) = world.ident_at(pos) {
+        spans.extend(ident.pos.into_opt());
+    }
+
+    let Ok(Some(ast)) = world.ast_at(pos) else {
+        return spans;
+    };
+
+    let push_ast = |spans: &mut Vec<RawSpan>, ast: &Ast<'_>| {
+        let Some(span) = ast.pos.into_opt() else {
+            return;
+        };
+
+        if let (Node::Record(record), Some(last)) = (&ast.node, spans.last()) {
+            spans.extend(
+                record
+                    .field_defs
+                    .iter()
+                    .filter_map(|def| def.pos.into_opt())
+                    .find(|def_span| contains(def_span, last)),
+            );
+        }
+
+        spans.push(span);
+    };
+
+    push_ast(&mut spans, ast);
+    if let Some(mut parents) = world.analysis_reg.get_parent_chain(ast) {
+        while let Some(parent) = parents.next() {
+            push_ast(&mut spans, parent);
+        }
+    }
+
+    // Each range must strictly contain the previous one.
+    let mut growing: Vec<RawSpan> = Vec::with_capacity(spans.len());
+    for span in spans {
+        if growing
+            .last()
+            .is_none_or(|last| contains(&span, last) && span != *last)
+        {
+            growing.push(span);
+        }
+    }
+    growing
+}
+
+fn selection_range(world: &World, spans: &[RawSpan]) -> Option<SelectionRange> {
+    let mut selection: Option<SelectionRange> = None;
+
+    for span in spans.iter().rev() {
+        let range = byte_span_to_range(world.sources.files(), span.src_id, span.to_range()).ok()?;
+        selection = Some(SelectionRange {
+            range,
+            parent: selection.map(Box::new),
+        });
+    }
+
+    selection
+}
+
+pub fn handle_selection_ranges(
+    params: SelectionRangeParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let world = &server.world;
+    let mut ranges = Vec::with_capacity(params.positions.len());
+
+    for position in params.positions {
+        let pos = world.position(&TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: params.text_document.uri.clone(),
+            },
+            position,
+        })?;
+
+        // The response must have one range per requested position. When we don't know any better,
+        // we select the empty range at the position.
+        let range =
+            selection_range(world, &enclosing_spans(world, pos)).unwrap_or(SelectionRange {
+                range: lsp_types::Range::new(position, position),
+                parent: None,
+            });
+        ranges.push(range);
+    }
+
+    server.reply(Response::new_ok(id, ranges));
+    Ok(())
+}
@@ -0,0 +1,563 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{
+    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
+    SemanticTokensLegend, SemanticTokensParams, SemanticTokensRangeParams,
+};
+use nickel_lang_core::{
+    ast::{
+        Annotation, Ast, Node,
+        pattern::{Pattern, PatternData},
+        primop::PrimOp,
+        typ::{
+            Type,
+            iter::{EnumRowsItem, RecordRowsItem},
+        },
+    },
+    files::FileId,
+    identifier::LocIdent,
+    position::{RawSpan, TermPos},
+    traverse::{TraverseAlloc, TraverseControl},
+    typ::TypeF,
+};
+
+use crate::{
+    codespan_lsp::byte_span_to_range,
+    error::Error,
+    field_walker::{Def, FieldDefPiece, FieldResolver},
+    server::Server,
+    world::World,
+};
+
+/// The token types reported by the server. The index of a type in this array is its identifier
+/// in the encoded tokens.
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::TYPE_PARAMETER,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::METHOD,
+];
+
+/// The token modifiers reported by the server. The index of a modifier in this array is the bit
+/// representing it in the encoded tokens.
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenType {
+    Namespace,
+    Type,
+    TypeParameter,
+    Parameter,
+    Variable,
+    Property,
+    EnumMember,
+    Function,
+    Method,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Modifiers(u32);
+
+impl Modifiers {
+    const DECLARATION: Modifiers = Modifiers(1 << 0);
+    const DEFAULT_LIBRARY: Modifiers = Modifiers(1 << 1);
+    const NONE: Modifiers = Modifiers(0);
+}
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// A semantic token with an absolute position.
+#[derive(Clone, Debug)]
+struct Token {
+    range: Range,
+    typ: TokenType,
+    modifiers: Modifiers,
+}
+
+/// Returns `true` if `ast` is a function, possibly behind annotations.
+fn is_function(ast: &Ast<'_>) -> bool {
+    match &ast.node {
+        Node::Fun { .. } | Node::Match(_) => true,
+        Node::Annotated { inner, .. } => is_function(inner),
+        _ => false,
+    }
+}
+
+fn is_arrow(typ: Option<&Type<'_>>) -> bool {
+    matches!(
+        typ,
+        Some(Type {
+            typ: TypeF::Arrow(..),
+            ..
+        })
+    )
+}
+
+/// Returns `true` if a field is defined as a function, either directly or through its type
+/// annotation.
+pub(crate) fn is_function_field(piece: &FieldDefPiece<'_>) -> bool {
+    piece.value().is_some_and(is_function)
+        || piece
+            .metadata()
+            .is_some_and(|metadata| is_arrow(metadata.annotation.typ.as_ref()))
+}
+
+/// Collects the semantic tokens of a file, from the analysis of its AST.
+struct TokenCollector<'a> {
+    world: &'a World,
+    resolver: FieldResolver<'a>,
+    file_id: FileId,
+    tokens: Vec<Token>,
+}
+
+impl<'a> TokenCollector<'a> {
+    fn push(&mut self, pos: TermPos, typ: TokenType, modifiers: Modifiers) {
+        let Some(span) = pos.into_opt().filter(|span| span.src_id == self.file_id) else {
+            return;
+        };
+
+        let Ok(range) =
+            byte_span_to_range(self.world.sources.files(), span.src_id, span.to_range())
+        else {
+            return;
+        };
+
+        // Semantic tokens can't span several lines. Identifiers never do, save for weird quoted
+        // identifiers, which we just don't highlight.
+        if range.start.line == range.end.line {
+            self.tokens.push(Token {
+                range,
+                typ,
+                modifiers,
+            });
+        }
+    }
+
+    /// Adds the token of an enum tag. Tags don't carry their own position, but the enum variant or
+    /// the enum pattern that they start does, so we find the end of the tag in the source.
+    fn tag(&mut self, pos: TermPos) {
+        let Some(span) = pos.into_opt() else {
+            return;
+        };
+
+        let source = self.world.sources.files().source(span.src_id);
+        let Some(text) = source
+            .get(span.to_range())
+            .and_then(|text| text.strip_prefix('\''))
+        else {
+            return;
+        };
+
+        let len = if let Some(quoted) = text.strip_prefix('"') {
+            quoted.find('"').map(|end| end + 2)
+        } else {
+            Some(
+                text.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '\'')))
+                    .unwrap_or(text.len()),
+            )
+        };
+
+        if let Some(len) = len {
+            let start = span.start.to_usize();
+            let span = RawSpan::from_range(span.src_id, start..start + len + 1);
+            self.push(span.into(), TokenType::EnumMember, Modifiers::NONE);
+        }
+    }
+
+    /// Returns `true` if `id` is a reference to the standard library, that is the `std`
+    /// variable which isn't shadowed by another definition.
+    fn is_std(&self, id: LocIdent) -> bool {
+        id.label() == "std"
+            && self
+                .world
+                .analysis_reg
+                .get_def(&id.into())
+                .is_some_and(|def| !def.loc_ident().pos.is_def())
+    }
+
+    /// Returns `true` if `ast` is a chain of field accesses rooted at the standard library.
+    fn accesses_std(&self, ast: &Ast<'_>) -> bool {
+        match &ast.node {
+            Node::Var(id) => self.is_std(*id),
+            Node::PrimOpApp {
+                op: PrimOp::RecordStatAccess(_),
+                args: [record],
+            } => self.accesses_std(record),
+            _ => false,
+        }
+    }
+
+    fn var(&mut self, id: LocIdent) {
+        if self.is_std(id) {
+            self.push(id.pos, TokenType::Namespace, Modifiers::DEFAULT_LIBRARY);
+            return;
+        }
+
+        let Some(def) = self.world.analysis_reg.get_def(&id.into()) else {
+            return;
+        };
+
+        let typ = match def {
+            Def::Fn { .. } => TokenType::Parameter,
+            Def::Let { value, path, .. } if path.is_empty() && is_function(value) => {
+                TokenType::Function
+            }
+            Def::Let { .. } | Def::MatchBinding { .. } => {
+                if is_arrow(self.world.analysis_reg.get_ident_type(&def.loc_ident())) {
+                    TokenType::Function
+                } else {
+                    TokenType::Variable
+                }
+            }
+            Def::Field { pieces, .. } if pieces.iter().any(is_function_field) => TokenType::Method,
+            Def::Field { .. } => TokenType::Property,
+        };
+
+        self.push(id.pos, typ, Modifiers::NONE);
+    }
+
+    /// Adds the token of the field of a static record access.
+    fn field_access(&mut self, id: LocIdent, record: &'a Ast<'a>) {
+        let pieces: Vec<_> = self
+            .resolver
+            .resolve_record(record)
+            .iter()
+            .flat_map(|record| record.field_pieces(id.ident()))
+            .collect();
+        let is_fun = pieces.iter().any(is_function_field);
+
+        if self.accesses_std(record) {
+            let is_module = pieces.iter().any(|piece| {
+                matches!(
+                    piece.value(),
+                    Some(Ast {
+                        node: Node::Record(_),
+                        ..
+                    })
+                )
+            });
+
+            let typ = if is_fun {
+                TokenType::Function
+            } else if is_module {
+                TokenType::Namespace
+            } else {
+                TokenType::Property
+            };
+
+            self.push(id.pos, typ, Modifiers::DEFAULT_LIBRARY);
+        } else if is_fun {
+            self.push(id.pos, TokenType::Method, Modifiers::NONE);
+        } else {
+            self.push(id.pos, TokenType::Property, Modifiers::NONE);
+        }
+    }
+
+    /// Adds the tokens of the variables, enum tags and fields of a pattern. `binding` is the type
+    /// of the variables bound by the pattern.
+    fn pattern(&mut self, pattern: &Pattern<'_>, binding: TokenType) {
+        match &pattern.data {
+            PatternData::Any(id) => self.push(id.pos, binding, Modifiers::DECLARATION),
+            PatternData::Record(record) => {
+                for field in record.patterns {
+                    // The bound variable comes first, so that it takes precedence over the field
+                    // name in the shorthand form `{foo}`.
+                    self.pattern(&field.pattern, binding);
+                    self.push(field.matched_id.pos, TokenType::Property, Modifiers::NONE);
+                    self.annotation(&field.annotation);
+                }
+            }
+            PatternData::Array(array) => {
+                for pattern in array.patterns {
+                    self.pattern(pattern, binding);
+                }
+            }
+            PatternData::Enum(enum_pattern) => {
+                self.tag(enum_pattern.pos);
+
+                if let Some(pattern) = &enum_pattern.pattern {
+                    self.pattern(pattern, binding);
+                }
+            }
+            PatternData::Or(or_pattern) => {
+                for pattern in or_pattern.patterns {
+                    self.pattern(pattern, binding);
+                }
+            }
+            PatternData::Wildcard | PatternData::Constant(_) => {}
+        }
+
+        if let Some(alias) = pattern.alias {
+            self.push(alias.pos, binding, Modifiers::DECLARATION);
+        }
+    }
+
+    fn annotation(&mut self, annot: &Annotation<'_>) {
+        for typ in annot.typ.iter().chain(annot.contracts) {
+            self.typ(typ);
+        }
+    }
+
+    /// Adds the tokens of a type. The terms appearing in the type are handled by the traversal of
+    /// the enclosing term, but contracts are highlighted as types beforehand.
+    fn typ(&mut self, typ: &Type<'_>) {
+        typ.traverse_ref(
+            &mut |typ: &Type<'_>, _: &()| {
+                match &typ.typ {
+                    TypeF::Dyn
+                    | TypeF::Number
+                    | TypeF::Bool
+                    | TypeF::String
+                    | TypeF::Symbol
+                    | TypeF::ForeignId => {
+                        self.push(typ.pos, TokenType::Type, Modifiers::DEFAULT_LIBRARY)
+                    }
+                    TypeF::Var(_) => self.push(typ.pos, TokenType::TypeParameter, Modifiers::NONE),
+                    TypeF::Forall { var, .. } => {
+                        self.push(var.pos, TokenType::TypeParameter, Modifiers::DECLARATION)
+                    }
+                    TypeF::Contract(ast) => self.contract(ast),
+                    TypeF::Enum(erows) => {
+                        for item in erows.iter() {
+                            match item {
+                                // The tags of enum types have no position, so we can't highlight
+                                // them.
+                                EnumRowsItem::Row(row) => {
+                                    if let Some(typ) = row.typ {
+                                        self.typ(typ);
+                                    }
+                                }
+                                EnumRowsItem::TailVar(id) => {
+                                    self.push(id.pos, TokenType::TypeParameter, Modifiers::NONE)
+                                }
+                            }
+                        }
+                    }
+                    TypeF::Record(rrows) => {
+                        for item in rrows.iter() {
+                            match item {
+                                RecordRowsItem::Row(row) => {
+                                    self.push(row.id.pos, TokenType::Property, Modifiers::NONE)
+                                }
+                                RecordRowsItem::TailVar(id) => {
+                                    self.push(id.pos, TokenType::TypeParameter, Modifiers::NONE)
+                                }
+                                RecordRowsItem::TailDyn => {}
+                            }
+                        }
+                    }
+                    TypeF::Arrow(..)
+                    | TypeF::Dict { .. }
+                    | TypeF::Array(_)
+                    | TypeF::Wildcard(_) => {}
+                }
+
+                TraverseControl::<(), ()>::Continue
+            },
+            &(),
+        );
+    }
+
+    /// Highlights the name of a contract, such as `Foo` in `x | Foo` or `Bar` in
+    /// `x | std.foo.Bar`.
+    fn contract(&mut self, ast: &Ast<'_>) {
+        match &ast.node {
+            Node::Var(id) if !self.is_std(*id) => {
+                self.push(id.pos, TokenType::Type, Modifiers::NONE)
+            }
+            Node::PrimOpApp {
+                op: PrimOp::RecordStatAccess(id),
+                args: [record],
+            } => {
+                let modifiers = if self.accesses_std(record) {
+                    Modifiers::DEFAULT_LIBRARY
+                } else {
+                    Modifiers::NONE
+                };
+
+                self.push(id.pos, TokenType::Type, modifiers);
+            }
+            // Applied contracts, as in `x | Foo Number`.
+            Node::App { head, .. } => self.contract(head),
+            _ => {}
+        }
+    }
+
+    fn ast(&mut self, ast: &'a Ast<'a>) {
+        match &ast.node {
+            Node::Var(id) => self.var(*id),
+            Node::EnumVariant { .. } => self.tag(ast.pos),
+            Node::Fun { args, .. } => {
+                for arg in args.iter() {
+                    self.pattern(arg, TokenType::Parameter);
+                }
+            }
+            Node::Let { bindings, .. } => {
+                for binding in bindings.iter() {
+                    let typ = match binding.pattern.data {
+                        PatternData::Any(_) if is_function(&binding.value) => TokenType::Function,
+                        _ => TokenType::Variable,
+                    };
+
+                    self.pattern(&binding.pattern, typ);
+                    self.annotation(&binding.metadata.annotation);
+                }
+            }
+            Node::Match(data) => {
+                for branch in data.branches.iter() {
+                    self.pattern(&branch.pattern, TokenType::Variable);
+                }
+            }
+            Node::Record(record) => {
+                for field_def in record.field_defs.iter() {
+                    let typ = match &field_def.value {
+                        Some(value) if is_function(value) => TokenType::Method,
+                        _ => TokenType::Property,
+                    };
+
+                    for id in field_def.path.iter().filter_map(|elem| elem.try_as_ident()) {
+                        self.push(id.pos, typ, Modifiers::DECLARATION);
+                    }
+
+                    self.annotation(&field_def.metadata.annotation);
+                }
+
+                for include in record.includes.iter() {
+                    self.push(
+                        include.ident.pos,
+                        TokenType::Property,
+                        Modifiers::DECLARATION,
+                    );
+                    self.annotation(&include.metadata.annotation);
+                }
+            }
+            Node::PrimOpApp {
+                op: PrimOp::RecordStatAccess(id),
+                args: [record],
+            } => self.field_access(*id, record),
+            Node::Annotated { annot, .. } => self.annotation(annot),
+            Node::Type(typ) => self.typ(typ),
+            _ => {}
+        }
+    }
+
+    /// Returns the tokens sorted by position. When several tokens start at the same position,
+    /// only the first one added is kept: this gives precedence to the context of an identifier
+    /// (for example, being used as a contract) over its generic classification.
+    fn finish(mut self) -> Vec<Token> {
+        // The sort is stable, which is important for deduplication.
+        self.tokens.sort_by_key(|token| token.range.start);
+        self.tokens.dedup_by_key(|token| token.range.start);
+        self.tokens
+    }
+}
+
+fn collect_tokens(world: &World, file_id: FileId) -> Result<Vec<Token>, ResponseError> {
+    let analysis = world.file_analysis(file_id)?;
+    let mut collector = TokenCollector {
+        world,
+        resolver: FieldResolver::new(world),
+        file_id,
+        tokens: Vec::new(),
+    };
+
+    analysis.ast().traverse_ref(
+        &mut |ast, _: &()| {
+            collector.ast(ast);
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    Ok(collector.finish())
+}
+
+/// Encodes tokens sorted by position in the relative format of the LSP.
+fn encode(tokens: impl IntoIterator<Item = Token>) -> Vec<SemanticToken> {
+    let mut prev_line = 0;
+    let mut prev_start = 0;
+
+    tokens
+        .into_iter()
+        .map(|token| {
+            let start = token.range.start;
+            let delta_line = start.line - prev_line;
+            let delta_start = if delta_line == 0 {
+                start.character - prev_start
+            } else {
+                start.character
+            };
+
+            prev_line = start.line;
+            prev_start = start.character;
+
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: token.range.end.character - start.character,
+                token_type: token.typ as u32,
+                token_modifiers_bitset: token.modifiers.0,
+            }
+        })
+        .collect()
+}
+
+fn file_id(server: &Server, uri: &lsp_types::Url) -> Result<FileId, ResponseError> {
+    Ok(server
+        .world
+        .file_id(uri)?
+        .ok_or_else(|| Error::FileNotFound(uri.clone()))?)
+}
+
+pub fn handle_semantic_tokens_full(
+    params: SemanticTokensParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = file_id(server, &params.text_document.uri)?;
+    let tokens = collect_tokens(&server.world, file_id)?;
+
+    server.reply(Response::new_ok(
+        id,
+        SemanticTokens {
+            result_id: None,
+            data: encode(tokens),
+        },
+    ));
+    Ok(())
+}
+
+pub fn handle_semantic_tokens_range(
+    params: SemanticTokensRangeParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = file_id(server, &params.text_document.uri)?;
+    let tokens = collect_tokens(&server.world, file_id)?;
+    let range = params.range;
+
+    server.reply(Response::new_ok(
+        id,
+        SemanticTokens {
+            result_id: None,
+            data: encode(
+                tokens
+                    .into_iter()
+                    .filter(|token| token.range.start < range.end && token.range.end > range.start),
+            ),
+        },
+    ));
+    Ok(())
+}
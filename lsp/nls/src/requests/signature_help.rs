@@ -0,0 +1,190 @@
+use codespan::ByteIndex;
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{
+    Documentation, MarkupContent, MarkupKind, ParameterInformation, ParameterLabel, SignatureHelp,
+    SignatureHelpParams, SignatureInformation,
+};
+use nickel_lang_core::{
+    ast::{Ast, Node, primop::PrimOp, typ::Type},
+    combine::Combine,
+    position::RawPos,
+    typ::TypeF,
+};
+use serde_json::Value;
+
+use crate::{
+    requests::hover::{HoverData, ident_hover, term_hover},
+    server::Server,
+    world::World,
+};
+
+/// A function being applied at the cursor, together with the index of the argument under the
+/// cursor.
+struct CallSite<'ast> {
+    head: &'ast Ast<'ast>,
+    active: usize,
+}
+
+/// Returns the position of the last non-whitespace character before the cursor, if any.
+fn last_non_whitespace(world: &World, pos: RawPos) -> Option<RawPos> {
+    let source = world.sources.files().source(pos.src_id);
+    let before = source.get(..pos.index.to_usize())?;
+    let (idx, _) = before.char_indices().rfind(|(_, c)| !c.is_whitespace())?;
+    Some(RawPos::new(pos.src_id, ByteIndex(idx as u32)))
+}
+
+/// Returns `true` if `ast` is a variable or a chain of static field accesses on a variable, such
+/// as `std.array.fold_left`.
+fn is_path(ast: &Ast<'_>) -> bool {
+    match &ast.node {
+        Node::Var(_) => true,
+        Node::PrimOpApp {
+            op: PrimOp::RecordStatAccess(_),
+            args: [parent],
+        } => is_path(parent),
+        _ => false,
+    }
+}
+
+/// Finds the innermost function application around the cursor. Applications whose head contains
+/// the cursor are skipped, since the cursor isn't on any of their arguments.
+///
+/// When the cursor is separated by whitespace from a function that isn't applied yet, as in
+/// `std.array.fold_left |`, we consider it to be a call site for the first argument.
+fn call_site<'ast>(world: &'ast World, pos: RawPos) -> Option<CallSite<'ast>> {
+    let anchor_pos = last_non_whitespace(world, pos)?;
+    let anchor = world.ast_at(anchor_pos).ok()??;
+    let cursor = pos.index;
+
+    let ends_before_cursor =
+        |ast: &Ast<'_>| ast.pos.into_opt().is_some_and(|span| span.end < cursor);
+
+    let mut ancestors = world.analysis_reg.get_parent_chain(anchor);
+    let mut ast = Some(anchor);
+
+    while let Some(current) = ast {
+        if let Node::App { head, args } = &current.node
+            && ends_before_cursor(head)
+        {
+            let active = args.iter().filter(|arg| ends_before_cursor(arg)).count();
+            return Some(CallSite { head, active });
+        }
+
+        ast = ancestors.as_mut().and_then(|ancestors| ancestors.next());
+    }
+
+    (is_path(anchor) && ends_before_cursor(anchor)).then_some(CallSite {
+        head: anchor,
+        active: 0,
+    })
+}
+
+/// Gathers the type information and the metadata of the function being applied, in the same
+/// way as hovering over it would.
+fn head_data<'ast>(world: &'ast World, head: &'ast Ast<'ast>) -> Option<HoverData<'ast>> {
+    let term_data = term_hover(head, world);
+
+    if let Node::Var(id) = &head.node {
+        Combine::combine(ident_hover((*id).into(), world), term_data)
+    } else {
+        term_data
+    }
+}
+
+/// Strips the leading `forall`s of a type and returns the domains of its arrows.
+fn arrow_domains<'a, 'ast>(mut typ: &'a Type<'ast>) -> Vec<&'a Type<'ast>> {
+    let mut params = Vec::new();
+
+    while let TypeF::Forall { body, .. } = &typ.typ {
+        typ = body;
+    }
+
+    while let TypeF::Arrow(dom, codom) = &typ.typ {
+        params.push(*dom);
+        typ = codom;
+    }
+
+    params
+}
+
+/// Builds the signature of a function from its type. The parameters are given as offsets into the
+/// label, so that the editor can highlight the active one.
+fn signature(typ: &Type<'_>, doc: Option<&str>, active: usize) -> SignatureInformation {
+    let label = typ.to_string();
+    let utf16_len = |s: &str| s.encode_utf16().count() as u32;
+
+    let mut offset = 0;
+    let parameters: Vec<_> = arrow_domains(typ)
+        .into_iter()
+        .map(|param| {
+            let param = param.to_string();
+            let start = label[offset..]
+                .find(&param)
+                .map(|idx| offset + idx)
+                .unwrap_or(offset);
+            offset = (start + param.len()).min(label.len());
+
+            ParameterInformation {
+                label: ParameterLabel::LabelOffsets([
+                    utf16_len(&label[..start]),
+                    utf16_len(&label[..offset]),
+                ]),
+                documentation: None,
+            }
+        })
+        .collect();
+
+    // If the function is applied to more arguments than its type has arrows, then it returns
+    // another function whose signature we don't know.
+    let active_parameter = (active < parameters.len()).then_some(active as u32);
+
+    SignatureInformation {
+        label,
+        documentation: doc.map(|doc| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: doc.to_owned(),
+            })
+        }),
+        parameters: Some(parameters),
+        active_parameter,
+    }
+}
+
+pub fn handle_signature_help(
+    params: SignatureHelpParams,
+    req_id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let pos = server
+        .world
+        .position(&params.text_document_position_params)?;
+    let world = &server.world;
+
+    let help = call_site(world, pos).and_then(|site| {
+        let data = head_data(world, site.head)?;
+        // We prefer the type inferred by the typechecker, and fall back to the first annotation
+        // that looks like a function type.
+        let typ = data
+            .ty
+            .into_iter()
+            .chain(data.annotations())
+            .find(|typ| !arrow_domains(typ).is_empty())?;
+
+        let signature = signature(typ, data.doc(), site.active);
+        let active_parameter = signature.active_parameter;
+
+        Some(SignatureHelp {
+            signatures: vec![signature],
+            active_signature: Some(0),
+            active_parameter,
+        })
+    });
+
+    match help {
+        Some(help) => server.reply(Response::new_ok(req_id, help)),
+        None => server.reply(Response::new_ok(req_id, Value::Null)),
+    }
+
+    Ok(())
+}
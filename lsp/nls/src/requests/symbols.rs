@@ -1,8 +1,12 @@
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{DocumentSymbol, DocumentSymbolParams, SymbolKind};
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolParams, Location, OneOf, SymbolKind, Url, WorkspaceSymbol,
+    WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
 
 use nickel_lang_core::{
     ast::{Ast, record::Record as RecordData, typ::Type},
+    files::FileId,
     identifier::Ident,
 };
 
@@ -26,6 +30,7 @@ fn symbols<'ast>(
     world: &'ast World,
     type_lookups: &CollectedTypes<'ast, Type<'ast>>,
     ast: &'ast Ast<'ast>,
+    only_in: Option<FileId>,
     max_depth: usize,
 ) -> Vec<DocumentSymbol> {
     let resolver = FieldResolver::new(world);
@@ -33,9 +38,11 @@ fn symbols<'ast>(
     root_records
         .into_iter()
         .flat_map(|rec| match rec {
-            Record::Term(data) => record_symbols(world, type_lookups, data, max_depth),
+            Record::Term(data) => record_symbols(world, type_lookups, data, only_in, max_depth),
             Record::Type(_) => Vec::new(),
-            Record::FieldDefPiece(fdp) => def_piece_symbols(world, type_lookups, fdp, max_depth),
+            Record::FieldDefPiece(fdp) => {
+                def_piece_symbols(world, type_lookups, fdp, only_in, max_depth)
+            }
         })
         .collect()
 }
@@ -44,6 +51,7 @@ fn record_symbols<'ast>(
     world: &'ast World,
     type_lookups: &CollectedTypes<'ast, Type<'ast>>,
     record: &'ast RecordData<'ast>,
+    only_in: Option<FileId>,
     max_depth: usize,
 ) -> Vec<DocumentSymbol> {
     record
@@ -55,6 +63,7 @@ fn record_symbols<'ast>(
                 type_lookups,
                 id,
                 &fields.into_iter().map(|fd| fd.into()).collect::<Vec<_>>(),
+                only_in,
                 max_depth,
             )
         })
@@ -65,25 +74,37 @@ fn def_piece_symbols<'ast>(
     world: &'ast World,
     type_lookups: &CollectedTypes<'ast, Type<'ast>>,
     def_piece: FieldDefPiece<'ast>,
+    only_in: Option<FileId>,
     max_depth: usize,
 ) -> Vec<DocumentSymbol> {
     def_piece
         .ident()
         .map(|id| {
-            def_pieces_symbols(world, type_lookups, id.ident, &[def_piece], max_depth)
-                .into_iter()
-                .collect()
+            def_pieces_symbols(
+                world,
+                type_lookups,
+                id.ident,
+                &[def_piece],
+                only_in,
+                max_depth,
+            )
+            .into_iter()
+            .collect()
         })
         .unwrap_or_default()
 }
 
 /// Return symbols for a definition consisting of an identifier and its definition pieces. The
 /// pieces are assumed to be such that `piece.field_def.path[piece.index]` is equal to `id`.
+///
+/// If `only_in` is set, symbols defined in other files (for example in an imported record) are
+/// skipped.
 fn def_pieces_symbols<'ast>(
     world: &'ast World,
     type_lookups: &CollectedTypes<'ast, Type<'ast>>,
     id: Ident,
     fields: &[FieldDefPiece<'ast>],
+    only_in: Option<FileId>,
     max_depth: usize,
 ) -> Option<DocumentSymbol> {
     // Unfortunately, we can't return several ranges for a single symbol.
@@ -106,6 +127,11 @@ fn def_pieces_symbols<'ast>(
     let ty = type_lookups.idents.get(&loc_id);
     let pos_id = pos_id.into_opt()?;
     let file_id = pos_id.src_id;
+
+    if only_in.is_some_and(|only_in| only_in != file_id) {
+        return None;
+    }
+
     let id_range = pos_id.to_range();
     let val_span = selected_def.span().unwrap_or(pos_id);
 
@@ -124,7 +150,7 @@ fn def_pieces_symbols<'ast>(
         fields
             .iter()
             .filter_map(|def_piece| def_piece.field_def.value.as_ref())
-            .flat_map(|v| symbols(world, type_lookups, v, depth))
+            .flat_map(|v| symbols(world, type_lookups, v, only_in, depth))
             .collect()
     });
 
@@ -156,7 +182,7 @@ pub fn handle_document_symbols(
     let type_lookups = &analysis.analysis().type_lookup;
     let ast = analysis.ast();
 
-    let mut symbols = symbols(&server.world, type_lookups, ast, MAX_SYMBOL_DEPTH);
+    let mut symbols = symbols(&server.world, type_lookups, ast, None, MAX_SYMBOL_DEPTH);
     // Sort so the response is deterministic.
     symbols.sort_by_key(|s| s.range.start);
 
@@ -164,3 +190,91 @@ pub fn handle_document_symbols(
 
     Ok(())
 }
+
+/// Returns `true` if the characters of `query` appear in `name` in the same order, ignoring case.
+/// This is the kind of fuzzy matching that editors expect from workspace symbol search.
+fn fuzzy_match(name: &str, query: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| name.any(|c| c == q))
+}
+
+/// Flattens a hierarchy of document symbols into workspace symbols, keeping those matching the
+/// query. The container of a nested symbol is the path of the fields it's defined in.
+fn flatten_symbols(
+    symbols: Vec<DocumentSymbol>,
+    uri: &Url,
+    container: Option<&str>,
+    query: &str,
+    acc: &mut Vec<WorkspaceSymbol>,
+) {
+    for symbol in symbols {
+        let path = match container {
+            Some(container) => format!("{container}.{}", symbol.name),
+            None => symbol.name.clone(),
+        };
+
+        if fuzzy_match(&symbol.name, query) {
+            acc.push(WorkspaceSymbol {
+                name: symbol.name,
+                kind: symbol.kind,
+                tags: None,
+                container_name: container.map(ToOwned::to_owned),
+                location: OneOf::Left(Location {
+                    uri: uri.clone(),
+                    range: symbol.selection_range,
+                }),
+                data: None,
+            });
+        }
+
+        if let Some(children) = symbol.children {
+            flatten_symbols(children, uri, Some(&path), query, acc);
+        }
+    }
+}
+
+pub fn handle_workspace_symbols(
+    params: WorkspaceSymbolParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    server.analyze_pending_files();
+
+    let world = &server.world;
+    let mut result = Vec::new();
+
+    for (file_id, uri) in &world.file_uris {
+        let Some(analysis) = world.analysis_reg.get(*file_id) else {
+            continue;
+        };
+
+        let type_lookups = &analysis.analysis().type_lookup;
+        let symbols = symbols(
+            world,
+            type_lookups,
+            analysis.ast(),
+            Some(*file_id),
+            MAX_SYMBOL_DEPTH,
+        );
+        flatten_symbols(symbols, uri, None, &params.query, &mut result);
+    }
+
+    // Sort so the response is deterministic.
+    result.sort_by(|s1, s2| {
+        let key = |s: &WorkspaceSymbol| match &s.location {
+            OneOf::Left(loc) => (loc.uri.to_string(), loc.range.start),
+            OneOf::Right(loc) => (loc.uri.to_string(), Default::default()),
+        };
+        key(s1).cmp(&key(s2))
+    });
+
+    server.reply(Response::new_ok(
+        id,
+        WorkspaceSymbolResponse::Nested(result),
+    ));
+
+    Ok(())
+}
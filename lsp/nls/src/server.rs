@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 use crossbeam::select;
@@ -7,30 +10,41 @@ use lsp_server::{
     Connection, ErrorCode, Message, Notification, RequestId, Response, ResponseError,
 };
 use lsp_types::{
-    CodeActionParams, CompletionOptions, CompletionParams, DiagnosticOptions,
-    DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportResult,
-    DocumentFormattingParams, DocumentSymbolParams, ExecuteCommandParams,
+    CodeAction, CodeActionParams, CodeLensOptions, CodeLensParams, CompletionOptions,
+    CompletionParams, DiagnosticOptions, DocumentDiagnosticParams, DocumentDiagnosticReport,
+    DocumentDiagnosticReportResult, DocumentFormattingParams, DocumentHighlightParams,
+    DocumentLinkOptions, DocumentLinkParams, DocumentSymbolParams, ExecuteCommandParams,
+    FileChangeType, FoldingRangeParams, FoldingRangeProviderCapability,
     FullDocumentDiagnosticReport, GotoDefinitionParams, HoverOptions, HoverParams,
-    HoverProviderCapability, OneOf, PublishDiagnosticsParams, ReferenceParams,
-    RelatedFullDocumentDiagnosticReport, RenameParams, ServerCapabilities,
-    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions, Url,
-    WorkDoneProgressOptions,
+    HoverProviderCapability, InlayHintParams, OneOf, PublishDiagnosticsParams, ReferenceParams,
+    RelatedFullDocumentDiagnosticReport, RenameParams, SelectionRangeParams,
+    SelectionRangeProviderCapability, SemanticTokensFullOptions, SemanticTokensOptions,
+    SemanticTokensParams, SemanticTokensRangeParams, SemanticTokensServerCapabilities,
+    ServerCapabilities, SignatureHelpOptions, SignatureHelpParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextDocumentSyncOptions, Url, WorkDoneProgressOptions,
+    WorkspaceSymbolParams,
     request::{Request as RequestTrait, *},
 };
 use nickel_lang_core::files::FileId;
 
 use crate::{
     actions,
-    background::{self, BackgroundJobs},
+    background::{self, BackgroundJobs, SettingsLoader},
     command,
-    config::LspConfig,
-    requests::{completion, formatting, goto, hover, rename, symbols},
+    config::{LspConfig, SETTINGS_FILE_NAME},
+    files::uri_to_path,
+    requests::{
+        code_lens, completion, document_highlight, document_link, folding_range, formatting, goto,
+        hover, inlay_hints, rename, selection_range, semantic_tokens, signature_help, symbols,
+    },
     task_queue::{DocumentSync, Task, TaskQueue},
     trace::Trace,
+    workspace::WorkspaceIndexer,
     world::World,
 };
 
 pub const COMPLETIONS_TRIGGERS: &[&str] = &[".", "\"", "/"];
+pub const SIGNATURE_HELP_TRIGGERS: &[&str] = &[" ", "("];
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum Shutdown {
@@ -49,6 +63,11 @@ pub struct Server {
     pub world: World,
     pub background_jobs: BackgroundJobs,
     pub task_queue: TaskQueue,
+    /// The files of the workspace that haven't been analyzed yet.
+    pub indexer: WorkspaceIndexer,
+    /// Requests about the whole workspace, which wait for the indexer to be done.
+    waiting_for_index: Vec<lsp_server::Request>,
+    settings_loader: SettingsLoader,
 }
 
 impl Server {
@@ -57,7 +76,7 @@ impl Server {
             text_document_sync: Some(TextDocumentSyncCapability::Options(
                 TextDocumentSyncOptions {
                     open_close: Some(true),
-                    change: Some(TextDocumentSyncKind::FULL),
+                    change: Some(TextDocumentSyncKind::INCREMENTAL),
                     ..TextDocumentSyncOptions::default()
                 },
             )),
@@ -68,6 +87,7 @@ impl Server {
             })),
             definition_provider: Some(OneOf::Left(true)),
             references_provider: Some(OneOf::Left(true)),
+            document_highlight_provider: Some(OneOf::Left(true)),
             completion_provider: Some(CompletionOptions {
                 trigger_characters: Some(
                     COMPLETIONS_TRIGGERS.iter().map(|s| s.to_string()).collect(),
@@ -75,13 +95,46 @@ impl Server {
                 ..Default::default()
             }),
             document_symbol_provider: Some(OneOf::Left(true)),
+            workspace_symbol_provider: Some(OneOf::Left(true)),
+            document_link_provider: Some(DocumentLinkOptions {
+                resolve_provider: Some(false),
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+            }),
             document_formatting_provider: Some(OneOf::Left(true)),
-            code_action_provider: Some(lsp_types::CodeActionProviderCapability::Simple(true)),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+            selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+            code_lens_provider: Some(CodeLensOptions {
+                resolve_provider: Some(false),
+            }),
+            code_action_provider: Some(lsp_types::CodeActionProviderCapability::Options(
+                lsp_types::CodeActionOptions {
+                    resolve_provider: Some(true),
+                    ..Default::default()
+                },
+            )),
             execute_command_provider: Some(lsp_types::ExecuteCommandOptions {
-                commands: vec!["eval".to_owned()],
+                commands: command::COMMANDS.iter().map(|s| s.to_string()).collect(),
                 ..Default::default()
             }),
             rename_provider: Some(OneOf::Left(true)),
+            semantic_tokens_provider: Some(
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    range: Some(true),
+                    ..Default::default()
+                }),
+            ),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            signature_help_provider: Some(SignatureHelpOptions {
+                trigger_characters: Some(
+                    SIGNATURE_HELP_TRIGGERS
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                ),
+                ..Default::default()
+            }),
             diagnostic_provider: Some(lsp_types::DiagnosticServerCapabilities::Options(
                 DiagnosticOptions {
                     inter_file_dependencies: true,
@@ -97,8 +150,42 @@ impl Server {
             connection,
             last_diagnostics: HashMap::new(),
             background_jobs: BackgroundJobs::new(config.eval_config.clone()),
+            settings_loader: SettingsLoader::new(config.eval_config.eval_limits.timeout),
             world: World::new(config),
             task_queue: TaskQueue::new(),
+            indexer: WorkspaceIndexer::default(),
+            waiting_for_index: Vec::new(),
+        }
+    }
+
+    /// Schedules the indexing of the Nickel files found in the given workspace folders, which are
+    /// also the only places where we look for project settings.
+    pub fn index_workspace(&mut self, roots: Vec<PathBuf>) {
+        self.world.set_workspace_roots(roots.clone());
+        self.indexer = WorkspaceIndexer::new(roots);
+    }
+
+    fn index_file(&mut self, path: &Path) {
+        debug!("Indexing {}", path.display());
+
+        if let Err(err) = self.world.index_file(path) {
+            warn!("Failed to index {}: {err}", path.display());
+        }
+    }
+
+    /// Analyzes the files whose diagnostics are still pending. This is used by requests whose
+    /// result would be incomplete otherwise, such as workspace symbols or references.
+    pub(crate) fn analyze_pending_files(&mut self) {
+        let stale: Vec<_> = self
+            .world
+            .file_uris
+            .keys()
+            .filter(|file_id| self.world.analysis_reg.get(**file_id).is_none())
+            .copied()
+            .collect();
+
+        for file_id in stale {
+            self.world.parse_and_typecheck(file_id);
         }
     }
 
@@ -140,8 +227,13 @@ impl Server {
     pub fn run(&mut self) -> Result<()> {
         trace!("Running...");
         loop {
+            for dir in self.world.take_pending_settings() {
+                self.settings_loader.load(dir);
+            }
+
             let never = crossbeam::channel::never();
             let bg = self.background_jobs.receiver().unwrap_or(&never);
+            let settings = self.settings_loader.receiver();
 
             if let Ok(msg) = self.connection.receiver.try_recv() {
                 let result = self.task_queue.queue_message(msg);
@@ -150,10 +242,22 @@ impl Server {
                 }
             } else if let Ok(diagnostics) = bg.try_recv() {
                 self.publish_background_diagnostics(diagnostics);
+            } else if let Ok(loaded) = settings.try_recv() {
+                self.apply_loaded_settings(loaded);
             } else if let Some(task) = self.task_queue.next_task() {
                 if self.handle_task(task)? == Shutdown::Shutdown {
                     break;
                 }
+            } else if !self.indexer.is_done() {
+                // Index the workspace one file at a time, so that we can handle any incoming
+                // message in between.
+                if let Some(path) = self.indexer.next_file() {
+                    self.index_file(&path);
+                }
+            } else if !self.waiting_for_index.is_empty() {
+                for req in std::mem::take(&mut self.waiting_for_index) {
+                    self.handle_request(req)?;
+                }
             } else {
                 select! {
                     recv(self.connection.receiver) -> msg => {
@@ -171,6 +275,10 @@ impl Server {
                         // Failure here means our background thread panicked, and that's a bug.
                         self.publish_background_diagnostics(msg.unwrap());
                     }
+                    recv(settings) -> msg => {
+                        // Same as above.
+                        self.apply_loaded_settings(msg.unwrap());
+                    }
                 }
             }
         }
@@ -185,6 +293,12 @@ impl Server {
         self.publish_diagnostics(uri, diagnostics);
     }
 
+    fn apply_loaded_settings(&mut self, loaded: background::LoadedSettings) {
+        if self.world.set_settings(loaded.dir, loaded.settings) {
+            self.apply_settings();
+        }
+    }
+
     fn handle_task(&mut self, task: Task) -> Result<Shutdown> {
         match task {
             Task::HandleRequest(req) => {
@@ -231,6 +345,8 @@ impl Server {
                 trace!("handle open notification");
                 let uri = params.text_document.uri.clone();
                 let invalid = crate::files::handle_open(self, params)?;
+                // Opening a file may have brought new project settings in.
+                self.restart_background_jobs_if_needed();
                 self.task_queue.add_diagnostics_task(uri);
                 for uri in invalid {
                     self.task_queue.add_diagnostics_task(uri);
@@ -259,10 +375,100 @@ impl Server {
                 }
                 Ok(())
             }
+            DocumentSync::ChangeConfiguration(params) => {
+                trace!("handle configuration change");
+                // Clients usually send all of their settings, in which case ours are in the `nls`
+                // section.
+                let settings = match params.settings {
+                    serde_json::Value::Object(mut settings) if settings.contains_key("nls") => {
+                        settings.remove("nls").unwrap()
+                    }
+                    settings => settings,
+                };
+                // Some clients send empty settings, either because they expect us to pull the
+                // configuration, which we don't support, or because the user didn't configure
+                // anything. Clients may also send partial settings, or settings that aren't ours,
+                // like the ones of the VS Code extension. So we update the configuration, starting
+                // from the initialization options, rather than replacing it.
+                if settings.is_null() {
+                    return Ok(());
+                }
+                if self.world.config.merge(settings)? {
+                    self.apply_settings();
+                }
+                Ok(())
+            }
+            DocumentSync::ChangeWatchedFiles(params) => {
+                trace!("handle watched files change");
+                for change in params.changes {
+                    let Ok(path) = uri_to_path(&change.uri) else {
+                        continue;
+                    };
+                    if let Some(dir) = path.parent()
+                        && path.file_name() == Some(SETTINGS_FILE_NAME.as_ref())
+                    {
+                        // The settings are applied once they're loaded.
+                        self.world.reload_settings(dir);
+                    } else {
+                        self.refresh_from_disk(path, change.typ);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Forgets the analyses that depend on a file which changed on disk. Files which aren't open
+    /// are analyzed again by the indexer, and the diagnostics of open files are refreshed.
+    fn refresh_from_disk(&mut self, path: PathBuf, change: FileChangeType) {
+        let invalid = self.world.invalidate_from_disk(&path);
+
+        for file_id in invalid {
+            if let Some(path) = self.world.sources.filesystem_path(file_id) {
+                self.indexer.push(path.to_owned());
+            } else if let Some(uri) = self.world.file_uris.get(&file_id) {
+                self.task_queue.add_diagnostics_task(uri.clone());
+            }
+        }
+
+        if change != FileChangeType::DELETED {
+            self.indexer.push(path);
+        }
+    }
+
+    /// Applies a change of the configuration or of the project settings, and refreshes the
+    /// diagnostics of every file that has some.
+    fn apply_settings(&mut self) {
+        self.world.apply_settings();
+        self.restart_background_jobs_if_needed();
+
+        for uri in self.last_diagnostics.keys() {
+            self.task_queue.add_diagnostics_task(uri.clone());
+        }
+    }
+
+    /// Restarts the background evaluator if its configuration changed, which happens when the
+    /// configuration of the server changes but also when we find new project settings.
+    fn restart_background_jobs_if_needed(&mut self) {
+        let config = self.world.background_eval_config();
+        if *self.background_jobs.config() != config {
+            self.background_jobs = BackgroundJobs::new(config);
         }
     }
 
     fn handle_request(&mut self, req: lsp_server::Request) -> Result<()> {
+        // Files that aren't open can also be relevant to these requests, so they wait until the
+        // workspace is indexed. We don't index everything right away, to keep handling the other
+        // messages in the meantime.
+        if matches!(
+            req.method.as_str(),
+            References::METHOD | Rename::METHOD | WorkspaceSymbolRequest::METHOD
+        ) && !self.indexer.is_done()
+        {
+            self.waiting_for_index.push(req);
+            return Ok(());
+        }
+
         Trace::receive(req.id.clone(), req.method.clone());
 
         let res = match req.method.as_str() {
@@ -295,6 +501,42 @@ impl Server {
                 symbols::handle_document_symbols(params, req.id.clone(), self)
             }
 
+            WorkspaceSymbolRequest::METHOD => {
+                debug!("handle workspace symbols");
+                let params: WorkspaceSymbolParams = serde_json::from_value(req.params).unwrap();
+                symbols::handle_workspace_symbols(params, req.id.clone(), self)
+            }
+
+            DocumentLinkRequest::METHOD => {
+                debug!("handle document links");
+                let params: DocumentLinkParams = serde_json::from_value(req.params).unwrap();
+                document_link::handle_document_links(params, req.id.clone(), self)
+            }
+
+            CodeLensRequest::METHOD => {
+                debug!("handle code lens");
+                let params: CodeLensParams = serde_json::from_value(req.params).unwrap();
+                code_lens::handle_code_lens(params, req.id.clone(), self)
+            }
+
+            FoldingRangeRequest::METHOD => {
+                debug!("handle folding ranges");
+                let params: FoldingRangeParams = serde_json::from_value(req.params).unwrap();
+                folding_range::handle_folding_ranges(params, req.id.clone(), self)
+            }
+
+            SelectionRangeRequest::METHOD => {
+                debug!("handle selection ranges");
+                let params: SelectionRangeParams = serde_json::from_value(req.params).unwrap();
+                selection_range::handle_selection_ranges(params, req.id.clone(), self)
+            }
+
+            DocumentHighlightRequest::METHOD => {
+                debug!("handle document highlight");
+                let params: DocumentHighlightParams = serde_json::from_value(req.params).unwrap();
+                document_highlight::handle_document_highlight(params, req.id.clone(), self)
+            }
+
             Formatting::METHOD => {
                 debug!("handle formatting");
                 let params: DocumentFormattingParams = serde_json::from_value(req.params).unwrap();
@@ -307,6 +549,12 @@ impl Server {
                 actions::handle_code_action(params, req.id.clone(), self)
             }
 
+            CodeActionResolveRequest::METHOD => {
+                debug!("code action resolve");
+                let action: CodeAction = serde_json::from_value(req.params).unwrap();
+                actions::handle_resolve_code_action(action, req.id.clone(), self)
+            }
+
             ExecuteCommand::METHOD => {
                 debug!("command");
                 let params: ExecuteCommandParams = serde_json::from_value(req.params).unwrap();
@@ -319,6 +567,30 @@ impl Server {
                 rename::handle_rename(params, req.id.clone(), self)
             }
 
+            SemanticTokensFullRequest::METHOD => {
+                debug!("semantic tokens");
+                let params: SemanticTokensParams = serde_json::from_value(req.params).unwrap();
+                semantic_tokens::handle_semantic_tokens_full(params, req.id.clone(), self)
+            }
+
+            SemanticTokensRangeRequest::METHOD => {
+                debug!("semantic tokens for a range");
+                let params: SemanticTokensRangeParams = serde_json::from_value(req.params).unwrap();
+                semantic_tokens::handle_semantic_tokens_range(params, req.id.clone(), self)
+            }
+
+            SignatureHelpRequest::METHOD => {
+                debug!("signature help");
+                let params: SignatureHelpParams = serde_json::from_value(req.params).unwrap();
+                signature_help::handle_signature_help(params, req.id.clone(), self)
+            }
+
+            InlayHintRequest::METHOD => {
+                debug!("inlay hints");
+                let params: InlayHintParams = serde_json::from_value(req.params).unwrap();
+                inlay_hints::handle_inlay_hints(params, req.id.clone(), self)
+            }
+
             DocumentDiagnosticRequest::METHOD => {
                 debug!("diagnostic request");
                 let params: DocumentDiagnosticParams = serde_json::from_value(req.params).unwrap();
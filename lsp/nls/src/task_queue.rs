@@ -7,11 +7,12 @@ use anyhow::Result;
 use log::debug;
 use lsp_server::{Message, Notification, RequestId};
 use lsp_types::{
-    CancelParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
-    DidOpenTextDocumentParams, NumberOrString, Url,
+    CancelParams, DidChangeConfigurationParams, DidChangeTextDocumentParams,
+    DidChangeWatchedFilesParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    NumberOrString, Url,
     notification::{
-        Cancel, DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
-        Notification as NotificationTrait,
+        Cancel, DidChangeConfiguration, DidChangeTextDocument, DidChangeWatchedFiles,
+        DidCloseTextDocument, DidOpenTextDocument, Notification as NotificationTrait,
     },
     request::{ExecuteCommand, Request as RequestTrait},
 };
@@ -45,6 +46,10 @@ pub enum DocumentSync {
     Open(DidOpenTextDocumentParams),
     Close(DidCloseTextDocumentParams),
     Change(DidChangeTextDocumentParams),
+    /// The configuration of the server changed.
+    ChangeConfiguration(DidChangeConfigurationParams),
+    /// Files watched by the client changed on disk.
+    ChangeWatchedFiles(DidChangeWatchedFilesParams),
 }
 
 /// Something that is either an LSP request or a document synchronization notification.
@@ -125,6 +130,16 @@ impl TaskQueue {
                     serde_json::from_value::<DidChangeTextDocumentParams>(notification.params)?;
                 self.add_sync_task(DocumentSync::Change(params));
             }
+            DidChangeConfiguration::METHOD => {
+                let params =
+                    serde_json::from_value::<DidChangeConfigurationParams>(notification.params)?;
+                self.add_sync_task(DocumentSync::ChangeConfiguration(params));
+            }
+            DidChangeWatchedFiles::METHOD => {
+                let params =
+                    serde_json::from_value::<DidChangeWatchedFilesParams>(notification.params)?;
+                self.add_sync_task(DocumentSync::ChangeWatchedFiles(params));
+            }
             Cancel::METHOD => {
                 let params = serde_json::from_value::<CancelParams>(notification.params)?;
                 let id = match params.id {
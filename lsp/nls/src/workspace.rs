@@ -0,0 +1,100 @@
+//! Indexing of the files of the workspace.
+//!
+//! NLS only knows about the files that are open in the editor and the files they import. In order
+//! for workspace symbols, references and renaming to cover the whole project, we walk the
+//! workspace folders and analyze the Nickel files we find there, one at a time, whenever the
+//! server has nothing better to do. Requests about the whole workspace wait for the indexing to be
+//! done, and files are indexed again when they change on disk.
+
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use lsp_types::Url;
+use nickel_lang_core::cache::InputFormat;
+use serde_json::Value;
+
+/// The paths of the workspace left to index.
+///
+/// Directories are only read when we get to them, so that creating the indexer for a large
+/// workspace doesn't block the server.
+#[derive(Debug, Default)]
+pub struct WorkspaceIndexer {
+    pending: VecDeque<PathBuf>,
+}
+
+/// Returns the workspace folders sent by the client when initializing the server, falling back to
+/// the root URI for clients that don't support multiple workspace folders.
+pub fn workspace_roots(initialize_params: &Value) -> Vec<PathBuf> {
+    let folders = initialize_params["workspaceFolders"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|folder| folder["uri"].as_str());
+    let root = initialize_params["rootUri"].as_str();
+
+    let mut roots: Vec<_> = folders
+        .chain(root)
+        .filter_map(|uri| Url::parse(uri).ok()?.to_file_path().ok())
+        .collect();
+    roots.dedup();
+    roots
+}
+
+/// Returns `true` if this directory shouldn't be indexed. We skip hidden directories, which
+/// contain things like version control data, as well as some common build and dependency
+/// directories.
+fn is_ignored_dir(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_none_or(|name| name.starts_with('.') || matches!(name, "target" | "node_modules"))
+}
+
+impl WorkspaceIndexer {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        WorkspaceIndexer {
+            pending: roots.into(),
+        }
+    }
+
+    /// Schedules the indexing of a file again, for example because it changed on disk.
+    pub fn push(&mut self, path: PathBuf) {
+        if !self.pending.contains(&path) {
+            self.pending.push_back(path);
+        }
+    }
+
+    /// Returns `true` if there is nothing left to index.
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Returns the next Nickel file to index, reading directories along the way as needed.
+    pub fn next_file(&mut self) -> Option<PathBuf> {
+        while let Some(path) = self.pending.pop_front() {
+            if path.is_dir() {
+                let entries = match std::fs::read_dir(&path) {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        debug!("Failed to read directory {}: {err}", path.display());
+                        continue;
+                    }
+                };
+
+                let mut children: Vec<_> = entries
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .filter(|child| !(child.is_dir() && is_ignored_dir(child)))
+                    .collect();
+                // Sort so that the indexing order doesn't depend on the filesystem.
+                children.sort();
+                self.pending.extend(children);
+            } else if InputFormat::from_path(&path) == Some(InputFormat::Nickel) {
+                return Some(path);
+            }
+        }
+
+        None
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     convert::Infallible,
     ffi::OsString,
     path::{Path, PathBuf},
@@ -16,9 +16,14 @@ use nickel_lang_core::{
         AstImportResolver, CacheHub, ImportData, ImportTarget, InputFormat, SourceCache,
         SourcePath, normalize_path,
     },
-    error::{ImportErrorKind, IntoDiagnostics, ParseErrors},
+    error::{
+        ImportErrorKind, IntoDiagnostics, ParseErrors, Warning,
+        warning::{WarningLevel, WarningLevels},
+    },
     eval::{VirtualMachine, VmContext, cache::CacheImpl, value::NickelValue},
-    files::FileId,
+    files::{FileId, Files},
+    lint::lint,
+    package::PackageMap,
     position::{PosTable, RawPos, RawSpan, TermPos},
     traverse::TraverseAlloc,
     typ::TypeF,
@@ -29,8 +34,8 @@ use crate::{
         AltFormatErrors, AnalysisRegistry, AnalysisRegistryRef, AnalysisState, AnalysisTarget,
         PackedAnalysis,
     },
-    config::LspConfig,
-    diagnostic::{DiagnosticCompat as _, SerializableDiagnostic},
+    config::{LspConfig, LspEvalConfig, LspFormatConfig, ProjectSettings, SETTINGS_FILE_NAME},
+    diagnostic::{DiagnosticCompat as _, HasFixData, SerializableDiagnostic},
     error::WarningReporter,
     field_walker::FieldResolver,
     files::uri_to_path,
@@ -40,6 +45,61 @@ use crate::{
 
 pub type ImportTargets = HashMap<FileId, HashMap<RawSpan, FileId>>;
 
+/// The package maps of the packages of the workspace, keyed by the directory whose files they
+/// apply to. The value is `None` if the package map couldn't be loaded.
+#[derive(Debug, Default)]
+pub struct PackageMaps(HashMap<PathBuf, Option<PackageMap>>);
+
+impl PackageMaps {
+    /// Returns the package map to resolve the package imports of a file with.
+    pub fn for_file(&self, sources: &SourceCache, file_id: FileId) -> Option<&PackageMap> {
+        match sources.packages.get(&file_id) {
+            // The files of a dependency are resolved with the package map that brought the
+            // dependency in.
+            Some(package) => self.0.values().flatten().find(|map| {
+                map.top_level
+                    .values()
+                    .chain(map.packages.values())
+                    .any(|dir| dir == package)
+            }),
+            None => {
+                let SourcePath::Path(path, _) = sources.file_paths.get(&file_id)? else {
+                    return None;
+                };
+                path.ancestors()
+                    .skip(1)
+                    .find_map(|dir| self.0.get(dir))?
+                    .as_ref()
+            }
+        }
+    }
+}
+
+/// The project settings found so far, by directory. A directory maps to `None` if it has no
+/// settings file, or if its settings aren't loaded yet.
+#[derive(Debug, Default)]
+pub struct ProjectSettingsMap(BTreeMap<PathBuf, Option<ProjectSettings>>);
+
+impl ProjectSettingsMap {
+    /// Returns the settings of the project containing `path`, if any.
+    pub fn settings_for(&self, path: &Path) -> Option<&ProjectSettings> {
+        path.ancestors()
+            .skip(1)
+            .find_map(|dir| self.0.get(dir)?.as_ref())
+    }
+
+    /// Returns the import paths from the settings of the project containing a file.
+    pub fn import_paths_for(&self, sources: &SourceCache, file_id: FileId) -> &[PathBuf] {
+        match sources.file_paths.get(&file_id) {
+            Some(SourcePath::Path(path, _)) => self
+                .settings_for(path)
+                .map(|settings| settings.import_paths.as_slice())
+                .unwrap_or_default(),
+            _ => &[],
+        }
+    }
+}
+
 /// All the state associated with the files we know about.
 ///
 /// Includes cached analyses, cached parse trees, etc.
@@ -72,10 +132,17 @@ pub struct World {
     compiled_stdlib: NickelValue,
     contract_configs: crate::contracts::ContractConfigsWatcher,
     pub config: LspConfig,
+    project_settings: ProjectSettingsMap,
+    /// The roots of the workspace folders. We only look for project settings inside them.
+    workspace_roots: Vec<PathBuf>,
+    /// The directories whose settings file must be loaded, see [Self::take_pending_settings].
+    pending_settings: Vec<PathBuf>,
     /// Since we hold a compiled version of the stdlib, we also need the corresponding position
     /// table. The pos table is also used for analysis of non-Nickel format (e.g. JSON or YAML)
     /// which are currently only representable in a "compiled" form.
     pos_table: PosTable,
+    /// The package maps of the packages we know about.
+    pub(crate) package_maps: PackageMaps,
 }
 
 impl World {
@@ -95,11 +162,6 @@ impl World {
         use nickel_lang_core::ast::compat::ToMainline;
 
         let mut sources = SourceCache::new();
-
-        if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
-            sources.add_import_paths(nickel_path.split(':'));
-        }
-
         let analysis_reg = AnalysisRegistry::with_std(&mut sources);
         let mut pos_table = PosTable::new();
         let compiled_stdlib = analysis_reg
@@ -107,7 +169,7 @@ impl World {
             .ast()
             .to_mainline(&mut pos_table);
 
-        Self {
+        let mut world = Self {
             sources,
             analysis_reg,
             import_data: ImportData::new(),
@@ -117,8 +179,14 @@ impl World {
             compiled_stdlib,
             contract_configs: cfgs,
             config,
+            project_settings: ProjectSettingsMap::default(),
+            workspace_roots: Vec::new(),
+            pending_settings: Vec::new(),
             pos_table,
-        }
+            package_maps: PackageMaps::default(),
+        };
+        world.sources.import_paths = world.import_paths();
+        world
     }
 
     /// Adds a new file to our world.
@@ -133,6 +201,9 @@ impl World {
     ) -> anyhow::Result<(FileId, HashSet<FileId>)> {
         let path = uri_to_path(&uri)?;
         self.contract_configs.watch_configs_for(&uri);
+        self.discover_settings(&path);
+        #[cfg(feature = "package-experimental")]
+        self.load_package_map(&path);
 
         // Invalidate the cache of every file that tried, but failed, to import a file
         // with a name like this.
@@ -165,6 +236,194 @@ impl World {
         Ok((file_id, invalid))
     }
 
+    /// Loads the package map of the package containing `path`, so that package imports can be
+    /// resolved, unless we already tried to.
+    ///
+    /// A package map applies to the files under the directory of its manifest. If the settings of
+    /// a project point to a manifest, it applies to the files of the project instead.
+    #[cfg(feature = "package-experimental")]
+    fn load_package_map(&mut self, path: &Path) {
+        let project = path
+            .ancestors()
+            .skip(1)
+            .find_map(|dir| Some((dir, self.project_settings.0.get(dir)?.as_ref()?)));
+        let lock_file = project.and_then(|(_, s)| s.package_lock_file.clone());
+        let Some((root, manifest)) = project
+            .and_then(|(dir, s)| Some((dir.to_owned(), s.package_manifest.clone()?)))
+            .or_else(|| {
+                let manifest = path.parent().and_then(crate::package::find_manifest)?;
+                Some((manifest.parent()?.to_owned(), manifest))
+            })
+        else {
+            return;
+        };
+
+        if self.package_maps.0.contains_key(&root) {
+            return;
+        }
+
+        let map = match crate::package::package_map(&manifest, lock_file.as_deref()) {
+            Ok(Some(map)) => Some(map),
+            Ok(None) => {
+                warn!(
+                    "no up-to-date lock file for {}, package imports won't be resolved",
+                    manifest.display()
+                );
+                None
+            }
+            Err(e) => {
+                warn!(
+                    "failed to load the package map of {}: {e}",
+                    manifest.display()
+                );
+                None
+            }
+        };
+
+        self.package_maps.0.insert(root, map);
+    }
+
+    /// Sets the roots of the workspace folders, inside which we look for project settings.
+    pub fn set_workspace_roots(&mut self, roots: Vec<PathBuf>) {
+        self.workspace_roots = roots;
+    }
+
+    fn in_workspace(&self, path: &Path) -> bool {
+        self.workspace_roots
+            .iter()
+            .any(|root| path.starts_with(root))
+    }
+
+    /// Looks for project settings in the ancestors of `path` that we haven't visited yet, up to
+    /// the root of its workspace folder. Settings files are Nickel programs, so we don't evaluate
+    /// the ones outside of the workspace just because a file was opened.
+    ///
+    /// The settings files we find aren't loaded right away, see [Self::take_pending_settings].
+    fn discover_settings(&mut self, path: &Path) {
+        for dir in path.ancestors().skip(1) {
+            // If we already visited this directory, we also visited its ancestors.
+            if !self.in_workspace(dir) || self.project_settings.0.contains_key(dir) {
+                break;
+            }
+
+            self.project_settings.0.insert(dir.to_owned(), None);
+            if dir.join(SETTINGS_FILE_NAME).is_file() {
+                self.pending_settings.push(dir.to_owned());
+            }
+        }
+    }
+
+    /// Schedules the reloading of the settings file in `dir` after it changed on disk.
+    pub fn reload_settings(&mut self, dir: &Path) {
+        if self.in_workspace(dir) {
+            self.pending_settings.push(dir.to_owned());
+        }
+    }
+
+    /// Returns the directories whose settings file must be loaded. They are evaluated in the
+    /// background and passed back to [Self::set_settings].
+    pub fn take_pending_settings(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.pending_settings)
+    }
+
+    /// Records the settings of the project at `dir`. Returns `true` if they changed, in which case
+    /// they must be applied with [Self::apply_settings].
+    pub fn set_settings(&mut self, dir: PathBuf, settings: Option<ProjectSettings>) -> bool {
+        self.project_settings
+            .0
+            .insert(dir, settings.clone())
+            .flatten()
+            != settings
+    }
+
+    /// Returns the settings of the project containing `path`, if any.
+    pub fn settings_for(&self, path: &Path) -> Option<&ProjectSettings> {
+        self.project_settings.settings_for(path)
+    }
+
+    /// Returns the import paths from `NICKEL_IMPORT_PATH` and the configuration, which apply to
+    /// every file. Project settings may add import paths for the files of the project, see
+    /// [Self::import_paths_for].
+    fn import_paths(&self) -> Vec<PathBuf> {
+        let env = std::env::var("NICKEL_IMPORT_PATH").unwrap_or_default();
+        let env = env
+            .split(':')
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+
+        env.chain(self.config.import_paths.iter().cloned())
+            .collect()
+    }
+
+    /// Returns the import paths used to resolve the imports of a file: the ones from the settings
+    /// of its project, if any, followed by the ones that apply to every file.
+    pub fn import_paths_for(&self, file_id: FileId) -> Vec<PathBuf> {
+        self.project_settings
+            .import_paths_for(&self.sources, file_id)
+            .iter()
+            .chain(&self.sources.import_paths)
+            .cloned()
+            .collect()
+    }
+
+    /// Applies a change of the configuration or of the project settings.
+    ///
+    /// Imports may resolve differently, so we start over: every analysis is invalidated and the
+    /// package map is reloaded.
+    pub fn apply_settings(&mut self) {
+        self.sources.import_paths = self.import_paths();
+
+        #[cfg(feature = "package-experimental")]
+        {
+            self.package_maps = PackageMaps::default();
+            let paths: Vec<_> = self
+                .file_uris
+                .values()
+                .filter_map(|uri| uri_to_path(uri).ok())
+                .collect();
+            for path in paths {
+                self.load_package_map(&path);
+            }
+        }
+
+        let files: Vec<_> = self.file_uris.keys().copied().collect();
+        for file_id in files {
+            self.invalidate(file_id);
+        }
+    }
+
+    /// Returns the configuration of the background evaluator, which must run if background
+    /// evaluation is enabled for at least one project.
+    pub fn background_eval_config(&self) -> LspEvalConfig {
+        let enabled_somewhere = self
+            .project_settings
+            .0
+            .values()
+            .flatten()
+            .any(|settings| settings.background_eval == Some(true));
+
+        LspEvalConfig {
+            disable: self.config.eval_config.disable && !enabled_somewhere,
+            ..self.config.eval_config.clone()
+        }
+    }
+
+    /// Whether the file at `uri` should be evaluated in the background.
+    pub fn background_eval_enabled(&self, uri: &Url) -> bool {
+        let settings = uri_to_path(uri)
+            .ok()
+            .and_then(|path| self.settings_for(&path)?.background_eval);
+        settings.unwrap_or(!self.config.eval_config.disable)
+    }
+
+    /// Returns the configuration of the formatter for the file at `uri`.
+    pub fn format_config(&self, uri: &Url) -> &LspFormatConfig {
+        uri_to_path(uri)
+            .ok()
+            .and_then(|path| self.settings_for(&path)?.format.as_ref())
+            .unwrap_or(&self.config.format)
+    }
+
     fn file_format(&self, file_id: FileId) -> Option<InputFormat> {
         if let Some(SourcePath::Path(_, format)) = self.sources.file_paths.get(&file_id) {
             Some(*format)
@@ -219,10 +478,58 @@ impl World {
         Ok((result.replacement_id.ok(), invalid))
     }
 
+    /// Loads a file of the workspace from the filesystem and analyzes it, so that it's taken into
+    /// account by workspace-wide requests even if it isn't open. Files that are already analyzed,
+    /// because they are open or imported by an open file, are left untouched.
+    pub fn index_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        let format = InputFormat::from_path(path).unwrap_or_default();
+        let file_id = self.sources.get_or_add_file(path, format)?.inner();
+        self.discover_settings(path);
+
+        if self.analysis_reg.get(file_id).is_some() {
+            return Ok(());
+        }
+
+        let uri = Url::from_file_path(normalize_path(path)?)
+            .map_err(|_| anyhow::anyhow!("invalid path {}", path.display()))?;
+        self.file_uris.entry(file_id).or_insert(uri);
+        // Diagnostics are only published for files that are open or imported by an open file. We
+        // only care about populating the analysis registry here.
+        self.parse_and_typecheck(file_id);
+
+        Ok(())
+    }
+
+    /// Forgets the analysis of a file which changed on disk, and the analyses of the files that
+    /// import it. Files open in the editor are left alone, since their contents don't come from
+    /// the disk. Returns the files importing the changed one, whose analysis is now missing.
+    pub fn invalidate_from_disk(&mut self, path: &Path) -> Vec<FileId> {
+        let Ok(path) = normalize_path(path) else {
+            return Vec::new();
+        };
+
+        let changed: Vec<_> = self
+            .sources
+            .file_paths
+            .keys()
+            .filter(|file_id| self.sources.filesystem_path(**file_id) == Some(path.as_path()))
+            .copied()
+            .collect();
+
+        changed
+            .into_iter()
+            .flat_map(|file_id| {
+                // The new version gets its own file id once it's loaded.
+                self.file_uris.remove(&file_id);
+                self.invalidate(file_id)
+            })
+            .collect()
+    }
+
     pub fn lsp_diagnostics(
         &self,
         file_id: FileId,
-        err: impl IntoDiagnostics,
+        err: impl IntoDiagnostics + HasFixData,
     ) -> Vec<SerializableDiagnostic> {
         SerializableDiagnostic::from(err, &mut self.sources.files().clone(), file_id)
     }
@@ -468,6 +775,8 @@ impl World {
                     &mut self.import_data,
                     &mut self.import_targets,
                     &mut self.file_uris,
+                    &self.package_maps,
+                    &self.project_settings,
                     reg,
                 ))
             })
@@ -638,6 +947,50 @@ impl World {
         diags
     }
 
+    /// Runs the static lints on a file, returning the warnings that aren't silenced. Files which
+    /// don't parse are not linted, as the lints would be unreliable on a partial AST.
+    pub fn lint_diagnostics(&mut self, file_id: FileId) -> Vec<SerializableDiagnostic> {
+        let Ok(analysis) = self.analysis_reg.get_or_err(file_id) else {
+            return Vec::new();
+        };
+
+        if !analysis.parse_errors().no_errors() {
+            return Vec::new();
+        }
+
+        let warnings = lint(analysis.ast());
+        let mut files = self.sources.files().clone();
+
+        warnings
+            .into_iter()
+            .flat_map(|warning| Self::warning_diagnostics(warning, &mut files, file_id))
+            .collect()
+    }
+
+    /// Converts a warning to diagnostics, according to its level: allowed warnings are dropped
+    /// and denied warnings are reported as errors.
+    fn warning_diagnostics(
+        warning: Warning,
+        files: &mut Files,
+        file_id: FileId,
+    ) -> Vec<SerializableDiagnostic> {
+        let level = WarningLevels::default().level_of(&warning, files);
+
+        let severity = match level {
+            WarningLevel::Allow => return Vec::new(),
+            WarningLevel::Warn => lsp_types::DiagnosticSeverity::WARNING,
+            WarningLevel::Deny => lsp_types::DiagnosticSeverity::ERROR,
+        };
+
+        SerializableDiagnostic::from(warning, files, file_id)
+            .into_iter()
+            .map(|diag| SerializableDiagnostic {
+                severity: Some(severity),
+                ..diag
+            })
+            .collect()
+    }
+
     /// Calls [PackedAnalysis::reparse_range] on the corresponding analysis, if any. If the
     /// reparsed AST import new files, they are parsed, typechecked and analysed as well, to
     /// provide the original completion request any needed information. Diagnostics are ignored: if
@@ -665,6 +1018,8 @@ impl World {
                     import_data: &mut self.import_data,
                     import_targets: &mut self.import_targets,
                     file_uris: &mut self.file_uris,
+                    package_maps: &self.package_maps,
+                    project_settings: &self.project_settings,
                 };
 
                 let _ = typecheck_visit(
@@ -733,10 +1088,12 @@ impl World {
                     .flat_map(|e| SerializableDiagnostic::from(e, &mut files, file_id)),
             );
             diags.extend(warnings.try_iter().flat_map(|(warning, mut files)| {
-                SerializableDiagnostic::from(warning, &mut files, file_id)
+                Self::warning_diagnostics(warning, &mut files, file_id)
             }));
         }
 
+        diags.extend(self.lint_diagnostics(file_id));
+
         diags.sort();
         diags.dedup();
         diags
@@ -851,11 +1208,17 @@ impl World {
                     spans
                 }
                 (Node::Import(_), _) => {
-                    let target = world
-                        .import_targets
-                        .get(&ast.pos.src_id()?)?
-                        .get(&ast.pos.into_opt()?)?;
-                    let pos = world.analysis_reg.get(*target)?.ast().pos.into_opt()?;
+                    let target = world.get_import_target(ast.pos)?;
+                    // Files that we couldn't analyze still have a location: their beginning.
+                    let pos = world
+                        .analysis_reg
+                        .get(target)
+                        .and_then(|analysis| analysis.ast().pos.into_opt())
+                        .unwrap_or(RawSpan {
+                            src_id: target,
+                            start: ByteIndex(0),
+                            end: ByteIndex(0),
+                        });
                     vec![pos]
                 }
                 (Node::Record(_), Some(ident_data)) => {
@@ -1010,6 +1373,8 @@ impl World {
         let mut cache = CacheHub::new();
         let mut pos_table = self.pos_table.clone();
         cache.sources = self.sources.clone();
+        cache.sources.import_paths = self.import_paths_for(file_id);
+        cache.sources.package_map = self.package_maps.for_file(&self.sources, file_id).cloned();
         cache.import_data = self.import_data.clone();
 
         cache.terms.insert(
@@ -1081,6 +1446,8 @@ pub(crate) struct WorldImportResolver<'a, 'std> {
     pub(crate) import_data: &'a mut ImportData,
     pub(crate) import_targets: &'a mut ImportTargets,
     pub(crate) file_uris: &'a mut HashMap<FileId, Url>,
+    pub(crate) package_maps: &'a PackageMaps,
+    pub(crate) project_settings: &'a ProjectSettingsMap,
 }
 
 impl AstImportResolver for WorldImportResolver<'_, '_> {
@@ -1097,7 +1464,8 @@ impl AstImportResolver for WorldImportResolver<'_, '_> {
         let (possible_parents, path, pkg_id, format) = match import {
             Import::Path { path, format } => {
                 // `parent` is the file that did the import. We first look in its containing
-                // directory, followed by the directories in the import path.
+                // directory, followed by the directories in the import path of its project and
+                // then in the global import path.
                 let parent_path = parent_id
                     .and_then(|parent| self.sources.file_paths.get(&parent))
                     .and_then(|path| <&OsStr>::try_from(path).ok())
@@ -1110,8 +1478,13 @@ impl AstImportResolver for WorldImportResolver<'_, '_> {
                     // This is useful when importing e.g. from the REPL or the CLI directly.
                     .unwrap_or_default();
 
+                let project_paths = parent_id
+                    .map(|parent| self.project_settings.import_paths_for(self.sources, parent))
+                    .unwrap_or_default();
+
                 (
                     std::iter::once(parent_path)
+                        .chain(project_paths.iter().cloned())
                         .chain(self.sources.import_paths.iter().cloned())
                         .collect(),
                     Path::new(path),
@@ -1120,10 +1493,8 @@ impl AstImportResolver for WorldImportResolver<'_, '_> {
                 )
             }
             Import::Package { id } => {
-                let package_map = self
-                    .sources
-                    .package_map
-                    .as_ref()
+                let package_map = parent_id
+                    .and_then(|parent| self.package_maps.for_file(self.sources, parent))
                     .ok_or(ImportErrorKind::NoPackageMap { pos: *pos })?;
                 let parent_path = parent_id
                     .and_then(|p| self.sources.packages.get(&p))
@@ -1,5 +1,12 @@
 use lsp_server::ErrorCode;
-use lsp_types::{ExecuteCommandParams, Url, request::ExecuteCommand};
+use lsp_types::{
+    CompletionParams, DidChangeConfigurationParams, DidChangeWatchedFilesParams,
+    ExecuteCommandParams, FileChangeType, FileEvent, PartialResultParams, Position, Range,
+    ReferenceContext, ReferenceParams, TextDocumentIdentifier, TextDocumentPositionParams, Url,
+    WorkDoneProgressParams, WorkspaceSymbolParams,
+    notification::{DidChangeConfiguration, DidChangeWatchedFiles},
+    request::{Completion, ExecuteCommand, References, WorkspaceSymbolRequest},
+};
 use nickel_lang_utils::project_root::project_root;
 use pretty_assertions::assert_eq;
 use serde_json::json;
@@ -136,6 +143,218 @@ fn refresh_missing_imports() {
     assert_eq!(dep_diags.uri, dep_uri);
 }
 
+#[test]
+fn incremental_sync() {
+    let _ = env_logger::try_init();
+    let lsp_options = json!({ "eval_config": { "disable": true } });
+    let mut harness = TestHarness::new_with_options(Some(lsp_options));
+
+    let test_uri = file_url_from_path("/test.ncl").unwrap();
+    harness.send_file(test_uri.clone(), "let x = 1 in\n{ foo = x }");
+    assert!(harness.wait_for_diagnostics().diagnostics.is_empty());
+
+    // Turn `1` into `1 +`, which doesn't parse.
+    harness.edit_file(
+        test_uri.clone(),
+        2,
+        Range::new(Position::new(0, 9), Position::new(0, 9)),
+        " +",
+    );
+    let diags = harness.wait_for_diagnostics().diagnostics;
+    assert!(!diags.is_empty());
+
+    // Complete the addition, which makes the file valid again.
+    harness.edit_file(
+        test_uri,
+        3,
+        Range::new(Position::new(0, 11), Position::new(0, 11)),
+        " 2",
+    );
+    assert!(harness.wait_for_diagnostics().diagnostics.is_empty());
+}
+
+#[test]
+fn workspace_index() {
+    let _ = env_logger::try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+
+    std::fs::write(root.join("lib.ncl"), "{ greeting = \"hi\" }").unwrap();
+    std::fs::create_dir(root.join("app")).unwrap();
+    std::fs::write(
+        root.join("app").join("main.ncl"),
+        "let lib = import \"../lib.ncl\" in\n{ message = lib.greeting }",
+    )
+    .unwrap();
+
+    let mut harness = TestHarness::new_with_workspace(Url::from_directory_path(&root).unwrap());
+
+    // Only `lib.ncl` is open, but `app/main.ncl` is part of the workspace.
+    let lib_uri = Url::from_file_path(root.join("lib.ncl")).unwrap();
+    harness.send_file(lib_uri.clone(), "{ greeting = \"hi\" }");
+    harness.wait_for_diagnostics();
+
+    harness.request::<WorkspaceSymbolRequest>(WorkspaceSymbolParams {
+        query: "mesg".to_owned(),
+        ..Default::default()
+    });
+    let references = ReferenceParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: lib_uri },
+            position: Position::new(0, 3),
+        },
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+        context: ReferenceContext {
+            include_declaration: false,
+        },
+    };
+    harness.request::<References>(references.clone());
+
+    // Files which aren't open are analyzed again when they change on disk.
+    std::fs::write(
+        root.join("app").join("main.ncl"),
+        "let lib = import \"../lib.ncl\" in\n{\n  message = lib.greeting\n}",
+    )
+    .unwrap();
+    harness.send_notification::<DidChangeWatchedFiles>(DidChangeWatchedFilesParams {
+        changes: vec![FileEvent {
+            uri: Url::from_file_path(root.join("app").join("main.ncl")).unwrap(),
+            typ: FileChangeType::CHANGED,
+        }],
+    });
+    harness.request::<References>(references);
+
+    let output = String::from_utf8(std::mem::take(&mut harness.out)).unwrap();
+    let root_uri = Url::from_directory_path(&root).unwrap();
+    let output = output.replace(root_uri.as_str(), "file:///");
+
+    assert_eq!(
+        output,
+        "message file:///app/main.ncl:1:2-1:9\n\n\
+         [file:///app/main.ncl:1:16-1:24]\n\
+         [file:///app/main.ncl:2:16-2:24]\n"
+    );
+}
+
+#[test]
+fn import_path_completion() {
+    let _ = env_logger::try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+
+    std::fs::create_dir(root.join("lib")).unwrap();
+    std::fs::write(root.join("lib").join("k8s.ncl"), "{}").unwrap();
+    std::fs::write(root.join("lib").join("notes.txt"), "").unwrap();
+
+    let mut harness = TestHarness::new();
+    let uri = Url::from_file_path(root.join("main.ncl")).unwrap();
+    harness.send_file(uri.clone(), "import \"lib/k8\"");
+    harness.wait_for_diagnostics();
+
+    // Completing a partial file name lists the entries of its directory.
+    harness.request::<Completion>(CompletionParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position::new(0, 14),
+        },
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+        context: None,
+    });
+
+    let output = String::from_utf8(std::mem::take(&mut harness.out)).unwrap();
+    assert_eq!(output, "[k8s.ncl, notes.txt]\n");
+}
+
+#[test]
+fn reload_configuration() {
+    let _ = env_logger::try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::create_dir(root.join("lib")).unwrap();
+    std::fs::write(root.join("lib").join("dep.ncl"), "42").unwrap();
+
+    let lsp_options = json!({ "eval_config": { "disable": true } });
+    let mut harness = TestHarness::new_with_options(Some(lsp_options));
+
+    let test_uri = file_url_from_path("/test.ncl").unwrap();
+    harness.send_file(test_uri.clone(), "import \"dep.ncl\"");
+    assert!(!harness.wait_for_diagnostics().diagnostics.is_empty());
+
+    // Clients send all their settings, ours being in the `nls` section next to the ones of the
+    // editor extension. The settings we don't get keep their current value.
+    harness.send_notification::<DidChangeConfiguration>(DidChangeConfigurationParams {
+        settings: json!({
+            "nls": {
+                "server": { "path": "nls" },
+                "import_paths": [root.join("lib")],
+            }
+        }),
+    });
+    let diags = harness.wait_for_diagnostics();
+    assert_eq!(diags.uri, test_uri);
+    assert!(diags.diagnostics.is_empty());
+}
+
+#[test]
+fn project_settings() {
+    let _ = env_logger::try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let workspace = root.join("workspace");
+    std::fs::create_dir(root.join("lib")).unwrap();
+    std::fs::write(root.join("lib").join("dep.ncl"), "42").unwrap();
+    // Settings outside of the workspace are ignored.
+    std::fs::write(
+        root.join("Nls-settings.ncl"),
+        "{ import_paths = [\"lib\"] }",
+    )
+    .unwrap();
+    // Only the first project of the workspace can import from `lib`.
+    for project in ["a", "b"] {
+        std::fs::create_dir_all(workspace.join(project)).unwrap();
+    }
+    std::fs::write(
+        workspace.join("a").join("Nls-settings.ncl"),
+        "{ import_paths = [\"../../lib\"], background_eval = false }",
+    )
+    .unwrap();
+
+    let mut harness =
+        TestHarness::new_with_workspace(Url::from_directory_path(&workspace).unwrap());
+    let a_uri = Url::from_file_path(workspace.join("a").join("main.ncl")).unwrap();
+    let b_uri = Url::from_file_path(workspace.join("b").join("main.ncl")).unwrap();
+
+    // The settings are loaded in the background, so the file may first be analyzed without them.
+    harness.send_file(a_uri.clone(), "import \"dep.ncl\"");
+    while !harness.wait_for_diagnostics().diagnostics.is_empty() {}
+
+    harness.send_file(b_uri.clone(), "import \"dep.ncl\"");
+    let diags = harness.wait_for_diagnostics();
+    assert_eq!(diags.uri, b_uri);
+    assert!(!diags.diagnostics.is_empty());
+
+    std::fs::write(
+        workspace.join("a").join("Nls-settings.ncl"),
+        "{ background_eval = false }",
+    )
+    .unwrap();
+    harness.send_notification::<DidChangeWatchedFiles>(DidChangeWatchedFilesParams {
+        changes: vec![FileEvent {
+            uri: Url::from_file_path(workspace.join("a").join("Nls-settings.ncl")).unwrap(),
+            typ: FileChangeType::CHANGED,
+        }],
+    });
+    let diags = loop {
+        let diags = harness.wait_for_diagnostics();
+        if diags.uri == a_uri {
+            break diags;
+        }
+    };
+    assert!(!diags.diagnostics.is_empty());
+}
+
 // This test is potentially subject to flakiness due to a race condition with how LSP messages are
 // read from stdin. It's possible for the main loop to check for new messages and not find one even
 // if there's a message waiting from stdin, and to begin handling the request without knowing that
@@ -157,6 +376,47 @@ fn cancel_request() {
     assert_eq!(err.code, ErrorCode::RequestCanceled as i32);
 }
 
+#[test]
+fn command_timeout() {
+    let _ = env_logger::try_init();
+    let mut harness = TestHarness::new();
+    let forever_uri = file_url_from_path("/forever.ncl").unwrap();
+    harness.send_file(
+        forever_uri.clone(),
+        "{ forever = let rec loop = fun x => loop x in loop 0 }",
+    );
+
+    let response = harness.request_raw::<ExecuteCommand>(ExecuteCommandParams {
+        command: "export".into(),
+        arguments: vec![
+            json!({ "uri": forever_uri }),
+            json!(["forever"]),
+            json!("json"),
+        ],
+        ..Default::default()
+    });
+    let err = response.error.unwrap();
+    assert!(
+        err.message.contains("didn't complete within"),
+        "{}",
+        err.message
+    );
+
+    // The worker was killed, but the server is still there.
+    let uri = file_url_from_path("/main.ncl").unwrap();
+    harness.send_file(uri.clone(), "{ foo = 1 }");
+    harness.request::<ExecuteCommand>(ExecuteCommandParams {
+        command: "export".into(),
+        arguments: vec![json!({ "uri": uri }), json!(["foo"]), json!("json")],
+        ..Default::default()
+    });
+    let output = String::from_utf8(harness.out).unwrap();
+    assert_eq!(
+        output,
+        "Object {\"content\": String(\"1\"), \"language\": String(\"json\")}\n"
+    );
+}
+
 #[test]
 fn apply_client_options() {
     let _ = env_logger::try_init();
@@ -185,3 +445,64 @@ fn apply_client_options() {
     let diags = harness.wait_for_diagnostics();
     assert!(diags.diagnostics.is_empty());
 }
+
+#[cfg(feature = "package-experimental")]
+#[test]
+fn package_maps_per_package() {
+    use lsp_types::{GotoDefinitionParams, request::GotoDefinition};
+    use nickel_lang_package::{ManifestFile, config::Config};
+
+    let _ = env_logger::try_init();
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+
+    let manifest = |name: &str, deps: &str| {
+        format!(
+            "{{ name = \"{name}\", description = \"{name}\", version = \"0.1.0\", authors = [], \
+             minimal_nickel_version = \"1.9.0\", dependencies = {{ {deps} }} }} \
+             | std.package.Manifest"
+        )
+    };
+
+    // Two packages with a dependency of the same name, but pointing to different packages.
+    for (pkg, lib) in [("a", "liba"), ("b", "libb")] {
+        std::fs::create_dir(root.join(lib)).unwrap();
+        std::fs::write(root.join(lib).join("Nickel-pkg.ncl"), manifest(lib, "")).unwrap();
+        std::fs::write(root.join(lib).join("main.ncl"), "{ value = 1 }").unwrap();
+
+        std::fs::create_dir(root.join(pkg)).unwrap();
+        std::fs::write(
+            root.join(pkg).join("Nickel-pkg.ncl"),
+            manifest(pkg, &format!("lib = 'Path \"../{lib}\"")),
+        )
+        .unwrap();
+        ManifestFile::from_path(root.join(pkg).join("Nickel-pkg.ncl"))
+            .unwrap()
+            .lock(Config::new().unwrap())
+            .unwrap();
+    }
+
+    let mut harness = TestHarness::new_with_workspace(Url::from_directory_path(&root).unwrap());
+
+    for pkg in ["a", "b"] {
+        let uri = Url::from_file_path(root.join(pkg).join("main.ncl")).unwrap();
+        harness.send_file(uri.clone(), "(import lib).value");
+        harness.request::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position::new(0, 15),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        });
+    }
+
+    let output = String::from_utf8(std::mem::take(&mut harness.out)).unwrap();
+    let root_uri = Url::from_directory_path(&root).unwrap();
+    let output = output.replace(root_uri.as_str(), "file:///");
+
+    assert_eq!(
+        output,
+        "file:///liba/main.ncl:0:2-0:7\nfile:///libb/main.ncl:0:2-0:7\n"
+    );
+}
@@ -1,5 +1,5 @@
 import { PathLike } from 'fs';
-import { workspace, ExtensionContext } from 'vscode';
+import { workspace, window, ExtensionContext, ViewColumn } from 'vscode';
 import { lookupInPath } from './toolchain';
 
 import {
@@ -47,8 +47,25 @@ export async function activate(context: ExtensionContext) {
 		// Register the server for nickel files
 		documentSelector: [{ scheme: 'file', language: 'nickel' }],
 		synchronize: {
+			// Notify the server about changes to its configuration
+			configurationSection: 'nls',
 			// Notify the server about file changes to .ncl files contained in the workspace
 			fileEvents: workspace.createFileSystemWatcher('**/*.ncl')
+		},
+		middleware: {
+			// The `export` command returns the exported value instead of writing it anywhere: show
+			// it in a new, unsaved document.
+			executeCommand: async (command, args, next) => {
+				const result = await next(command, args);
+				if (command === "export" && result) {
+					const doc = await workspace.openTextDocument({
+						content: result.content,
+						language: result.language,
+					});
+					await window.showTextDocument(doc, { viewColumn: ViewColumn.Beside, preview: true });
+				}
+				return result;
+			}
 		}
 	};
 
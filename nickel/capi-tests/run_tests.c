@@ -20,6 +20,10 @@ const char* BAD_EXAMPLE = " \
 
 const char* TRACE_EXAMPLE = "std.trace \"hi\" 1";
 
+const char* LOOP_EXAMPLE = "let rec loop = fun n => if n < 0 then n else loop (n + 1) in loop 0";
+
+const char* APPLY_EXAMPLE = "let offset = 1 in fun x => { port = x.port + offset }";
+
 struct buffer {
   char *ptr;
   uintptr_t capacity;
@@ -87,6 +91,69 @@ int main() {
   assert(result == NICKEL_RESULT_OK);
   assert(!strcmp(buf.ptr, "std.trace: hi\n"));
 
+  // Test function application
+  result = nickel_context_eval_deep(ctx, APPLY_EXAMPLE, expr, error);
+  assert(result == NICKEL_RESULT_OK);
+  assert(nickel_expr_is_function(expr));
+
+  nickel_expr *arg = nickel_expr_alloc();
+  result = nickel_expr_from_json("{ \"port\": 8080 }", arg, error);
+  assert(result == NICKEL_RESULT_OK);
+
+  nickel_expr *applied = nickel_expr_alloc();
+  nickel_expr const *args[] = { arg };
+  result = nickel_context_apply(ctx, expr, args, 1, applied, error);
+  assert(result == NICKEL_RESULT_OK);
+  assert(nickel_expr_is_record(applied));
+
+  rec = nickel_expr_as_record(applied);
+  nickel_record_key_value_by_index(rec, 0, &key, &len, val);
+  assert(nickel_expr_is_number(val));
+  nickel_number const *num = nickel_expr_as_number(val);
+  assert(nickel_number_is_i64(num));
+  assert(nickel_number_as_i64(num) == 8081);
+
+  result = nickel_expr_from_json("{ \"port\": \"http\" }", arg, error);
+  assert(result == NICKEL_RESULT_OK);
+  result = nickel_context_apply(ctx, expr, args, 1, applied, error);
+  assert(result == NICKEL_RESULT_ERR);
+
+  result = nickel_expr_from_json("{ \"port\": ", arg, error);
+  assert(result == NICKEL_RESULT_ERR);
+  buf.len = 0;
+  nickel_error_display(error, write_callback, &buf, NICKEL_ERROR_FORMAT_TEXT);
+  assert(strstr(buf.ptr, "json parse error") != NULL);
+
+  // Test resource limits and cancellation
+  nickel_context *limited = nickel_context_alloc();
+  nickel_context_set_max_steps(limited, 10000);
+  result = nickel_context_eval_deep(limited, EXAMPLE, expr, error);
+  assert(result == NICKEL_RESULT_OK);
+  result = nickel_context_eval_deep(limited, LOOP_EXAMPLE, expr, error);
+  assert(result == NICKEL_RESULT_ERR);
+  assert(nickel_error_interruption(error) == NICKEL_INTERRUPTION_STEP_LIMIT);
+
+  nickel_context_set_max_steps(limited, 0);
+  nickel_cancellation_handle *handle = nickel_cancellation_handle_alloc();
+  nickel_context_set_cancellation_handle(limited, handle);
+  nickel_cancellation_handle_cancel(handle);
+  nickel_cancellation_handle_free(handle);
+  result = nickel_context_eval_deep(limited, LOOP_EXAMPLE, expr, error);
+  assert(result == NICKEL_RESULT_ERR);
+  assert(nickel_error_interruption(error) == NICKEL_INTERRUPTION_CANCELLED);
+
+  // Removing the handle makes evaluations succeed again.
+  nickel_context_set_cancellation_handle(limited, NULL);
+  result = nickel_context_eval_deep(limited, EXAMPLE, expr, error);
+  assert(result == NICKEL_RESULT_OK);
+  nickel_context_free(limited);
+
+  result = nickel_context_eval_deep(ctx, BAD_EXAMPLE, expr, error);
+  assert(result == NICKEL_RESULT_ERR);
+  assert(nickel_error_interruption(error) == NICKEL_INTERRUPTION_NONE);
+
+  nickel_expr_free(applied);
+  nickel_expr_free(arg);
   nickel_expr_free(val);
   nickel_expr_free(expr);
   nickel_error_free(error);
@@ -2,6 +2,8 @@
 //!
 //! These bindings cover
 //! - evaluation (including lazy/partial evaluation),
+//! - application of Nickel functions to arguments,
+//! - resource limits and cancellation of evaluations,
 //! - inspection of the results as structured values,
 //! - serialization to JSON, TOML, and YAML,
 //! - error reporting.
@@ -67,11 +69,18 @@ use std::{
     ffi::{CStr, c_char, c_int, c_void},
     io::Write,
     ptr,
+    time::Duration,
 };
 
-use nickel_lang_core::eval::value::{self, Container, NickelValue};
+use nickel_lang_core::{
+    error::ParseError,
+    eval::value::{self, Container, NickelValue},
+    files::Files,
+};
 
-use crate::{Array, Context, Error, ErrorFormat, Expr, Number, Record};
+use crate::{
+    Array, CancellationHandle, Context, Error, ErrorFormat, Expr, Interruption, Number, Record,
+};
 
 /// The main entry point.
 pub struct nickel_context {
@@ -101,6 +110,13 @@ pub struct nickel_error {
     inner: Option<Error>,
 }
 
+/// A handle to cancel evaluations, possibly from another thread.
+///
+/// See [`nickel_context_set_cancellation_handle`].
+pub struct nickel_cancellation_handle {
+    inner: CancellationHandle,
+}
+
 /// A Nickel expression.
 ///
 /// This might be fully evaluated (for example, if you got it from [`nickel_context_eval_deep`])
@@ -307,6 +323,24 @@ pub enum nickel_error_format {
     NICKEL_ERROR_FORMAT_TOML = 4,
 }
 
+/// The reasons why an evaluation can be stopped before completion. See
+/// [`nickel_error_interruption`].
+#[repr(C)]
+pub enum nickel_interruption {
+    /// The evaluation wasn't interrupted: it failed for another reason.
+    NICKEL_INTERRUPTION_NONE = 0,
+    /// The limit set by [`nickel_context_set_max_steps`] was reached.
+    NICKEL_INTERRUPTION_STEP_LIMIT = 1,
+    /// The limit set by [`nickel_context_set_max_allocations`] was reached.
+    NICKEL_INTERRUPTION_ALLOCATION_LIMIT = 2,
+    /// The limit set by [`nickel_context_set_max_stack_depth`] was reached.
+    NICKEL_INTERRUPTION_STACK_DEPTH_LIMIT = 3,
+    /// The limit set by [`nickel_context_set_timeout_ms`] was reached.
+    NICKEL_INTERRUPTION_TIMEOUT = 4,
+    /// The evaluation was cancelled with [`nickel_cancellation_handle_cancel`].
+    NICKEL_INTERRUPTION_CANCELLED = 5,
+}
+
 impl From<nickel_error_format> for ErrorFormat {
     fn from(e: nickel_error_format) -> Self {
         match e {
@@ -391,6 +425,135 @@ pub unsafe extern "C" fn nickel_context_set_source_name(
     }
 }
 
+/// Limit the number of steps of each evaluation performed with this context.
+///
+/// Limits apply separately to each evaluation (each call to a function such as
+/// [`nickel_context_eval_deep`]). An evaluation that reaches a limit fails, and
+/// [`nickel_error_interruption`] tells which limit was reached. No limit is set by default, and
+/// setting a limit to 0 removes it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_context_set_max_steps(
+    mut ctx: *mut nickel_context,
+    max_steps: u64,
+) {
+    // Safety: `ctx` is required to be a valid pointer to a box-allocated context, as
+    // returned by `nickel_context_alloc`.
+    unsafe {
+        nickel_context::as_rust_mut(&mut ctx)
+            .vm_ctxt
+            .eval_limits
+            .max_steps = (max_steps > 0).then_some(max_steps);
+    }
+}
+
+/// Limit the number of thunks allocated by each evaluation performed with this context, as a
+/// deterministic proxy for memory usage. Only thunks, the suspended computations created for
+/// bindings, record fields, array elements and function arguments, are counted: other values
+/// aren't. See [`nickel_context_set_max_steps`] for how limits apply.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_context_set_max_allocations(
+    mut ctx: *mut nickel_context,
+    max_allocations: u64,
+) {
+    // Safety: `ctx` is required to be a valid pointer to a box-allocated context, as
+    // returned by `nickel_context_alloc`.
+    unsafe {
+        nickel_context::as_rust_mut(&mut ctx)
+            .vm_ctxt
+            .eval_limits
+            .max_allocations = (max_allocations > 0).then_some(max_allocations);
+    }
+}
+
+/// Limit the size of the evaluation stack, which grows with the depth of recursive calls. See
+/// [`nickel_context_set_max_steps`] for how limits apply.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_context_set_max_stack_depth(
+    mut ctx: *mut nickel_context,
+    max_stack_depth: usize,
+) {
+    // Safety: `ctx` is required to be a valid pointer to a box-allocated context, as
+    // returned by `nickel_context_alloc`.
+    unsafe {
+        nickel_context::as_rust_mut(&mut ctx)
+            .vm_ctxt
+            .eval_limits
+            .max_stack_depth = (max_stack_depth > 0).then_some(max_stack_depth);
+    }
+}
+
+/// Limit the wall-clock duration, in milliseconds, of each evaluation performed with this
+/// context. See [`nickel_context_set_max_steps`] for how limits apply.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_context_set_timeout_ms(
+    mut ctx: *mut nickel_context,
+    timeout_ms: u64,
+) {
+    // Safety: `ctx` is required to be a valid pointer to a box-allocated context, as
+    // returned by `nickel_context_alloc`.
+    unsafe {
+        nickel_context::as_rust_mut(&mut ctx)
+            .vm_ctxt
+            .eval_limits
+            .timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
+    }
+}
+
+/// Make the evaluations performed with this context cancellable through `handle`, or not
+/// cancellable anymore if `handle` is NULL.
+///
+/// `handle` is only borrowed temporarily: the context keeps its own reference to the underlying
+/// state, so the handle can be freed at any time.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_context_set_cancellation_handle(
+    mut ctx: *mut nickel_context,
+    handle: *const nickel_cancellation_handle,
+) {
+    // Safety: `ctx` is required to be a valid pointer to a box-allocated context, as
+    // returned by `nickel_context_alloc`, and `handle` to be either NULL or a valid pointer to a
+    // cancellation handle.
+    unsafe {
+        let handle = handle.as_ref().map(|handle| handle.inner.clone().0);
+        nickel_context::as_rust_mut(&mut ctx)
+            .vm_ctxt
+            .eval_limits
+            .cancellation = handle;
+    }
+}
+
+/// Allocate a new [`nickel_cancellation_handle`].
+///
+/// Returns a newly-allocated handle that can be freed with [`nickel_cancellation_handle_free`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_cancellation_handle_alloc() -> *mut nickel_cancellation_handle {
+    Box::into_raw(Box::new(nickel_cancellation_handle {
+        inner: CancellationHandle::new(),
+    }))
+}
+
+/// Free a [`nickel_cancellation_handle`] that was created with
+/// [`nickel_cancellation_handle_alloc`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_cancellation_handle_free(handle: *mut nickel_cancellation_handle) {
+    // Safety: `handle` is required to be a valid pointer to a box-allocated handle, as
+    // returned by `nickel_cancellation_handle_alloc`.
+    let _ = unsafe { Box::from_raw(handle) };
+}
+
+/// Cancel the evaluations using this handle.
+///
+/// This function can be called from any thread, including while an evaluation is running in
+/// another thread. The evaluations stop shortly after, and [`nickel_error_interruption`] returns
+/// `NICKEL_INTERRUPTION_CANCELLED` for their errors. Cancellation is permanent: evaluations
+/// started later with this handle fail as well.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_cancellation_handle_cancel(
+    handle: *const nickel_cancellation_handle,
+) {
+    // Safety: `handle` is required to be a valid pointer to a cancellation handle.
+    unsafe { handle.as_ref().unwrap().inner.cancel() }
+}
+
 /// Perform some sort of evaluation, and return the error appropriately.
 ///
 /// # Safety
@@ -669,6 +832,15 @@ pub unsafe extern "C" fn nickel_expr_is_array(expr: *const nickel_expr) -> c_int
     unsafe { nickel_expr::as_rust(&expr).is_array() as c_int }
 }
 
+/// Is this expression a function?
+///
+/// Functions can be applied to arguments with [`nickel_context_apply`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_expr_is_function(expr: *const nickel_expr) -> c_int {
+    // Safety: `expr` is expected to be a valid pointer to an expression
+    unsafe { nickel_expr::as_rust(&expr).is_function() as c_int }
+}
+
 /// Has this expression been evaluated?
 ///
 /// An evaluated expression is either null, or it's a number, bool, string, record, array, or enum.
@@ -1152,6 +1324,116 @@ pub unsafe extern "C" fn nickel_context_eval_expr_shallow(
     }
 }
 
+/// Reports malformed JSON given to [`nickel_expr_from_json`].
+fn json_error(error: serde_json::Error) -> Error {
+    Error {
+        error: Box::new(
+            ParseError::ExternalFormatError("json".to_owned(), error.to_string(), None).into(),
+        ),
+        files: Files::empty(),
+    }
+}
+
+/// Convert a JSON value to a Nickel expression, for example to pass it as an argument to
+/// [`nickel_context_apply`].
+///
+/// - `json` is a null-terminated string containing UTF-8-encoded JSON.
+/// - `out_expr` is either NULL or something that was created with [`nickel_expr_alloc`]
+/// - `out_error` can be NULL if you aren't interested in getting detailed
+///   error messages
+///
+/// If the conversion is successful, returns `NICKEL_RESULT_OK` and replaces the value at
+/// `out_expr` (if non-NULL) with the resulting expression.
+///
+/// If `json` isn't valid JSON, returns `NICKEL_RESULT_ERR` and replaces the value at `out_error`
+/// (if non-NULL) by a pointer to a newly-allocated Nickel error. That error should be freed with
+/// `nickel_error_free` when you are done with it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_expr_from_json(
+    json: *const c_char,
+    mut out_expr: *mut nickel_expr,
+    out_error: *mut nickel_error,
+) -> nickel_result {
+    // Safety: the various expectations are requirements from C caller (`json` is a valid UTF8 C
+    // String, pointers are valid and allocated from the corresponding `xxx_alloc` functions, etc.
+    unsafe {
+        let json = CStr::from_ptr(json).to_str().unwrap();
+        let expr = serde_json::from_str::<serde_json::Value>(json)
+            .map_err(json_error)
+            .and_then(|value| Expr::from_serde(&value));
+
+        match expr {
+            Ok(expr) => {
+                if !out_expr.is_null() {
+                    *nickel_expr::as_rust_mut(&mut out_expr) = expr;
+                }
+                nickel_result::NICKEL_RESULT_OK
+            }
+            Err(e) => {
+                if !out_error.is_null() {
+                    (*out_error).inner = Some(e);
+                }
+                nickel_result::NICKEL_RESULT_ERR
+            }
+        }
+    }
+}
+
+/// Apply a function to arguments, and evaluate the result deeply.
+///
+/// - `function` is an expression, typically coming from the evaluation of a program such as
+///   `fun env => { ... }` (see [`nickel_expr_is_function`]).
+/// - `args` points to `nargs` expressions, which can come from previous evaluations or from
+///   [`nickel_expr_from_json`]. It can be NULL if `nargs` is zero.
+/// - `out_expr` is either NULL or something that was created with [`nickel_expr_alloc`]
+/// - `out_error` can be NULL if you aren't interested in getting detailed
+///   error messages
+///
+/// If evaluation is successful, returns `NICKEL_RESULT_OK` and replaces the value at `out_expr`
+/// (if non-NULL) with the result of the application.
+///
+/// If evaluation fails, returns `NICKEL_RESULT_ERR` and replaces the value at `out_error` (if
+/// non-NULL) by a pointer to a newly-allocated Nickel error. That error should be freed with
+/// `nickel_error_free` when you are done with it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_context_apply(
+    mut ctx: *mut nickel_context,
+    function: *const nickel_expr,
+    args: *const *const nickel_expr,
+    nargs: usize,
+    mut out_expr: *mut nickel_expr,
+    out_error: *mut nickel_error,
+) -> nickel_result {
+    // As for `nickel_context_eval_expr_shallow`, we clone the arguments instead of consuming them.
+    //
+    // Safety: pre-conditions of this function
+    unsafe {
+        let args: Vec<Expr> = if nargs == 0 {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(args, nargs)
+                .iter()
+                .map(|arg| nickel_expr::as_rust(arg).clone())
+                .collect()
+        };
+
+        match nickel_context::as_rust_mut(&mut ctx).apply(nickel_expr::as_rust(&function), &args) {
+            Ok(out) => {
+                if !out_expr.is_null() {
+                    *nickel_expr::as_rust_mut(&mut out_expr) = out;
+                }
+                nickel_result::NICKEL_RESULT_OK
+            }
+            Err(e) => {
+                if !out_error.is_null() {
+                    (*out_error).inner = Some(e);
+                }
+                nickel_result::NICKEL_RESULT_ERR
+            }
+        }
+    }
+}
+
 /// Allocate a new `nickel_error`.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn nickel_error_alloc() -> *mut nickel_error {
@@ -1166,6 +1448,36 @@ pub unsafe extern "C" fn nickel_error_free(err: *mut nickel_error) {
     let _ = unsafe { Box::from_raw(err) };
 }
 
+/// If an evaluation was stopped by one of the limits of its context, or by a cancellation handle,
+/// returns which one. Returns `NICKEL_INTERRUPTION_NONE` for other errors.
+///
+/// `err` must have been allocated by `nickel_error_alloc` and initialized by some failing
+/// function (like `nickel_context_eval_deep`).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nickel_error_interruption(
+    err: *const nickel_error,
+) -> nickel_interruption {
+    // Safety: `err` is required to be valid pointer to `nickel_error`.
+    let err = unsafe { err.as_ref() }
+        .unwrap()
+        .inner
+        .as_ref()
+        .expect("uninitialized error");
+
+    match err.interruption() {
+        None => nickel_interruption::NICKEL_INTERRUPTION_NONE,
+        Some(Interruption::StepLimit) => nickel_interruption::NICKEL_INTERRUPTION_STEP_LIMIT,
+        Some(Interruption::AllocationLimit) => {
+            nickel_interruption::NICKEL_INTERRUPTION_ALLOCATION_LIMIT
+        }
+        Some(Interruption::StackDepthLimit) => {
+            nickel_interruption::NICKEL_INTERRUPTION_STACK_DEPTH_LIMIT
+        }
+        Some(Interruption::Timeout) => nickel_interruption::NICKEL_INTERRUPTION_TIMEOUT,
+        Some(Interruption::Cancelled) => nickel_interruption::NICKEL_INTERRUPTION_CANCELLED,
+    }
+}
+
 /// Write out an error as a user- or machine-readable diagnostic.
 ///
 /// - `err` must have been allocated by `nickel_error_alloc` and initialized by some failing
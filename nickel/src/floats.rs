@@ -0,0 +1,280 @@
+//! Rejects the floats that Nickel can't represent.
+//!
+//! Rust values are converted to Nickel through their JSON representation, but `serde_json`
+//! silently turns NaN and infinities into `null`. [check_finite] walks the value beforehand to
+//! report them instead.
+
+use serde::{
+    Serializer,
+    ser::{self, Error as _, Serialize},
+};
+
+/// Fails if `value` contains a float that is NaN or infinite.
+pub(crate) fn check_finite(value: &impl Serialize) -> Result<(), serde_json::Error> {
+    value.serialize(FiniteCheck)
+}
+
+/// A serializer that doesn't output anything, but fails on non-finite floats.
+struct FiniteCheck;
+
+type Result<T = (), E = serde_json::Error> = std::result::Result<T, E>;
+
+impl Serializer for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, _v: bool) -> Result {
+        Ok(())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result {
+        Ok(())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result {
+        Ok(())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result {
+        Ok(())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result {
+        Ok(())
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result {
+        Ok(())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result {
+        Ok(())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result {
+        Ok(())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result {
+        Ok(())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result {
+        Ok(())
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result {
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result {
+        if v.is_finite() {
+            Ok(())
+        } else {
+            Err(serde_json::Error::custom(format!(
+                "{v} isn't a finite number"
+            )))
+        }
+    }
+
+    fn serialize_char(self, _v: char) -> Result {
+        Ok(())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result {
+        Ok(())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result {
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        value.serialize(FiniteCheck)
+    }
+
+    fn end(self) -> Result {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        value.serialize(FiniteCheck)
+    }
+
+    fn end(self) -> Result {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        value.serialize(FiniteCheck)
+    }
+
+    fn end(self) -> Result {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        value.serialize(FiniteCheck)
+    }
+
+    fn end(self) -> Result {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result {
+        key.serialize(FiniteCheck)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+        value.serialize(FiniteCheck)
+    }
+
+    fn end(self) -> Result {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result {
+        value.serialize(FiniteCheck)
+    }
+
+    fn end(self) -> Result {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for FiniteCheck {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result {
+        value.serialize(FiniteCheck)
+    }
+
+    fn end(self) -> Result {
+        Ok(())
+    }
+}
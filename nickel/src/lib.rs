@@ -13,35 +13,44 @@
 //!   other languages.
 
 use std::{
+    collections::HashMap,
     ffi::OsString,
     io::{Cursor, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
 use codespan_reporting::term::termcolor::{Ansi, NoColor, WriteColor};
 use malachite::base::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode};
 
 use nickel_lang_core::{
-    cache::{CacheHub, InputFormat, SourcePath},
+    cache::{CacheHub, InputFormat, SourceLoader, SourcePath},
+    closurize::Closurize as _,
     deserialize::RustDeserializationError as DeserializationError,
     error::{
-        Error as NickelCoreError, IOError, IntoDiagnostics, NullReporter, PointedExportErrorData,
-        report::DiagnosticsWrapper,
+        ConversionError, Error as NickelCoreError, IOError, IntoDiagnostics, NullReporter,
+        PointedExportErrorData, report::DiagnosticsWrapper,
     },
     eval::{
-        VirtualMachine, VmContext,
-        cache::CacheImpl,
+        Closure, VirtualMachine, VmContext,
+        cache::{Cache as _, CacheImpl},
+        limits,
+        native::{NativeFunction, NativeFunctionError as NickelCoreNativeFunctionError},
         value::{self, ArrayData, Container, NickelValue},
     },
     files::Files,
     identifier::{Ident, LocIdent},
+    label::Label,
     position::PosIdx,
     serialize::{ExportFormat, to_string, validate},
     term::{
-        self, RuntimeContract,
+        self, BinaryOp, RuntimeContract,
+        make::{self as mk_term, builder},
         record::{Field, RecordData},
     },
 };
+use serde::{Deserialize as _, Serialize};
 
 /// Both [Array] and [Record] are borrowing from an underlying allocation. However,
 /// [nickel_lang_core::eval::value::NickelValue] inline empty containers, meaning that
@@ -77,6 +86,7 @@ pub enum ErrorFormat {
 
 #[cfg(feature = "capi")]
 pub mod capi;
+mod floats;
 
 /// The main entry point.
 ///
@@ -91,6 +101,8 @@ pub mod capi;
 pub struct Context {
     name: Option<String>,
     vm_ctxt: VmContext<CacheHub, CacheImpl>,
+    /// Values to merge into the evaluated programs, with the path of the field they override.
+    overrides: Vec<(Vec<String>, NickelValue)>,
 }
 
 impl Default for Context {
@@ -151,6 +163,110 @@ impl Error {
     }
 }
 
+impl Error {
+    /// If evaluation was stopped by one of the limits configured on the [`Context`], or by a
+    /// [`CancellationHandle`], returns which one.
+    pub fn interruption(&self) -> Option<Interruption> {
+        let NickelCoreError::EvalError(error) = &*self.error else {
+            return None;
+        };
+
+        error.error.interruption().map(Interruption::from)
+    }
+}
+
+/// The reason why an evaluation was stopped before completion. See [`Error::interruption`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Interruption {
+    /// The limit set by [`Context::with_max_steps`] was reached.
+    StepLimit,
+    /// The limit set by [`Context::with_max_allocations`] was reached.
+    AllocationLimit,
+    /// The limit set by [`Context::with_max_stack_depth`] was reached.
+    StackDepthLimit,
+    /// The limit set by [`Context::with_timeout`] was reached.
+    Timeout,
+    /// The evaluation was cancelled through a [`CancellationHandle`].
+    Cancelled,
+}
+
+/// An error returned by a native function. See [`Context::with_native_function`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NativeFunctionError {
+    /// The argument `index`, starting from zero, doesn't have the expected shape, described by
+    /// `expected`, such as `a non-empty array`. This is reported like the type errors of the
+    /// functions of the standard library.
+    InvalidArgument { index: usize, expected: String },
+    /// The argument `index`, starting from zero, has the declared type but is still invalid, such
+    /// as a string that isn't a valid URL. This is reported as a violation of the contract of the
+    /// function by the caller, with the given message.
+    Blame { index: usize, message: String },
+    /// Any other error, reported with the given message.
+    Other(String),
+}
+
+impl From<String> for NativeFunctionError {
+    fn from(message: String) -> Self {
+        NativeFunctionError::Other(message)
+    }
+}
+
+impl From<NativeFunctionError> for NickelCoreNativeFunctionError {
+    fn from(error: NativeFunctionError) -> Self {
+        match error {
+            NativeFunctionError::InvalidArgument { index, expected } => {
+                NickelCoreNativeFunctionError::InvalidArgument { index, expected }
+            }
+            NativeFunctionError::Blame { index, message } => {
+                NickelCoreNativeFunctionError::Blame { index, message }
+            }
+            NativeFunctionError::Other(message) => NickelCoreNativeFunctionError::Other(message),
+        }
+    }
+}
+
+impl From<limits::Interruption> for Interruption {
+    fn from(interruption: limits::Interruption) -> Self {
+        match interruption {
+            limits::Interruption::StepLimit => Interruption::StepLimit,
+            limits::Interruption::AllocationLimit => Interruption::AllocationLimit,
+            limits::Interruption::StackDepthLimit => Interruption::StackDepthLimit,
+            limits::Interruption::Timeout => Interruption::Timeout,
+            limits::Interruption::Cancelled => Interruption::Cancelled,
+        }
+    }
+}
+
+/// A handle to cancel evaluations from another thread. See [`Context::with_cancellation`].
+///
+/// Clones share the same state. Cancellation is permanent: once [`Self::cancel`] has been called,
+/// the evaluations using this handle, running or future, fail.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationHandle(limits::CancellationHandle);
+
+impl CancellationHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests the cancellation of the evaluations using this handle. The evaluations stop
+    /// shortly after, with an error whose [`Error::interruption`] is
+    /// [`Interruption::Cancelled`].
+    pub fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+impl From<limits::CancellationHandle> for CancellationHandle {
+    fn from(handle: limits::CancellationHandle) -> Self {
+        CancellationHandle(handle)
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
         Error {
@@ -169,14 +285,132 @@ impl From<nickel_lang_core::error::ExportErrorData> for Error {
     }
 }
 
+/// A source of imported files, for programs whose imports don't live (only) in the filesystem.
+///
+/// See [`Context::with_import_loader`].
+pub trait ImportLoader: Send + Sync {
+    /// Returns the content of the file at `path`, or `None` if this loader doesn't provide it.
+    ///
+    /// Imports are relative to the importing file, so the paths are relative if the main program
+    /// has a relative name (see [`Context::with_source_name`]): importing `"lib/foo.ncl"` from a
+    /// program named `main.ncl` loads `lib/foo.ncl`. Paths are normalized, so that importing
+    /// `"../bar.ncl"` from `lib/foo.ncl` loads `bar.ncl`.
+    fn load_file(&self, path: &Path) -> Option<std::io::Result<String>>;
+
+    /// Returns the directory of the package with the given id, or `None` if this loader doesn't
+    /// provide it. The main file of the package is then loaded from `main.ncl` in this directory,
+    /// like any other file.
+    fn package_dir(&self, id: &str) -> Option<PathBuf> {
+        let _ = id;
+        None
+    }
+}
+
+/// An in-memory bundle of files, indexed by path.
+impl ImportLoader for HashMap<PathBuf, String> {
+    fn load_file(&self, path: &Path) -> Option<std::io::Result<String>> {
+        self.get(path).cloned().map(Ok)
+    }
+}
+
+/// Adapts an [ImportLoader] to the interface of `nickel-lang-core`.
+struct Loader<L>(L);
+
+impl<L: ImportLoader> SourceLoader for Loader<L> {
+    fn load_file(&self, path: &Path) -> Option<std::io::Result<String>> {
+        self.0.load_file(path)
+    }
+
+    fn package_dir(&self, id: &str) -> Option<PathBuf> {
+        self.0.package_dir(id)
+    }
+}
+
 impl Context {
     pub fn new() -> Self {
         Self {
             name: Default::default(),
             vm_ctxt: VmContext::new(CacheHub::new(), std::io::sink(), NullReporter {}),
+            overrides: Vec::new(),
         }
     }
 
+    /// Binds a Rust value to a variable, which is then in scope in the programs evaluated by this
+    /// context, like the standard library.
+    ///
+    /// Bindings are typechecked as `Dyn`. Binding the same name twice replaces the previous
+    /// value. This fails if the value can't be represented in Nickel, for example if it's a map
+    /// with composite keys.
+    pub fn with_binding(mut self, name: &str, value: &impl Serialize) -> Result<Self, Error> {
+        let value = to_nickel_value(value)?;
+        let ident = Ident::new(name);
+        self.vm_ctxt.extend_env.retain(|(id, _)| *id != ident);
+        self.vm_ctxt.extend_env.push((ident, value));
+        Ok(self)
+    }
+
+    /// Overrides a field of the programs evaluated by this context with a Rust value.
+    ///
+    /// The value is merged into the program with the `force` priority, so it takes precedence
+    /// over any definition of the field, as with the `--override` option of the Nickel command
+    /// line program. `path` is the path of the field, such as `["server", "port"]` for
+    /// `server.port`. This fails if the value can't be represented in Nickel, for example if it's
+    /// a map with composite keys.
+    pub fn with_override(mut self, path: &[&str], value: &impl Serialize) -> Result<Self, Error> {
+        let value = to_nickel_value(value)?;
+        let path = path.iter().map(|id| id.to_string()).collect();
+        self.overrides.push((path, value));
+        Ok(self)
+    }
+
+    /// Exposes a Rust function to the programs evaluated by this context under the name `name`,
+    /// with the Nickel type `typ`, such as `String -> Number`.
+    ///
+    /// The function is handled like a function of the standard library: programs are typechecked
+    /// against `typ`, and the corresponding contract checks the arguments and the return value at
+    /// runtime. The arguments are fully evaluated before `fun` is called, with one [Expr] per arrow
+    /// at the top-level of `typ`. Returning an error aborts the evaluation, reporting it as
+    /// described by [NativeFunctionError].
+    ///
+    /// Registering the same name twice replaces the previous function. This fails if `typ` isn't
+    /// a valid Nickel type.
+    pub fn with_native_function<F, T>(
+        mut self,
+        name: &str,
+        typ: &str,
+        fun: F,
+    ) -> Result<Self, Error>
+    where
+        F: Fn(&[Expr]) -> Result<T, NativeFunctionError> + 'static,
+        T: Serialize,
+    {
+        let native = NativeFunction::parse(
+            &mut self.vm_ctxt.import_resolver,
+            &mut self.vm_ctxt.pos_table,
+            Ident::new(name),
+            typ,
+            move |args| {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|value| Expr {
+                        value: value.clone(),
+                    })
+                    .collect();
+                let result = fun(&args).map_err(NickelCoreNativeFunctionError::from)?;
+
+                try_to_nickel_value(&result).map_err(|e| {
+                    NickelCoreNativeFunctionError::Other(format!(
+                        "couldn't convert the result to Nickel: {e}"
+                    ))
+                })
+            },
+        )
+        .map_err(|error| self.wrap_error(error.into()))?;
+
+        self.vm_ctxt.add_native_function(native);
+        Ok(self)
+    }
+
     /// Adds entries to the interpreter's search path for imports.
     ///
     /// When importing a file, Nickel searches for it relative to the file doing the
@@ -194,6 +428,76 @@ impl Context {
         self
     }
 
+    /// Provides imported files and packages through a custom loader.
+    ///
+    /// The loader takes precedence over the filesystem: when importing a file, the loader is
+    /// asked for each candidate path first, and the filesystem is only searched if it doesn't
+    /// provide any of them. Packages that the loader doesn't know about are not resolved, since
+    /// the embedded interpreter has no package map.
+    pub fn with_import_loader(mut self, loader: impl ImportLoader + 'static) -> Self {
+        self.vm_ctxt
+            .import_resolver
+            .sources
+            .set_loader(Arc::new(Loader(loader)));
+        self
+    }
+
+    /// Allows or denies reading imported files from the filesystem. Filesystem access is allowed
+    /// by default.
+    ///
+    /// When it's denied, the only files that can be imported are the ones provided by the import
+    /// loader (see [`Self::with_import_loader`]), which is useful to evaluate untrusted programs.
+    pub fn with_filesystem_access(mut self, allow: bool) -> Self {
+        self.vm_ctxt.import_resolver.sources.allow_filesystem = allow;
+        self
+    }
+
+    /// Limits the number of steps of each evaluation.
+    ///
+    /// Limits apply separately to each evaluation, that is to each call to a method such as
+    /// [`Self::eval_deep`] or [`Self::apply`]. An evaluation reaching a limit fails with an error
+    /// whose [`Error::interruption`] tells which limit was reached. No limit is set by default.
+    ///
+    /// Steps are a deterministic measure of the work performed by the interpreter, which makes
+    /// this limit more reproducible than [`Self::with_timeout`].
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.vm_ctxt.eval_limits.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Limits the number of thunks allocated by each evaluation, as a deterministic proxy for its
+    /// memory usage. Only thunks are counted, not every allocated value: see
+    /// [`EvalLimits::max_allocations`](nickel_lang_core::eval::limits::EvalLimits::max_allocations).
+    /// See [`Self::with_max_steps`] for how limits apply.
+    pub fn with_max_allocations(mut self, max_allocations: u64) -> Self {
+        self.vm_ctxt.eval_limits.max_allocations = Some(max_allocations);
+        self
+    }
+
+    /// Limits the size of the evaluation stack, which grows with the depth of recursive calls.
+    /// See [`Self::with_max_steps`] for how limits apply.
+    pub fn with_max_stack_depth(mut self, max_stack_depth: usize) -> Self {
+        self.vm_ctxt.eval_limits.max_stack_depth = Some(max_stack_depth);
+        self
+    }
+
+    /// Limits the wall-clock duration of each evaluation. See [`Self::with_max_steps`] for how
+    /// limits apply.
+    ///
+    /// The clock is only checked periodically, so an evaluation can run slightly longer than
+    /// `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.vm_ctxt.eval_limits.timeout = Some(timeout);
+        self
+    }
+
+    /// Makes the evaluations of this context cancellable through `handle`, which can be sent to
+    /// another thread.
+    pub fn with_cancellation(mut self, handle: CancellationHandle) -> Self {
+        self.vm_ctxt.eval_limits.cancellation = Some(handle.0);
+        self
+    }
+
     /// Provides a destination for the output of `std.trace`.
     ///
     /// If you don't provide a destination, `std.trace` will have
@@ -232,7 +536,22 @@ impl Context {
             )
             .map_err(|err| IOError(err.to_string()))?;
 
-        let value = self.vm_ctxt.prepare_eval(file_id)?;
+        let mut value = self.vm_ctxt.prepare_eval(file_id)?;
+
+        if !self.overrides.is_empty() {
+            let overrides = self
+                .overrides
+                .iter()
+                .fold(builder::Record::new(), |record, (path, value)| {
+                    record
+                        .path(path)
+                        .priority(term::MergePriority::Top)
+                        .value(value.clone())
+                })
+                .build();
+            value = mk_term::op2(BinaryOp::Merge(Label::default().into()), value, overrides);
+        }
+
         let vm = VirtualMachine::new(&mut self.vm_ctxt);
 
         f(vm, value)
@@ -247,7 +566,7 @@ impl Context {
             src,
             |mut vm: VirtualMachine<'_, CacheHub, CacheImpl>, value| {
                 Ok(Expr {
-                    value: vm.eval_full(value)?,
+                    value: close(vm.eval_full_closure(value.into())?),
                 })
             },
         )
@@ -282,7 +601,7 @@ impl Context {
             src,
             |mut vm: VirtualMachine<'_, CacheHub, CacheImpl>, value| {
                 Ok(Expr {
-                    value: vm.eval(value)?,
+                    value: close(vm.eval_closure(value.into())?),
                 })
             },
         )
@@ -294,10 +613,32 @@ impl Context {
     ///
     /// This has no effect if the expression is already evaluated (see [`Expr::is_value`]).
     pub fn eval_expr_shallow(&mut self, expr: Expr) -> Result<Expr, Error> {
-        let value = VirtualMachine::new(&mut self.vm_ctxt).eval(expr.value);
-        let value = value.map_err(|error| self.wrap_error(error.into()))?;
+        let closure = VirtualMachine::new(&mut self.vm_ctxt).eval_closure(expr.value.into());
+        let closure = closure.map_err(|error| self.wrap_error(error.into()))?;
+
+        Ok(Expr {
+            value: close(closure),
+        })
+    }
 
-        Ok(Expr { value })
+    /// Applies a function to arguments and evaluates the result deeply, as
+    /// [`eval_deep`][Self::eval_deep] does.
+    ///
+    /// The function typically comes from the evaluation of a program such as `fun env => { ... }`
+    /// (see [`Expr::is_function`]). The arguments can come from previous evaluations, or from Rust
+    /// values through [`Expr::from_serde`]. Applying something that isn't a function, or a
+    /// function to arguments that it doesn't accept, results in the same errors as in Nickel.
+    pub fn apply(&mut self, function: &Expr, args: &[Expr]) -> Result<Expr, Error> {
+        let app = args.iter().fold(function.value.clone(), |head, arg| {
+            NickelValue::term_posless(term::Term::app(head, arg.value.clone()))
+        });
+
+        let closure = VirtualMachine::new(&mut self.vm_ctxt).eval_full_closure(app.into());
+        let closure = closure.map_err(|error| self.wrap_error(error.into()))?;
+
+        Ok(Expr {
+            value: close(closure),
+        })
     }
 
     /// Converts an expression to JSON.
@@ -346,6 +687,47 @@ impl Context {
     }
 }
 
+/// Turns the result of an evaluation into a standalone value.
+///
+/// The free variables of a function live in the environment of the closure, which is otherwise
+/// dropped (the content of records, arrays and enum variants is closurized, so it doesn't need
+/// it). We keep functions together with their environment in a thunk, so that they can be applied
+/// later.
+fn close(closure: Closure) -> NickelValue {
+    if is_function(&closure.value) && !closure.env.is_empty() {
+        closure.value.closurize(&mut CacheImpl::new(), closure.env)
+    } else {
+        closure.value
+    }
+}
+
+fn is_function(value: &NickelValue) -> bool {
+    match value.as_thunk() {
+        Some(thunk) => is_function(&thunk.borrow().value),
+        None => matches!(value.as_term(), Some(term::Term::Fun(_))),
+    }
+}
+
+/// Converts a Rust value to a Nickel value, going through its JSON representation.
+fn try_to_nickel_value(value: &impl Serialize) -> Result<NickelValue, serde_json::Error> {
+    floats::check_finite(value)?;
+    serde_json::to_value(value).and_then(NickelValue::deserialize)
+}
+
+fn to_nickel_value(value: &impl Serialize) -> Result<NickelValue, Error> {
+    try_to_nickel_value(value).map_err(conversion_error)
+}
+
+/// Reports a failure to convert a Rust value to Nickel.
+fn conversion_error(error: impl std::fmt::Display) -> Error {
+    Error {
+        error: Box::new(NickelCoreError::ConversionError(ConversionError(
+            error.to_string(),
+        ))),
+        files: Files::empty(),
+    }
+}
+
 /// A Nickel expression.
 ///
 /// This might be fully evaluated (for example, if you got it from [`Context::eval_deep`])
@@ -521,6 +903,13 @@ impl Expr {
         self.as_array().is_some()
     }
 
+    /// Is this expression a function?
+    ///
+    /// Functions can be applied to arguments with [`Context::apply`].
+    pub fn is_function(&self) -> bool {
+        is_function(&self.value)
+    }
+
     /// Has this expression been evaluated?
     ///
     /// An evaluated expression is either null, or it's a number, bool, string, record, array, or
@@ -531,6 +920,17 @@ impl Expr {
         self.value.is_whnf()
     }
 
+    /// Converts any type that implements `serde::Serialize` into an expression, for example to
+    /// pass it as an argument to [`Context::apply`].
+    ///
+    /// This fails if the value can't be represented in Nickel, for example if it's a map with
+    /// composite keys or a float that is NaN or infinite.
+    pub fn from_serde(value: &impl Serialize) -> Result<Expr, Error> {
+        Ok(Expr {
+            value: to_nickel_value(value)?,
+        })
+    }
+
     /// Converts this expression into any type that implements `serde::Deserialize`.
     ///
     /// This expression should be fully evaluated, or the conversion will fail. The
@@ -862,4 +1262,290 @@ mod tests {
             assert!(ctxt.eval_expr_shallow(elt).is_err());
         }
     }
+
+    #[derive(serde::Serialize)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    #[test]
+    fn bindings_and_overrides() {
+        let server = Server {
+            host: "example.com".to_owned(),
+            port: 443,
+        };
+        let mut ctxt = Context::new()
+            .with_binding("server", &server)
+            .unwrap()
+            .with_override(&["replicas"], &3)
+            .unwrap()
+            .with_override(&["tags"], &["prod", "eu"])
+            .unwrap();
+
+        let expr = ctxt
+            .eval_deep(
+                "{ url = \"https://%{server.host}:%{std.to_string server.port}\", replicas = 1, tags | Array String }",
+            )
+            .unwrap();
+        let rec = expr.as_record().unwrap();
+        assert_eq!(
+            Some("https://example.com:443"),
+            rec.value_by_name("url").unwrap().as_str()
+        );
+        assert_eq!(Some(3), rec.value_by_name("replicas").unwrap().as_i64());
+        assert_eq!(
+            2,
+            rec.value_by_name("tags").unwrap().as_array().unwrap().len()
+        );
+
+        // Maps with composite keys don't have a Nickel counterpart.
+        let map = std::collections::BTreeMap::from([((1, 2), 3)]);
+        assert!(Context::new().with_binding("map", &map).is_err());
+
+        // Neither do NaN and infinities, which shouldn't silently become `null`.
+        for x in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(Expr::from_serde(&x).is_err());
+            assert!(Expr::from_serde(&vec![Some(1.5), Some(x)]).is_err());
+        }
+        assert_eq!(Some(1.5), Expr::from_serde(&1.5f32).unwrap().as_f64());
+    }
+
+    #[test]
+    fn native_functions() {
+        let secrets = HashMap::from([("db", "hunter2")]);
+        let mut ctxt = Context::new()
+            .with_native_function("secret", "String -> String", move |args| {
+                let key = args[0].as_str().unwrap();
+                secrets
+                    .get(key)
+                    .map(|secret| secret.to_string())
+                    .ok_or_else(|| format!("unknown secret `{key}`").into())
+            })
+            .unwrap()
+            .with_native_function("add", "Number -> Number -> Number", |args| {
+                Ok(args[0].as_i64().unwrap() + args[1].as_i64().unwrap())
+            })
+            .unwrap()
+            .with_native_function("port", "String -> Number", |args| {
+                let port = args[0].as_str().unwrap();
+                port.parse::<u16>().map_err(|_| NativeFunctionError::Blame {
+                    index: 0,
+                    message: format!("`{port}` isn't a valid port"),
+                })
+            })
+            .unwrap()
+            .with_native_function("head", "Array Number -> Number", |args| {
+                let array = args[0].as_array().unwrap();
+                if array.is_empty() {
+                    return Err(NativeFunctionError::InvalidArgument {
+                        index: 0,
+                        expected: "a non-empty array".to_owned(),
+                    });
+                }
+                Ok(array.get(0).unwrap().as_i64().unwrap())
+            })
+            .unwrap();
+
+        // Arguments are evaluated deeply before the call, and the function can be partially
+        // applied.
+        let expr = ctxt
+            .eval_deep("{ password = secret (std.string.join \"\" [\"d\", \"b\"]), three = std.function.pipe 1 [add 2] }")
+            .unwrap();
+        let rec = expr.as_record().unwrap();
+        assert_eq!(
+            Some("hunter2"),
+            rec.value_by_name("password").unwrap().as_str()
+        );
+        assert_eq!(Some(3), rec.value_by_name("three").unwrap().as_i64());
+
+        // The typechecker knows the type of native functions...
+        assert!(ctxt.eval_deep("(secret 1 : String)").is_err());
+        assert!(ctxt.eval_deep("(add 1 2 : Number)").is_ok());
+        // ...and the contract is checked at runtime.
+        assert!(ctxt.eval_deep("secret 1").is_err());
+        // Errors returned by the function abort the evaluation.
+        assert!(ctxt.eval_deep("secret \"api\"").is_err());
+        assert_eq!(Some(80), ctxt.eval_deep("port \"80\"").unwrap().as_i64());
+        assert_eq!(Some(1), ctxt.eval_deep("head [1, 2]").unwrap().as_i64());
+        for (src, message) in [
+            ("port \"http\"", "`http` isn't a valid port"),
+            ("head []", "a non-empty array"),
+        ] {
+            let Err(err) = ctxt.eval_deep(src) else {
+                panic!("wanted an error");
+            };
+            let mut out = Vec::new();
+            err.format(&mut out, ErrorFormat::Text).unwrap();
+            assert!(String::from_utf8(out).unwrap().contains(message));
+        }
+
+        assert!(
+            Context::new()
+                .with_native_function("broken", "String ->", |_| Ok(()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let mut ctxt = Context::new();
+        let template = ctxt
+            .eval_shallow(
+                "let suffix = \".example.com\" in fun env server => { host = \"%{server.name}.%{env}%{suffix}\", port | Number = server.port }",
+            )
+            .unwrap();
+        assert!(template.is_function());
+        assert!(!Expr::from_serde(&1).unwrap().is_function());
+
+        let server = Server {
+            host: "db".to_owned(),
+            port: 5432,
+        };
+        let args = [
+            Expr::from_serde(&"prod").unwrap(),
+            Expr::from_serde(&serde_json::json!({ "name": server.host, "port": server.port }))
+                .unwrap(),
+        ];
+        let result = ctxt.apply(&template, &args).unwrap();
+        let rec = result.as_record().unwrap();
+        assert_eq!(
+            Some("db.prod.example.com"),
+            rec.value_by_name("host").unwrap().as_str()
+        );
+        assert_eq!(Some(5432), rec.value_by_name("port").unwrap().as_i64());
+
+        // Partial application returns a function, which can be applied again.
+        let partial = ctxt.apply(&template, &args[..1]).unwrap();
+        assert!(partial.is_function());
+        assert!(ctxt.apply(&partial, &args[1..]).is_ok());
+
+        // Errors are reported as for Nickel applications.
+        let bad_port = Expr::from_serde(&serde_json::json!({ "name": "db", "port": "x" })).unwrap();
+        assert!(ctxt.apply(&template, &[args[0].clone(), bad_port]).is_err());
+        assert!(ctxt.apply(&args[0], &args[1..]).is_err());
+
+        // Functions keep their environment after a deep evaluation too.
+        let add = ctxt.eval_deep("let y = 1 in fun x => x + y").unwrap();
+        let three = ctxt.apply(&add, &[Expr::from_serde(&2).unwrap()]).unwrap();
+        assert_eq!(Some(3), three.as_i64());
+    }
+
+    struct Packages;
+
+    impl ImportLoader for Packages {
+        fn load_file(&self, path: &Path) -> Option<std::io::Result<String>> {
+            match path.to_str()? {
+                "pkgs/greeter/main.ncl" => {
+                    Some(Ok("{ greet = fun name => \"hi %{name}\" }".into()))
+                }
+                "broken.ncl" => Some(Err(std::io::Error::other("corrupted"))),
+                _ => None,
+            }
+        }
+
+        fn package_dir(&self, id: &str) -> Option<PathBuf> {
+            (id == "greeter").then(|| PathBuf::from("pkgs/greeter"))
+        }
+    }
+
+    #[test]
+    fn import_loader() {
+        let files = HashMap::from([
+            (
+                PathBuf::from("lib/port.ncl"),
+                "(import \"../default.ncl\") + 1".into(),
+            ),
+            (PathBuf::from("default.ncl"), "8079".into()),
+        ]);
+        let expr = Context::new()
+            .with_source_name("main.ncl".into())
+            .with_import_loader(files)
+            .eval_deep("{ port = import \"lib/port.ncl\" }")
+            .unwrap();
+        let port = expr.as_record().unwrap().value_by_name("port").unwrap();
+        assert_eq!(Some(8080), port.as_i64());
+
+        let expr = Context::new()
+            .with_import_loader(Packages)
+            .eval_deep("(import greeter).greet \"bob\"")
+            .unwrap();
+        assert_eq!(Some("hi bob"), expr.as_str());
+
+        // Errors of the loader are reported instead of looking for the file elsewhere.
+        let Err(err) = Context::new()
+            .with_import_loader(Packages)
+            .eval_deep("import \"broken.ncl\"")
+        else {
+            panic!("wanted an error");
+        };
+        let mut out = Vec::new();
+        err.format(&mut out, ErrorFormat::Text).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("corrupted"));
+    }
+
+    #[test]
+    fn deny_filesystem_access() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("secret.ncl"), "42").unwrap();
+        let main = dir.path().join("main.ncl").to_string_lossy().into_owned();
+
+        let mut ctxt = Context::new().with_source_name(main.clone());
+        assert!(ctxt.eval_deep("import \"secret.ncl\"").is_ok());
+
+        let mut ctxt = Context::new()
+            .with_source_name(main)
+            .with_filesystem_access(false);
+        assert!(ctxt.eval_deep("import \"secret.ncl\"").is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let sum = "std.array.fold_left (+) 0 (std.array.range 0 10000)";
+        let forever = "let rec loop = fun n => if n < 0 then n else loop (n + 1) in loop 0";
+
+        let mut ctxt = Context::new()
+            .with_max_steps(1_000_000)
+            .with_max_stack_depth(1000);
+        assert_eq!(Some(49995000), ctxt.eval_deep(sum).unwrap().as_i64());
+
+        let err = ctxt
+            .eval_deep("let rec f = fun n => if n == 0 then 0 else 1 + f (n - 1) in f 10000")
+            .err()
+            .unwrap();
+        assert_eq!(Some(Interruption::StackDepthLimit), err.interruption());
+
+        let err = ctxt.eval_deep(forever).err().unwrap();
+        assert_eq!(Some(Interruption::StepLimit), err.interruption());
+        // The context can still be used after an interrupted evaluation.
+        assert_eq!(Some(49995000), ctxt.eval_deep(sum).unwrap().as_i64());
+
+        let err = Context::new()
+            .with_max_allocations(100)
+            .eval_deep(sum)
+            .err()
+            .unwrap();
+        assert_eq!(Some(Interruption::AllocationLimit), err.interruption());
+
+        let err = Context::new()
+            .with_timeout(Duration::from_millis(20))
+            .eval_deep(forever)
+            .err()
+            .unwrap();
+        assert_eq!(Some(Interruption::Timeout), err.interruption());
+
+        let handle = CancellationHandle::new();
+        let mut ctxt = Context::new().with_cancellation(handle.clone());
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.cancel();
+        });
+        let err = ctxt.eval_deep(forever).err().unwrap();
+        assert_eq!(Some(Interruption::Cancelled), err.interruption());
+        canceller.join().unwrap();
+
+        // Other errors aren't interruptions.
+        let err = Context::new().eval_deep("1 + \"a\"").err().unwrap();
+        assert_eq!(None, err.interruption());
+    }
 }
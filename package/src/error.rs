@@ -104,6 +104,8 @@ pub enum Error {
     /// gix's errors are highly structured, and for many of them we only
     /// care about reporting them as strings.
     OtherGit(anyhow::Error),
+    /// A program couldn't be built for another reason than an I/O error.
+    ProgramBuilder(BuilderError),
 }
 
 impl std::error::Error for Error {}
@@ -217,6 +219,7 @@ impl std::fmt::Display for Error {
             Error::OtherGit(error) => {
                 write!(f, "{error}")
             }
+            Error::ProgramBuilder(error) => error.fmt(f),
             Error::Resolution(e) => {
                 writeln!(f, "package version resolution failed:")?;
                 crate::resolve::print_resolve_error(f, e)
@@ -316,6 +319,7 @@ impl From<BuilderError> for Error {
                 error: std::io::Error::other("ProgramBuilder::build: no inputs were added"),
             },
             BuilderError::Io { path, error } => Error::Io { path, error },
+            error @ BuilderError::NativeFunctionType { .. } => Error::ProgramBuilder(error),
         }
     }
 }
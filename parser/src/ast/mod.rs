@@ -65,6 +65,9 @@ pub enum InputFormat {
     #[cfg(feature = "nix-experimental")]
     Nix,
     Text,
+    Csv,
+    Tsv,
+    Ini,
 }
 
 impl InputFormat {
@@ -78,6 +81,9 @@ impl InputFormat {
             #[cfg(feature = "nix-experimental")]
             Some("nix") => Some(InputFormat::Nix),
             Some("txt") => Some(InputFormat::Text),
+            Some("csv") => Some(InputFormat::Csv),
+            Some("tsv") => Some(InputFormat::Tsv),
+            Some("ini") => Some(InputFormat::Ini),
             _ => None,
         }
     }
@@ -89,6 +95,9 @@ impl InputFormat {
             InputFormat::Yaml => "Yaml",
             InputFormat::Toml => "Toml",
             InputFormat::Text => "Text",
+            InputFormat::Csv => "Csv",
+            InputFormat::Tsv => "Tsv",
+            InputFormat::Ini => "Ini",
             #[cfg(feature = "nix-experimental")]
             InputFormat::Nix => "Nix",
         }
@@ -111,6 +120,9 @@ impl std::str::FromStr for InputFormat {
             "Text" => InputFormat::Text,
             "Yaml" => InputFormat::Yaml,
             "Toml" => InputFormat::Toml,
+            "Csv" => InputFormat::Csv,
+            "Tsv" => InputFormat::Tsv,
+            "Ini" => InputFormat::Ini,
             #[cfg(feature = "nix-experimental")]
             "Nix" => InputFormat::Nix,
             _ => return Err(()),
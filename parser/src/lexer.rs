@@ -630,6 +630,8 @@ pub struct Lexer<'input> {
     /// previous mode together with its associated state is pushed on this stack. It can be then
     /// restored once the current mode is exited (in the string example, when the string ends).
     pub modes: Vec<Mode>,
+    /// Whether to emit [NormalToken::LineComment] tokens, which are skipped by default.
+    pub keep_comments: bool,
 }
 
 impl<'input> Lexer<'input> {
@@ -640,9 +642,18 @@ impl<'input> Lexer<'input> {
                 logos_lexer: NormalToken::lexer(s),
             }),
             modes: Vec::new(),
+            keep_comments: false,
         }
     }
 
+    /// Makes the lexer emit comments as [NormalToken::LineComment] tokens instead of skipping
+    /// them. The parser doesn't expect comments, so such a lexer is only useful to inspect the
+    /// tokens of a source directly.
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
     fn enter_strlike<F>(&mut self, morph: F)
     where
         F: FnOnce(NormalLexer<'input>) -> ModalLexer<'input>,
@@ -825,7 +836,7 @@ impl<'input> Lexer<'input> {
                 }
             }
             // Ignore comment
-            NormalToken::LineComment => return self.next(),
+            NormalToken::LineComment if !self.keep_comments => return self.next(),
             NormalToken::Error => {
                 return Some(Err(LexicalError::Generic(span)));
             }
@@ -1,26 +1,202 @@
-use std::ffi::OsString;
+use std::{ffi::OsString, time::Duration};
 
+use nickel_lang::{Context, ErrorFormat, Expr, Interruption};
 use nickel_lang_core::{
     error::{
         Error,
         report::{ColorOpt, report_as_str},
     },
-    eval::cache::{Cache, CacheImpl},
+    eval::{
+        cache::{Cache, CacheImpl},
+        limits::{self, EvalLimits},
+    },
     program::{Program, ProgramBuilder},
     serialize,
 };
 
-use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+};
 
 create_exception!(nickel, NickelException, PyException);
+create_exception!(
+    nickel,
+    StepLimitExceeded,
+    NickelException,
+    "The evaluation reached the step limit set by `Limits.max_steps`."
+);
+create_exception!(
+    nickel,
+    AllocationLimitExceeded,
+    NickelException,
+    "The evaluation reached the thunk allocation limit set by `Limits.max_allocations`."
+);
+create_exception!(
+    nickel,
+    StackDepthLimitExceeded,
+    NickelException,
+    "The evaluation reached the stack depth limit set by `Limits.max_stack_depth`."
+);
+create_exception!(
+    nickel,
+    EvaluationTimeout,
+    NickelException,
+    "The evaluation took longer than `Limits.timeout`."
+);
+create_exception!(
+    nickel,
+    EvaluationCancelled,
+    NickelException,
+    "The evaluation was cancelled through a `CancellationHandle`."
+);
+
+/// Turn a diagnostic message into the exception corresponding to `interruption`, or into a plain
+/// [NickelException] if the evaluation wasn't interrupted.
+fn new_exception(interruption: Option<Interruption>, message: String) -> PyErr {
+    match interruption {
+        None => NickelException::new_err(message),
+        Some(Interruption::StepLimit) => StepLimitExceeded::new_err(message),
+        Some(Interruption::AllocationLimit) => AllocationLimitExceeded::new_err(message),
+        Some(Interruption::StackDepthLimit) => StackDepthLimitExceeded::new_err(message),
+        Some(Interruption::Timeout) => EvaluationTimeout::new_err(message),
+        Some(Interruption::Cancelled) => EvaluationCancelled::new_err(message),
+    }
+}
 
 /// Turn an internal Nickel error into a PyErr with a fancy diagnostic message
 fn error_to_exception<E: Into<Error>, EC: Cache>(error: E, program: &mut Program<EC>) -> PyErr {
-    NickelException::new_err(report_as_str(
-        &mut program.files(),
-        error.into(),
-        ColorOpt::default(),
-    ))
+    let error = error.into();
+
+    let interruption = match &error {
+        Error::EvalError(data) => data.error.interruption().map(Interruption::from),
+        _ => None,
+    };
+
+    new_exception(
+        interruption,
+        report_as_str(&mut program.files(), error, ColorOpt::default()),
+    )
+}
+
+/// Turn an error of the embedding API into a PyErr with a diagnostic message
+fn embedded_error_to_exception(error: nickel_lang::Error) -> PyErr {
+    let mut out = Vec::new();
+    match error.format(&mut out, ErrorFormat::Text) {
+        Ok(()) => new_exception(
+            error.interruption(),
+            String::from_utf8_lossy(&out).into_owned(),
+        ),
+        Err(format_error) => new_exception(error.interruption(), format_error.to_string()),
+    }
+}
+
+/// A handle to cancel evaluations from another thread.
+///
+/// Pass it to an evaluation through `Limits(cancellation=handle)`, and call `handle.cancel()` from
+/// another thread: the evaluation then raises `EvaluationCancelled`. Cancellation is permanent:
+/// evaluations started later with the same handle are cancelled as well.
+#[pyclass(frozen, from_py_object)]
+#[derive(Clone, Default)]
+pub struct CancellationHandle(limits::CancellationHandle);
+
+#[pymethods]
+impl CancellationHandle {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the cancellation of the evaluations using this handle.
+    fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+/// Bounds on the work performed by an evaluation, to evaluate untrusted code.
+///
+/// # Parameters
+///
+/// - `max_steps`: the maximum number of evaluation steps. Raises `StepLimitExceeded`.
+/// - `max_allocations`: the maximum number of thunks allocated, as a deterministic proxy for
+///   memory usage. Only thunks, the suspended computations created for bindings, record fields,
+///   array elements and function arguments, are counted: other values aren't. Raises
+///   `AllocationLimitExceeded`.
+/// - `max_stack_depth`: the maximum size of the evaluation stack, which grows with the depth of
+///   recursive calls. Raises `StackDepthLimitExceeded`.
+/// - `timeout`: the maximum duration of the evaluation, in seconds. Raises `EvaluationTimeout`.
+/// - `cancellation`: a `CancellationHandle` to cancel the evaluation from another thread. Raises
+///   `EvaluationCancelled`.
+#[pyclass(frozen, from_py_object)]
+#[derive(Clone, Default)]
+pub struct Limits {
+    max_steps: Option<u64>,
+    max_allocations: Option<u64>,
+    max_stack_depth: Option<usize>,
+    timeout: Option<Duration>,
+    cancellation: Option<CancellationHandle>,
+}
+
+#[pymethods]
+impl Limits {
+    #[new]
+    #[pyo3(signature = (*, max_steps=None, max_allocations=None, max_stack_depth=None, timeout=None, cancellation=None))]
+    fn new(
+        max_steps: Option<u64>,
+        max_allocations: Option<u64>,
+        max_stack_depth: Option<usize>,
+        timeout: Option<f64>,
+        cancellation: Option<CancellationHandle>,
+    ) -> PyResult<Self> {
+        let timeout = timeout
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|error| PyValueError::new_err(format!("invalid timeout: {error}")))?;
+
+        Ok(Limits {
+            max_steps,
+            max_allocations,
+            max_stack_depth,
+            timeout,
+            cancellation,
+        })
+    }
+}
+
+impl Limits {
+    fn to_eval_limits(&self) -> EvalLimits {
+        EvalLimits {
+            max_steps: self.max_steps,
+            max_allocations: self.max_allocations,
+            max_stack_depth: self.max_stack_depth,
+            timeout: self.timeout,
+            cancellation: self.cancellation.as_ref().map(|handle| handle.0.clone()),
+        }
+    }
+
+    fn configure(&self, mut context: Context) -> Context {
+        if let Some(max_steps) = self.max_steps {
+            context = context.with_max_steps(max_steps);
+        }
+        if let Some(max_allocations) = self.max_allocations {
+            context = context.with_max_allocations(max_allocations);
+        }
+        if let Some(max_stack_depth) = self.max_stack_depth {
+            context = context.with_max_stack_depth(max_stack_depth);
+        }
+        if let Some(timeout) = self.timeout {
+            context = context.with_timeout(timeout);
+        }
+        if let Some(handle) = &self.cancellation {
+            context = context.with_cancellation(handle.0.clone().into());
+        }
+        context
+    }
 }
 
 /// Evaluate from a Python str of a Nickel expression to a Python str of the resulting JSON.
@@ -32,37 +208,109 @@ fn error_to_exception<E: Into<Error>, EC: Cache>(error: E, program: &mut Program
 ///   stand-alone binary, the import paths are controlled by the `NICKEL_IMPORT_PATH` environment
 ///   variable and the `--import-path` CLI argument. In the Python bindings, you need to provide
 ///   them explicitly instead.
+/// - `limits`: optional `Limits` on the work performed by the evaluation.
+///
+/// The evaluation releases the GIL, so that other Python threads can run (and cancel it) in the
+/// meantime.
 #[pyfunction]
-#[pyo3(signature = (expr, import_paths=None))]
-pub fn run(expr: String, import_paths: Option<Vec<OsString>>) -> PyResult<String> {
-    let mut builder = ProgramBuilder::new().add_source_string(expr, "python");
-    if let Some(import_paths) = import_paths {
-        builder = builder.add_import_paths(import_paths);
-    }
-    let mut program: Program<CacheImpl> = builder
-        .build()
-        .expect("building from a single in-memory source cannot fail");
-
-    let term = program
-        .eval_full()
-        .map_err(|error| error_to_exception(error, &mut program))?;
-
-    serialize::validate(serialize::ExportFormat::Json, &term).map_err(|error| {
-        error_to_exception(
-            error.with_pos_table(program.pos_table().clone()),
-            &mut program,
-        )
-    })?;
-
-    let json_string =
-        serialize::to_string(serialize::ExportFormat::Json, &term).map_err(|error| {
+#[pyo3(signature = (expr, import_paths=None, limits=None))]
+pub fn run(
+    py: Python<'_>,
+    expr: String,
+    import_paths: Option<Vec<OsString>>,
+    limits: Option<Limits>,
+) -> PyResult<String> {
+    py.detach(move || {
+        let mut builder = ProgramBuilder::new().add_source_string(expr, "python");
+        if let Some(import_paths) = import_paths {
+            builder = builder.add_import_paths(import_paths);
+        }
+        if let Some(limits) = limits {
+            builder = builder.with_eval_limits(limits.to_eval_limits());
+        }
+        let mut program: Program<CacheImpl> = builder
+            .build()
+            .expect("building from a single in-memory source cannot fail");
+
+        let term = program
+            .eval_full()
+            .map_err(|error| error_to_exception(error, &mut program))?;
+
+        serialize::validate(serialize::ExportFormat::Json, &term).map_err(|error| {
             error_to_exception(
                 error.with_pos_table(program.pos_table().clone()),
                 &mut program,
             )
         })?;
 
-    Ok(json_string)
+        let json_string =
+            serialize::to_string(serialize::ExportFormat::Json, &term).map_err(|error| {
+                error_to_exception(
+                    error.with_pos_table(program.pos_table().clone()),
+                    &mut program,
+                )
+            })?;
+
+        Ok(json_string)
+    })
+}
+
+/// Evaluate a Nickel expression to a function, apply it to Python values and return a Python str
+/// of the resulting JSON.
+///
+/// # Parameters
+///
+/// - `expr`: the Nickel expression to evaluate, such as a configuration template
+///   `fun env => { ... }`.
+/// - `args`: the arguments to apply the function to. They are converted to Nickel through the
+///   `json` module, so they must be serializable as JSON.
+/// - `import_paths`: optional list of paths to search for imported files, as for [run].
+/// - `limits`: optional `Limits` on the work performed by the evaluation, as for [run].
+#[pyfunction]
+#[pyo3(signature = (expr, args, import_paths=None, limits=None))]
+pub fn apply(
+    py: Python<'_>,
+    expr: String,
+    args: Vec<Bound<'_, PyAny>>,
+    import_paths: Option<Vec<OsString>>,
+    limits: Option<Limits>,
+) -> PyResult<String> {
+    let dumps = py.import("json")?.getattr("dumps")?;
+    let args = args
+        .iter()
+        .map(|arg| {
+            let json: String = dumps.call1((arg,))?.extract()?;
+            serde_json::from_str::<serde_json::Value>(&json)
+                .map_err(|error| NickelException::new_err(error.to_string()))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    py.detach(move || {
+        let args = args
+            .iter()
+            .map(Expr::from_serde)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(embedded_error_to_exception)?;
+
+        let mut context = Context::default().with_source_name("python".to_owned());
+        if let Some(import_paths) = import_paths {
+            context = context.with_added_import_paths(import_paths);
+        }
+        if let Some(limits) = limits {
+            context = limits.configure(context);
+        }
+
+        let function = context
+            .eval_shallow(&expr)
+            .map_err(embedded_error_to_exception)?;
+        let result = context
+            .apply(&function, &args)
+            .map_err(embedded_error_to_exception)?;
+
+        context
+            .expr_to_json(&result)
+            .map_err(embedded_error_to_exception)
+    })
 }
 
 #[pymodule]
@@ -71,5 +319,14 @@ mod nickel {
     use super::run;
 
     #[pymodule_export]
-    use super::NickelException;
+    use super::apply;
+
+    #[pymodule_export]
+    use super::{CancellationHandle, Limits};
+
+    #[pymodule_export]
+    use super::{
+        AllocationLimitExceeded, EvaluationCancelled, EvaluationTimeout, NickelException,
+        StackDepthLimitExceeded, StepLimitExceeded,
+    };
 }
@@ -0,0 +1,70 @@
+import threading
+import unittest
+
+import nickel
+
+LOOP = "let rec loop = fun n => if n < 0 then n else loop (n + 1) in loop 0"
+DEEP = "let rec f = fun n => if n == 0 then 0 else 1 + f (n - 1) in f 10000"
+
+
+class TestLimits(unittest.TestCase):
+    def test_within_limits(self):
+        limits = nickel.Limits(max_steps=1_000_000, max_stack_depth=1000, timeout=10.0)
+        self.assertEqual(nickel.run("{ x = 1 + 1 }", limits=limits), '{\n  "x": 2\n}')
+        self.assertEqual(nickel.apply("fun x => x + 1", [1], limits=limits), "2")
+
+    def test_step_limit(self):
+        with self.assertRaises(nickel.StepLimitExceeded):
+            nickel.run(LOOP, limits=nickel.Limits(max_steps=10_000))
+        with self.assertRaises(nickel.StepLimitExceeded):
+            nickel.apply(LOOP, [], limits=nickel.Limits(max_steps=10_000))
+
+    def test_allocation_limit(self):
+        with self.assertRaises(nickel.AllocationLimitExceeded):
+            nickel.run(
+                "std.array.generate (fun i => i + 1) 100000",
+                limits=nickel.Limits(max_allocations=1000),
+            )
+
+    def test_stack_depth_limit(self):
+        with self.assertRaises(nickel.StackDepthLimitExceeded):
+            nickel.run(DEEP, limits=nickel.Limits(max_stack_depth=1000))
+
+    def test_timeout(self):
+        with self.assertRaises(nickel.EvaluationTimeout):
+            nickel.run(LOOP, limits=nickel.Limits(timeout=0.1))
+
+    def test_invalid_timeout(self):
+        with self.assertRaises(ValueError):
+            nickel.Limits(timeout=-1.0)
+
+    def test_limits_are_nickel_exceptions(self):
+        with self.assertRaises(nickel.NickelException):
+            nickel.run(LOOP, limits=nickel.Limits(max_steps=10_000))
+
+
+class TestCancellationHandle(unittest.TestCase):
+    def test_cancel_before(self):
+        handle = nickel.CancellationHandle()
+        self.assertFalse(handle.is_cancelled())
+        handle.cancel()
+        self.assertTrue(handle.is_cancelled())
+
+        # Cancellation is permanent.
+        for _ in range(2):
+            with self.assertRaises(nickel.EvaluationCancelled):
+                nickel.run(LOOP, limits=nickel.Limits(cancellation=handle))
+
+    def test_cancel_from_another_thread(self):
+        handle = nickel.CancellationHandle()
+        timer = threading.Timer(0.1, handle.cancel)
+        timer.start()
+        try:
+            with self.assertRaises(nickel.EvaluationCancelled):
+                nickel.run(LOOP, limits=nickel.Limits(timeout=60.0, cancellation=handle))
+        finally:
+            timer.cancel()
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -184,6 +184,8 @@ macro_rules! ncl_bench_group {
                                     reporter: Box::new(nickel_lang_core::error::NullReporter {}),
                                     cache: eval_cache.clone(),
                                     extend_env: Vec::new(),
+                                    native_functions: Vec::new(),
+                                    eval_limits: Default::default(),
                                     #[cfg(feature = "incremental-experimental")]
                                     enable_incremental_evaluation: false,
                                     pos_table,